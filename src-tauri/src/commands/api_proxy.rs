@@ -3,6 +3,10 @@
 use crate::modules::api_proxy::{
     self, ApiProxyConfig, ProxyStatus,
 };
+use crate::modules::api_proxy_keys::{self, ApiKeyEntry};
+use crate::modules::api_proxy_audit::{self, AuditRecord, AuditRecordSummary, AuditSettings};
+use crate::modules::api_proxy_routes::{self, ModelRoute};
+use crate::modules::api_proxy_tls::{self, TlsSettings};
 
 /// 获取 API 代理配置
 #[tauri::command]
@@ -10,10 +14,110 @@ pub fn get_api_proxy_config() -> ApiProxyConfig {
     api_proxy::load_proxy_config()
 }
 
-/// 保存 API 代理配置
+/// 保存 API 代理配置；代理运行中时立即生效，仅端口 / 局域网访问变更时重新绑定监听
 #[tauri::command]
-pub fn save_api_proxy_config(config: ApiProxyConfig) -> Result<(), String> {
-    api_proxy::save_proxy_config(&config)
+pub async fn save_api_proxy_config(config: ApiProxyConfig) -> Result<(), String> {
+    api_proxy::save_proxy_config(&config)?;
+    api_proxy::rebind_proxy_if_needed(false).await.map(|_| ())
+}
+
+/// 列出命名 API Key
+#[tauri::command]
+pub fn list_api_proxy_keys() -> Result<Vec<ApiKeyEntry>, String> {
+    api_proxy_keys::list_api_keys()
+}
+
+/// 创建命名 API Key（Key 值由后端生成并返回）
+#[tauri::command]
+pub fn create_api_proxy_key(key: ApiKeyEntry) -> Result<ApiKeyEntry, String> {
+    api_proxy_keys::create_api_key(key)
+}
+
+/// 更新命名 API Key 的名称、Provider/账号范围、限额；enabled=false 即吊销
+#[tauri::command]
+pub fn update_api_proxy_key(key: ApiKeyEntry) -> Result<ApiKeyEntry, String> {
+    api_proxy_keys::update_api_key(key)
+}
+
+/// 删除命名 API Key
+#[tauri::command]
+pub fn delete_api_proxy_key(id: String) -> Result<(), String> {
+    api_proxy_keys::delete_api_key(&id)
+}
+
+/// 获取模型路由表（统一入口 /v1/* 使用）
+#[tauri::command]
+pub fn get_api_proxy_model_routes() -> Result<Vec<ModelRoute>, String> {
+    api_proxy_routes::list_model_routes()
+}
+
+/// 保存模型路由表（整表替换，运行中的代理立即生效）
+#[tauri::command]
+pub fn save_api_proxy_model_routes(routes: Vec<ModelRoute>) -> Result<(), String> {
+    api_proxy_routes::save_model_routes(routes)
+}
+
+/// 获取 HTTPS 设置
+#[tauri::command]
+pub fn get_api_proxy_tls_settings() -> TlsSettings {
+    api_proxy_tls::load_tls_settings()
+}
+
+/// 保存 HTTPS 设置；代理运行中时重新绑定监听，进行中的请求在旧监听上完成
+#[tauri::command]
+pub async fn save_api_proxy_tls_settings(settings: TlsSettings) -> Result<ProxyStatus, String> {
+    api_proxy_tls::save_tls_settings(&settings)?;
+    api_proxy::rebind_proxy_if_needed(true).await
+}
+
+/// 重新生成自签名证书（证书指纹会变化）
+#[tauri::command]
+pub async fn reset_api_proxy_tls_cert() -> Result<ProxyStatus, String> {
+    api_proxy_tls::reset_self_signed_cert()?;
+    api_proxy::rebind_proxy_if_needed(true).await
+}
+
+/// 获取请求审计设置
+#[tauri::command]
+pub fn get_api_proxy_audit_settings() -> AuditSettings {
+    api_proxy_audit::load_audit_settings()
+}
+
+/// 保存请求审计设置（enabled=false 即停止记录）
+#[tauri::command]
+pub fn save_api_proxy_audit_settings(settings: AuditSettings) -> Result<(), String> {
+    api_proxy_audit::save_audit_settings(&settings)
+}
+
+/// 按时间倒序列出审计记录
+#[tauri::command]
+pub fn list_api_proxy_audit_records(
+    limit: Option<u32>,
+    offset: Option<u32>,
+    provider: Option<String>,
+) -> Result<Vec<AuditRecordSummary>, String> {
+    api_proxy_audit::list_records(limit.unwrap_or(100), offset.unwrap_or(0), provider.as_deref())
+}
+
+/// 查看单条审计记录（含请求体、上游 payload 与返回内容）
+#[tauri::command]
+pub fn get_api_proxy_audit_record(id: String) -> Result<Option<AuditRecord>, String> {
+    api_proxy_audit::get_record(&id)
+}
+
+/// 清空审计记录
+#[tauri::command]
+pub fn clear_api_proxy_audit_records() -> Result<(), String> {
+    api_proxy_audit::clear_records()
+}
+
+/// 用指定账号重新发送审计记录中的请求（account_email 为空则按负载均衡策略选择）
+#[tauri::command]
+pub async fn replay_api_proxy_audit_record(
+    id: String,
+    account_email: Option<String>,
+) -> Result<api_proxy::AuditReplayResult, String> {
+    api_proxy::replay_audit_record(&id, account_email.as_deref()).await
 }
 
 /// 获取代理服务状态
@@ -36,17 +140,28 @@ pub fn stop_api_proxy() -> Result<(), String> {
 }
 
 /// 重启代理服务
+/// 运行中时重新加载配置并重新绑定监听，进行中的流式请求在旧监听上继续完成；未运行时直接启动
 #[tauri::command]
 pub async fn restart_api_proxy() -> Result<ProxyStatus, String> {
-    // 先停止
-    let _ = api_proxy::stop_proxy_server();
-    // 等一下让端口释放
-    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
-    // 重新启动
     let config = api_proxy::load_proxy_config();
+    if api_proxy::get_proxy_status().running {
+        api_proxy::apply_running_config(&config);
+        return api_proxy::rebind_proxy_if_needed(true).await;
+    }
     api_proxy::start_proxy_server(config).await
 }
 
+/// 查询代理用量账本（按 日期 × Provider × 账号 × 模型 × API Key 聚合）
+/// days: 最近 N 天（含今天），为空则返回全部；provider / api_key_id: 仅返回指定 Provider / API Key
+#[tauri::command]
+pub fn get_api_proxy_usage(
+    days: Option<u32>,
+    provider: Option<String>,
+    api_key_id: Option<String>,
+) -> Result<Vec<crate::modules::api_proxy_usage::UsageLedgerEntry>, String> {
+    crate::modules::api_proxy_usage::query_usage_ledger(days, provider.as_deref(), api_key_id.as_deref())
+}
+
 /// 获取指定账号可用的模型列表及配额状态
 #[tauri::command]
 pub async fn fetch_models_for_account(email: String) -> Result<Vec<api_proxy::QuotaModelInfo>, String> {
@@ -71,6 +186,7 @@ pub async fn fetch_codex_models() -> Vec<String> {
     let account = crate::modules::codex_account::get_current_account()
         .or_else(|| {
             crate::modules::codex_account::list_accounts()
+                .ok()?
                 .into_iter()
                 .find(|a| !a.tokens.access_token.is_empty())
         });
@@ -92,7 +208,8 @@ pub async fn fetch_codex_models() -> Vec<String> {
 /// 获取 Kiro 获取模型列表，从远端服务真实拉取
 #[tauri::command]
 pub async fn fetch_kiro_models(email: String) -> Result<Vec<String>, String> {
-    let accounts = crate::modules::kiro_account::list_accounts();
+    let accounts = crate::modules::kiro_account::list_accounts()
+        .map_err(|e| format!("获取账号失败: {}", e))?;
     let account = accounts.iter()
         .find(|a| a.email == email && {
             // 检查账号是否未被禁用
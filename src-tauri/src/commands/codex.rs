@@ -2,6 +2,7 @@ use crate::models::codex::{CodexAccount, CodexQuota, CodexTokens};
 use crate::modules::{
     codex_account, codex_oauth, codex_quota, config, logger, opencode_auth, process,
 };
+use crate::modules::websocket_platforms::Platform;
 #[cfg(target_os = "macos")]
 use tauri::Emitter;
 use tauri::AppHandle;
@@ -9,7 +10,7 @@ use tauri::AppHandle;
 /// 列出所有 Codex 账号
 #[tauri::command]
 pub fn list_codex_accounts() -> Result<Vec<CodexAccount>, String> {
-    Ok(codex_account::list_accounts())
+    codex_account::list_accounts()
 }
 
 /// 获取当前激活的 Codex 账号
@@ -80,8 +81,11 @@ pub async fn switch_codex_account(
             if process::is_codex_running() {
                 logger::log_info("检测到 Codex 正在运行，将按默认实例 PID 逻辑重启");
             }
-            match crate::commands::codex_instance::codex_start_instance("__default__".to_string())
-                .await
+            match crate::commands::instance::start_instance(
+                crate::modules::instance_manager::InstancePlatform::Codex,
+                crate::modules::instance_manager::DEFAULT_INSTANCE_ID.to_string(),
+            )
+            .await
             {
                 Ok(_) => {}
                 Err(e) => {
@@ -100,6 +104,11 @@ pub async fn switch_codex_account(
     }
 
     let _ = crate::modules::tray::update_tray_menu(&app);
+    crate::modules::websocket::broadcast_platform_account_switched(
+        Platform::Codex,
+        &account.id,
+        &account.email,
+    );
     Ok(account)
 }
 
@@ -199,7 +208,7 @@ async fn save_codex_oauth_tokens(tokens: CodexTokens) -> Result<CodexAccount, St
     }
 
     let loaded =
-        codex_account::load_account(&account.id).ok_or_else(|| "账号保存后无法读取".to_string())?;
+        codex_account::load_account(&account.id)?.ok_or_else(|| "账号保存后无法读取".to_string())?;
     logger::log_info(&format!(
         "Codex OAuth 账号已保存: account_id={}, email={}",
         loaded.id, loaded.email
@@ -287,7 +296,7 @@ pub async fn add_codex_account_with_token(
         logger::log_error(&format!("刷新配额失败: {}", e));
     }
 
-    codex_account::load_account(&account.id).ok_or_else(|| "账号保存后无法读取".to_string())
+    codex_account::load_account(&account.id)?.ok_or_else(|| "账号保存后无法读取".to_string())
 }
 
 /// 检查 Codex OAuth 端口是否被占用
@@ -0,0 +1,52 @@
+//! 凭据加密存储 Tauri 命令
+
+use crate::modules::credential_vault::{self, VaultMode, VaultStatus};
+
+/// 派生密钥与重写账号文件较慢，放到阻塞线程执行
+async fn run_blocking<T: Send + 'static>(
+    task: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(task)
+        .await
+        .map_err(|e| format!("执行失败: {}", e))?
+}
+
+/// 凭据库状态
+#[tauri::command]
+pub fn vault_get_status() -> VaultStatus {
+    credential_vault::get_status()
+}
+
+/// 开启凭据库并加密现有账号，返回加密的文件数
+#[tauri::command]
+pub async fn vault_enable(mode: VaultMode, passphrase: Option<String>) -> Result<usize, String> {
+    run_blocking(move || credential_vault::enable(mode, passphrase.as_deref())).await
+}
+
+/// 解锁凭据库，解锁后通知前端重新加载账号
+#[tauri::command]
+pub async fn vault_unlock(passphrase: Option<String>) -> Result<VaultStatus, String> {
+    run_blocking(move || credential_vault::unlock(passphrase.as_deref())).await?;
+    crate::emit_event("accounts:refresh", "vault");
+    Ok(credential_vault::get_status())
+}
+
+/// 锁定凭据库
+#[tauri::command]
+pub fn vault_lock() -> Result<VaultStatus, String> {
+    credential_vault::lock()?;
+    crate::emit_event("accounts:refresh", "vault");
+    Ok(credential_vault::get_status())
+}
+
+/// 加密仍为明文的账号文件，返回加密的文件数
+#[tauri::command]
+pub async fn vault_migrate() -> Result<usize, String> {
+    run_blocking(credential_vault::migrate).await
+}
+
+/// 关闭凭据库并将账号文件还原为明文，返回解密的文件数
+#[tauri::command]
+pub async fn vault_disable() -> Result<usize, String> {
+    run_blocking(credential_vault::disable).await
+}
@@ -1,12 +1,14 @@
 use tauri::{AppHandle, Emitter};
 
 use crate::models::github_copilot::{GitHubCopilotAccount, GitHubCopilotOAuthStartResponse};
+use crate::modules::instance_manager::{InstancePlatform, DEFAULT_INSTANCE_ID};
+use crate::modules::websocket_platforms::Platform;
 use crate::modules::{github_copilot_account, github_copilot_oauth, logger};
 
 /// 列出所有 GitHub Copilot 账号
 #[tauri::command]
 pub fn list_github_copilot_accounts() -> Result<Vec<GitHubCopilotAccount>, String> {
-    Ok(github_copilot_account::list_accounts())
+    github_copilot_account::list_accounts()
 }
 
 /// 删除 GitHub Copilot 账号
@@ -145,7 +147,7 @@ pub async fn inject_github_copilot_to_vscode(
     account_id: String,
 ) -> Result<String, String> {
     logger::log_info(&format!("开始切换 GitHub Copilot 账号: {}", account_id));
-    let account = github_copilot_account::load_account(&account_id)
+    let account = github_copilot_account::load_account(&account_id)?
         .ok_or_else(|| format!("GitHub Copilot account not found: {}", account_id))?;
     logger::log_info(&format!(
         "正在切换到 GitHub Copilot 账号: {} (ID: {})",
@@ -166,34 +168,39 @@ pub async fn inject_github_copilot_to_vscode(
         ));
     }
 
-    let launch_warning =
-        match crate::commands::github_copilot_instance::github_copilot_start_instance(
-            "__default__".to_string(),
-        )
-        .await
-        {
-            Ok(_) => None,
-            Err(e) => {
-                if e.starts_with("APP_PATH_NOT_FOUND:") || e.contains("启动 VS Code 失败") {
-                    logger::log_warn(&format!("GitHub Copilot 默认实例启动失败: {}", e));
-                    if e.starts_with("APP_PATH_NOT_FOUND:") || e.contains("APP_PATH_NOT_FOUND:") {
-                        let _ = app.emit(
-                            "app:path_missing",
-                            serde_json::json!({ "app": "vscode", "retry": { "kind": "default" } }),
-                        );
-                    }
-                    Some(e)
-                } else {
-                    return Err(e);
+    let launch_warning = match crate::commands::instance::start_instance(
+        InstancePlatform::GithubCopilot,
+        DEFAULT_INSTANCE_ID.to_string(),
+    )
+    .await
+    {
+        Ok(_) => None,
+        Err(e) => {
+            if e.starts_with("APP_PATH_NOT_FOUND:") || e.contains("启动 VS Code 失败") {
+                logger::log_warn(&format!("GitHub Copilot 默认实例启动失败: {}", e));
+                if e.starts_with("APP_PATH_NOT_FOUND:") || e.contains("APP_PATH_NOT_FOUND:") {
+                    let _ = app.emit(
+                        "app:path_missing",
+                        serde_json::json!({ "app": "vscode", "retry": { "kind": "default" } }),
+                    );
                 }
+                Some(e)
+            } else {
+                return Err(e);
             }
-        };
+        }
+    };
 
     logger::log_info(&format!(
         "GitHub Copilot 账号切换完成: {}",
         account.github_login
     ));
     let _ = crate::modules::tray::update_tray_menu(&app);
+    crate::modules::websocket::broadcast_platform_account_switched(
+        Platform::GithubCopilot,
+        &account.id,
+        account.github_email.as_deref().unwrap_or(&account.github_login),
+    );
     if let Some(err) = launch_warning {
         Ok(format!("切换完成，但 VS Code 启动失败: {}", err))
     } else {
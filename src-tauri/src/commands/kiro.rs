@@ -2,11 +2,13 @@ use std::time::Instant;
 use tauri::{AppHandle, Emitter};
 
 use crate::models::kiro::{KiroAccount, KiroOAuthStartResponse};
+use crate::modules::instance_manager::{InstancePlatform, DEFAULT_INSTANCE_ID};
+use crate::modules::websocket_platforms::Platform;
 use crate::modules::{kiro_account, kiro_oauth, logger};
 
 #[tauri::command]
 pub fn list_kiro_accounts() -> Result<Vec<KiroAccount>, String> {
-    Ok(kiro_account::list_accounts())
+    kiro_account::list_accounts()
 }
 
 #[tauri::command]
@@ -163,7 +165,7 @@ pub async fn inject_kiro_to_vscode(app: AppHandle, account_id: String) -> Result
         account_id
     ));
 
-    let account = kiro_account::load_account(&account_id)
+    let account = kiro_account::load_account(&account_id)?
         .ok_or_else(|| format!("Kiro account not found: {}", account_id))?;
 
     if let Err(err) = crate::modules::kiro_instance::update_default_settings(
@@ -174,8 +176,9 @@ pub async fn inject_kiro_to_vscode(app: AppHandle, account_id: String) -> Result
         logger::log_warn(&format!("更新 Kiro 默认实例绑定账号失败: {}", err));
     }
 
-    let launch_warning = match crate::commands::kiro_instance::kiro_start_instance(
-        "__default__".to_string(),
+    let launch_warning = match crate::commands::instance::start_instance(
+        InstancePlatform::Kiro,
+        DEFAULT_INSTANCE_ID.to_string(),
     )
     .await
     {
@@ -197,6 +200,11 @@ pub async fn inject_kiro_to_vscode(app: AppHandle, account_id: String) -> Result
     };
 
     let _ = crate::modules::tray::update_tray_menu(&app);
+    crate::modules::websocket::broadcast_platform_account_switched(
+        Platform::Kiro,
+        &account.id,
+        &account.email,
+    );
 
     if let Some(err) = launch_warning {
         logger::log_warn(&format!(
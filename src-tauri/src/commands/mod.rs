@@ -2,19 +2,16 @@ pub mod account;
 pub mod announcement;
 pub mod api_proxy;
 pub mod codex;
-pub mod codex_instance;
+pub mod credential_vault;
 pub mod device;
 pub mod github_copilot;
-pub mod github_copilot_instance;
 pub mod group;
 pub mod import;
 pub mod instance;
 pub mod kiro;
-pub mod kiro_instance;
 pub mod oauth;
 pub mod system;
 pub mod update;
 pub mod wakeup;
 pub mod windsurf;
-pub mod windsurf_instance;
 pub mod warp;
@@ -5,7 +5,7 @@ use crate::modules;
 use crate::modules::config::{
     self, CloseWindowBehavior, MinimizeWindowBehavior, UserConfig, DEFAULT_WS_PORT,
 };
-use crate::modules::websocket;
+use crate::modules::{websocket, websocket_auth};
 
 /// 网络服务配置（前端使用）
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -195,6 +195,40 @@ pub fn save_network_config(ws_enabled: bool, ws_port: u16) -> Result<bool, Strin
     Ok(needs_restart)
 }
 
+/// 已配对的 WebSocket 客户端
+#[tauri::command]
+pub fn ws_list_clients() -> Result<Vec<websocket_auth::PairedClient>, String> {
+    websocket_auth::list_clients()
+}
+
+/// 修改 WebSocket 客户端的能力
+#[tauri::command]
+pub fn ws_update_client_capabilities(
+    client_id: String,
+    capabilities: Vec<websocket_auth::Capability>,
+) -> Result<(), String> {
+    websocket_auth::update_client_capabilities(&client_id, capabilities)
+}
+
+/// 吊销 WebSocket 客户端
+#[tauri::command]
+pub fn ws_revoke_client(client_id: String) -> Result<(), String> {
+    websocket_auth::revoke_client(&client_id)
+}
+
+/// 获取配对密钥（供无法读取数据目录的客户端手动填写）
+#[tauri::command]
+pub fn ws_get_pairing_secret() -> Result<String, String> {
+    websocket_auth::ensure_secret()
+}
+
+/// 重新生成配对密钥，返回新密钥
+#[tauri::command]
+pub fn ws_reset_pairing_secret() -> Result<String, String> {
+    websocket_auth::reset_secret()?;
+    websocket_auth::ensure_secret()
+}
+
 /// 获取通用设置配置
 #[tauri::command]
 pub fn get_general_config() -> Result<GeneralConfig, String> {
@@ -25,15 +25,19 @@ pub async fn fetch_available_models() -> Result<Vec<modules::wakeup::AvailableMo
 
 #[tauri::command]
 pub async fn wakeup_sync_state(
-    app: AppHandle,
     enabled: bool,
     tasks: Vec<modules::wakeup_scheduler::WakeupTaskInput>,
 ) -> Result<(), String> {
     modules::wakeup_scheduler::sync_state(enabled, tasks);
-    modules::wakeup_scheduler::ensure_started(app);
+    modules::wakeup_scheduler::ensure_started();
     Ok(())
 }
 
+#[tauri::command]
+pub fn wakeup_preview_crontab(expr: String, count: Option<usize>) -> Result<Vec<i64>, String> {
+    modules::wakeup_cron::preview_next_runs(&expr, count.unwrap_or(5))
+}
+
 #[tauri::command]
 pub fn wakeup_load_history() -> Result<Vec<modules::wakeup_history::WakeupHistoryItem>, String> {
     modules::wakeup_history::load_history()
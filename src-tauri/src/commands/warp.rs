@@ -4,7 +4,7 @@ use tauri::command;
 
 #[command]
 pub fn get_warp_accounts() -> Result<Vec<WarpAccount>, String> {
-    Ok(warp_account::list_accounts())
+    warp_account::list_accounts()
 }
 
 #[command]
@@ -2,11 +2,13 @@ use std::time::Instant;
 use tauri::{AppHandle, Emitter};
 
 use crate::models::windsurf::{WindsurfAccount, WindsurfOAuthStartResponse};
+use crate::modules::instance_manager::{InstancePlatform, DEFAULT_INSTANCE_ID};
+use crate::modules::websocket_platforms::Platform;
 use crate::modules::{logger, windsurf_account, windsurf_oauth};
 
 #[tauri::command]
 pub fn list_windsurf_accounts() -> Result<Vec<WindsurfAccount>, String> {
-    Ok(windsurf_account::list_accounts())
+    windsurf_account::list_accounts()
 }
 
 #[tauri::command]
@@ -202,7 +204,7 @@ pub async fn inject_windsurf_to_vscode(
         "[Windsurf Switch] 开始切换账号: account_id={}",
         account_id
     ));
-    let account = windsurf_account::load_account(&account_id)
+    let account = windsurf_account::load_account(&account_id)?
         .ok_or_else(|| format!("Windsurf account not found: {}", account_id))?;
     logger::log_info(&format!(
         "[Windsurf Switch] 目标账号信息: login={}, email={}",
@@ -218,8 +220,9 @@ pub async fn inject_windsurf_to_vscode(
         logger::log_warn(&format!("更新 Windsurf 默认实例绑定账号失败: {}", e));
     }
 
-    let launch_warning = match crate::commands::windsurf_instance::windsurf_start_instance(
-        "__default__".to_string(),
+    let launch_warning = match crate::commands::instance::start_instance(
+        InstancePlatform::Windsurf,
+        DEFAULT_INSTANCE_ID.to_string(),
     )
     .await
     {
@@ -240,6 +243,12 @@ pub async fn inject_windsurf_to_vscode(
         }
     };
 
+    crate::modules::websocket::broadcast_platform_account_switched(
+        Platform::Windsurf,
+        &account.id,
+        account.github_email.as_deref().unwrap_or(&account.github_login),
+    );
+
     if let Some(err) = launch_warning {
         let _ = crate::modules::tray::update_tray_menu(&app);
         logger::log_warn(&format!(
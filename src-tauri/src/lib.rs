@@ -23,6 +23,19 @@ pub fn get_app_handle() -> Option<&'static tauri::AppHandle> {
     APP_HANDLE.get()
 }
 
+/// 向前端发送事件；无界面（--daemon）模式下没有 webview，改为写入日志
+pub fn emit_event<S: serde::Serialize + Clone>(event: &str, payload: S) {
+    match APP_HANDLE.get() {
+        Some(app_handle) => {
+            let _ = app_handle.emit(event, payload);
+        }
+        None => {
+            let payload = serde_json::to_string(&payload).unwrap_or_default();
+            logger::log_info(&format!("[Event] {} {}", event, payload));
+        }
+    }
+}
+
 #[cfg(target_os = "macos")]
 fn apply_macos_activation_policy(app: &tauri::AppHandle) {
     let config = modules::config::get_user_config();
@@ -55,6 +68,11 @@ fn apply_macos_activation_policy(app: &tauri::AppHandle) {
 pub fn run() {
     logger::init_logger();
 
+    if std::env::args().skip(1).any(|arg| arg == "--daemon") {
+        modules::daemon::run();
+        return;
+    }
+
     #[cfg(target_os = "linux")]
     {
         if std::env::var_os("WEBKIT_DISABLE_DMABUF_RENDERER").is_none() {
@@ -218,6 +236,11 @@ pub fn run() {
             commands::system::get_downloads_dir,
             commands::system::get_network_config,
             commands::system::save_network_config,
+            commands::system::ws_list_clients,
+            commands::system::ws_update_client_capabilities,
+            commands::system::ws_revoke_client,
+            commands::system::ws_get_pairing_secret,
+            commands::system::ws_reset_pairing_secret,
             commands::system::get_general_config,
             commands::system::save_general_config,
             commands::system::save_tray_platform_layout,
@@ -232,6 +255,7 @@ pub fn run() {
             commands::wakeup::trigger_wakeup,
             commands::wakeup::fetch_available_models,
             commands::wakeup::wakeup_sync_state,
+            commands::wakeup::wakeup_preview_crontab,
             commands::wakeup::wakeup_load_history,
             commands::wakeup::wakeup_add_history,
             commands::wakeup::wakeup_clear_history,
@@ -294,16 +318,6 @@ pub fn run() {
             commands::github_copilot::update_github_copilot_account_tags,
             commands::github_copilot::get_github_copilot_accounts_index_path,
             commands::github_copilot::inject_github_copilot_to_vscode,
-            // GitHub Copilot Instance Commands
-            commands::github_copilot_instance::github_copilot_get_instance_defaults,
-            commands::github_copilot_instance::github_copilot_list_instances,
-            commands::github_copilot_instance::github_copilot_create_instance,
-            commands::github_copilot_instance::github_copilot_update_instance,
-            commands::github_copilot_instance::github_copilot_delete_instance,
-            commands::github_copilot_instance::github_copilot_start_instance,
-            commands::github_copilot_instance::github_copilot_stop_instance,
-            commands::github_copilot_instance::github_copilot_open_instance_window,
-            commands::github_copilot_instance::github_copilot_close_all_instances,
             // Windsurf Commands
             commands::windsurf::list_windsurf_accounts,
             commands::windsurf::delete_windsurf_account,
@@ -337,36 +351,6 @@ pub fn run() {
             commands::kiro::update_kiro_account_tags,
             commands::kiro::get_kiro_accounts_index_path,
             commands::kiro::inject_kiro_to_vscode,
-            // Windsurf Instance Commands
-            commands::windsurf_instance::windsurf_get_instance_defaults,
-            commands::windsurf_instance::windsurf_list_instances,
-            commands::windsurf_instance::windsurf_create_instance,
-            commands::windsurf_instance::windsurf_update_instance,
-            commands::windsurf_instance::windsurf_delete_instance,
-            commands::windsurf_instance::windsurf_start_instance,
-            commands::windsurf_instance::windsurf_stop_instance,
-            commands::windsurf_instance::windsurf_open_instance_window,
-            commands::windsurf_instance::windsurf_close_all_instances,
-            // Kiro Instance Commands
-            commands::kiro_instance::kiro_get_instance_defaults,
-            commands::kiro_instance::kiro_list_instances,
-            commands::kiro_instance::kiro_create_instance,
-            commands::kiro_instance::kiro_update_instance,
-            commands::kiro_instance::kiro_delete_instance,
-            commands::kiro_instance::kiro_start_instance,
-            commands::kiro_instance::kiro_stop_instance,
-            commands::kiro_instance::kiro_open_instance_window,
-            commands::kiro_instance::kiro_close_all_instances,
-            // Codex Instance Commands
-            commands::codex_instance::codex_get_instance_defaults,
-            commands::codex_instance::codex_list_instances,
-            commands::codex_instance::codex_create_instance,
-            commands::codex_instance::codex_update_instance,
-            commands::codex_instance::codex_delete_instance,
-            commands::codex_instance::codex_start_instance,
-            commands::codex_instance::codex_stop_instance,
-            commands::codex_instance::codex_open_instance_window,
-            commands::codex_instance::codex_close_all_instances,
             // Instance Commands
             commands::instance::get_instance_defaults,
             commands::instance::list_instances,
@@ -380,6 +364,21 @@ pub fn run() {
             // API Proxy Commands
             commands::api_proxy::get_api_proxy_config,
             commands::api_proxy::save_api_proxy_config,
+            commands::api_proxy::list_api_proxy_keys,
+            commands::api_proxy::create_api_proxy_key,
+            commands::api_proxy::update_api_proxy_key,
+            commands::api_proxy::delete_api_proxy_key,
+            commands::api_proxy::get_api_proxy_model_routes,
+            commands::api_proxy::save_api_proxy_model_routes,
+            commands::api_proxy::get_api_proxy_tls_settings,
+            commands::api_proxy::save_api_proxy_tls_settings,
+            commands::api_proxy::reset_api_proxy_tls_cert,
+            commands::api_proxy::get_api_proxy_audit_settings,
+            commands::api_proxy::save_api_proxy_audit_settings,
+            commands::api_proxy::list_api_proxy_audit_records,
+            commands::api_proxy::get_api_proxy_audit_record,
+            commands::api_proxy::clear_api_proxy_audit_records,
+            commands::api_proxy::replay_api_proxy_audit_record,
             commands::api_proxy::get_api_proxy_status,
             commands::api_proxy::start_api_proxy,
             commands::api_proxy::stop_api_proxy,
@@ -387,15 +386,26 @@ pub fn run() {
             commands::api_proxy::fetch_models_for_account,
             commands::api_proxy::fetch_codex_models,
             commands::api_proxy::fetch_kiro_models,
+            commands::api_proxy::get_api_proxy_usage,
             // Warp Commands
             commands::warp::get_warp_accounts,
             commands::warp::delete_warp_accounts,
             commands::warp::update_warp_account_tags,
+            // Credential Vault Commands
+            commands::credential_vault::vault_get_status,
+            commands::credential_vault::vault_enable,
+            commands::credential_vault::vault_unlock,
+            commands::credential_vault::vault_lock,
+            commands::credential_vault::vault_migrate,
+            commands::credential_vault::vault_disable,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application");
 
     app.run(|app_handle, event| {
+        if matches!(event, tauri::RunEvent::Exit) {
+            modules::api_proxy_usage::flush_usage_ledger();
+        }
         #[cfg(target_os = "macos")]
         {
             if let RunEvent::Reopen { .. } = event {
@@ -100,7 +100,7 @@ pub fn load_account(account_id: &str) -> Result<Account, String> {
     let content =
         fs::read_to_string(&account_path).map_err(|e| format!("读取账号数据失败: {}", e))?;
 
-    serde_json::from_str(&content).map_err(|e| format!("解析账号数据失败: {}", e))
+    modules::credential_vault::open_json(&content).map_err(|e| format!("解析账号数据失败: {}", e))
 }
 
 /// 保存账号数据
@@ -108,10 +108,11 @@ pub fn save_account(account: &Account) -> Result<(), String> {
     let accounts_dir = get_accounts_dir()?;
     let account_path = accounts_dir.join(format!("{}.json", account.id));
 
-    let content =
-        serde_json::to_string_pretty(account).map_err(|e| format!("序列化账号数据失败: {}", e))?;
+    let content = modules::credential_vault::seal_json(account)
+        .map_err(|e| format!("序列化账号数据失败: {}", e))?;
 
-    fs::write(&account_path, content).map_err(|e| format!("保存账号数据失败: {}", e))
+    modules::credential_vault::write_private_file(&account_path, content.as_bytes())
+        .map_err(|e| format!("保存账号数据失败: {}", e))
 }
 
 fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, String> {
@@ -1077,6 +1078,9 @@ pub async fn refresh_all_quotas_logic() -> Result<RefreshStats, String> {
         elapsed.as_millis()
     ));
 
+    if success > 0 {
+        modules::websocket::broadcast_quota_updated(modules::websocket_platforms::Platform::Antigravity);
+    }
     Ok(RefreshStats {
         total,
         success,
@@ -5,7 +5,7 @@
 
 use axum::{
     body::Body,
-    extract::{Path, State},
+    extract::{ConnectInfo, Path, State},
     http::{HeaderMap, Method, StatusCode},
     response::{IntoResponse, Response},
     routing::{any, get},
@@ -14,14 +14,15 @@ use axum::{
 use reqwest::{Client, Proxy};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::net::SocketAddr;
-use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, OnceLock, RwLock};
 use tokio::sync::watch;
 use tower_http::cors::{Any, CorsLayer};
 
+use super::api_proxy_keys::{ApiKeyRejection, ApiKeyScope};
 use super::config;
 use super::logger;
 
@@ -191,13 +192,27 @@ pub struct ProviderProxyConfig {
     #[serde(default)]
     pub enabled: bool,
 
-    /// 负载均衡策略: "round_robin" | "random" | "single"
+    /// 负载均衡策略: "round_robin" | "random" | "single" | "least_used" | "quota_weighted"
+    /// - least_used: 优先选择请求模型剩余配额最多的账号
+    /// - quota_weighted: 按剩余配额加权随机
     #[serde(default = "default_strategy")]
     pub strategy: String,
 
     /// 参与轮询的账号 ID 列表（空 = 全部）
     #[serde(default)]
     pub account_ids: Vec<String>,
+
+    /// 单个账号同时进行的请求数上限（0 = 不限制）
+    #[serde(default)]
+    pub max_concurrent_per_account: u32,
+
+    /// 所有账号槽位占满时的最长排队时间（秒），超时返回 429；0 = 不排队
+    #[serde(default = "default_queue_timeout_secs")]
+    pub queue_timeout_secs: u64,
+
+    /// 会话粘滞时长（秒）：同一会话在闲置超过该时长前固定使用同一账号；0 = 关闭
+    #[serde(default = "default_session_ttl_secs")]
+    pub session_ttl_secs: u64,
 }
 
 fn default_proxy_port() -> u16 {
@@ -212,6 +227,14 @@ fn default_strategy() -> String {
     "round_robin".to_string()
 }
 
+fn default_queue_timeout_secs() -> u64 {
+    30
+}
+
+fn default_session_ttl_secs() -> u64 {
+    30 * 60
+}
+
 fn default_warp_api_url() -> String {
     "http://127.0.0.1:8010".to_string()
 }
@@ -225,6 +248,9 @@ impl Default for ApiProxyConfig {
                 enabled: false,
                 strategy: "round_robin".to_string(),
                 account_ids: Vec::new(),
+                max_concurrent_per_account: 0,
+                queue_timeout_secs: default_queue_timeout_secs(),
+                session_ttl_secs: default_session_ttl_secs(),
             },
         );
         providers.insert(
@@ -233,6 +259,9 @@ impl Default for ApiProxyConfig {
                 enabled: false,
                 strategy: "round_robin".to_string(),
                 account_ids: Vec::new(),
+                max_concurrent_per_account: 0,
+                queue_timeout_secs: default_queue_timeout_secs(),
+                session_ttl_secs: default_session_ttl_secs(),
             },
         );
         providers.insert(
@@ -241,6 +270,9 @@ impl Default for ApiProxyConfig {
                 enabled: false,
                 strategy: "round_robin".to_string(),
                 account_ids: Vec::new(),
+                max_concurrent_per_account: 0,
+                queue_timeout_secs: default_queue_timeout_secs(),
+                session_ttl_secs: default_session_ttl_secs(),
             },
         );
         providers.insert(
@@ -249,6 +281,20 @@ impl Default for ApiProxyConfig {
                 enabled: false,
                 strategy: "round_robin".to_string(),
                 account_ids: Vec::new(),
+                max_concurrent_per_account: 0,
+                queue_timeout_secs: default_queue_timeout_secs(),
+                session_ttl_secs: default_session_ttl_secs(),
+            },
+        );
+        providers.insert(
+            "github_copilot".to_string(),
+            ProviderProxyConfig {
+                enabled: false,
+                strategy: "round_robin".to_string(),
+                account_ids: Vec::new(),
+                max_concurrent_per_account: 0,
+                queue_timeout_secs: default_queue_timeout_secs(),
+                session_ttl_secs: default_session_ttl_secs(),
             },
         );
         providers.insert(
@@ -257,6 +303,9 @@ impl Default for ApiProxyConfig {
                 enabled: false,
                 strategy: "round_robin".to_string(),
                 account_ids: Vec::new(),
+                max_concurrent_per_account: 0,
+                queue_timeout_secs: default_queue_timeout_secs(),
+                session_ttl_secs: default_session_ttl_secs(),
             },
         );
 
@@ -280,6 +329,9 @@ impl Default for ProviderProxyConfig {
             enabled: false,
             strategy: "round_robin".to_string(),
             account_ids: Vec::new(),
+            max_concurrent_per_account: 0,
+            queue_timeout_secs: default_queue_timeout_secs(),
+            session_ttl_secs: default_session_ttl_secs(),
         }
     }
 }
@@ -300,6 +352,22 @@ struct ProviderUpstream {
 
 
 
+/// Copilot API 要求的编辑器请求头
+const COPILOT_EDITOR_HEADERS: &[(&str, &str)] = &[
+    ("Editor-Version", "vscode/1.99.0"),
+    ("Editor-Plugin-Version", "copilot-chat/0.26.0"),
+    ("Copilot-Integration-Id", "vscode-chat"),
+    ("Openai-Intent", "conversation-panel"),
+];
+
+/// 上游为 Copilot API（api.githubcopilot.com）的 Provider
+fn uses_copilot_api(provider: &str) -> bool {
+    matches!(provider, "github_copilot" | "windsurf")
+}
+
+/// copilot_token 剩余有效期低于该值时提前刷新
+const COPILOT_TOKEN_REFRESH_MARGIN_SECS: i64 = 300;
+
 fn get_provider_upstream(provider: &str) -> Option<ProviderUpstream> {
     match provider {
         "antigravity" => Some(ProviderUpstream {
@@ -323,6 +391,11 @@ fn get_provider_upstream(provider: &str) -> Option<ProviderUpstream> {
             auth_header: "Authorization",
             auth_prefix: "Bearer ",
         }),
+        "github_copilot" => Some(ProviderUpstream {
+            base_url: "https://api.githubcopilot.com",
+            auth_header: "Authorization",
+            auth_prefix: "Bearer ",
+        }),
         "warp" => Some(ProviderUpstream {
             base_url: "https://app.warp.dev",
             auth_header: "Authorization",
@@ -343,12 +416,32 @@ pub struct ProxyStatus {
     pub port: u16,
     pub actual_port: Option<u16>,
     pub enabled_providers: Vec<String>,
+    /// 因 429/402/403 暂时移出账号池的账号
+    #[serde(default)]
+    pub cooldowns: Vec<super::api_proxy_cooldown::AccountCooldownInfo>,
+    /// 是否以 HTTPS 提供服务
+    #[serde(default)]
+    pub tls: bool,
+    /// HTTPS 证书 SHA-256 指纹，供客户端固定证书
+    #[serde(default)]
+    pub tls_fingerprint: Option<String>,
+    /// 有请求进行中的账号并发占用
+    #[serde(default)]
+    pub queues: Vec<super::api_proxy_limiter::AccountSlotInfo>,
+    /// 各 Provider 排队等待并发槽位的请求数
+    #[serde(default)]
+    pub queued: HashMap<String, usize>,
+    /// 各 Provider 粘滞中的会话数
+    #[serde(default)]
+    pub sessions: HashMap<String, usize>,
 }
 
 /// 账号凭据（包含 token + project_id + 域名选择标记）
 #[derive(Debug, Clone)]
 struct AccountCredential {
     id: String,
+    /// 账号邮箱（Windsurf 为 GitHub 登录名），用于日志与用量账本展示
+    email: String,
     access_token: String,
     project_id: String,
     /// GCP ToS 账号走 prod 端点
@@ -357,90 +450,318 @@ struct AccountCredential {
     profile_arn: Option<String>,
     /// Codex/ChatGPT 的 account_id（用于 ChatGPT-Account-Id 请求头）
     chatgpt_account_id: Option<String>,
+    /// 剩余配额百分比 (模型名, 0-100)；"*" 表示与模型无关的账号级配额，空 = 无配额数据
+    quota_percentages: Vec<(String, i32)>,
+    /// 受配额保护的模型（配额感知策略下跳过）
+    protected_models: HashSet<String>,
 }
 
 /// 代理服务器内部状态
 /// 使用双客户端：Antigravity/Kiro 等直连，Codex 单独可选走代理，避免代理影响 Google 等上游
 struct ProxyServerState {
     config: RwLock<ApiProxyConfig>,
-    /// 直连客户端，用于 Antigravity、Kiro、Windsurf 等（不走系统代理，避免代理仅允许 ChatGPT 时导致 Google 不可用）
-    http_client: Client,
-    /// 仅 Codex 使用：若配置了系统代理则走代理，否则与 http_client 行为一致
-    codex_http_client: Client,
+    /// 上游 HTTP 客户端，request_timeout 变更时热替换
+    clients: RwLock<UpstreamClients>,
     round_robin_counters: RwLock<HashMap<String, AtomicUsize>>,
+    /// 账号冷却表（所有 Provider 共享）
+    cooldowns: super::api_proxy_cooldown::CooldownTable,
+    /// GET /v1/models 的模型列表缓存
+    model_catalog: super::api_proxy_models::ModelCatalogCache,
+    /// GET /metrics 导出的运行指标
+    metrics: Arc<super::api_proxy_metrics::ProxyMetrics>,
+    /// 单账号并发槽位与排队
+    limiter: super::api_proxy_limiter::ConcurrencyLimiter,
+    /// 会话 → 账号粘滞绑定
+    sessions: super::api_proxy_sessions::SessionAffinity,
 }
 
-impl ProxyServerState {
-    fn new(config: ApiProxyConfig) -> Self {
-        let timeout = config.request_timeout;
-        let http_client = Client::builder()
+/// 上游 HTTP 客户端
+#[derive(Clone)]
+struct UpstreamClients {
+    /// 直连客户端，用于 Antigravity、Kiro、Windsurf 等（不走系统代理，避免代理仅允许 ChatGPT 时导致 Google 不可用）
+    direct: Client,
+    /// 仅 Codex 使用：若配置了系统代理则走代理，否则与 direct 行为一致
+    codex: Client,
+}
+
+impl UpstreamClients {
+    fn build(timeout: u64) -> Self {
+        let direct = Client::builder()
             .timeout(std::time::Duration::from_secs(timeout))
             .build()
             .unwrap_or_default();
 
-        let codex_http_client = if let Some(proxy_url) = resolve_http_proxy() {
+        let codex = if let Some(proxy_url) = resolve_http_proxy() {
             let mut builder = Client::builder().timeout(std::time::Duration::from_secs(timeout));
             if let Ok(proxy) = Proxy::all(&proxy_url) {
                 builder = builder.proxy(proxy);
                 logger::log_info(&format!("[ApiProxy] Codex 使用网络代理: {}", proxy_url));
             }
-            builder.build().unwrap_or_else(|_| http_client.clone())
+            builder.build().unwrap_or_else(|_| direct.clone())
         } else {
-            http_client.clone()
+            direct.clone()
         };
+        Self { direct, codex }
+    }
+}
 
+impl ProxyServerState {
+    fn new(config: ApiProxyConfig) -> Self {
+        let clients = UpstreamClients::build(config.request_timeout);
         Self {
             config: RwLock::new(config),
-            http_client,
-            codex_http_client,
+            clients: RwLock::new(clients),
             round_robin_counters: RwLock::new(HashMap::new()),
+            cooldowns: super::api_proxy_cooldown::CooldownTable::new(),
+            model_catalog: super::api_proxy_models::ModelCatalogCache::new(),
+            metrics: Arc::new(super::api_proxy_metrics::ProxyMetrics::new()),
+            limiter: super::api_proxy_limiter::ConcurrencyLimiter::new(),
+            sessions: super::api_proxy_sessions::SessionAffinity::new(),
+        }
+    }
+
+    fn http_client(&self) -> Client {
+        self.clients.read().map(|c| c.direct.clone()).unwrap_or_default()
+    }
+
+    fn codex_http_client(&self) -> Client {
+        self.clients.read().map(|c| c.codex.clone()).unwrap_or_default()
+    }
+
+    /// 将新配置应用到运行中的服务：Provider 启停、账号、策略、API Key 下一个请求即生效，
+    /// 超时变更时重建上游客户端（进行中的请求继续使用旧客户端）；端口与监听地址由 rebind_proxy_if_needed 处理
+    fn apply_config(&self, new_config: ApiProxyConfig) {
+        let timeout_changed = self
+            .config
+            .read()
+            .map(|c| c.request_timeout != new_config.request_timeout)
+            .unwrap_or(true);
+        if timeout_changed {
+            if let Ok(mut clients) = self.clients.write() {
+                *clients = UpstreamClients::build(new_config.request_timeout);
+            }
+            logger::log_info(&format!(
+                "[ApiProxy] 上游请求超时已更新为 {} 秒",
+                new_config.request_timeout
+            ));
+        }
+        if let Ok(mut cfg) = self.config.write() {
+            *cfg = new_config;
         }
     }
 
-    /// 获取下一个要使用的账号凭据
+    /// 选号并占用该账号的并发槽位
     /// override_account_email: 请求头 X-Selected-Account-Email，优先于配置中的 selected_account_email，确保本次请求使用指定账号
-    async fn get_next_credential(
+    /// model: 请求的模型，供配额感知策略按模型比较剩余配额
+    /// 策略选中的账号已满时换用其它有空位的账号，全部占满则排队等待，超时返回 Saturated；
+    /// session: 会话标识，选中的账号会绑定到该会话，后续请求优先使用
+    async fn checkout_credential(
         &self,
         provider: &str,
         override_account_email: Option<&str>,
+        model: Option<&str>,
+        caller: &ApiKeyScope,
+        session: Option<&str>,
+    ) -> Result<(AccountCredential, Option<super::api_proxy_limiter::SlotPermit>), CheckoutError> {
+        let (limit, queue_timeout, session_ttl) = {
+            let config = self.config.read().map_err(|e| CheckoutError::Unavailable(format!("锁读取失败: {}", e)))?;
+            config
+                .providers
+                .get(provider)
+                .map(|p| (p.max_concurrent_per_account as usize, p.queue_timeout_secs, p.session_ttl_secs))
+                .unwrap_or((0, 0, 0))
+        };
+        let mut ranked = self
+            .ranked_credentials(provider, override_account_email, model, caller, session)
+            .await
+            .map_err(CheckoutError::Unavailable)?;
+        // 显式指定账号的请求不参与粘滞
+        let pin = |cred: &AccountCredential| {
+            if let Some(session) = session.filter(|_| session_ttl > 0 && override_account_email.is_none()) {
+                let now = chrono::Utc::now().timestamp();
+                self.sessions.pin(provider, &caller.key_id, session, &cred.id, now, session_ttl);
+            }
+        };
+        if limit == 0 {
+            let cred = ranked.swap_remove(0);
+            pin(&cred);
+            return Ok((cred, None));
+        }
+
+        let ids: Vec<&str> = ranked.iter().map(|c| c.id.as_str()).collect();
+        let acquired = self
+            .limiter
+            .acquire(provider, &ids, limit, std::time::Duration::from_secs(queue_timeout))
+            .await;
+        match acquired {
+            Ok((idx, permit)) => {
+                if !permit.waited.is_zero() {
+                    self.metrics.record_queue_wait(provider, permit.waited);
+                }
+                let cred = ranked.swap_remove(idx);
+                pin(&cred);
+                Ok((cred, Some(permit)))
+            }
+            Err(timeout) => {
+                self.metrics.record_queue_rejection(provider);
+                Err(CheckoutError::Saturated(format!(
+                    "Provider '{}' 的账号并发已满（每账号 {} 个），排队 {} 秒后仍无空位",
+                    provider,
+                    limit,
+                    timeout.waited.as_secs()
+                )))
+            }
+        }
+    }
+
+    /// 重试换号：先归还当前账号的并发槽位，再经 checkout_credential 重新选号占位，
+    /// 重试请求同样受 max_concurrent_per_account 限制
+    async fn checkout_for_retry(
+        &self,
+        provider: &str,
+        model: Option<&str>,
+        caller: &ApiKeyScope,
+        slot: &super::api_proxy_limiter::HeldSlot,
     ) -> Result<AccountCredential, String> {
-        let (enabled, strategy) = {
+        slot.release();
+        let (cred, permit) = self
+            .checkout_credential(provider, None, model, caller, None)
+            .await
+            .map_err(CheckoutError::into_message)?;
+        slot.hold(permit);
+        Ok(cred)
+    }
+
+    /// 按策略排序的可用凭据：首个为策略选中的账号，其余为策略允许的候选，按轮转顺序排列
+    /// 会话已绑定且该账号仍可用（未冷却）时只返回该账号，不再换号
+    async fn ranked_credentials(
+        &self,
+        provider: &str,
+        override_account_email: Option<&str>,
+        model: Option<&str>,
+        caller: &ApiKeyScope,
+        session: Option<&str>,
+    ) -> Result<Vec<AccountCredential>, String> {
+        if super::credential_vault::is_locked() {
+            return Err("凭据库已锁定，请在应用中解锁后重试".to_string());
+        }
+        let (enabled, strategy, session_ttl) = {
             let config = self.config.read().map_err(|e| format!("锁读取失败: {}", e))?;
             let provider_config = config
                 .providers
                 .get(provider)
                 .ok_or_else(|| format!("Provider '{}' 未配置", provider))?;
-            (provider_config.enabled, provider_config.strategy.clone())
+            (
+                provider_config.enabled,
+                provider_config.strategy.clone(),
+                provider_config.session_ttl_secs,
+            )
         };
 
         if !enabled {
             return Err(format!("Provider '{}' 未启用", provider));
         }
 
-        let creds = self.get_available_credentials(provider, override_account_email).await?;
+        let mut creds = self.get_available_credentials(provider, override_account_email).await?;
+
+        // 命名 API Key 限定了可用账号
+        creds.retain(|c| caller.allows_account(&c.id, &c.email));
 
         if creds.is_empty() {
             return Err(format!("Provider '{}' 没有可用的账号", provider));
         }
 
-        // 根据策略选择账号
-        let idx = match strategy.as_str() {
-            "random" => rand::random::<usize>() % creds.len(),
-            "single" => 0,
+        // 跳过冷却中的账号（显式指定账号时不跳过）
+        if override_account_email.is_none() {
+            let now = chrono::Utc::now().timestamp();
+            let (cooling, ready): (Vec<_>, Vec<_>) = creds
+                .into_iter()
+                .partition(|c| self.cooldowns.is_cooling(provider, &c.id, now));
+            if ready.is_empty() {
+                let wait = self
+                    .cooldowns
+                    .earliest_release(provider, cooling.iter().map(|c| c.id.as_str()))
+                    .map(|until| until - now)
+                    .unwrap_or(0);
+                return Err(format!(
+                    "Provider '{}' 的账号均在冷却中，最早 {} 秒后恢复",
+                    provider, wait
+                ));
+            }
+            creds = ready;
+
+            // 会话粘滞：绑定的账号进入冷却（额度不足或鉴权失败）后才按策略改选
+            if let Some(session) = session.filter(|_| session_ttl > 0) {
+                let pinned = self.sessions.pinned(provider, &caller.key_id, session, now);
+                if let Some(pos) = pinned.and_then(|id| creds.iter().position(|c| c.id == id)) {
+                    return Ok(vec![creds.swap_remove(pos)]);
+                }
+            }
+        }
+
+        // 根据策略选择账号；allowed 为策略允许溢出的候选（None = 全部）
+        let (idx, allowed) = match strategy.as_str() {
+            "random" => (rand::random::<usize>() % creds.len(), None),
+            "single" => (0, Some(vec![0])),
+            "least_used" | "quota_weighted" => {
+                let threshold = super::config::get_user_config().auto_switch_threshold.clamp(0, 100);
+                let candidates = quota_aware_candidates(&creds, model, threshold)?;
+                let idx = if strategy == "quota_weighted" {
+                    pick_quota_weighted(&candidates, rand::random::<u64>())
+                } else {
+                    // 剩余配额并列最高的账号之间轮询，避免总是命中同一个
+                    let best = candidates.iter().map(|(_, pct)| *pct).max().unwrap_or(0);
+                    let top: Vec<usize> = candidates
+                        .iter()
+                        .filter(|(_, pct)| *pct == best)
+                        .map(|(i, _)| *i)
+                        .collect();
+                    top[self.next_round_robin(provider)? % top.len()]
+                };
+                (idx, Some(candidates.into_iter().map(|(i, _)| i).collect()))
+            }
             _ => {
                 // round_robin (默认)
-                let mut counters = self
-                    .round_robin_counters
-                    .write()
-                    .map_err(|e| format!("锁写入失败: {}", e))?;
-                let counter = counters
-                    .entry(provider.to_string())
-                    .or_insert_with(|| AtomicUsize::new(0));
-                counter.fetch_add(1, Ordering::Relaxed) % creds.len()
+                (self.next_round_robin(provider)? % creds.len(), None)
             }
         };
 
-        Ok(creds[idx].clone())
+        Ok(order_by_strategy(creds, idx, allowed.as_deref()))
+    }
+
+    /// 上游返回 429/402/403 时冷却账号
+    fn trip_cooldown(
+        &self,
+        provider: &str,
+        cred: &AccountCredential,
+        status: u16,
+        retry_after: Option<&str>,
+        body: &str,
+    ) {
+        let now = chrono::Utc::now().timestamp();
+        let reason = super::api_proxy_cooldown::summarize_reason(body);
+        let cause = super::api_proxy_cooldown::CooldownCause {
+            status,
+            reset_seconds: super::api_proxy_cooldown::parse_reset_seconds(retry_after, body, now),
+            reason: &reason,
+        };
+        let until = self.cooldowns.trip(provider, &cred.id, &cred.email, cause, now);
+        self.metrics.record_cooldown(provider, &cred.id, &cred.email);
+        logger::log_warn(&format!(
+            "[ApiProxy] {} 账号 {} 进入冷却 (HTTP {})，{} 秒后恢复",
+            provider, cred.email, status, until - now
+        ));
+    }
+
+    fn next_round_robin(&self, provider: &str) -> Result<usize, String> {
+        let mut counters = self
+            .round_robin_counters
+            .write()
+            .map_err(|e| format!("锁写入失败: {}", e))?;
+        let counter = counters
+            .entry(provider.to_string())
+            .or_insert_with(|| AtomicUsize::new(0));
+        Ok(counter.fetch_add(1, Ordering::Relaxed))
     }
 
     /// 按账号 ID 获取指定凭据（用于 403 刷新后重试同一账号）
@@ -449,12 +770,42 @@ impl ProxyServerState {
         Ok(creds.into_iter().find(|c| c.id == account_id))
     }
 
-    /// 获取 Provider 的可用凭据列表
+    /// 获取 Provider 的可用凭据列表（已按配置的 account_ids 过滤）
     /// override_account_email: 若为 Some，则仅返回该邮箱的凭据（请求头 X-Selected-Account-Email 优先于配置）
     async fn get_available_credentials(
         &self,
         provider: &str,
         override_account_email: Option<&str>,
+    ) -> Result<Vec<AccountCredential>, String> {
+        let mut creds = self.load_provider_credentials(provider, override_account_email).await?;
+        self.retain_configured_accounts(provider, &mut creds);
+        Ok(creds)
+    }
+
+    /// 仅保留 Provider 配置 account_ids 中列出的账号（按 ID 或邮箱匹配，空列表 = 全部）
+    /// 每次选号时读取当前配置，修改 account_ids 后下一个请求即生效
+    fn retain_configured_accounts(&self, provider: &str, creds: &mut Vec<AccountCredential>) {
+        let account_ids = self
+            .config
+            .read()
+            .ok()
+            .and_then(|c| c.providers.get(provider).map(|p| p.account_ids.clone()))
+            .unwrap_or_default();
+        if account_ids.is_empty() {
+            return;
+        }
+        creds.retain(|c| {
+            account_ids
+                .iter()
+                .any(|id| *id == c.id || id.eq_ignore_ascii_case(&c.email))
+        });
+    }
+
+    /// 从各平台账号存储读取凭据（未按 account_ids 过滤）
+    async fn load_provider_credentials(
+        &self,
+        provider: &str,
+        override_account_email: Option<&str>,
     ) -> Result<Vec<AccountCredential>, String> {
         match provider {
             "antigravity" => {
@@ -479,11 +830,14 @@ impl ProxyServerState {
                     })
                     .map(|a| AccountCredential {
                         id: a.id.clone(),
+                        email: a.email.clone(),
                         access_token: a.token.access_token.clone(),
                         project_id: a.token.project_id.clone().unwrap_or_default(),
                         is_gcp_tos: a.token.is_gcp_tos.unwrap_or(false),
                         profile_arn: None,
                         chatgpt_account_id: None,
+                        quota_percentages: antigravity_quota_percentages(a),
+                        protected_models: a.protected_models.clone(),
                     })
                     .collect();
                 if creds.is_empty() && !selected_email.is_empty() {
@@ -492,7 +846,8 @@ impl ProxyServerState {
                 Ok(creds)
             }
             "codex" => {
-                let accounts = super::codex_account::list_accounts();
+                let accounts = super::codex_account::list_accounts()
+                    .map_err(|e| format!("获取账号失败: {}", e))?;
                 let selected_email = override_account_email
                     .map(|s| s.to_string())
                     .or_else(|| {
@@ -515,11 +870,14 @@ impl ProxyServerState {
                         });
                         AccountCredential {
                             id: a.id.clone(),
+                            email: a.email.clone(),
                             access_token: a.tokens.access_token.clone(),
                             project_id: String::new(),
                             is_gcp_tos: false,
                             profile_arn: None,
                             chatgpt_account_id,
+                            quota_percentages: codex_quota_percentages(a),
+                            protected_models: HashSet::new(),
                         }
                     })
                     .collect();
@@ -529,7 +887,8 @@ impl ProxyServerState {
                 Ok(creds)
             }
             "kiro" => {
-                let mut accounts = super::kiro_account::list_accounts();
+                let mut accounts = super::kiro_account::list_accounts()
+                    .map_err(|e| format!("获取账号失败: {}", e))?;
                 let now = chrono::Utc::now().timestamp();
                 for account in accounts.iter_mut() {
                     let expires = account.expires_at.unwrap_or(now + 3600);
@@ -565,11 +924,14 @@ impl ProxyServerState {
                                 .and_then(|v| v.get("profileArn").and_then(|p| p.as_str()).map(|s| s.to_string())));
                         AccountCredential {
                             id: a.id.clone(),
+                            email: a.email.clone(),
                             access_token: a.access_token.clone(),
                             project_id: a.idc_region.clone().unwrap_or_else(|| "us-east-1".to_string()),
                             is_gcp_tos: false,
                             profile_arn,
                             chatgpt_account_id: None,
+                            quota_percentages: kiro_quota_percentages(a),
+                            protected_models: HashSet::new(),
                         }
                     })
                     .collect();
@@ -579,7 +941,8 @@ impl ProxyServerState {
                 Ok(creds)
             }
             "windsurf" => {
-                let mut accounts = super::windsurf_account::list_accounts();
+                let mut accounts = super::windsurf_account::list_accounts()
+                    .map_err(|e| format!("获取账号失败: {}", e))?;
                 let now = chrono::Utc::now().timestamp();
                 for account in accounts.iter_mut() {
                     let expires = account.copilot_expires_at.unwrap_or(now + 3600);
@@ -597,27 +960,87 @@ impl ProxyServerState {
                     .filter(|a| !a.copilot_token.is_empty())
                     .map(|a| AccountCredential {
                         id: a.id.clone(),
+                        email: a.github_login.clone(),
+                        access_token: a.copilot_token.clone(),
+                        project_id: String::new(),
+                        is_gcp_tos: false,
+                        profile_arn: None,
+                        chatgpt_account_id: None,
+                        quota_percentages: Vec::new(),
+                        protected_models: HashSet::new(),
+                    })
+                    .collect();
+                Ok(creds)
+            }
+            "github_copilot" => {
+                let mut accounts = super::github_copilot_account::list_accounts()
+                    .map_err(|e| format!("获取账号失败: {}", e))?;
+                let now = chrono::Utc::now().timestamp();
+                for account in accounts.iter_mut() {
+                    // copilot_token 有效期约 30 分钟，临近过期时用 GitHub access token 换新
+                    let expires = account.copilot_expires_at.unwrap_or(now + 3600);
+                    if expires < now + COPILOT_TOKEN_REFRESH_MARGIN_SECS {
+                        match super::github_copilot_account::refresh_account_token(&account.id).await {
+                            Ok(refreshed) => *account = refreshed,
+                            Err(e) => logger::log_warn(&format!(
+                                "[ApiProxy] GitHub Copilot 账号 {} 刷新失败: {}",
+                                account.github_login, e
+                            )),
+                        }
+                    }
+                }
+                let selected_email = override_account_email
+                    .map(|s| s.to_string())
+                    .or_else(|| {
+                        self.config.read().ok().map(|c| c.selected_account_email.clone())
+                    })
+                    .unwrap_or_default();
+                let creds: Vec<AccountCredential> = accounts
+                    .iter()
+                    .filter(|a| !a.copilot_token.is_empty() && a.copilot_chat_enabled != Some(false))
+                    .filter(|a| a.copilot_expires_at.is_none_or(|exp| exp > now))
+                    .filter(|a| {
+                        selected_email.is_empty()
+                            || a.github_login == selected_email
+                            || a.github_email.as_deref() == Some(selected_email.as_str())
+                    })
+                    .map(|a| AccountCredential {
+                        id: a.id.clone(),
+                        email: a
+                            .github_email
+                            .clone()
+                            .filter(|e| !e.trim().is_empty())
+                            .unwrap_or_else(|| a.github_login.clone()),
                         access_token: a.copilot_token.clone(),
                         project_id: String::new(),
                         is_gcp_tos: false,
                         profile_arn: None,
                         chatgpt_account_id: None,
+                        quota_percentages: super::github_copilot_account::proxy_quota_percentages(a),
+                        protected_models: HashSet::new(),
                     })
                     .collect();
+                if creds.is_empty() && !selected_email.is_empty() {
+                    return Err(format!("指定 GitHub Copilot 账号 {} 不可用或未找到", selected_email));
+                }
                 Ok(creds)
             }
             "warp" => {
-                let accounts = super::warp_account::list_accounts();
+                let accounts = super::warp_account::list_accounts()
+                    .map_err(|e| format!("获取账号失败: {}", e))?;
                 let creds: Vec<AccountCredential> = accounts
                     .iter()
                     .filter(|a| !a.auth_token.is_empty())
                     .map(|a| AccountCredential {
                         id: a.id.clone(),
+                        email: a.email.clone(),
                         access_token: a.auth_token.clone(),
                         project_id: String::new(),
                         is_gcp_tos: false,
                         profile_arn: None,
                         chatgpt_account_id: None,
+                        quota_percentages: Vec::new(),
+                        protected_models: HashSet::new(),
                     })
                     .collect();
                 Ok(creds)
@@ -629,6 +1052,180 @@ impl ProxyServerState {
 
 type SharedState = Arc<ProxyServerState>;
 
+/// 选号失败的原因
+enum CheckoutError {
+    /// 没有可用账号（未启用、全部冷却等）
+    Unavailable(String),
+    /// 所有账号的并发槽位均已占满且排队超时
+    Saturated(String),
+}
+
+impl CheckoutError {
+    fn into_message(self) -> String {
+        match self {
+            CheckoutError::Unavailable(e) | CheckoutError::Saturated(e) => e,
+        }
+    }
+
+    fn into_response(self) -> Response {
+        match self {
+            CheckoutError::Unavailable(e) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                Json(serde_json::json!({"error": e})),
+            )
+                .into_response(),
+            CheckoutError::Saturated(e) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [("retry-after", super::api_proxy_limiter::QUEUE_RETRY_AFTER_SECS.to_string())],
+                Json(serde_json::json!({"error": e})),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// 读取字符串响应头
+fn header_str(headers: &reqwest::header::HeaderMap, name: &str) -> Option<String> {
+    headers.get(name).and_then(|v| v.to_str().ok()).map(|s| s.to_string())
+}
+
+// ============================================================================
+// 配额感知的账号选择
+// ============================================================================
+
+/// Antigravity：每个模型的剩余配额；配额被封禁时视为 0
+fn antigravity_quota_percentages(account: &crate::models::Account) -> Vec<(String, i32)> {
+    match account.quota.as_ref() {
+        Some(q) if q.is_forbidden => vec![("*".to_string(), 0)],
+        Some(q) => q.models.iter().map(|m| (m.name.clone(), m.percentage)).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Codex：5 小时与周窗口中较紧的一个，与模型无关
+fn codex_quota_percentages(account: &crate::models::codex::CodexAccount) -> Vec<(String, i32)> {
+    let Some(q) = account.quota.as_ref() else {
+        return Vec::new();
+    };
+    let mut pct = q.hourly_percentage;
+    if q.weekly_window_present.unwrap_or(true) {
+        pct = pct.min(q.weekly_percentage);
+    }
+    vec![("*".to_string(), pct.clamp(0, 100))]
+}
+
+/// Kiro：Prompt Credits 剩余比例，与模型无关
+fn kiro_quota_percentages(account: &crate::models::kiro::KiroAccount) -> Vec<(String, i32)> {
+    match (account.credits_total, account.credits_used) {
+        (Some(total), used) if total.is_finite() && total > 0.0 => {
+            let remaining = (total - used.unwrap_or(0.0)).max(0.0);
+            vec![("*".to_string(), ((remaining / total) * 100.0).round().clamp(0.0, 100.0) as i32)]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// 凭据对指定模型的剩余配额百分比；无配额数据时为 None
+/// 优先精确匹配模型名，其次账号级配额 "*"，再次前缀匹配，最后取各模型平均值
+fn credential_remaining_percentage(cred: &AccountCredential, model: Option<&str>) -> Option<i32> {
+    if cred.quota_percentages.is_empty() {
+        return None;
+    }
+    let model = model.map(|m| m.to_lowercase()).unwrap_or_default();
+    let find = |pred: &dyn Fn(&str) -> bool| {
+        cred.quota_percentages
+            .iter()
+            .find(|(name, _)| pred(&name.to_lowercase()))
+            .map(|(_, pct)| *pct)
+    };
+    if !model.is_empty() {
+        if let Some(pct) = find(&|name| name == model) {
+            return Some(pct);
+        }
+    }
+    if let Some(pct) = find(&|name| name == "*") {
+        return Some(pct);
+    }
+    if !model.is_empty() {
+        if let Some(pct) = find(&|name| model.starts_with(name) || name.starts_with(model.as_str())) {
+            return Some(pct);
+        }
+    }
+    let sum: i32 = cred.quota_percentages.iter().map(|(_, pct)| *pct).sum();
+    Some(sum / cred.quota_percentages.len() as i32)
+}
+
+/// 配额感知策略的候选集：(凭据下标, 剩余百分比)
+/// 跳过对该模型启用配额保护的账号，以及剩余配额低于自动切号阈值的账号；
+/// 无配额数据的账号视为 100%。全部低于阈值时退回未保护的账号，避免整个池不可用
+fn quota_aware_candidates(
+    creds: &[AccountCredential],
+    model: Option<&str>,
+    threshold: i32,
+) -> Result<Vec<(usize, i32)>, String> {
+    let unprotected: Vec<(usize, i32)> = creds
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| model.map(|m| !c.protected_models.contains(m)).unwrap_or(true))
+        .map(|(i, c)| (i, credential_remaining_percentage(c, model).unwrap_or(100)))
+        .collect();
+    if unprotected.is_empty() {
+        return Err(format!("所有账号均已对模型 {} 启用配额保护", model.unwrap_or_default()));
+    }
+    let above: Vec<(usize, i32)> = unprotected.iter().copied().filter(|(_, pct)| *pct > threshold).collect();
+    if above.is_empty() {
+        logger::log_warn(&format!(
+            "[ApiProxy] 所有账号剩余配额均不高于阈值 {}%，回退到全部未保护账号",
+            threshold
+        ));
+        return Ok(unprotected);
+    }
+    Ok(above)
+}
+
+/// Claude tool_choice → Gemini functionCallingConfig（auto 为默认行为，无需下发）
+fn google_function_calling_config(tool_choice: &Value) -> Option<Value> {
+    match tool_choice.get("type").and_then(|v| v.as_str())? {
+        "none" => Some(serde_json::json!({"mode": "NONE"})),
+        "any" => Some(serde_json::json!({"mode": "ANY"})),
+        "tool" => {
+            let name = tool_choice.get("name").and_then(|v| v.as_str())?;
+            Some(serde_json::json!({"mode": "ANY", "allowedFunctionNames": [name]}))
+        }
+        _ => None,
+    }
+}
+
+/// 选中的账号在前，其余 allowed 中的账号按轮转顺序在后（allowed 为 None 时保留全部）
+/// 并发槽位已满时只会溢出到这些账号
+fn order_by_strategy(
+    creds: Vec<AccountCredential>,
+    picked: usize,
+    allowed: Option<&[usize]>,
+) -> Vec<AccountCredential> {
+    let len = creds.len();
+    let mut slots: Vec<Option<AccountCredential>> = creds.into_iter().map(Some).collect();
+    (0..len)
+        .map(|offset| (picked + offset) % len)
+        .filter(|i| allowed.is_none_or(|allowed| allowed.contains(i)))
+        .filter_map(|i| slots[i].take())
+        .collect()
+}
+
+/// 按剩余配额加权随机选择（每个候选至少权重 1）
+fn pick_quota_weighted(candidates: &[(usize, i32)], seed: u64) -> usize {
+    let total: u64 = candidates.iter().map(|(_, pct)| (*pct).max(1) as u64).sum();
+    let mut point = seed % total.max(1);
+    for (idx, pct) in candidates {
+        let weight = (*pct).max(1) as u64;
+        if point < weight {
+            return *idx;
+        }
+        point -= weight;
+    }
+    candidates[0].0
+}
+
 // ============================================================================
 // 全局代理服务实例
 // ============================================================================
@@ -637,6 +1234,42 @@ static PROXY_RUNNING: OnceLock<AtomicBool> = OnceLock::new();
 static PROXY_ACTUAL_PORT: OnceLock<RwLock<Option<u16>>> = OnceLock::new();
 static PROXY_SHUTDOWN_TX: OnceLock<RwLock<Option<watch::Sender<()>>>> = OnceLock::new();
 static PROXY_STATE: OnceLock<RwLock<Option<SharedState>>> = OnceLock::new();
+/// 当前监听对应的 (配置端口, 是否允许局域网访问)
+static PROXY_BINDING: OnceLock<RwLock<Option<(u16, bool)>>> = OnceLock::new();
+/// 当前 HTTPS 监听的证书指纹（HTTP 监听时为 None）
+static PROXY_TLS_FINGERPRINT: OnceLock<RwLock<Option<String>>> = OnceLock::new();
+/// 每次绑定监听递增；旧监听排空退出时不影响新监听的运行状态
+static PROXY_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// 运行中代理的共享状态
+fn running_state() -> Option<SharedState> {
+    if !is_proxy_running() {
+        return None;
+    }
+    PROXY_STATE
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .and_then(|guard| guard.clone())
+}
+
+fn set_proxy_binding(binding: Option<(u16, bool)>) {
+    if let Ok(mut b) = PROXY_BINDING.get_or_init(|| RwLock::new(None)).write() {
+        *b = binding;
+    }
+}
+
+fn set_proxy_tls_fingerprint(fingerprint: Option<String>) {
+    if let Ok(mut f) = PROXY_TLS_FINGERPRINT.get_or_init(|| RwLock::new(None)).write() {
+        *f = fingerprint;
+    }
+}
+
+fn get_proxy_tls_fingerprint() -> Option<String> {
+    PROXY_TLS_FINGERPRINT
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .and_then(|f| f.clone())
+}
 
 fn is_proxy_running() -> bool {
     PROXY_RUNNING
@@ -684,10 +1317,15 @@ pub fn load_proxy_config() -> ApiProxyConfig {
     if !config_path.exists() {
         return ApiProxyConfig::default();
     }
-    match std::fs::read_to_string(&config_path) {
+    let mut proxy_config: ApiProxyConfig = match std::fs::read_to_string(&config_path) {
         Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
-        Err(_) => ApiProxyConfig::default(),
+        Err(_) => return ApiProxyConfig::default(),
+    };
+    // 旧配置缺少后续新增的 Provider，补齐默认项
+    for (name, provider_config) in ApiProxyConfig::default().providers {
+        proxy_config.providers.entry(name).or_insert(provider_config);
     }
+    proxy_config
 }
 
 /// 保存代理配置
@@ -701,20 +1339,7 @@ pub fn save_proxy_config(proxy_config: &ApiProxyConfig) -> Result<(), String> {
         .map_err(|e| format!("序列化配置失败: {}", e))?;
     std::fs::write(&config_path, json).map_err(|e| format!("写入配置失败: {}", e))?;
 
-    // 同步更新运行中代理的内存配置
-    if let Some(lock) = PROXY_STATE.get() {
-        if let Ok(guard) = lock.read() {
-            if let Some(state) = guard.as_ref() {
-                if let Ok(mut cfg) = state.config.write() {
-                    *cfg = proxy_config.clone();
-                    logger::log_info(&format!(
-                        "[ApiProxy] 运行中配置已同步: selected_account={}",
-                        proxy_config.selected_account_email
-                    ));
-                }
-            }
-        }
-    }
+    apply_running_config(proxy_config);
 
     logger::log_info(&format!(
         "[ApiProxy] 配置已保存: enabled={}, port={}",
@@ -723,16 +1348,61 @@ pub fn save_proxy_config(proxy_config: &ApiProxyConfig) -> Result<(), String> {
     Ok(())
 }
 
+/// 同步更新运行中代理的内存配置（无需重启监听）
+pub fn apply_running_config(proxy_config: &ApiProxyConfig) {
+    if let Some(state) = running_state() {
+        state.apply_config(proxy_config.clone());
+        logger::log_info(&format!(
+            "[ApiProxy] 运行中配置已同步: selected_account={}",
+            proxy_config.selected_account_email
+        ));
+    }
+}
+
 // ============================================================================
 // HTTP 路由和处理器
 // ============================================================================
 
+/// GET /metrics：Prometheus 文本格式的运行指标
+/// 配置了 API Key 时需携带 Key；未配置时仅允许本机访问
+async fn metrics_handler(
+    State(state): State<SharedState>,
+    ConnectInfo(PeerAddr(peer)): ConnectInfo<PeerAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let admin_key = state.config.read().map(|c| c.api_key.clone()).unwrap_or_default();
+    let has_any_key = match super::api_proxy_keys::has_any_key(&admin_key) {
+        Ok(has_any_key) => has_any_key,
+        Err(e) => return rejection_response(ApiKeyRejection::Unavailable(e)),
+    };
+    if has_any_key {
+        if let Err(rejection) = authenticate_caller(&state, &headers) {
+            return rejection_response(rejection);
+        }
+    } else if !peer.ip().is_loopback() {
+        return rejection_response(ApiKeyRejection::Forbidden(
+            "未配置 API Key 时 /metrics 仅允许本机访问".to_string(),
+        ));
+    }
+
+    let mut cooling: HashMap<String, usize> = HashMap::new();
+    for info in state.cooldowns.snapshot(chrono::Utc::now().timestamp()) {
+        *cooling.entry(info.provider).or_default() += 1;
+    }
+    (
+        [("content-type", "text/plain; version=0.0.4; charset=utf-8")],
+        state.metrics.render(&cooling, &state.limiter.queue_depths()),
+    )
+        .into_response()
+}
+
 /// 健康检查
 async fn health_check() -> impl IntoResponse {
     Json(serde_json::json!({
         "status": "ok",
         "service": "cockpit-tools-api-proxy",
-        "version": env!("CARGO_PKG_VERSION")
+        "version": env!("CARGO_PKG_VERSION"),
+        "vault_locked": super::credential_vault::is_locked()
     }))
 }
 
@@ -754,12 +1424,31 @@ fn convert_claude_to_google(body: &Value, project_id: &str) -> Result<(Value, bo
         .and_then(|v| v.as_bool())
         .unwrap_or(false);
 
+    // tool_use id → 工具名（functionResponse 需要回填对应的函数名）
+    let mut tool_names: HashMap<String, String> = HashMap::new();
+    for msg in messages {
+        if let Some(blocks) = msg.get("content").and_then(|v| v.as_array()) {
+            for block in blocks {
+                if block.get("type").and_then(|v| v.as_str()) == Some("tool_use") {
+                    if let (Some(id), Some(name)) = (
+                        block.get("id").and_then(|v| v.as_str()),
+                        block.get("name").and_then(|v| v.as_str()),
+                    ) {
+                        tool_names.insert(id.to_string(), name.to_string());
+                    }
+                }
+            }
+        }
+    }
+
     // 转换 messages → contents
     let mut contents: Vec<Value> = Vec::new();
     for msg in messages {
         let role = msg.get("role").and_then(|v| v.as_str()).unwrap_or("user");
         // Google API: user → user, assistant → model
         let gemini_role = if role == "assistant" { "model" } else { "user" };
+        // 仅携带签名的空 thinking 块：签名属于紧随其后的 functionCall
+        let mut pending_signature: Option<String> = None;
 
         let parts = match msg.get("content") {
             Some(Value::String(s)) => {
@@ -796,6 +1485,11 @@ fn convert_claude_to_google(body: &Value, project_id: &str) -> Result<(Value, bo
                             let thinking = block.get("thinking")
                                 .and_then(|v| v.as_str())
                                 .unwrap_or("");
+                            let signature = block.get("signature").and_then(|v| v.as_str()).filter(|s| !s.is_empty());
+                            if thinking.is_empty() {
+                                pending_signature = signature.map(|s| s.to_string());
+                                continue;
+                            }
                             let mut part = serde_json::json!({
                                 "text": thinking,
                                 "thought": true
@@ -806,13 +1500,16 @@ fn convert_claude_to_google(body: &Value, project_id: &str) -> Result<(Value, bo
                             parts_vec.push(part);
                         }
                         Some("tool_use") => {
-                            let fc = serde_json::json!({
+                            let mut fc = serde_json::json!({
                                 "functionCall": {
                                     "id": block.get("id").unwrap_or(&Value::Null),
                                     "name": block.get("name").unwrap_or(&Value::Null),
                                     "args": block.get("input").unwrap_or(&serde_json::json!({}))
                                 }
                             });
+                            if let Some(sig) = pending_signature.take() {
+                                fc["thoughtSignature"] = Value::String(sig);
+                            }
                             parts_vec.push(fc);
                         }
                         Some("tool_result") => {
@@ -826,11 +1523,19 @@ fn convert_claude_to_google(body: &Value, project_id: &str) -> Result<(Value, bo
                                     _ => c.to_string(),
                                 })
                                 .unwrap_or_default();
+                            let tool_use_id = block.get("tool_use_id").and_then(|v| v.as_str()).unwrap_or("");
+                            let name = tool_names.get(tool_use_id).map(|s| s.as_str()).unwrap_or("tool_result");
+                            let is_error = block.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false);
+                            let response = if is_error {
+                                serde_json::json!({"error": output})
+                            } else {
+                                serde_json::json!({"output": output})
+                            };
                             let fr = serde_json::json!({
                                 "functionResponse": {
-                                    "id": block.get("tool_use_id").unwrap_or(&Value::Null),
-                                    "name": "tool_result",
-                                    "response": {"output": output}
+                                    "id": tool_use_id,
+                                    "name": name,
+                                    "response": response
                                 }
                             });
                             parts_vec.push(fr);
@@ -926,6 +1631,9 @@ fn convert_claude_to_google(body: &Value, project_id: &str) -> Result<(Value, bo
             })
         }).collect();
         request_body["tools"] = Value::Array(gemini_tools);
+        if let Some(config) = body.get("tool_choice").and_then(google_function_calling_config) {
+            request_body["toolConfig"] = serde_json::json!({"functionCallingConfig": config});
+        }
     }
 
     // 组装最终 payload — 对齐 gcli2api 已验证可用的格式
@@ -939,102 +1647,278 @@ fn convert_claude_to_google(body: &Value, project_id: &str) -> Result<(Value, bo
     Ok((final_payload, stream))
 }
 
-/// 将 Google SSE 事件转换为 Claude SSE 事件
-fn convert_google_sse_to_claude(data: &str, _model: &str, _msg_id: &str) -> Vec<String> {
-    let mut events = Vec::new();
+/// 提取 Google 响应（v1internal 包装或原始格式）中的首个候选
+fn google_first_candidate(json: &Value) -> Option<&Value> {
+    json.get("response")
+        .and_then(|r| r.get("candidates"))
+        .or_else(|| json.get("candidates"))
+        .and_then(|c| c.get(0))
+}
 
-    logger::log_info(&format!("[ApiProxy DEBUG] Google SSE Data: {}", data));
+/// Google usageMetadata → Claude usage（thinking token 计入 output）
+fn google_usage_to_claude(json: &Value) -> Option<Value> {
+    let usage = json
+        .get("response")
+        .and_then(|r| r.get("usageMetadata"))
+        .or_else(|| json.get("usageMetadata"))?;
+    let get = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    let cached = get("cachedContentTokenCount");
+    Some(serde_json::json!({
+        "input_tokens": get("promptTokenCount").saturating_sub(cached),
+        "output_tokens": get("candidatesTokenCount") + get("thoughtsTokenCount"),
+        "cache_read_input_tokens": cached
+    }))
+}
 
-    // 解析 Google 的 SSE data 行
-    let json: Value = match serde_json::from_str(data) {
-        Ok(v) => v,
-        Err(e) => {
-            logger::log_info(&format!("[ApiProxy DEBUG] Parse fail: {}", e));
-            return events;
-        }
-    };
+/// Google finishReason → Claude stop_reason
+fn google_finish_reason_to_claude(reason: &str, has_tool_use: bool) -> &'static str {
+    if has_tool_use {
+        "tool_use"
+    } else if reason == "MAX_TOKENS" {
+        "max_tokens"
+    } else {
+        "end_turn"
+    }
+}
 
-    // 提取 response.candidates[0].content.parts
-    let candidates = json.get("response").and_then(|r| r.get("candidates")).or_else(|| json.get("candidates"));
-    let parts = match candidates
-        .and_then(|c| c.get(0))
-        .and_then(|c| c.get("content"))
-        .and_then(|c| c.get("parts"))
+/// Gemini functionCall part → Claude tool_use block（缺少 id 时生成）
+fn google_function_call_to_tool_use(fc: &Value) -> Value {
+    let id = fc
+        .get("id")
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| format!("toolu_{}", uuid::Uuid::new_v4().simple()));
+    serde_json::json!({
+        "type": "tool_use",
+        "id": id,
+        "name": fc.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+        "input": fc.get("args").cloned().unwrap_or_else(|| serde_json::json!({}))
+    })
+}
+
+/// 将非流式 Google 响应重建为完整的 Claude message
+fn convert_google_response_to_claude(json: &Value, model: &str) -> Value {
+    let mut content: Vec<Value> = Vec::new();
+    let mut has_tool_use = false;
+    let parts = google_first_candidate(json)
+        .and_then(|c| c.pointer("/content/parts"))
         .and_then(|p| p.as_array())
-    {
-        Some(p) => p,
-        None => return events,
-    };
+        .map(|a| a.as_slice())
+        .unwrap_or_default();
 
     for part in parts {
+        let signature = part.get("thoughtSignature").and_then(|v| v.as_str());
+        if let Some(fc) = part.get("functionCall") {
+            if let Some(sig) = signature {
+                content.push(serde_json::json!({"type": "thinking", "thinking": "", "signature": sig}));
+            }
+            content.push(google_function_call_to_tool_use(fc));
+            has_tool_use = true;
+            continue;
+        }
+        let text = part.get("text").and_then(|v| v.as_str()).unwrap_or("");
         let is_thought = part.get("thought").and_then(|v| v.as_bool()).unwrap_or(false);
-        let text = part.get("text").and_then(|v| v.as_str());
-        let thought_signature = part.get("thoughtSignature").and_then(|v| v.as_str());
-
-        // 如果是 thought = true 或者包含 thoughtSignature，则作为 thinking 块发送
-        if is_thought || thought_signature.is_some() {
-            if let Some(content) = text.or(thought_signature) {
-                let event = serde_json::json!({
-                    "type": "content_block_delta",
-                    "index": 0,
-                    "delta": {
-                        "type": "thinking_delta",
-                        "thinking": content
-                    }
-                });
-                events.push(format!("data: {}\n\n", event));
-            }
-        } else if let Some(content) = text {
-            // 普通 text delta
-            let event = serde_json::json!({
-                "type": "content_block_delta",
-                "index": 0,
-                "delta": {
-                    "type": "text_delta",
-                    "text": content
+        let last_type = content.last().and_then(|b| b.get("type")).and_then(|v| v.as_str()).map(|s| s.to_string());
+        if is_thought {
+            if last_type.as_deref() == Some("thinking") {
+                let last = content.last_mut().unwrap();
+                let merged = format!("{}{}", last["thinking"].as_str().unwrap_or(""), text);
+                last["thinking"] = Value::String(merged);
+                if let Some(sig) = signature {
+                    last["signature"] = Value::String(sig.to_string());
                 }
-            });
-            events.push(format!("data: {}\n\n", event));
+            } else {
+                content.push(serde_json::json!({"type": "thinking", "thinking": text, "signature": signature.unwrap_or("")}));
+            }
+        } else if !text.is_empty() {
+            if last_type.as_deref() == Some("text") {
+                let last = content.last_mut().unwrap();
+                let merged = format!("{}{}", last["text"].as_str().unwrap_or(""), text);
+                last["text"] = Value::String(merged);
+            } else {
+                content.push(serde_json::json!({"type": "text", "text": text}));
+            }
         }
     }
 
-    // 检查是否结束
-    let finish_reason = json.get("candidates")
-        .and_then(|c| c.get(0))
+    let finish_reason = google_first_candidate(json)
         .and_then(|c| c.get("finishReason"))
-        .and_then(|v| v.as_str());
-
-    if let Some(reason) = finish_reason {
-        if reason == "STOP" || reason == "MAX_TOKENS" {
-            // 提取 usage
-            let usage = json.get("usageMetadata");
-            let input_tokens = usage
-                .and_then(|u| u.get("promptTokenCount"))
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0);
-            let output_tokens = usage
-                .and_then(|u| u.get("candidatesTokenCount"))
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0);
+        .and_then(|v| v.as_str())
+        .unwrap_or("STOP");
+    serde_json::json!({
+        "id": format!("msg_{}", chrono::Utc::now().timestamp_millis()),
+        "type": "message",
+        "role": "assistant",
+        "model": model,
+        "content": content,
+        "stop_reason": google_finish_reason_to_claude(finish_reason, has_tool_use),
+        "stop_sequence": null,
+        "usage": google_usage_to_claude(json)
+            .unwrap_or_else(|| serde_json::json!({"input_tokens": 0, "output_tokens": 0}))
+    })
+}
 
-            let stop_reason = if reason == "MAX_TOKENS" { "max_tokens" } else { "end_turn" };
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GoogleBlockKind {
+    Text,
+    Thinking,
+}
 
-            let end_event = serde_json::json!({
-                "type": "message_delta",
-                "delta": {
-                    "stop_reason": stop_reason,
-                    "stop_sequence": null
-                },
-                "usage": {
-                    "input_tokens": input_tokens,
-                    "output_tokens": output_tokens
+/// Google SSE → Claude SSE 的有状态转换器：维护内容块索引，
+/// 将 functionCall 转为 tool_use 块，并在流结束时补发 message_delta / message_stop
+struct GoogleSseConverter {
+    next_index: usize,
+    open_block: Option<GoogleBlockKind>,
+    has_tool_use: bool,
+    finish_reason: Option<String>,
+    usage: Option<Value>,
+}
+
+impl GoogleSseConverter {
+    fn new() -> Self {
+        Self {
+            next_index: 0,
+            open_block: None,
+            has_tool_use: false,
+            finish_reason: None,
+            usage: None,
+        }
+    }
+
+    fn close_block(&mut self, events: &mut Vec<String>) {
+        if self.open_block.take().is_some() {
+            let stop = serde_json::json!({"type": "content_block_stop", "index": self.next_index - 1});
+            events.push(format!("event: content_block_stop\ndata: {}\n\n", stop));
+        }
+    }
+
+    fn start_block(&mut self, content_block: Value, events: &mut Vec<String>) -> usize {
+        self.close_block(events);
+        let index = self.next_index;
+        self.next_index += 1;
+        let start = serde_json::json!({
+            "type": "content_block_start",
+            "index": index,
+            "content_block": content_block
+        });
+        events.push(format!("event: content_block_start\ndata: {}\n\n", start));
+        index
+    }
+
+    fn ensure_block(&mut self, kind: GoogleBlockKind, events: &mut Vec<String>) -> usize {
+        if self.open_block == Some(kind) {
+            return self.next_index - 1;
+        }
+        let content_block = match kind {
+            GoogleBlockKind::Text => serde_json::json!({"type": "text", "text": ""}),
+            GoogleBlockKind::Thinking => serde_json::json!({"type": "thinking", "thinking": "", "signature": ""}),
+        };
+        let index = self.start_block(content_block, events);
+        self.open_block = Some(kind);
+        index
+    }
+
+    fn delta(index: usize, delta: Value) -> String {
+        let event = serde_json::json!({"type": "content_block_delta", "index": index, "delta": delta});
+        format!("event: content_block_delta\ndata: {}\n\n", event)
+    }
+
+    /// 处理一条 Google SSE data 行，返回 Claude SSE 事件
+    fn convert(&mut self, data: &str) -> Vec<String> {
+        let mut events = Vec::new();
+
+        logger::log_info(&format!("[ApiProxy DEBUG] Google SSE Data: {}", data));
+
+        let json: Value = match serde_json::from_str(data) {
+            Ok(v) => v,
+            Err(e) => {
+                logger::log_info(&format!("[ApiProxy DEBUG] Parse fail: {}", e));
+                return events;
+            }
+        };
+
+        if let Some(usage) = google_usage_to_claude(&json) {
+            self.usage = Some(usage);
+        }
+        let candidate = match google_first_candidate(&json) {
+            Some(c) => c,
+            None => return events,
+        };
+        if let Some(reason) = candidate.get("finishReason").and_then(|v| v.as_str()) {
+            self.finish_reason = Some(reason.to_string());
+        }
+
+        let parts = candidate
+            .pointer("/content/parts")
+            .and_then(|p| p.as_array())
+            .map(|a| a.as_slice())
+            .unwrap_or_default();
+        for part in parts {
+            let signature = part.get("thoughtSignature").and_then(|v| v.as_str());
+
+            if let Some(fc) = part.get("functionCall") {
+                // functionCall 的签名放入其前方的 thinking 块，回传时再挂回 functionCall
+                if let Some(sig) = signature {
+                    let index = self.ensure_block(GoogleBlockKind::Thinking, &mut events);
+                    events.push(Self::delta(index, serde_json::json!({"type": "signature_delta", "signature": sig})));
                 }
-            });
-            events.push(format!("data: {}\n\n", end_event));
+                let mut tool_use = google_function_call_to_tool_use(fc);
+                let input = tool_use["input"].to_string();
+                tool_use["input"] = serde_json::json!({});
+                let index = self.start_block(tool_use, &mut events);
+                events.push(Self::delta(index, serde_json::json!({"type": "input_json_delta", "partial_json": input})));
+                let stop = serde_json::json!({"type": "content_block_stop", "index": index});
+                events.push(format!("event: content_block_stop\ndata: {}\n\n", stop));
+                self.has_tool_use = true;
+                continue;
+            }
+
+            let text = part.get("text").and_then(|v| v.as_str()).unwrap_or("");
+            let is_thought = part.get("thought").and_then(|v| v.as_bool()).unwrap_or(false);
+            if is_thought {
+                let index = self.ensure_block(GoogleBlockKind::Thinking, &mut events);
+                if !text.is_empty() {
+                    events.push(Self::delta(index, serde_json::json!({"type": "thinking_delta", "thinking": text})));
+                }
+                if let Some(sig) = signature {
+                    events.push(Self::delta(index, serde_json::json!({"type": "signature_delta", "signature": sig})));
+                }
+            } else if !text.is_empty() {
+                let index = self.ensure_block(GoogleBlockKind::Text, &mut events);
+                events.push(Self::delta(index, serde_json::json!({"type": "text_delta", "text": text})));
+            }
         }
+
+        events
     }
 
-    events
+    /// 流结束：关闭打开的内容块，发送 message_delta（stop_reason + usage）与 message_stop
+    fn finish(&mut self) -> Vec<String> {
+        let mut events = Vec::new();
+        if self.next_index == 0 {
+            // 上游没有任何输出时保留一个空文本块，兼容要求至少一个内容块的客户端
+            self.ensure_block(GoogleBlockKind::Text, &mut events);
+        }
+        self.close_block(&mut events);
+
+        let stop_reason = google_finish_reason_to_claude(
+            self.finish_reason.as_deref().unwrap_or("STOP"),
+            self.has_tool_use,
+        );
+        let end_event = serde_json::json!({
+            "type": "message_delta",
+            "delta": {
+                "stop_reason": stop_reason,
+                "stop_sequence": null
+            },
+            "usage": self.usage.clone().unwrap_or_else(|| serde_json::json!({"output_tokens": 0}))
+        });
+        events.push(format!("event: message_delta\ndata: {}\n\n", end_event));
+        let msg_stop = serde_json::json!({"type": "message_stop"});
+        events.push(format!("event: message_stop\ndata: {}\n\n", msg_stop));
+        events
+    }
 }
 
 /// 通过 loadCodeAssist API 获取 project_id
@@ -1424,7 +2308,11 @@ fn kiro_convert_tools(tools: &[Value]) -> (Vec<Value>, String) {
         let func = tool.get("function").unwrap_or(tool);
         let name = func.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
         let description = func.get("description").and_then(|v| v.as_str()).unwrap_or("");
-        let parameters = func.get("parameters").cloned().unwrap_or(serde_json::json!({}));
+        // OpenAI: function.parameters；Anthropic: input_schema
+        let parameters = func.get("parameters")
+            .or_else(|| func.get("input_schema"))
+            .cloned()
+            .unwrap_or(serde_json::json!({}));
 
         // 验证工具名长度
         if name.len() > TOOL_NAME_MAX_LENGTH {
@@ -1484,6 +2372,28 @@ fn kiro_convert_tool_uses(tool_calls: &[Value]) -> Vec<Value> {
     }).collect()
 }
 
+/// 将 Anthropic 格式 assistant content 中的 tool_use block 转换为 OpenAI tool_calls 格式
+fn kiro_tool_use_blocks_to_tool_calls(content: &Value) -> Vec<Value> {
+    let arr = match content.as_array() {
+        Some(a) => a,
+        None => return vec![],
+    };
+    arr.iter().filter_map(|item| {
+        if item.get("type").and_then(|v| v.as_str()) != Some("tool_use") {
+            return None;
+        }
+        let input = item.get("input").cloned().unwrap_or(serde_json::json!({}));
+        Some(serde_json::json!({
+            "id": item.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+            "type": "function",
+            "function": {
+                "name": item.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                "arguments": input.to_string()
+            }
+        }))
+    }).collect()
+}
+
 /// 将 tool_results（用户消息中的工具返回）转换为 Kiro toolResults 格式
 fn kiro_convert_tool_results(content: &Value) -> Vec<Value> {
     let arr = match content.as_array() {
@@ -1578,8 +2488,10 @@ fn build_kiro_payload(
         return Err("没有消息可发送".to_string());
     }
 
-    // --- 提取 system prompt ---
-    let mut system_prompt = String::new();
+    // --- 提取 system prompt（Anthropic 格式为顶层 system 字段，OpenAI 格式为 system 角色消息） ---
+    let mut system_prompt = client_body.get("system")
+        .map(kiro_extract_text)
+        .unwrap_or_default();
     let mut non_system_msgs: Vec<&Value> = Vec::new();
     for msg in messages {
         let role = msg.get("role").and_then(|v| v.as_str()).unwrap_or("user");
@@ -1642,17 +2554,29 @@ fn build_kiro_payload(
             _ => "user".to_string(),
         };
 
-        let text = kiro_extract_text(content_val);
+        let mut text = kiro_extract_text(content_val);
         let images = kiro_extract_images(content_val);
 
-        // 提取 tool_calls（assistant 消息）
-        let tool_calls: Vec<Value> = msg.get("tool_calls")
+        // 提取 tool_calls（assistant 消息；OpenAI 的 tool_calls 字段或 Anthropic 的 tool_use block）
+        let mut tool_calls: Vec<Value> = msg.get("tool_calls")
             .and_then(|v| v.as_array())
             .cloned()
             .unwrap_or_default();
+        tool_calls.extend(kiro_tool_use_blocks_to_tool_calls(content_val));
 
         // 提取 tool_results（user 消息中的 content block）
-        let tool_results = kiro_convert_tool_results(content_val);
+        let mut tool_results = kiro_convert_tool_results(content_val);
+
+        // OpenAI 格式的 tool 角色消息：整条消息即为一个工具结果
+        if role == "tool" {
+            let tool_use_id = msg.get("tool_call_id").and_then(|v| v.as_str()).unwrap_or("");
+            let result_content = if text.is_empty() { "(empty result)".to_string() } else { std::mem::take(&mut text) };
+            tool_results.push(serde_json::json!({
+                "content": [{"text": result_content}],
+                "status": "success",
+                "toolUseId": tool_use_id
+            }));
+        }
 
         unified_msgs.push(KiroMsg {
             role: normalized_role,
@@ -1896,11 +2820,26 @@ struct KiroEvent {
     thinking_content: String,
     tool_use: Option<serde_json::Value>,
     context_usage_percentage: Option<f64>,
+    /// meteringEvent 上报的 credit 消耗
+    metering_credits: Option<f64>,
+}
+
+impl KiroEvent {
+    fn new(event_type: &str) -> Self {
+        Self {
+            event_type: event_type.to_string(),
+            content: String::new(),
+            thinking_content: String::new(),
+            tool_use: None,
+            context_usage_percentage: None,
+            metering_credits: None,
+        }
+    }
 }
 
-#[allow(dead_code)]
 struct KiroStreamParser {
     stream_buffer: Vec<u8>,
+    /// 正在拼接中的工具调用（Kiro 以 name/toolUseId → input 片段 → stop 的形式增量下发）
     current_tool_call: Option<serde_json::Map<String, serde_json::Value>>,
     emitted_tool_ids: std::collections::HashSet<String>,
 }
@@ -1914,107 +2853,210 @@ impl KiroStreamParser {
         }
     }
 
-
-
     /// 从 Kiro AWS Event Stream 二进制帧中提取 JSON 事件（带有状态维护）
     fn feed(&mut self, chunk: &[u8]) -> Vec<KiroEvent> {
         self.stream_buffer.extend_from_slice(chunk);
-        let mut events = Vec::new();
-        let mut consumed = 0;
-
-        while consumed + 12 <= self.stream_buffer.len() {
-            // 读取 total_length（大端序 4 字节）
-            let total_length = u32::from_be_bytes([
-                self.stream_buffer[consumed], self.stream_buffer[consumed + 1],
-                self.stream_buffer[consumed + 2], self.stream_buffer[consumed + 3],
-            ]) as usize;
-
-            if total_length < 16 || consumed + total_length > self.stream_buffer.len() {
-                break; // 帧不完整，等待更多数据
-            }
-
-            // headers_length
-            let headers_length = u32::from_be_bytes([
-                self.stream_buffer[consumed + 4], self.stream_buffer[consumed + 5],
-                self.stream_buffer[consumed + 6], self.stream_buffer[consumed + 7],
-            ]) as usize;
-
-            // payload 起始位置 = prelude(12) + headers
-            let payload_start = consumed + 12 + headers_length;
-            // payload 长度 = total - prelude(12) - headers - message_crc(4)
-            let payload_length = total_length.saturating_sub(12 + headers_length + 4);
-
-            if payload_start + payload_length <= self.stream_buffer.len() && payload_length > 0 {
-                if let Ok(payload_str) = std::str::from_utf8(&self.stream_buffer[payload_start..payload_start + payload_length]) {
-                    let trimmed = payload_str.trim();
-                    if !trimmed.is_empty() {
-                        crate::modules::logger::log_info(&format!("[Kiro Raw JSON] {}", trimmed));
-                        if let Ok(v) = serde_json::from_str::<serde_json::Value>(trimmed) {
-                            if let Some(ev) = self.process_kiro_json_event(v) {
-                                // Token 计数和上下文比例提取 STREAM-3 准备
-                                if let Some(_meta) = ev.context_usage_percentage {
-                                     // 将在处理处用到
-                                }
-                                events.push(ev);
-                            }
-                        }
-                    }
-                }
-            }
-
-            consumed += total_length;
-        }
-
+        let (payloads, consumed) = kiro_parse_event_stream_frames(&self.stream_buffer);
         if consumed > 0 {
             self.stream_buffer.drain(0..consumed);
         }
 
+        let mut events = Vec::new();
+        for payload in payloads {
+            crate::modules::logger::log_info(&format!("[Kiro Raw JSON] {}", payload));
+            if let Ok(v) = serde_json::from_str::<serde_json::Value>(&payload) {
+                events.extend(self.process_kiro_json_event(v));
+            }
+        }
         events
     }
 
-    fn process_kiro_json_event(&mut self, v: serde_json::Value) -> Option<KiroEvent> {
-        let mut event = KiroEvent {
-            event_type: String::new(),
-            content: String::new(),
-            thinking_content: String::new(),
-            tool_use: None,
-            context_usage_percentage: v.get("contextUsagePercentage").and_then(|v| v.as_f64()),
+    /// 流结束时调用：输出尚未收到 stop 的工具调用
+    fn finish(&mut self) -> Vec<KiroEvent> {
+        self.finalize_tool_call().into_iter().collect()
+    }
+
+    /// 结束当前工具调用，解析累积的 input 字符串为 JSON
+    fn finalize_tool_call(&mut self) -> Option<KiroEvent> {
+        let tool = self.current_tool_call.take()?;
+        let tool_use_id = tool.get("toolUseId").and_then(|v| v.as_str()).unwrap_or("").to_string();
+        if !tool_use_id.is_empty() && !self.emitted_tool_ids.insert(tool_use_id.clone()) {
+            return None;
+        }
+        let raw_input = tool.get("input").and_then(|v| v.as_str()).unwrap_or("");
+        let input: Value = if raw_input.trim().is_empty() {
+            serde_json::json!({})
+        } else {
+            serde_json::from_str(raw_input).unwrap_or_else(|e| {
+                logger::log_warn(&format!("[ApiProxy] Kiro 工具参数解析失败 ({}): {}", tool_use_id, e));
+                serde_json::json!({})
+            })
         };
+        let mut event = KiroEvent::new("tool_use");
+        event.tool_use = Some(serde_json::json!({
+            "toolUseId": tool_use_id,
+            "name": tool.get("name").cloned().unwrap_or(Value::Null),
+            "input": input
+        }));
+        Some(event)
+    }
+
+    fn process_kiro_json_event(&mut self, v: serde_json::Value) -> Vec<KiroEvent> {
+        let mut events = Vec::new();
 
-        // Kiro 原始流直接返回 content 字段
+        // Kiro 原始流直接返回 content 字段（followupPrompt 为推荐问题，忽略）
         if let Some(content) = v.get("content").and_then(|c| c.as_str()) {
-            if !content.is_empty() {
-                event.event_type = "content".to_string();
+            if !content.is_empty() && v.get("followupPrompt").is_none() {
+                let mut event = KiroEvent::new("content");
                 event.content = content.to_string();
-                return Some(event);
+                events.push(event);
+            }
+            return events;
+        }
+
+        // 工具调用开始：{"name": "...", "toolUseId": "...", "input": "...", "stop": false}
+        if let (Some(name), Some(tool_use_id)) = (
+            v.get("name").and_then(|n| n.as_str()),
+            v.get("toolUseId").and_then(|n| n.as_str()),
+        ) {
+            let same_tool = self
+                .current_tool_call
+                .as_ref()
+                .and_then(|t| t.get("toolUseId"))
+                .and_then(|id| id.as_str())
+                == Some(tool_use_id);
+            if !same_tool {
+                events.extend(self.finalize_tool_call());
+                let mut tool = serde_json::Map::new();
+                tool.insert("name".to_string(), Value::String(name.to_string()));
+                tool.insert("toolUseId".to_string(), Value::String(tool_use_id.to_string()));
+                tool.insert("input".to_string(), Value::String(String::new()));
+                self.current_tool_call = Some(tool);
+            }
+        }
+
+        // 工具参数片段：input 可能为字符串片段或完整对象
+        if let Some(input) = v.get("input") {
+            if let Some(tool) = self.current_tool_call.as_mut() {
+                let piece = match input {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                };
+                if let Some(Value::String(acc)) = tool.get_mut("input") {
+                    acc.push_str(&piece);
+                }
             }
         }
 
-        // Kiro toolUse 处理 (通常为包含 toolUse 的对象)
+        if v.get("stop").and_then(|s| s.as_bool()).unwrap_or(false) {
+            events.extend(self.finalize_tool_call());
+        }
+
+        // 兼容嵌套的 toolUse 对象
         if let Some(tool_use) = v.get("toolUse") {
-            // 目前简化处理，将整个 toolUse 返回，后续逻辑需要的话可以在此封装为函数调用的 JSON 格式
-            event.event_type = "tool_use".to_string();
+            let mut event = KiroEvent::new("tool_use");
             event.tool_use = Some(tool_use.clone());
-            return Some(event);
+            events.push(event);
+        }
+
+        // 上下文占用比例（用于额度/用量展示）
+        if let Some(pct) = v.get("contextUsagePercentage").and_then(|v| v.as_f64()) {
+            let mut event = KiroEvent::new("context_usage");
+            event.context_usage_percentage = Some(pct);
+            events.push(event);
+        }
+
+        // 计量事件：{"unit": "credit", "unitPlural": "credits", "usage": 0.12}
+        if let (Some(_), Some(credits)) = (
+            v.get("unit").and_then(|u| u.as_str()),
+            v.get("usage").and_then(|u| u.as_f64()),
+        ) {
+            let mut event = KiroEvent::new("metering");
+            event.metering_credits = Some(credits);
+            events.push(event);
         }
 
         // 判断是否有结束信号
         let t = v.get("type").and_then(|t| t.as_str())
             .or_else(|| v.get("eventType").and_then(|t| t.as_str()));
-        if let Some(t) = t {
-            match t {
-                "message_stop" | "end_turn" | "messageStop" => {
-                    event.event_type = "message_stop".to_string();
-                    return Some(event);
-                }
-                _ => {}
-            }
+        if let Some("message_stop" | "end_turn" | "messageStop") = t {
+            events.extend(self.finalize_tool_call());
+            events.push(KiroEvent::new("message_stop"));
         }
 
-        None
+        events
     }
 }
 
+/// 将 Kiro 工具调用转换为 Claude tool_use 内容块
+fn kiro_tool_use_to_claude_block(tool_obj: &Value) -> Value {
+    serde_json::json!({
+        "type": "tool_use",
+        "id": tool_obj.get("toolUseId").and_then(|v| v.as_str()).unwrap_or(""),
+        "name": tool_obj.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+        "input": tool_obj.get("input").cloned().unwrap_or(serde_json::json!({}))
+    })
+}
+
+/// Kiro 请求体构建失败时的 400 响应
+fn kiro_invalid_request(message: String) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({
+            "type": "error",
+            "error": { "type": "invalid_request_error", "message": message }
+        })),
+    )
+        .into_response()
+}
+
+/// 打开一个文本内容块的 Claude SSE 事件
+fn kiro_text_block_start_sse(index: usize) -> String {
+    let block_start = serde_json::json!({
+        "type": "content_block_start",
+        "index": index,
+        "content_block": { "type": "text", "text": "" }
+    });
+    format!("event: content_block_start\ndata: {}\n\n", block_start)
+}
+
+/// 生成一个完整 tool_use 内容块的 Claude SSE 事件（start → input_json_delta → stop）
+fn kiro_tool_use_sse_events(index: usize, tool_obj: &Value) -> Vec<String> {
+    let block = kiro_tool_use_to_claude_block(tool_obj);
+    let input_json = block.get("input").map(|v| v.to_string()).unwrap_or_else(|| "{}".to_string());
+    let mut start_block = block.clone();
+    start_block["input"] = serde_json::json!({});
+
+    let cb_start = serde_json::json!({
+        "type": "content_block_start",
+        "index": index,
+        "content_block": start_block
+    });
+    let tool_delta = serde_json::json!({
+        "type": "content_block_delta",
+        "index": index,
+        "delta": { "type": "input_json_delta", "partial_json": input_json }
+    });
+    let cb_stop = serde_json::json!({"type": "content_block_stop", "index": index});
+    vec![
+        format!("event: content_block_start\ndata: {}\n\n", cb_start),
+        format!("event: content_block_delta\ndata: {}\n\n", tool_delta),
+        format!("event: content_block_stop\ndata: {}\n\n", cb_stop),
+    ]
+}
+
+/// Kiro 模型上下文窗口大小（用于从 contextUsagePercentage 估算输入 token）
+const KIRO_CONTEXT_WINDOW_TOKENS: f64 = 200_000.0;
+
+/// Kiro 不返回 token 用量，按输出字符数（约 4 字符/token）与上下文占用百分比估算 Claude usage
+fn kiro_estimate_usage(output_chars: usize, context_usage_percentage: Option<f64>) -> Value {
+    let input_tokens = context_usage_percentage
+        .map(|pct| (pct / 100.0 * KIRO_CONTEXT_WINDOW_TOKENS).round() as u64)
+        .unwrap_or(0);
+    let output_tokens = output_chars.div_ceil(4) as u64;
+    serde_json::json!({"input_tokens": input_tokens, "output_tokens": output_tokens})
+}
+
 /// 从 Kiro AWS Event Stream 二进制帧中提取 JSON 事件
 fn kiro_parse_event_stream_frames(buffer: &[u8]) -> (Vec<String>, usize) {
     let mut events = Vec::new();
@@ -2149,6 +3191,135 @@ pub fn get_codex_model_list() -> Vec<String> {
     ]
 }
 
+/// 解析 Copilot GET /models 响应，仅保留聊天模型
+/// 结构: { "data": [ { "id": "gpt-4.1", "capabilities": { "type": "chat" }, "model_picker_enabled": true } ] }
+pub fn parse_copilot_models_response(body: &str) -> Result<Vec<String>, String> {
+    let v: serde_json::Value =
+        serde_json::from_str(body).map_err(|e| format!("JSON 解析失败: {}", e))?;
+    let arr = v
+        .get("data")
+        .and_then(|a| a.as_array())
+        .ok_or("响应中未找到模型列表 (data)")?;
+    let mut ids: Vec<String> = Vec::new();
+    for m in arr {
+        let is_chat = m
+            .pointer("/capabilities/type")
+            .and_then(|t| t.as_str())
+            .is_none_or(|t| t == "chat");
+        if let Some(id) = m.get("id").and_then(|x| x.as_str()).filter(|_| is_chat) {
+            if !ids.iter().any(|existing| existing == id) {
+                ids.push(id.to_string());
+            }
+        }
+    }
+    Ok(ids)
+}
+
+/// 从 Copilot API GET /models 拉取当前席位可用的模型列表
+pub async fn fetch_copilot_models_remote(copilot_token: &str) -> Result<Vec<String>, String> {
+    let mut builder = Client::builder().timeout(std::time::Duration::from_secs(15));
+    if let Some(proxy_url) = resolve_http_proxy() {
+        if let Ok(proxy) = Proxy::all(proxy_url) {
+            builder = builder.proxy(proxy);
+        }
+    }
+    let client = builder.build().map_err(|e| format!("构建 HTTP 客户端失败: {}", e))?;
+    let mut req = client
+        .get("https://api.githubcopilot.com/models")
+        .header("Authorization", format!("Bearer {}", copilot_token))
+        .header("Accept", "application/json");
+    for (name, value) in COPILOT_EDITOR_HEADERS {
+        req = req.header(*name, *value);
+    }
+    let resp = req.send().await.map_err(|e| format!("请求失败: {}", e))?;
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(format!("HTTP {}: {}", status, body.chars().take(200).collect::<String>()));
+    }
+    parse_copilot_models_response(&body)
+}
+
+/// 参与 GET /v1/models 汇总的 Provider（按同名模型的优先级排列）
+/// Windsurf / Warp 的 /models 由上游直接透传
+const MODEL_LIST_PROVIDERS: &[&str] = &["kiro", "antigravity", "codex", "github_copilot"];
+
+/// 用 Provider 池中第一个可用账号拉取模型列表（结果缓存 MODEL_CACHE_TTL）
+async fn provider_model_list(state: &SharedState, provider: &str) -> Result<Vec<String>, String> {
+    let now = std::time::Instant::now();
+    if let Some(models) = state.model_catalog.get(provider, now) {
+        return Ok(models);
+    }
+    let cred = state
+        .get_available_credentials(provider, None)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("Provider '{}' 没有可用的账号", provider))?;
+
+    let models = match provider {
+        "antigravity" => fetch_available_models(&cred.access_token, &cred.project_id)
+            .await?
+            .into_iter()
+            .map(|m| m.id)
+            .collect(),
+        // fetch_kiro_models 已合并 KIRO_HIDDEN_MODELS，失败时使用兜底列表
+        "kiro" => match fetch_kiro_models(&cred.access_token, cred.profile_arn.as_deref()).await {
+            Ok(models) => models,
+            Err(e) => {
+                logger::log_warn(&format!("[ApiProxy] 拉取 Kiro 模型列表失败，使用兜底列表: {}", e));
+                KIRO_FALLBACK_MODELS
+                    .iter()
+                    .chain(KIRO_HIDDEN_MODELS)
+                    .map(|s| s.to_string())
+                    .collect()
+            }
+        },
+        "codex" => match fetch_codex_models_remote(&cred.access_token, cred.chatgpt_account_id.as_deref()).await {
+            Ok(models) if !models.is_empty() => models,
+            Ok(_) => get_codex_model_list(),
+            Err(e) => {
+                logger::log_warn(&format!("[ApiProxy] 拉取 Codex 模型列表失败，使用兜底列表: {}", e));
+                get_codex_model_list()
+            }
+        },
+        "github_copilot" => fetch_copilot_models_remote(&cred.access_token).await?,
+        _ => return Err(format!("Provider '{}' 不支持模型列表", provider)),
+    };
+    state.model_catalog.put(provider, models.clone(), now);
+    Ok(models)
+}
+
+/// OpenAI 格式 GET /v1/models：汇总 providers 中已启用的 Provider，并附带路由表别名
+async fn models_response(state: &SharedState, providers: &[&str], include_aliases: bool) -> Response {
+    let enabled: Vec<&str> = match state.config.read() {
+        Ok(config) => providers
+            .iter()
+            .copied()
+            .filter(|p| config.providers.get(*p).is_some_and(|c| c.enabled))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    let mut lists = Vec::new();
+    for provider in enabled {
+        match provider_model_list(state, provider).await {
+            Ok(models) => lists.push((provider.to_string(), models)),
+            Err(e) => logger::log_warn(&format!("[ApiProxy] {} 模型列表不可用: {}", provider, e)),
+        }
+    }
+    let aliases: Vec<String> = if include_aliases {
+        super::api_proxy_routes::list_model_routes()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|r| r.alias)
+            .filter(|a| !a.ends_with('*'))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    Json(super::api_proxy_models::openai_model_list(&lists, &aliases)).into_response()
+}
+
 /// 获取 Antigravity 端点列表（根据账号类型选择优先级）
 fn get_antigravity_endpoints(is_gcp_tos: bool) -> Vec<&'static str> {
     if is_gcp_tos {
@@ -2179,6 +3350,8 @@ async fn send_antigravity_request(
     let query = if stream { "?alt=sse" } else { "" };
 
     let mut last_err = String::from("所有端点均失败");
+    // 所有端点都返回可重试错误时，把最后一个响应交给调用方（保留 429 的 Retry-After 与错误体）
+    let mut last_resp: Option<reqwest::Response> = None;
 
     for (idx, base_url) in endpoints.iter().enumerate() {
         let url = format!("{}:{}{}" , base_url, method, query);
@@ -2213,6 +3386,7 @@ async fn send_antigravity_request(
                     "[ApiProxy] 端点 {} 返回 {}，尝试下一个",
                     base_url, status
                 ));
+                last_resp = Some(r);
             }
             Err(e) => {
                 last_err = format!("网络错误: {}", e);
@@ -2224,652 +3398,577 @@ async fn send_antigravity_request(
         }
     }
 
-    Err(last_err)
+    last_resp.ok_or(last_err)
 }
 
-/// 代理转发处理器
-async fn proxy_handler(
-    State(state): State<SharedState>,
-    Path((provider, rest)): Path<(String, String)>,
-    method: Method,
-    headers: HeaderMap,
-    body: Body,
+/// Antigravity 请求处理：Claude Messages → Google v1internal，响应转换回 Claude 格式
+async fn handle_antigravity(
+    state: &SharedState,
+    cred: &AccountCredential,
+    caller: &ApiKeyScope,
+    client_body: Value,
 ) -> Response {
-    // 1. 检查 API Key
-    {
-        let config = match state.config.read() {
-            Ok(c) => c,
-            Err(_) => {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(serde_json::json!({"error": "内部错误"})),
-                )
-                    .into_response();
-            }
-        };
-        if !config.api_key.is_empty() {
-            let auth = headers
-                .get("authorization")
-                .and_then(|v| v.to_str().ok())
-                .unwrap_or("");
-            let expected = format!("Bearer {}", config.api_key);
-            if auth != expected {
-                let key_header = headers
-                    .get("x-api-key")
-                    .and_then(|v| v.to_str().ok())
-                    .unwrap_or("");
-                if key_header != config.api_key {
-                    return (
-                        StatusCode::UNAUTHORIZED,
-                        Json(serde_json::json!({"error": "Unauthorized: Invalid API Key"})),
-                    )
-                        .into_response();
-                }
-            }
-        }
-    }
+    let model = client_body.get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or("claude-sonnet-4-20250514")
+        .to_string();
 
-    // 2. 获取 Provider 上游信息
-    let upstream = match get_provider_upstream(&provider) {
-        Some(u) => u,
-        None => {
+    // 转换为 Google 格式
+    let (mut google_payload, stream) = match convert_claude_to_google(&client_body, &cred.project_id) {
+        Ok(r) => r,
+        Err(e) => {
             return (
-                StatusCode::NOT_FOUND,
-                Json(serde_json::json!({"error": format!("Unknown provider: {}", provider)})),
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({
+                    "type": "error",
+                    "error": { "type": "invalid_request_error", "message": e }
+                })),
             )
                 .into_response();
         }
     };
 
-    // 3. 获取凭据（请求头 X-Selected-Account-Email 优先，确保使用用户在 Chat 页选中的账号）
-    let override_account_email = headers
-        .get("x-selected-account-email")
-        .and_then(|v| v.to_str().ok())
-        .filter(|s| !s.trim().is_empty());
-    let cred = match state.get_next_credential(&provider, override_account_email).await {
-        Ok(c) => c,
-        Err(e) => {
-            return (
-                StatusCode::SERVICE_UNAVAILABLE,
-                Json(serde_json::json!({"error": e})),
-            )
-                .into_response();
-        }
-    };
-
-    // 4. 读取请求 body
-    let body_bytes = match axum::body::to_bytes(body, 10 * 1024 * 1024).await {
-        Ok(b) => b,
-        Err(e) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({"error": format!("读取请求体失败: {}", e)})),
-            )
-                .into_response();
-        }
-    };
+    logger::log_info(&format!(
+        "[ApiProxy] Antigravity Claude→Google | model={} stream={} project={}",
+        model, stream, &cred.project_id
+    ));
 
-    // ===== Antigravity: Claude → Google 协议转换 =====
-    if provider == "antigravity" {
-        // 解析客户端发来的 Claude Messages 格式
-        let client_body: Value = match serde_json::from_slice(&body_bytes) {
-            Ok(v) => v,
-            Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({"error": format!("JSON 解析失败: {}", e)})),
-                )
-                    .into_response();
+    // 如果 project_id 为空，通过 loadCodeAssist API 动态获取
+    let mut effective_cred = cred.clone();
+    if effective_cred.project_id.is_empty() {
+        logger::log_info("[ApiProxy] project_id 为空，尝试通过 loadCodeAssist 获取...");
+        match fetch_project_id_via_load_code_assist(&state.http_client(), &effective_cred.access_token).await {
+            Ok(pid) => {
+                logger::log_info(&format!("[ApiProxy] ✓ 获取到 project_id: {}", pid));
+                effective_cred.project_id = pid.clone();
+                // 更新 payload 中的 project
+                let mut updated_payload = google_payload.clone();
+                updated_payload["project"] = Value::String(pid);
+                google_payload = updated_payload;
             }
-        };
-
-        let model = client_body.get("model")
-            .and_then(|v| v.as_str())
-            .unwrap_or("claude-sonnet-4-20250514")
-            .to_string();
-
-        // 转换为 Google 格式
-        let (mut google_payload, stream) = match convert_claude_to_google(&client_body, &cred.project_id) {
-            Ok(r) => r,
             Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({
-                        "type": "error",
-                        "error": { "type": "invalid_request_error", "message": e }
-                    })),
-                )
-                    .into_response();
+                logger::log_info(&format!("[ApiProxy] ⚠️ loadCodeAssist 失败: {}", e));
             }
-        };
+        }
+    }
 
+    // 调试日志：输出完整 payload (前500字符)
+    if let Ok(payload_str) = serde_json::to_string_pretty(&google_payload) {
         logger::log_info(&format!(
-            "[ApiProxy] Antigravity Claude→Google | model={} stream={} project={}",
-            model, stream, &cred.project_id
+            "[ApiProxy] Google payload (前500字符): {}",
+            &payload_str[..payload_str.len().min(500)]
         ));
+    }
 
-        // 如果 project_id 为空，通过 loadCodeAssist API 动态获取
-        let mut effective_cred = cred.clone();
-        if effective_cred.project_id.is_empty() {
-            logger::log_info("[ApiProxy] project_id 为空，尝试通过 loadCodeAssist 获取...");
-            match fetch_project_id_via_load_code_assist(&state.http_client, &effective_cred.access_token).await {
-                Ok(pid) => {
-                    logger::log_info(&format!("[ApiProxy] ✓ 获取到 project_id: {}", pid));
-                    effective_cred.project_id = pid.clone();
-                    // 更新 payload 中的 project
-                    let mut updated_payload = google_payload.clone();
-                    updated_payload["project"] = Value::String(pid);
-                    google_payload = updated_payload;
-                }
-                Err(e) => {
-                    logger::log_info(&format!("[ApiProxy] ⚠️ loadCodeAssist 失败: {}", e));
-                }
-            }
-        }
-
-        // 调试日志：输出完整 payload (前500字符)
-        if let Ok(payload_str) = serde_json::to_string_pretty(&google_payload) {
-            logger::log_info(&format!(
-                "[ApiProxy] Google payload (前500字符): {}",
-                &payload_str[..payload_str.len().min(500)]
-            ));
-        }
-
-        // 发送请求（多端点降级）
-        let resp = match send_antigravity_request(
-            &state.http_client, &effective_cred, &google_payload, stream
-        ).await {
-            Ok(r) => r,
-            Err(e) => {
-                let debug_info = format!(
-                    "上游请求失败: {} | project_id={} | model={}",
-                    e,
-                    if effective_cred.project_id.is_empty() { "<空>" } else { &effective_cred.project_id },
-                    model
-                );
-                return (
-                    StatusCode::BAD_GATEWAY,
-                    Json(serde_json::json!({
-                        "type": "error",
-                        "error": { "type": "api_error", "message": debug_info }
-                    })),
-                )
-                    .into_response();
-            }
-        };
-
-        let status = StatusCode::from_u16(resp.status().as_u16())
-            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    super::api_proxy_audit::note_upstream_payload(&google_payload);
+    super::api_proxy_audit::note_account(&effective_cred.id, &effective_cred.email);
 
-        if !status.is_success() {
-            // 错误响应直接透传
-            let err_text = resp.text().await.unwrap_or_default();
-            logger::log_info(&format!("[ApiProxy] Antigravity 上游错误 {}: {}", status, &err_text[..err_text.len().min(500)]));
+    // 发送请求（多端点降级）
+    let resp = match send_antigravity_request(
+        &state.http_client(), &effective_cred, &google_payload, stream
+    ).await {
+        Ok(r) => r,
+        Err(e) => {
+            let debug_info = format!(
+                "上游请求失败: {} | project_id={} | model={}",
+                e,
+                if effective_cred.project_id.is_empty() { "<空>" } else { &effective_cred.project_id },
+                model
+            );
             return (
-                status,
+                StatusCode::BAD_GATEWAY,
                 Json(serde_json::json!({
                     "type": "error",
-                    "error": { "type": "api_error", "message": err_text }
+                    "error": { "type": "api_error", "message": debug_info }
                 })),
             )
                 .into_response();
         }
+    };
 
-        if stream {
-            // SSE 流式：Google 格式 → Claude 格式
-            let msg_id = format!("msg_{}", chrono::Utc::now().timestamp_millis());
-            let model_clone = model.clone();
-
-            // 先发 message_start 事件
-            let start_event = serde_json::json!({
-                "type": "message_start",
-                "message": {
-                    "id": msg_id,
-                    "type": "message",
-                    "role": "assistant",
-                    "model": model_clone,
-                    "content": [],
-                    "stop_reason": null
-                }
-            });
-            let start_str = format!("event: message_start\ndata: {}\n\n", start_event);
+    let status = StatusCode::from_u16(resp.status().as_u16())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    state.metrics.record_upstream("antigravity", &effective_cred.id, &effective_cred.email, status.as_u16());
 
-            // content_block_start
-            let block_start = serde_json::json!({
-                "type": "content_block_start",
-                "index": 0,
-                "content_block": { "type": "text", "text": "" }
-            });
-            let block_start_str = format!("event: content_block_start\ndata: {}\n\n", block_start);
+    if !status.is_success() {
+        // 错误响应直接透传
+        let retry_after = header_str(resp.headers(), "retry-after");
+        let err_text = resp.text().await.unwrap_or_default();
+        logger::log_info(&format!("[ApiProxy] Antigravity 上游错误 {}: {}", status, &err_text[..err_text.len().min(500)]));
+        if super::api_proxy_cooldown::is_cooldown_status(status.as_u16()) {
+            state.trip_cooldown("antigravity", &effective_cred, status.as_u16(), retry_after.as_deref(), &err_text);
+        }
+        return (
+            status,
+            Json(serde_json::json!({
+                "type": "error",
+                "error": { "type": "api_error", "message": err_text }
+            })),
+        )
+            .into_response();
+    }
 
-            let prefix = format!("{}{}", start_str, block_start_str);
+    state.cooldowns.record_success("antigravity", &effective_cred.id);
 
-            // 转换 Google SSE 流 → Claude SSE 流
-            let google_stream = resp.bytes_stream();
-            let msg_id_clone = msg_id.clone();
+    if stream {
+        // SSE 流式：Google 格式 → Claude 格式
+        let msg_id = format!("msg_{}", chrono::Utc::now().timestamp_millis());
+        let model_clone = model.clone();
 
-            let claude_stream = async_stream::stream! {
-                // 先发前缀事件
-                yield Ok::<bytes::Bytes, String>(bytes::Bytes::from(prefix));
+        // 先发 message_start 事件
+        let start_event = serde_json::json!({
+            "type": "message_start",
+            "message": {
+                "id": msg_id,
+                "type": "message",
+                "role": "assistant",
+                "model": model_clone,
+                "content": [],
+                "stop_reason": null,
+                "stop_sequence": null,
+                "usage": { "input_tokens": 0, "output_tokens": 0 }
+            }
+        });
+        let prefix = format!("event: message_start\ndata: {}\n\n", start_event);
 
-                let mut buffer = String::new();
-                use futures_util::StreamExt;
+        // 转换 Google SSE 流 → Claude SSE 流（内容块按需打开）
+        let google_stream = resp.bytes_stream();
+        let cred_id = effective_cred.id.clone();
+        let cred_email = effective_cred.email.clone();
+        let api_key_id = caller.key_id.clone();
 
-                let mut stream = google_stream;
-                while let Some(chunk) = stream.next().await {
-                    match chunk {
-                        Ok(bytes) => {
-                            buffer.push_str(&String::from_utf8_lossy(&bytes));
+        let claude_stream = async_stream::stream! {
+            // 先发前缀事件
+            yield Ok::<bytes::Bytes, String>(bytes::Bytes::from(prefix));
 
-                            // 按行处理 SSE 事件
-                            while let Some(pos) = buffer.find("\n") {
-                                let line = buffer[..pos].trim().to_string();
-                                buffer = buffer[pos + 1..].to_string();
+            let mut converter = GoogleSseConverter::new();
+            let mut buffer = String::new();
+            use futures_util::StreamExt;
 
-                                if !line.starts_with("data: ") {
-                                    continue;
-                                }
-                                let data = &line[6..];
-                                if data == "[DONE]" {
-                                    continue;
-                                }
+            let mut stream = google_stream;
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        buffer.push_str(&String::from_utf8_lossy(&bytes));
 
-                                let claude_events = convert_google_sse_to_claude(
-                                    data, &model_clone, &msg_id_clone
-                                );
-                                for event in claude_events {
-                                    yield Ok(bytes::Bytes::from(event));
-                                }
+                        // 按行处理 SSE 事件
+                        while let Some(pos) = buffer.find("\n") {
+                            let line = buffer[..pos].trim().to_string();
+                            buffer = buffer[pos + 1..].to_string();
+
+                            if !line.starts_with("data: ") {
+                                continue;
+                            }
+                            let data = &line[6..];
+                            if data == "[DONE]" {
+                                continue;
+                            }
+
+                            for event in converter.convert(data) {
+                                yield Ok(bytes::Bytes::from(event));
                             }
-                        }
-                        Err(e) => {
-                            yield Err(format!("流读取错误: {}", e));
-                            break;
                         }
                     }
+                    Err(e) => {
+                        yield Err(format!("流读取错误: {}", e));
+                        break;
+                    }
                 }
+            }
 
-                // 发送结束事件
-                let block_stop = serde_json::json!({"type": "content_block_stop", "index": 0});
-                yield Ok(bytes::Bytes::from(format!("event: content_block_stop\ndata: {}\n\n", block_stop)));
-
-                let msg_stop = serde_json::json!({"type": "message_stop"});
-                yield Ok(bytes::Bytes::from(format!("event: message_stop\ndata: {}\n\n", msg_stop)));
-            };
-
-            return Response::builder()
-                .status(200)
-                .header("Content-Type", "text/event-stream")
-                .header("Cache-Control", "no-cache")
-                .header("Connection", "keep-alive")
-                .body(Body::from_stream(claude_stream))
-                .unwrap()
-                .into_response();
-        } else {
-            // 非流式：Google 响应 → Claude 响应
-            let google_resp: Value = match resp.json().await {
-                Ok(v) => v,
-                Err(e) => {
-                    return (
-                        StatusCode::BAD_GATEWAY,
-                        Json(serde_json::json!({"error": format!("解析上游响应失败: {}", e)})),
-                    )
-                        .into_response();
-                }
-            };
-
-            // 提取文本
-            let candidates = google_resp.get("response").and_then(|r| r.get("candidates")).or_else(|| google_resp.get("candidates"));
-            let text = candidates
-                .and_then(|c| c.get(0))
-                .and_then(|c| c.get("content"))
-                .and_then(|c| c.get("parts"))
-                .and_then(|p| p.as_array())
-                .map(|parts| {
-                    parts.iter()
-                        .filter_map(|p| p.get("text").and_then(|v| v.as_str()))
-                        .collect::<Vec<_>>()
-                        .join("")
-                })
-                .unwrap_or_default();
-
-            let claude_resp = serde_json::json!({
-                "id": format!("msg_{}", chrono::Utc::now().timestamp_millis()),
-                "type": "message",
-                "role": "assistant",
-                "model": model,
-                "content": [{"type": "text", "text": text}],
-                "stop_reason": "end_turn"
-            });
-
-            return (StatusCode::OK, Json(claude_resp)).into_response();
-        }
-    }
+            // 发送结束事件
+            for event in converter.finish() {
+                yield Ok(bytes::Bytes::from(event));
+            }
+            if let Some(usage) = converter.usage.as_ref() {
+                let usage = super::api_proxy_usage::TokenUsage::from_claude(usage);
+                super::api_proxy_usage::record_usage(
+                    &super::api_proxy_usage::UsageSource {
+                        provider: "antigravity",
+                        account_id: &cred_id,
+                        account_email: &cred_email,
+                        model: &model_clone,
+                        api_key_id: &api_key_id,
+                    },
+                    &usage,
+                );
+            }
+        };
 
-    // ===== Kiro: Amazon Q 协议转换（全面增强版） =====
-    if provider == "kiro" {
-        let client_body: Value = match serde_json::from_slice(&body_bytes) {
+        Response::builder()
+            .status(200)
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .header("Connection", "keep-alive")
+            .body(Body::from_stream(claude_stream))
+            .unwrap()
+            .into_response()
+    } else {
+        // 非流式：Google 响应 → Claude 响应
+        let google_resp: Value = match resp.json().await {
             Ok(v) => v,
             Err(e) => {
                 return (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({"error": format!("JSON 解析失败: {}", e)})),
-                ).into_response();
+                    StatusCode::BAD_GATEWAY,
+                    Json(serde_json::json!({"error": format!("解析上游响应失败: {}", e)})),
+                )
+                    .into_response();
             }
         };
 
-        let model = client_body.get("model")
-            .and_then(|v| v.as_str())
-            .unwrap_or("claude-sonnet-4-5")
-            .to_string();
+        let claude_resp = convert_google_response_to_claude(&google_resp, &model);
+        let usage = super::api_proxy_usage::TokenUsage::from_claude(&claude_resp["usage"]);
+        super::api_proxy_usage::record_usage(
+            &super::api_proxy_usage::UsageSource {
+                provider: "antigravity",
+                account_id: &effective_cred.id,
+                account_email: &effective_cred.email,
+                model: &model,
+                api_key_id: &caller.key_id,
+            },
+            &usage,
+        );
+        (StatusCode::OK, Json(claude_resp)).into_response()
+    }
+}
 
-        let stream = client_body.get("stream")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
+/// Kiro 请求处理：客户端消息 → Kiro generateAssistantResponse，响应转换为 Claude 格式
+async fn handle_kiro(
+    state: &SharedState,
+    cred: &AccountCredential,
+    caller: &ApiKeyScope,
+    slot: &super::api_proxy_limiter::HeldSlot,
+    client_body: Value,
+    default_base_url: &str,
+) -> Response {
+    let model = client_body.get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or("claude-sonnet-4-5")
+        .to_string();
 
-        // 检查是否请求 thinking/reasoning
-        let enable_thinking = client_body.get("reasoning_effort").is_some()
-            || client_body.get("thinking").is_some()
-            || client_body.get("enable_thinking")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
+    let stream = client_body.get("stream")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
 
-        // 使用 build_kiro_payload 构建完整的 Kiro 请求
-        let kiro_payload = match build_kiro_payload(
-            &client_body,
-            &model,
-            cred.profile_arn.as_deref(),
-            enable_thinking,
-        ) {
-            Ok(p) => p,
-            Err(e) => {
-                return (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({
-                        "type": "error",
-                        "error": { "type": "invalid_request_error", "message": e }
-                    })),
-                ).into_response();
-            }
-        };
+    // 检查是否请求 thinking/reasoning
+    let enable_thinking = client_body.get("reasoning_effort").is_some()
+        || client_body.get("thinking").is_some()
+        || client_body.get("enable_thinking")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
 
+    // 请求体与端点都取决于账号的 profileArn，换号重试时按新账号重新构建
+    let build_request = |cred: &AccountCredential| -> Result<(Value, String), String> {
+        let payload = build_kiro_payload(&client_body, &model, cred.profile_arn.as_deref(), enable_thinking)?;
         // 确定 Kiro API 端点（基于 profileArn 的 region）
-        let kiro_base_url = if let Some(ref arn) = cred.profile_arn {
+        let base_url = if let Some(ref arn) = cred.profile_arn {
             let region = parse_profile_arn_region(arn);
             kiro_runtime_endpoint_for_region(region.as_deref())
         } else {
-            upstream.base_url.to_string()
+            default_base_url.to_string()
         };
-        let url = format!("{}/generateAssistantResponse", kiro_base_url);
+        Ok((payload, format!("{}/generateAssistantResponse", base_url)))
+    };
+    let (mut kiro_payload, mut url) = match build_request(cred) {
+        Ok(request) => request,
+        Err(e) => return kiro_invalid_request(e),
+    };
 
-        logger::log_info(&format!(
-            "[ApiProxy] Kiro {} (model={}, thinking={}, tools={})",
-            if stream { "stream" } else { "non-stream" },
-            model,
-            enable_thinking,
-            client_body.get("tools").and_then(|v| v.as_array()).map(|a| a.len()).unwrap_or(0),
-        ));
+    logger::log_info(&format!(
+        "[ApiProxy] Kiro {} (model={}, thinking={}, tools={})",
+        if stream { "stream" } else { "non-stream" },
+        model,
+        enable_thinking,
+        client_body.get("tools").and_then(|v| v.as_array()).map(|a| a.len()).unwrap_or(0),
+    ));
 
-        // 发送请求（支持 403 自动刷新 token 重试 + 429 指数退避）
-        let max_retries = 3u32;
-        let mut last_error = String::new();
-        let mut resp_result: Option<reqwest::Response> = None;
-        let mut current_cred = cred.clone();
-
-        for attempt in 0..max_retries {
-            let req_builder = state.http_client.post(&url)
-                .header("Authorization", format!("Bearer {}", current_cred.access_token))
-                .header("Content-Type", "application/x-amzn-json-1.0")
-                .header("Accept", "application/json")
-                .header("X-Amz-Target", "AmazonCodeWhispererStreamingService.GenerateAssistantResponse")
-                .header("amz-sdk-request", format!("attempt={}; max={}", attempt + 1, max_retries))
-                .header("x-amzn-kiro-agent-mode", "vibe")
-                .header("x-amzn-codewhisperer-optout", "true")
-                .header("x-amz-user-agent", "aws-sdk-js/1.0.27 KiroIDE-0.7.45-fetch")
-                .header("User-Agent", "aws-sdk-js/1.0.27 ua/2.1 os/win32#10.0.19044 lang/js md/nodejs#22.21.1 api/codewhispererstreaming#1.0.27 m/E KiroIDE-0.7.45-fetch")
-                .json(&kiro_payload);
-
-            match req_builder.send().await {
-                Ok(resp) => {
-                    let status_code = resp.status().as_u16();
-
-                    // 403 → 刷新该账号 token 并用新凭据重试
-                    if status_code == 403 && attempt + 1 < max_retries {
-                        let err_text = resp.text().await.unwrap_or_default();
-                        logger::log_warn(&format!(
-                            "[ApiProxy] Kiro 403 (attempt {}/{}), 尝试刷新 token: {}",
-                            attempt + 1, max_retries, err_text
-                        ));
-                        if let Ok(()) = super::kiro_account::refresh_account_token(&current_cred.id).await.map(|_| ()) {
-                            if let Ok(Some(refreshed_cred)) = state.get_credential_by_id("kiro", &current_cred.id).await {
-                                current_cred = refreshed_cred;
-                                logger::log_info("[ApiProxy] Kiro token 已刷新，使用新凭据重试");
-                            }
+    // 发送请求（支持 403 自动刷新 token 重试 + 429 指数退避）
+    let max_retries = 3u32;
+    let mut last_error = String::new();
+    let mut resp_result: Option<reqwest::Response> = None;
+    let mut current_cred = cred.clone();
+    super::api_proxy_audit::note_upstream_payload(&kiro_payload);
+
+    for attempt in 0..max_retries {
+        super::api_proxy_audit::note_account(&current_cred.id, &current_cred.email);
+        if attempt > 0 {
+            (kiro_payload, url) = match build_request(&current_cred) {
+                Ok(request) => request,
+                Err(e) => return kiro_invalid_request(e),
+            };
+            super::api_proxy_audit::note_upstream_payload(&kiro_payload);
+        }
+        let req_builder = state.http_client().post(&url)
+            .header("Authorization", format!("Bearer {}", current_cred.access_token))
+            .header("Content-Type", "application/x-amzn-json-1.0")
+            .header("Accept", "application/json")
+            .header("X-Amz-Target", "AmazonCodeWhispererStreamingService.GenerateAssistantResponse")
+            .header("amz-sdk-request", format!("attempt={}; max={}", attempt + 1, max_retries))
+            .header("x-amzn-kiro-agent-mode", "vibe")
+            .header("x-amzn-codewhisperer-optout", "true")
+            .header("x-amz-user-agent", "aws-sdk-js/1.0.27 KiroIDE-0.7.45-fetch")
+            .header("User-Agent", "aws-sdk-js/1.0.27 ua/2.1 os/win32#10.0.19044 lang/js md/nodejs#22.21.1 api/codewhispererstreaming#1.0.27 m/E KiroIDE-0.7.45-fetch")
+            .json(&kiro_payload);
+
+        match req_builder.send().await {
+            Ok(resp) => {
+                let status_code = resp.status().as_u16();
+                state.metrics.record_upstream("kiro", &current_cred.id, &current_cred.email, status_code);
+
+                // 403 → 刷新该账号 token 并用新凭据重试
+                if status_code == 403 && attempt + 1 < max_retries {
+                    let err_text = resp.text().await.unwrap_or_default();
+                    logger::log_warn(&format!(
+                        "[ApiProxy] Kiro 403 (attempt {}/{}), 尝试刷新 token: {}",
+                        attempt + 1, max_retries, err_text
+                    ));
+                    if let Ok(()) = super::kiro_account::refresh_account_token(&current_cred.id).await.map(|_| ()) {
+                        if let Ok(Some(refreshed_cred)) = state.get_credential_by_id("kiro", &current_cred.id).await {
+                            current_cred = refreshed_cred;
+                            logger::log_info("[ApiProxy] Kiro token 已刷新，使用新凭据重试");
                         }
-                        last_error = format!("403 Forbidden: {}", err_text);
-                        continue;
                     }
+                    last_error = format!("403 Forbidden: {}", err_text);
+                    state.metrics.record_retry("kiro", &current_cred.id, &current_cred.email);
+                    continue;
+                }
 
-                    // 429 → 额度不足/限流，切换账号重试
-                    if status_code == 429 && attempt + 1 < max_retries {
-                        let err_text = resp.text().await.unwrap_or_default();
-                        logger::log_warn(&format!(
-                            "[ApiProxy] Kiro 账号 {} 限速 (HTTP {}), 准备切换账号重试...",
-                            current_cred.id, status_code
-                        ));
-                        last_error = format!("429 Too Many Requests: {}", err_text);
-                        
-                        match state.get_next_credential("kiro", None).await {
-                            Ok(new_cred) => {
-                                current_cred = new_cred;
-                                continue;
-                            }
-                            Err(e) => {
-                                logger::log_warn(&format!("[ApiProxy] Kiro 无法获取下一个账号用于重试: {}", e));
-                                break;
+                // 429 → 额度不足/限流，冷却该账号并切换账号重试
+                if status_code == 429 && attempt + 1 < max_retries {
+                    let retry_after = header_str(resp.headers(), "retry-after");
+                    let err_text = resp.text().await.unwrap_or_default();
+                    state.trip_cooldown("kiro", &current_cred, status_code, retry_after.as_deref(), &err_text);
+                    logger::log_warn(&format!(
+                        "[ApiProxy] Kiro 账号 {} 限速 (HTTP {}), 准备切换账号重试...",
+                        current_cred.id, status_code
+                    ));
+                    last_error = format!("429 Too Many Requests: {}", err_text);
+                    
+                    match state.checkout_for_retry("kiro", Some(&model), caller, slot).await {
+                        Ok(new_cred) => {
+                            state.metrics.record_retry("kiro", &current_cred.id, &current_cred.email);
+                            if new_cred.id != current_cred.id {
+                                state.metrics.record_rotation("kiro");
                             }
+                            current_cred = new_cred;
+                            continue;
+                        }
+                        Err(e) => {
+                            logger::log_warn(&format!("[ApiProxy] Kiro 无法获取下一个账号用于重试: {}", e));
+                            break;
                         }
                     }
+                }
 
-                    if !resp.status().is_success() {
-                        let err_text = resp.text().await.unwrap_or_default();
-                        logger::log_info(&format!("[ApiProxy] Kiro 错误 {}: {}", status_code, err_text));
-                        
-                        // 分类网络错误，提供友好提示
-                        let (err_type, err_msg) = match status_code {
-                            400 => ("invalid_request_error", format!("Kiro API 请求参数错误: {}", err_text)),
-                            401 => ("authentication_error", format!("Kiro 认证失败，请重新登录: {}", err_text)),
-                            403 => ("permission_error", format!("Kiro 无权访问: {}", err_text)),
-                            429 => ("rate_limit_error", "Kiro API 请求频率超限，请稍后重试".to_string()),
-                            500..=599 => ("api_error", format!("Kiro 服务端错误 ({}): {}", status_code, err_text)),
-                            _ => ("api_error", format!("Kiro 请求失败 ({}): {}", status_code, err_text)),
-                        };
-                        
-                        return (
-                            StatusCode::from_u16(status_code).unwrap_or(StatusCode::BAD_GATEWAY),
-                            Json(serde_json::json!({
-                                "type": "error",
-                                "error": { "type": err_type, "message": err_msg }
-                            })),
-                        ).into_response();
+                if !resp.status().is_success() {
+                    let retry_after = header_str(resp.headers(), "retry-after");
+                    let err_text = resp.text().await.unwrap_or_default();
+                    logger::log_info(&format!("[ApiProxy] Kiro 错误 {}: {}", status_code, err_text));
+                    if super::api_proxy_cooldown::is_cooldown_status(status_code) {
+                        state.trip_cooldown("kiro", &current_cred, status_code, retry_after.as_deref(), &err_text);
                     }
-
-                    resp_result = Some(resp);
-                    break;
+                    
+                    // 分类网络错误，提供友好提示
+                    let (err_type, err_msg) = match status_code {
+                        400 => ("invalid_request_error", format!("Kiro API 请求参数错误: {}", err_text)),
+                        401 => ("authentication_error", format!("Kiro 认证失败，请重新登录: {}", err_text)),
+                        403 => ("permission_error", format!("Kiro 无权访问: {}", err_text)),
+                        429 => ("rate_limit_error", "Kiro API 请求频率超限，请稍后重试".to_string()),
+                        500..=599 => ("api_error", format!("Kiro 服务端错误 ({}): {}", status_code, err_text)),
+                        _ => ("api_error", format!("Kiro 请求失败 ({}): {}", status_code, err_text)),
+                    };
+                    
+                    return (
+                        StatusCode::from_u16(status_code).unwrap_or(StatusCode::BAD_GATEWAY),
+                        Json(serde_json::json!({
+                            "type": "error",
+                            "error": { "type": err_type, "message": err_msg }
+                        })),
+                    ).into_response();
                 }
-                Err(e) => {
-                    last_error = format!("网络错误: {}", e);
-                    if attempt + 1 < max_retries {
-                        // 网络错误重试
-                        let delay = std::time::Duration::from_secs(1u64.pow(attempt + 1));
-                        logger::log_warn(&format!(
-                            "[ApiProxy] Kiro 网络错误 (attempt {}/{}): {}, 等待 {:?}",
-                            attempt + 1, max_retries, e, delay
-                        ));
-                        tokio::time::sleep(delay).await;
-                        continue;
-                    }
+
+                state.cooldowns.record_success("kiro", &current_cred.id);
+                resp_result = Some(resp);
+                break;
+            }
+            Err(e) => {
+                last_error = format!("网络错误: {}", e);
+                if attempt + 1 < max_retries {
+                    // 网络错误重试
+                    let delay = std::time::Duration::from_secs(1u64.pow(attempt + 1));
+                    logger::log_warn(&format!(
+                        "[ApiProxy] Kiro 网络错误 (attempt {}/{}): {}, 等待 {:?}",
+                        attempt + 1, max_retries, e, delay
+                    ));
+                    tokio::time::sleep(delay).await;
+                    state.metrics.record_retry("kiro", &current_cred.id, &current_cred.email);
+                    continue;
                 }
             }
         }
+    }
 
-        let resp = match resp_result {
-            Some(r) => r,
-            None => {
-                return (
-                    StatusCode::BAD_GATEWAY,
-                    Json(serde_json::json!({
-                        "type": "error",
-                        "error": {
-                            "type": "connectivity_error",
-                            "message": format!("Kiro 请求在 {} 次重试后仍然失败: {}", max_retries, last_error)
-                        }
-                    })),
-                ).into_response();
-            }
-        };
-
-        if stream {
-            let msg_id = format!("msg_{}", chrono::Utc::now().timestamp_millis());
-            let model_clone = model.clone();
-
-            let start_event = serde_json::json!({
-                "type": "message_start",
-                "message": {
-                    "id": msg_id,
-                    "type": "message",
-                    "role": "assistant",
-                    "model": model_clone,
-                    "content": [],
-                    "stop_reason": null
-                }
-            });
-            let start_str = format!("event: message_start\ndata: {}\n\n", start_event);
-            let block_start = serde_json::json!({
-                "type": "content_block_start",
-                "index": 0,
-                "content_block": { "type": "text", "text": "" }
-            });
-            let block_start_str = format!("event: content_block_start\ndata: {}\n\n", block_start);
-            let prefix = format!("{}{}", start_str, block_start_str);
+    let resp = match resp_result {
+        Some(r) => r,
+        None => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({
+                    "type": "error",
+                    "error": {
+                        "type": "connectivity_error",
+                        "message": format!("Kiro 请求在 {} 次重试后仍然失败: {}", max_retries, last_error)
+                    }
+                })),
+            ).into_response();
+        }
+    };
 
-            let mut kiro_stream = resp.bytes_stream();
-            let cred_id = cred.id.clone();
-            let claude_stream = async_stream::stream! {
-                yield Ok::<bytes::Bytes, String>(bytes::Bytes::from(prefix));
-                use futures_util::StreamExt;
-                let mut parser = KiroStreamParser::new();
-                let mut content_block_index: usize = 0;
-                let mut first_token_received = false;
-                let mut has_stop_event = false;
-                let first_token_start = std::time::Instant::now();
-                let first_token_timeout = std::time::Duration::from_secs(30);
-
-                while let Some(chunk) = kiro_stream.next().await {
-                    match chunk {
-                        Ok(bytes) => {
-                            if !first_token_received && first_token_start.elapsed() > first_token_timeout {
-                                logger::log_warn("[ApiProxy] Kiro 接收首 Token 超时");
-                                let timeout_msg = serde_json::json!({
-                                    "type": "content_block_delta",
-                                    "index": 0,
-                                    "delta": {
-                                        "type": "text_delta",
-                                        "text": "[Kiro API 响应超时，模型未在 30 秒内开始输出]"
-                                    }
-                                });
-                                yield Ok(bytes::Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", timeout_msg)));
-                                break;
-                            }
+    if stream {
+        let msg_id = format!("msg_{}", chrono::Utc::now().timestamp_millis());
+        let model_clone = model.clone();
 
-                            // 喂给有状态流解析器
-                            let events = parser.feed(&bytes);
+        let start_event = serde_json::json!({
+            "type": "message_start",
+            "message": {
+                "id": msg_id,
+                "type": "message",
+                "role": "assistant",
+                "model": model_clone,
+                "content": [],
+                "stop_reason": null,
+                "stop_sequence": null,
+                "usage": { "input_tokens": 0, "output_tokens": 0 }
+            }
+        });
+        let start_str = format!("event: message_start\ndata: {}\n\n", start_event);
+        let prefix = format!("{}{}", start_str, kiro_text_block_start_sse(0));
+
+        let mut kiro_stream = resp.bytes_stream();
+        let cred_id = current_cred.id.clone();
+        let cred_email = current_cred.email.clone();
+        let api_key_id = caller.key_id.clone();
+        let claude_stream = async_stream::stream! {
+            yield Ok::<bytes::Bytes, String>(bytes::Bytes::from(prefix));
+            use futures_util::StreamExt;
+            let mut parser = KiroStreamParser::new();
+            let mut content_block_index: usize = 0;
+            // 当前打开的文本块下标；工具块之后的文本另开新块
+            let mut text_block: Option<usize> = Some(0);
+            let mut saw_tool_use = false;
+            let mut output_chars: usize = 0;
+            let mut context_pct: Option<f64> = None;
+            let mut credits = 0.0;
+            let mut first_token_received = false;
+            let first_token_start = std::time::Instant::now();
+            let first_token_timeout = std::time::Duration::from_secs(30);
 
-                            for ev in events {
-                                // STREAM-3: Token 计数（利用 contextUsagePercentage）
-                                if let Some(pct) = ev.context_usage_percentage {
-                                    crate::modules::kiro_account::update_account_quota(&cred_id, pct).ok();
+            while let Some(chunk) = kiro_stream.next().await {
+                match chunk {
+                    Ok(bytes) => {
+                        if !first_token_received && first_token_start.elapsed() > first_token_timeout {
+                            logger::log_warn("[ApiProxy] Kiro 接收首 Token 超时");
+                            let timeout_msg = serde_json::json!({
+                                "type": "content_block_delta",
+                                "index": 0,
+                                "delta": {
+                                    "type": "text_delta",
+                                    "text": "[Kiro API 响应超时，模型未在 30 秒内开始输出]"
                                 }
+                            });
+                            yield Ok(bytes::Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", timeout_msg)));
+                            break;
+                        }
 
-                                match ev.event_type.as_str() {
-                                    "content" => {
-                                        first_token_received = true;
-                                        if !ev.content.is_empty() {
-                                            let delta = serde_json::json!({
-                                                "type": "content_block_delta",
-                                                "index": 0,
-                                                "delta": { "type": "text_delta", "text": ev.content }
-                                            });
-                                            yield Ok(bytes::Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", delta)));
-                                        }
-                                    }
-                                    "thinking" => {
-                                        first_token_received = true;
-                                        if !ev.thinking_content.is_empty() {
-                                            let delta = serde_json::json!({
-                                                "type": "content_block_delta",
-                                                "index": 0,
-                                                "delta": { "type": "thinking_delta", "thinking": ev.thinking_content }
-                                            });
-                                            yield Ok(bytes::Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", delta)));
-                                        }
+                        // 喂给有状态流解析器
+                        let events = parser.feed(&bytes);
+
+                        for ev in events {
+                            // STREAM-3: Token 计数（利用 contextUsagePercentage）
+                            if let Some(pct) = ev.context_usage_percentage {
+                                context_pct = Some(pct);
+                            }
+                            credits += ev.metering_credits.unwrap_or(0.0);
+
+                            match ev.event_type.as_str() {
+                                "content" => {
+                                    first_token_received = true;
+                                    if !ev.content.is_empty() {
+                                        let index = match text_block {
+                                            Some(index) => index,
+                                            None => {
+                                                content_block_index += 1;
+                                                text_block = Some(content_block_index);
+                                                yield Ok(bytes::Bytes::from(kiro_text_block_start_sse(content_block_index)));
+                                                content_block_index
+                                            }
+                                        };
+                                        output_chars += ev.content.chars().count();
+                                        let delta = serde_json::json!({
+                                            "type": "content_block_delta",
+                                            "index": index,
+                                            "delta": { "type": "text_delta", "text": ev.content }
+                                        });
+                                        yield Ok(bytes::Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", delta)));
                                     }
-                                    "tool_use" => {
-                                        if let Some(tool_obj) = ev.tool_use {
-                                            first_token_received = true;
-                                            content_block_index += 1;
-                                            
-                                            // 发送 tool_use 类型，符合 Anthropic Tool Call 格式
-                                            let cb_start = serde_json::json!({
-                                                "type": "content_block_start",
-                                                "index": content_block_index,
-                                                "content_block": tool_obj
-                                            });
-                                            yield Ok(bytes::Bytes::from(format!("event: content_block_start\ndata: {}\n\n", cb_start)));
-                                            
-                                            // TODO: 工具输入参数如果在响应过程中增量到来，此处做了简化（合并在 block_stop 前完成传输）。
-                                            // 因为我们在 content_block_stop 时才 emit tool_use，参数已完整，因此可以紧接着发送空 delta 然后 close：
-                                            let tool_delta = serde_json::json!({
-                                                "type": "content_block_delta",
-                                                "index": content_block_index,
-                                                "delta": {
-                                                    "type": "input_json_delta",
-                                                    "partial_json": ""
-                                                }
-                                            });
-                                            yield Ok(bytes::Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", tool_delta)));
-
-                                            let cb_stop = serde_json::json!({
-                                                "type": "content_block_stop",
-                                                "index": content_block_index
-                                            });
-                                            yield Ok(bytes::Bytes::from(format!("event: content_block_stop\ndata: {}\n\n", cb_stop)));
-                                        }
+                                }
+                                "thinking" => {
+                                    first_token_received = true;
+                                    if !ev.thinking_content.is_empty() {
+                                        let index = match text_block {
+                                            Some(index) => index,
+                                            None => {
+                                                content_block_index += 1;
+                                                text_block = Some(content_block_index);
+                                                yield Ok(bytes::Bytes::from(kiro_text_block_start_sse(content_block_index)));
+                                                content_block_index
+                                            }
+                                        };
+                                        output_chars += ev.thinking_content.chars().count();
+                                        let delta = serde_json::json!({
+                                            "type": "content_block_delta",
+                                            "index": index,
+                                            "delta": { "type": "thinking_delta", "thinking": ev.thinking_content }
+                                        });
+                                        yield Ok(bytes::Bytes::from(format!("event: content_block_delta\ndata: {}\n\n", delta)));
                                     }
-                                    "message_stop" => {
-                                        has_stop_event = true;
-                                        let stop_event = serde_json::json!({"type": "message_stop"});
-                                        yield Ok(bytes::Bytes::from(format!("event: message_stop\ndata: {}\n\n", stop_event)));
+                                }
+                                "tool_use" => {
+                                    if let Some(tool_obj) = ev.tool_use {
+                                        first_token_received = true;
+                                        // 工具块开始前关闭文本块，保证内容块不交叠
+                                        if let Some(index) = text_block.take() {
+                                            let block_stop = serde_json::json!({"type": "content_block_stop", "index": index});
+                                            yield Ok(bytes::Bytes::from(format!("event: content_block_stop\ndata: {}\n\n", block_stop)));
+                                        }
+                                        saw_tool_use = true;
+                                        output_chars += tool_obj.to_string().chars().count();
+                                        content_block_index += 1;
+                                        for sse in kiro_tool_use_sse_events(content_block_index, &tool_obj) {
+                                            yield Ok(bytes::Bytes::from(sse));
+                                        }
                                     }
-                                    _ => {}
                                 }
+                                _ => {}
                             }
                         }
-                        Err(e) => {
-                            // 网络错误分类
-                            let err_msg = format!("{}", e);
-                            let (category, user_msg) = if err_msg.contains("timed out") || err_msg.contains("timeout") {
-                                ("timeout_read", "读取超时 - Kiro 服务端停止响应")
-                            } else if err_msg.contains("connection") || err_msg.contains("Connection") {
-                                ("connection_error", "连接错误 - 与 Kiro 服务的连接中断")
-                            } else {
-                                ("stream_error", "流读取错误")
-                            };
-                            logger::log_warn(&format!("[ApiProxy] Kiro 流错误 [{}]: {}", category, err_msg));
-                            
+                    }
+                    Err(e) => {
+                        // 网络错误分类
+                        let err_msg = format!("{}", e);
+                        let (category, user_msg) = if err_msg.contains("timed out") || err_msg.contains("timeout") {
+                            ("timeout_read", "读取超时 - Kiro 服务端停止响应")
+                        } else if err_msg.contains("connection") || err_msg.contains("Connection") {
+                            ("connection_error", "连接错误 - 与 Kiro 服务的连接中断")
+                        } else {
+                            ("stream_error", "流读取错误")
+                        };
+                        logger::log_warn(&format!("[ApiProxy] Kiro 流错误 [{}]: {}", category, err_msg));
+                        
+                        if let Some(index) = text_block {
                             let err_delta = serde_json::json!({
                                 "type": "content_block_delta",
-                                "index": 0,
+                                "index": index,
                                 "delta": {
                                     "type": "text_delta",
                                     "text": format!("\n\n[{}: {}]", user_msg, err_msg)
@@ -2878,134 +3977,815 @@ async fn proxy_handler(
                             yield Ok(bytes::Bytes::from(format!(
                                 "event: content_block_delta\ndata: {}\n\n", err_delta
                             )));
-                            break;
                         }
+                        break;
                     }
                 }
+            }
 
-                // STREAM-4: 正常结束但没有 message_stop 时，补发停止信号
-                if !has_stop_event {
-                    let block_stop = serde_json::json!({"type": "content_block_stop", "index": 0});
-                    yield Ok(bytes::Bytes::from(format!("event: content_block_stop\ndata: {}\n\n", block_stop)));
-                    let msg_stop = serde_json::json!({"type": "message_stop"});
-                    yield Ok(bytes::Bytes::from(format!("event: message_stop\ndata: {}\n\n", msg_stop)));
+            // 流结束时仍未收到 stop 的工具调用
+            for ev in parser.finish() {
+                if let Some(tool_obj) = ev.tool_use {
+                    if let Some(index) = text_block.take() {
+                        let block_stop = serde_json::json!({"type": "content_block_stop", "index": index});
+                        yield Ok(bytes::Bytes::from(format!("event: content_block_stop\ndata: {}\n\n", block_stop)));
+                    }
+                    saw_tool_use = true;
+                    output_chars += tool_obj.to_string().chars().count();
+                    content_block_index += 1;
+                    for sse in kiro_tool_use_sse_events(content_block_index, &tool_obj) {
+                        yield Ok(bytes::Bytes::from(sse));
+                    }
                 }
-            };
+            }
+
+            // STREAM-4: 关闭文本块，发送 message_delta（stop_reason + 估算 usage）与 message_stop
+            if let Some(index) = text_block {
+                let block_stop = serde_json::json!({"type": "content_block_stop", "index": index});
+                yield Ok(bytes::Bytes::from(format!("event: content_block_stop\ndata: {}\n\n", block_stop)));
+            }
+            let stop_reason = if saw_tool_use { "tool_use" } else { "end_turn" };
+            let usage = kiro_estimate_usage(output_chars, context_pct);
+            let mut ledger_usage = super::api_proxy_usage::TokenUsage::from_claude(&usage);
+            ledger_usage.credits = credits;
+            crate::modules::kiro_account::add_credits_used(&cred_id, credits).ok();
+            super::api_proxy_usage::record_usage(
+                &super::api_proxy_usage::UsageSource {
+                    provider: "kiro",
+                    account_id: &cred_id,
+                    account_email: &cred_email,
+                    model: &model_clone,
+                    api_key_id: &api_key_id,
+                },
+                &ledger_usage,
+            );
+            let msg_delta = serde_json::json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": stop_reason, "stop_sequence": null },
+                "usage": usage
+            });
+            yield Ok(bytes::Bytes::from(format!("event: message_delta\ndata: {}\n\n", msg_delta)));
+            let msg_stop = serde_json::json!({"type": "message_stop"});
+            yield Ok(bytes::Bytes::from(format!("event: message_stop\ndata: {}\n\n", msg_stop)));
+        };
+
+        Response::builder()
+            .status(200)
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .header("Connection", "keep-alive")
+            .body(Body::from_stream(claude_stream))
+            .unwrap()
+            .into_response()
+    } else {
+        // 非流式：收集完整的流式响应后返回
+        use futures_util::StreamExt;
+        let mut parser = KiroStreamParser::new();
+        let mut full_content = String::new();
+        let mut thinking_content = String::new();
+        let mut in_thinking = false;
+        let mut kiro_stream = resp.bytes_stream();
+        let mut tool_uses_output: Vec<Value> = Vec::new();
+        let mut context_pct: Option<f64> = None;
+        let mut credits = 0.0;
+
+        while let Some(chunk) = kiro_stream.next().await {
+            match chunk {
+                Ok(bytes) => {
+                    for ev in parser.feed(&bytes) {
+                        if let Some(pct) = ev.context_usage_percentage {
+                            context_pct = Some(pct);
+                        }
+                        credits += ev.metering_credits.unwrap_or(0.0);
+                        // 收集 tool uses
+                        if let Some(tool_use) = ev.tool_use {
+                            tool_uses_output.push(tool_use);
+                            continue;
+                        }
+                        if ev.event_type != "content" {
+                            continue;
+                        }
+                        let content = ev.content.as_str();
+                        if enable_thinking {
+                            if content.contains("<thinking>") {
+                                in_thinking = true;
+                                let after = content.split("<thinking>").last().unwrap_or("");
+                                thinking_content.push_str(after);
+                                continue;
+                            }
+                            if in_thinking && content.contains("</thinking>") {
+                                let before = content.split("</thinking>").next().unwrap_or("");
+                                thinking_content.push_str(before);
+                                in_thinking = false;
+                                let after = content.split("</thinking>").last().unwrap_or("").trim();
+                                if !after.is_empty() {
+                                    full_content.push_str(after);
+                                }
+                                continue;
+                            }
+                            if in_thinking {
+                                thinking_content.push_str(content);
+                                continue;
+                            }
+                        }
+                        full_content.push_str(content);
+                    }
+                }
+                Err(e) => {
+                    logger::log_warn(&format!("[ApiProxy] Kiro 非流式读取错误: {}", e));
+                    break;
+                }
+            }
+        }
+        tool_uses_output.extend(parser.finish().into_iter().filter_map(|ev| ev.tool_use));
+
+        // 构建 Claude 格式非流式响应
+        let mut content_blocks: Vec<Value> = Vec::new();
 
+        // thinking block
+        if !thinking_content.is_empty() {
+            content_blocks.push(serde_json::json!({
+                "type": "thinking",
+                "thinking": thinking_content
+            }));
+        }
+
+        // text block
+        if !full_content.is_empty() {
+            content_blocks.push(serde_json::json!({"type": "text", "text": full_content}));
+        }
+
+        // tool_use blocks
+        for tu in &tool_uses_output {
+            content_blocks.push(kiro_tool_use_to_claude_block(tu));
+        }
+
+        if content_blocks.is_empty() {
+            content_blocks.push(serde_json::json!({"type": "text", "text": "(empty response)"}));
+        }
+
+        let stop_reason = if !tool_uses_output.is_empty() { "tool_use" } else { "end_turn" };
+        let output_chars = full_content.chars().count()
+            + thinking_content.chars().count()
+            + tool_uses_output.iter().map(|t| t.to_string().chars().count()).sum::<usize>();
+        let usage = kiro_estimate_usage(output_chars, context_pct);
+        let mut ledger_usage = super::api_proxy_usage::TokenUsage::from_claude(&usage);
+        ledger_usage.credits = credits;
+        crate::modules::kiro_account::add_credits_used(&current_cred.id, credits).ok();
+        super::api_proxy_usage::record_usage(
+            &super::api_proxy_usage::UsageSource {
+                provider: "kiro",
+                account_id: &current_cred.id,
+                account_email: &current_cred.email,
+                model: &model,
+                api_key_id: &caller.key_id,
+            },
+            &ledger_usage,
+        );
+
+        let claude_resp = serde_json::json!({
+            "id": format!("msg_{}", chrono::Utc::now().timestamp_millis()),
+            "type": "message",
+            "role": "assistant",
+            "model": model,
+            "content": content_blocks,
+            "stop_reason": stop_reason,
+            "stop_sequence": null,
+            "usage": usage
+        });
+        (StatusCode::OK, Json(claude_resp)).into_response()
+    }
+}
+
+/// Codex 请求处理：OpenAI chat/completions → ChatGPT /responses，响应转换回 OpenAI 格式
+async fn handle_codex_chat(
+    state: &SharedState,
+    cred: &AccountCredential,
+    caller: &ApiKeyScope,
+    slot: &super::api_proxy_limiter::HeldSlot,
+    client_body: Value,
+) -> Response {
+    let model = client_body
+        .get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or(super::api_proxy_codex::DEFAULT_CODEX_MODEL)
+        .to_string();
+    let stream = client_body.get("stream").and_then(|v| v.as_bool()).unwrap_or(true);
+    let codex_payload = match super::api_proxy_codex::build_codex_payload(&client_body) {
+        Ok(p) => p,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": e})),
+            )
+                .into_response();
+        }
+    };
+    let mut current_cred = cred.clone();
+    let max_retries = 3;
+    let mut last_error_response = None;
+    super::api_proxy_audit::note_upstream_payload(&codex_payload);
+
+    for attempt in 0..max_retries {
+        super::api_proxy_audit::note_account(&current_cred.id, &current_cred.email);
+        let mut codex_req = state.codex_http_client().post(super::api_proxy_codex::CODEX_RESPONSES_URL)
+            .header("Authorization", format!("Bearer {}", current_cred.access_token))
+            .header("Content-Type", "application/json")
+            .header("User-Agent", "codex_cli_rs/0.104.0")
+            .header("Accept", "text/event-stream")
+            .header("Origin", "https://chatgpt.com")
+            .header("Referer", "https://chatgpt.com/")
+            .header("originator", "codex_cli_rs")
+            .json(&codex_payload);
+        if let Some(ref acc_id) = current_cred.chatgpt_account_id {
+            if !acc_id.is_empty() {
+                codex_req = codex_req.header("ChatGPT-Account-Id", acc_id.as_str());
+            }
+        }
+        logger::log_info(&format!(
+            "[ApiProxy] Codex chat -> /responses (model={}, stream={}, attempt={})",
+            model, stream, attempt + 1
+        ));
+        let resp = match codex_req.send().await {
+            Ok(r) => r,
+            Err(e) => {
+                return (
+                    StatusCode::BAD_GATEWAY,
+                    Json(serde_json::json!({
+                        "error": format!("上游请求失败: {}", e)
+                    })),
+                )
+                    .into_response();
+            }
+        };
+        let status = resp.status();
+        state.metrics.record_upstream("codex", &current_cred.id, &current_cred.email, status.as_u16());
+
+        if super::api_proxy_cooldown::is_cooldown_status(status.as_u16()) {
+            logger::log_warn(&format!("[ApiProxy] Codex 账号 {} 额度不足/限流 (HTTP {}), 准备切换账号重试...", current_cred.id, status));
+            let retry_after = header_str(resp.headers(), "retry-after");
+            let err_text = resp.text().await.unwrap_or_default();
+            state.trip_cooldown("codex", &current_cred, status.as_u16(), retry_after.as_deref(), &err_text);
+            last_error_response = Some((
+                StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
+                Json(serde_json::json!({"error": err_text.clone(), "detail": err_text})),
+            ).into_response());
+
+            if attempt < max_retries - 1 {
+                // 获取下一个账号用于重试，忽略 override
+                match state.checkout_for_retry("codex", Some(&model), caller, slot).await {
+                    Ok(new_cred) => {
+                        state.metrics.record_retry("codex", &current_cred.id, &current_cred.email);
+                        if new_cred.id != current_cred.id {
+                            state.metrics.record_rotation("codex");
+                        }
+                        current_cred = new_cred;
+                        continue;
+                    }
+                    Err(e) => {
+                        logger::log_warn(&format!("[ApiProxy] Codex 无法获取下一个账号用于重试: {}", e));
+                        break; // 无法获取新账号，终止重试
+                    }
+                }
+            } else {
+                break;
+            }
+        } else if !status.is_success() {
+            let err_text = resp.text().await.unwrap_or_default();
+            return (
+                StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
+                Json(serde_json::json!({"error": err_text.clone(), "detail": err_text})),
+            )
+                .into_response();
+        }
+
+        state.cooldowns.record_success("codex", &current_cred.id);
+        let mut codex_stream = resp.bytes_stream();
+        let include_usage = client_body
+            .pointer("/stream_options/include_usage")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let mut converter = super::api_proxy_codex::CodexToOpenAiStream::new(&model, include_usage);
+        let cred_id = current_cred.id.clone();
+        let cred_email = current_cred.email.clone();
+        let api_key_id = caller.key_id.clone();
+        let model_clone = model.clone();
+
+        if stream {
+            let openai_stream = async_stream::stream! {
+                use futures_util::StreamExt;
+                let mut buffer = String::new();
+                while let Some(chunk) = codex_stream.next().await {
+                    match chunk {
+                        Ok(bytes) => {
+                            buffer.push_str(&String::from_utf8_lossy(&bytes));
+                            while let Some(pos) = buffer.find('\n') {
+                                let line = buffer[..pos].trim().to_string();
+                                buffer = buffer[pos + 1..].to_string();
+                                let data = match line.strip_prefix("data:") {
+                                    Some(d) => d.trim(),
+                                    None => continue,
+                                };
+                                if data.is_empty() || data == "[DONE]" {
+                                    continue;
+                                }
+                                if let Ok(val) = serde_json::from_str::<Value>(data) {
+                                    for out in converter.on_event(&val) {
+                                        yield Ok::<bytes::Bytes, String>(bytes::Bytes::from(out));
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            logger::log_warn(&format!("[ApiProxy] Codex 流读取错误: {}", e));
+                            break;
+                        }
+                    }
+                }
+                for out in converter.finish() {
+                    yield Ok(bytes::Bytes::from(out));
+                }
+                if let Some(usage) = converter.usage() {
+                    let usage = super::api_proxy_usage::TokenUsage::from_openai(usage);
+                    super::api_proxy_usage::record_usage(
+                        &super::api_proxy_usage::UsageSource {
+                            provider: "codex",
+                            account_id: &cred_id,
+                            account_email: &cred_email,
+                            model: &model_clone,
+                            api_key_id: &api_key_id,
+                        },
+                        &usage,
+                    );
+                }
+            };
             return Response::builder()
                 .status(200)
                 .header("Content-Type", "text/event-stream")
                 .header("Cache-Control", "no-cache")
                 .header("Connection", "keep-alive")
-                .body(Body::from_stream(claude_stream))
+                .body(Body::from_stream(openai_stream))
                 .unwrap()
                 .into_response();
-        } else {
-            // 非流式：收集完整的流式响应后返回
-            use futures_util::StreamExt;
-            let mut binary_buffer: Vec<u8> = Vec::new();
-            let mut full_content = String::new();
-            let mut thinking_content = String::new();
-            let mut in_thinking = false;
-            let mut kiro_stream = resp.bytes_stream();
-            let mut tool_uses_output: Vec<Value> = Vec::new();
-
-            while let Some(chunk) = kiro_stream.next().await {
-                match chunk {
-                    Ok(bytes) => {
-                        binary_buffer.extend_from_slice(&bytes);
-                        let (events, consumed) = kiro_parse_event_stream_frames(&binary_buffer);
-                        if consumed > 0 {
-                            binary_buffer = binary_buffer[consumed..].to_vec();
-                        }
+        }
 
-                        for event_str in events {
-                            if let Ok(val) = serde_json::from_str::<Value>(&event_str) {
-                                if let Some(content) = val.get("content").and_then(|v| v.as_str()) {
-                                    if enable_thinking {
-                                        if content.contains("<thinking>") {
-                                            in_thinking = true;
-                                            let after = content.split("<thinking>").last().unwrap_or("");
-                                            thinking_content.push_str(after);
-                                            continue;
-                                        }
-                                        if in_thinking && content.contains("</thinking>") {
-                                            let before = content.split("</thinking>").next().unwrap_or("");
-                                            thinking_content.push_str(before);
-                                            in_thinking = false;
-                                            let after = content.split("</thinking>").last().unwrap_or("").trim();
-                                            if !after.is_empty() {
-                                                full_content.push_str(after);
-                                            }
-                                            continue;
-                                        }
-                                        if in_thinking {
-                                            thinking_content.push_str(content);
-                                            continue;
-                                        }
-                                    }
-                                    full_content.push_str(content);
-                                }
-                                // 收集 tool uses
-                                if let Some(tool_use) = val.get("toolUse") {
-                                    tool_uses_output.push(tool_use.clone());
-                                }
+        // 非流式：上游仍为 SSE，聚合为 chat.completion
+        use futures_util::StreamExt;
+        let mut buffer = String::new();
+        while let Some(chunk) = codex_stream.next().await {
+            match chunk {
+                Ok(bytes) => {
+                    buffer.push_str(&String::from_utf8_lossy(&bytes));
+                    while let Some(pos) = buffer.find('\n') {
+                        let line = buffer[..pos].trim().to_string();
+                        buffer = buffer[pos + 1..].to_string();
+                        if let Some(data) = line.strip_prefix("data:").map(|d| d.trim()) {
+                            if let Ok(val) = serde_json::from_str::<Value>(data) {
+                                converter.on_event(&val);
                             }
                         }
                     }
-                    Err(e) => {
-                        logger::log_warn(&format!("[ApiProxy] Kiro 非流式读取错误: {}", e));
-                        break;
-                    }
+                }
+                Err(e) => {
+                    logger::log_warn(&format!("[ApiProxy] Codex 非流式读取错误: {}", e));
+                    break;
                 }
             }
+        }
+        if let Some(err) = converter.error() {
+            return (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({"error": err, "detail": err})),
+            )
+                .into_response();
+        }
+        if let Some(usage) = converter.usage() {
+            let usage = super::api_proxy_usage::TokenUsage::from_openai(usage);
+            super::api_proxy_usage::record_usage(
+                &super::api_proxy_usage::UsageSource {
+                    provider: "codex",
+                    account_id: &cred_id,
+                    account_email: &cred_email,
+                    model: &model_clone,
+                    api_key_id: &caller.key_id,
+                },
+                &usage,
+            );
+        }
+        return (StatusCode::OK, Json(converter.into_completion())).into_response();
+    } // end of retry loop
 
-            // 构建 Claude 格式非流式响应
-            let mut content_blocks: Vec<Value> = Vec::new();
+    last_error_response.unwrap_or_else(|| {
+        (
+            StatusCode::BAD_GATEWAY,
+            Json(serde_json::json!({"error": "Codex 请求在重试后仍然失败"})),
+        )
+            .into_response()
+    })
+}
 
-            // thinking block
-            if !thinking_content.is_empty() {
-                content_blocks.push(serde_json::json!({
-                    "type": "thinking",
-                    "thinking": thinking_content
-                }));
-            }
+/// 鉴权失败 → HTTP 响应
+fn rejection_response(rejection: ApiKeyRejection) -> Response {
+    match rejection {
+        ApiKeyRejection::Unauthorized => (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({"error": "Unauthorized: Invalid API Key"})),
+        )
+            .into_response(),
+        ApiKeyRejection::Forbidden(msg) => {
+            (StatusCode::FORBIDDEN, Json(serde_json::json!({"error": msg}))).into_response()
+        }
+        ApiKeyRejection::RateLimited(msg, retry_after) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [("retry-after", retry_after.to_string())],
+            Json(serde_json::json!({"error": msg})),
+        )
+            .into_response(),
+        ApiKeyRejection::Unavailable(msg) => {
+            logger::log_error(&format!("[ApiProxy] API Key 校验失败: {}", msg));
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"error": format!("API Key 校验失败: {}", msg)})),
+            )
+                .into_response()
+        }
+    }
+}
 
-            // text block
-            if !full_content.is_empty() {
-                content_blocks.push(serde_json::json!({"type": "text", "text": full_content}));
-            }
+/// 检查 API Key（管理员 Key 或命名 Key），命名 Key 同时计入频率限制
+/// 配置锁异常时按未授权处理
+fn authenticate_caller(
+    state: &SharedState,
+    headers: &HeaderMap,
+) -> Result<Option<super::api_proxy_keys::ApiKeyEntry>, ApiKeyRejection> {
+    let admin_key = state
+        .config
+        .read()
+        .map(|c| c.api_key.clone())
+        .map_err(|_| ApiKeyRejection::Unauthorized)?;
+    let presented = super::api_proxy_keys::presented_key(
+        headers.get("authorization").and_then(|v| v.to_str().ok()),
+        headers.get("x-api-key").and_then(|v| v.to_str().ok()),
+    );
+    super::api_proxy_keys::authenticate(&admin_key, presented)
+}
 
-            // tool_use blocks
-            for tu in &tool_uses_output {
-                content_blocks.push(serde_json::json!({
-                    "type": "tool_use",
-                    "id": tu.get("toolUseId").and_then(|v| v.as_str()).unwrap_or(""),
-                    "name": tu.get("name").and_then(|v| v.as_str()).unwrap_or(""),
-                    "input": tu.get("input").cloned().unwrap_or(serde_json::json!({}))
-                }));
-            }
+/// 读取请求 body（上限 10MB）
+async fn read_body(body: Body) -> Result<axum::body::Bytes, Response> {
+    axum::body::to_bytes(body, 10 * 1024 * 1024).await.map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"error": format!("读取请求体失败: {}", e)})),
+        )
+            .into_response()
+    })
+}
+
+/// 请求头 X-Selected-Account-Email（Chat 页选中的账号）
+fn selected_account_email(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get("x-selected-account-email")
+        .and_then(|v| v.to_str().ok())
+        .filter(|s| !s.trim().is_empty())
+}
+
+/// 请求头 X-Session-Id（客户端显式指定的会话）
+fn session_header(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(super::api_proxy_sessions::SESSION_HEADER)
+        .and_then(|v| v.to_str().ok())
+}
+
+/// 代理转发处理器
+async fn proxy_handler(
+    State(state): State<SharedState>,
+    Path((provider, rest)): Path<(String, String)>,
+    method: Method,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    // 1. 检查 API Key 及其 Provider 范围
+    let api_key = match authenticate_caller(&state, &headers) {
+        Ok(k) => k,
+        Err(rejection) => return rejection_response(rejection),
+    };
+    let caller = match super::api_proxy_keys::scope_for_provider(api_key.as_ref(), &provider) {
+        Ok(scope) => scope,
+        Err(rejection) => return rejection_response(rejection),
+    };
+
+    // 2. 检查 Provider
+    if get_provider_upstream(&provider).is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": format!("Unknown provider: {}", provider)})),
+        )
+            .into_response();
+    }
+
+    // GET /{provider}/v1/models：返回该 Provider 的模型列表
+    if method == Method::GET && rest == "v1/models" && MODEL_LIST_PROVIDERS.contains(&provider.as_str()) {
+        return models_response(&state, &[provider.as_str()], false).await;
+    }
+
+    // 3. 读取请求 body
+    let body_bytes = match read_body(body).await {
+        Ok(b) => b,
+        Err(resp) => return resp,
+    };
+
+    // 4. 获取凭据（请求头 X-Selected-Account-Email 优先，确保使用用户在 Chat 页选中的账号）
+    // 请求模型用于配额感知策略，会话标识用于粘滞选号
+    let parsed_body = serde_json::from_slice::<Value>(&body_bytes).unwrap_or(Value::Null);
+    let requested_model = parsed_body.get("model").and_then(|v| v.as_str());
+    let session = super::api_proxy_sessions::session_key(session_header(&headers), &parsed_body);
+    let (cred, slot) = match state
+        .checkout_credential(
+            &provider,
+            selected_account_email(&headers),
+            requested_model,
+            &caller,
+            session.as_deref(),
+        )
+        .await
+    {
+        Ok(checked_out) => checked_out,
+        Err(e) => return e.into_response(),
+    };
+
+    let request = ForwardRequest { provider, rest, method, headers, body_bytes };
+    audited_forward(state, request, caller, cred, slot).await
+}
 
-            if content_blocks.is_empty() {
-                content_blocks.push(serde_json::json!({"type": "text", "text": "(empty response)"}));
+/// 统一入口 /v1/*：按模型路由表选择 Provider 与上游模型，首选池没有可用账号时依次回退
+async fn unified_handler(
+    State(state): State<SharedState>,
+    Path(rest): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let api_key = match authenticate_caller(&state, &headers) {
+        Ok(k) => k,
+        Err(rejection) => return rejection_response(rejection),
+    };
+
+    // GET /v1/models：汇总调用方有权访问的 Provider
+    if method == Method::GET && rest == "models" {
+        let providers: Vec<&str> = MODEL_LIST_PROVIDERS
+            .iter()
+            .copied()
+            .filter(|p| super::api_proxy_keys::scope_for_provider(api_key.as_ref(), p).is_ok())
+            .collect();
+        return models_response(&state, &providers, true).await;
+    }
+
+    let body_bytes = match read_body(body).await {
+        Ok(b) => b,
+        Err(resp) => return resp,
+    };
+    let mut client_body: Value = match serde_json::from_slice(&body_bytes) {
+        Ok(v) => v,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": format!("JSON 解析失败: {}", e)})),
+            )
+                .into_response();
+        }
+    };
+    let requested_model = match client_body.get("model").and_then(|v| v.as_str()) {
+        Some(m) if !m.trim().is_empty() => m.to_string(),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({"error": "缺少 model 字段，无法路由"})),
+            )
+                .into_response();
+        }
+    };
+
+    let routes = super::api_proxy_routes::list_model_routes().unwrap_or_default();
+    let targets = super::api_proxy_routes::resolve_targets(&routes, &requested_model);
+    if targets.is_empty() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": format!("模型 '{}' 未配置路由", requested_model)})),
+        )
+            .into_response();
+    }
+
+    let session = super::api_proxy_sessions::session_key(session_header(&headers), &client_body);
+    let mut errors = Vec::new();
+    let mut saturated = false;
+    for target in targets {
+        if get_provider_upstream(&target.provider).is_none() {
+            errors.push(format!("{}: Unknown provider", target.provider));
+            continue;
+        }
+        let caller = match super::api_proxy_keys::scope_for_provider(api_key.as_ref(), &target.provider) {
+            Ok(scope) => scope,
+            Err(ApiKeyRejection::Forbidden(msg)) => {
+                errors.push(msg);
+                continue;
             }
+            Err(rejection) => return rejection_response(rejection),
+        };
+        let upstream_model = if target.model.is_empty() {
+            requested_model.clone()
+        } else {
+            target.model.clone()
+        };
+        match state
+            .checkout_credential(
+                &target.provider,
+                selected_account_email(&headers),
+                Some(&upstream_model),
+                &caller,
+                session.as_deref(),
+            )
+            .await
+        {
+            Ok(checked_out) => {
+                logger::log_info(&format!(
+                    "[ApiProxy] 路由 {} -> {}/{}",
+                    requested_model, target.provider, upstream_model
+                ));
+                client_body["model"] = Value::String(upstream_model);
+                let body_bytes = axum::body::Bytes::from(client_body.to_string());
+                let request = ForwardRequest {
+                    provider: target.provider,
+                    rest: format!("v1/{}", rest),
+                    method,
+                    headers,
+                    body_bytes,
+                };
+                let (cred, slot) = checked_out;
+                return audited_forward(state, request, caller, cred, slot).await;
+            }
+            Err(CheckoutError::Unavailable(e)) => errors.push(format!("{}: {}", target.provider, e)),
+            Err(CheckoutError::Saturated(e)) => {
+                saturated = true;
+                errors.push(format!("{}: {}", target.provider, e));
+            }
+        }
+    }
+
+    let message = format!("模型 '{}' 的所有路由均不可用：{}", requested_model, errors.join("；"));
+    // 有路由只是并发已满时提示客户端稍后重试
+    if saturated {
+        CheckoutError::Saturated(message).into_response()
+    } else {
+        CheckoutError::Unavailable(message).into_response()
+    }
+}
+
+/// 已完成鉴权与选号、待转发的请求
+struct ForwardRequest {
+    provider: String,
+    /// Provider 之后的路径，如 v1/messages
+    rest: String,
+    method: Method,
+    headers: HeaderMap,
+    body_bytes: axum::body::Bytes,
+}
+
+/// 转发请求并记录运行指标；开启审计时记录请求、上游 payload 与返回内容
+/// 并发槽位随响应体一起释放（流式响应在流结束时释放）；重试换号时槽位随之更换
+async fn audited_forward(
+    state: SharedState,
+    request: ForwardRequest,
+    caller: ApiKeyScope,
+    cred: AccountCredential,
+    slot: Option<super::api_proxy_limiter::SlotPermit>,
+) -> Response {
+    let started = std::time::Instant::now();
+    let metrics = Arc::clone(&state.metrics);
+    let provider = request.provider.clone();
+    let (account_id, account_email) = (cred.id.clone(), cred.email.clone());
+    let slot = super::api_proxy_limiter::HeldSlot::new(slot);
+    let resp = forward_with_audit(state, request, caller, cred, slot.clone()).await;
+    metrics.record_request(&provider, &account_id, &account_email, resp.status().as_u16(), started.elapsed());
+
+    let is_stream = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.contains("text/event-stream"));
+    if !is_stream && !slot.is_held() {
+        return resp;
+    }
+    // 响应体存续期间：流式响应计入进行中的流，并持有账号并发槽位
+    use futures_util::StreamExt;
+    let guard = is_stream.then(|| metrics.track_stream(&provider));
+    let (parts, body) = resp.into_parts();
+    let stream = body.into_data_stream().map(move |chunk| {
+        let _held = (&guard, &slot);
+        chunk
+    });
+    Response::from_parts(parts, Body::from_stream(stream))
+}
+
+async fn forward_with_audit(
+    state: SharedState,
+    request: ForwardRequest,
+    caller: ApiKeyScope,
+    cred: AccountCredential,
+    slot: super::api_proxy_limiter::HeldSlot,
+) -> Response {
+    let draft = super::api_proxy_audit::begin(super::api_proxy_audit::AuditStart {
+        provider: &request.provider,
+        path: &request.rest,
+        client_body: &request.body_bytes,
+        api_key_id: &caller.key_id,
+        account_id: &cred.id,
+        account_email: &cred.email,
+    });
+    let Some(draft) = draft else {
+        return forward_request(state, request, caller, cred, slot).await;
+    };
+    let (resp, notes) = super::api_proxy_audit::capture(forward_request(state, request, caller, cred, slot)).await;
+    draft.finish(resp, notes)
+}
+
+/// 审计记录重放结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditReplayResult {
+    pub status: u16,
+    pub latency_ms: u64,
+    pub body: String,
+}
+
+/// 用指定账号（为空则按策略选择）重新发送一条审计记录中的请求，需代理正在运行
+pub async fn replay_audit_record(id: &str, account_email: Option<&str>) -> Result<AuditReplayResult, String> {
+    let record = super::api_proxy_audit::get_record(id)?.ok_or_else(|| format!("未找到审计记录: {}", id))?;
+    if !record.replayable {
+        return Err("该记录的请求体经过脱敏或截断，与原始请求不一致，无法重放".to_string());
+    }
+    let state = PROXY_STATE
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .and_then(|guard| guard.clone())
+        .ok_or("代理服务未运行")?;
+
+    let provider = record.summary.provider.clone();
+    let caller = ApiKeyScope::default();
+    let model = Some(record.summary.model.as_str()).filter(|m| !m.is_empty());
+    let account_email = account_email.filter(|e| !e.trim().is_empty());
+    let (cred, slot) = state
+        .checkout_credential(&provider, account_email, model, &caller, None)
+        .await
+        .map_err(CheckoutError::into_message)?;
+    logger::log_info(&format!(
+        "[ApiProxy] 重放审计记录 {} -> {} ({})",
+        id, provider, cred.email
+    ));
+
+    let started = std::time::Instant::now();
+    let request = ForwardRequest {
+        provider,
+        rest: record.summary.path.clone(),
+        method: Method::POST,
+        headers: HeaderMap::new(),
+        body_bytes: axum::body::Bytes::from(record.client_body),
+    };
+    let resp = audited_forward(state, request, caller, cred, slot).await;
+    let status = resp.status().as_u16();
+    let bytes = axum::body::to_bytes(resp.into_body(), 20 * 1024 * 1024)
+        .await
+        .map_err(|e| format!("读取重放响应失败: {}", e))?;
+    Ok(AuditReplayResult {
+        status,
+        latency_ms: started.elapsed().as_millis() as u64,
+        body: String::from_utf8_lossy(&bytes).into_owned(),
+    })
+}
 
-            let stop_reason = if !tool_uses_output.is_empty() { "tool_use" } else { "end_turn" };
+/// 按 Provider 转换协议并转发到上游
+async fn forward_request(
+    state: SharedState,
+    request: ForwardRequest,
+    caller: ApiKeyScope,
+    cred: AccountCredential,
+    slot: super::api_proxy_limiter::HeldSlot,
+) -> Response {
+    let ForwardRequest { provider, rest, method, headers, body_bytes } = request;
+    let Some(upstream) = get_provider_upstream(&provider) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"error": format!("Unknown provider: {}", provider)})),
+        )
+            .into_response();
+    };
 
-            let claude_resp = serde_json::json!({
-                "id": format!("msg_{}", chrono::Utc::now().timestamp_millis()),
-                "type": "message",
-                "role": "assistant",
-                "model": model,
-                "content": content_blocks,
-                "stop_reason": stop_reason
-            });
-            return (StatusCode::OK, Json(claude_resp)).into_response();
-        }
-    }
+    // OpenAI chat/completions 入口：以 Claude 格式输出的 Provider 需要双向转换
+    let is_openai_chat = rest == "v1/chat/completions";
+    // Anthropic Messages 入口：以 OpenAI 格式输出的 Provider 需要双向转换
+    let is_claude_messages = rest == "v1/messages";
 
-    // ===== Codex: OpenAI chat/completions → ChatGPT /responses 协议转换 =====
-    if provider == "codex" && rest == "v1/chat/completions" {
+    // ===== Antigravity: Claude → Google 协议转换 =====
+    // ===== Kiro: Amazon Q 协议转换（全面增强版） =====
+    if provider == "antigravity" || provider == "kiro" {
         let client_body: Value = match serde_json::from_slice(&body_bytes) {
             Ok(v) => v,
             Err(e) => {
@@ -3016,218 +4796,69 @@ async fn proxy_handler(
                     .into_response();
             }
         };
+
+        if !is_openai_chat {
+            return if provider == "antigravity" {
+                handle_antigravity(&state, &cred, &caller, client_body).await
+            } else {
+                handle_kiro(&state, &cred, &caller, &slot, client_body, upstream.base_url).await
+            };
+        }
+
         let model = client_body
             .get("model")
             .and_then(|v| v.as_str())
-            .unwrap_or("gpt-5.1-codex")
+            .unwrap_or_default()
             .to_string();
-        let stream = client_body.get("stream").and_then(|v| v.as_bool()).unwrap_or(true);
-        let messages = client_body
-            .get("messages")
-            .and_then(|v| v.as_array())
-            .ok_or_else(|| "缺少 messages")
-            .map_err(|e| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    Json(serde_json::json!({"error": e})),
-                )
-                    .into_response()
-            });
-        let messages = match messages {
-            Ok(m) => m,
-            Err(r) => return r,
+        let include_usage = client_body
+            .pointer("/stream_options/include_usage")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let resp = if provider == "antigravity" {
+            let claude_body = super::api_proxy_openai::openai_chat_to_claude(&client_body);
+            handle_antigravity(&state, &cred, &caller, claude_body).await
+        } else {
+            // build_kiro_payload 原生支持 OpenAI messages/tools，只需转换响应
+            handle_kiro(&state, &cred, &caller, &slot, client_body, upstream.base_url).await
         };
-        // 转换为 ChatGPT /responses 的 input 格式
-        let input: Vec<Value> = messages
-            .iter()
-            .filter_map(|m| {
-                let role = m.get("role")?.as_str()?;
-                let content = m.get("content")?;
-                let text = match content {
-                    Value::String(s) => s.clone(),
-                    Value::Array(arr) => {
-                        let parts: Vec<String> = arr
-                            .iter()
-                            .filter_map(|p| {
-                                p.get("text").and_then(|t| t.as_str()).map(|s| s.to_string())
-                            })
-                            .collect();
-                        parts.join("")
-                    }
-                    _ => return None,
-                };
-                if text.is_empty() {
-                    return None;
-                }
-                let text_type = if role == "assistant" { "output_text" } else { "input_text" };
-                Some(serde_json::json!({
-                    "role": role,
-                    "content": [{"type": text_type, "text": text}]
-                }))
-            })
-            .collect();
-        if input.is_empty() {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({"error": "messages 无有效文本内容"})),
-            )
-                .into_response();
-        }
-        let codex_payload = serde_json::json!({
-            "model": model,
-            "input": input,
-            "stream": stream,
-            "store": false,
-            "instructions": "You are a helpful assistant."
-        });
-        let mut current_cred = cred.clone();
-        let max_retries = 3;
-        let mut last_error_response = None;
-
-        for attempt in 0..max_retries {
-            let codex_url = "https://chatgpt.com/backend-api/codex/responses";
-            let mut codex_req = state.codex_http_client.post(codex_url)
-                .header("Authorization", format!("Bearer {}", current_cred.access_token))
-                .header("Content-Type", "application/json")
-                .header("User-Agent", "codex_cli_rs/0.104.0")
-                .header("Accept", "text/event-stream")
-                .header("Origin", "https://chatgpt.com")
-                .header("Referer", "https://chatgpt.com/")
-                .header("originator", "codex_cli_rs")
-                .json(&codex_payload);
-            if let Some(ref acc_id) = current_cred.chatgpt_account_id {
-                if !acc_id.is_empty() {
-                    codex_req = codex_req.header("ChatGPT-Account-Id", acc_id.as_str());
-                }
-            }
-            logger::log_info(&format!(
-                "[ApiProxy] Codex chat -> /responses (model={}, stream={}, attempt={})",
-                model, stream, attempt + 1
-            ));
-            let resp = match codex_req.send().await {
-                Ok(r) => r,
-                Err(e) => {
-                    return (
-                        StatusCode::BAD_GATEWAY,
-                        Json(serde_json::json!({
-                            "error": format!("上游请求失败: {}", e)
-                        })),
-                    )
-                        .into_response();
-                }
-            };
-            let status = resp.status();
-            
-            if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::PAYMENT_REQUIRED || status == StatusCode::FORBIDDEN {
-                logger::log_warn(&format!("[ApiProxy] Codex 账号 {} 额度不足/限流 (HTTP {}), 准备切换账号重试...", current_cred.id, status));
-                let err_text = resp.text().await.unwrap_or_default();
-                last_error_response = Some((
-                    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
-                    Json(serde_json::json!({"error": err_text.clone(), "detail": err_text})),
-                ).into_response());
-                
-                if attempt < max_retries - 1 {
-                    // 获取下一个账号用于重试，忽略 override
-                    match state.get_next_credential(&provider, None).await {
-                        Ok(new_cred) => {
-                            current_cred = new_cred;
-                            continue;
-                        }
-                        Err(e) => {
-                            logger::log_warn(&format!("[ApiProxy] Codex 无法获取下一个账号用于重试: {}", e));
-                            break; // 无法获取新账号，终止重试
-                        }
-                    }
-                } else {
-                    break;
-                }
-            } else if !status.is_success() {
-                let err_text = resp.text().await.unwrap_or_default();
+        return super::api_proxy_openai::claude_response_to_openai(resp, model, include_usage).await;
+    }
+
+
+    // ===== Codex: OpenAI chat/completions → ChatGPT /responses 协议转换 =====
+    // Claude Messages 入口先转为 OpenAI chat，再把响应转换回 Claude 格式
+    if provider == "codex" && (is_openai_chat || is_claude_messages) {
+        let client_body: Value = match serde_json::from_slice(&body_bytes) {
+            Ok(v) => v,
+            Err(e) => {
                 return (
-                    StatusCode::from_u16(status.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
-                    Json(serde_json::json!({"error": err_text.clone(), "detail": err_text})),
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({"error": format!("JSON 解析失败: {}", e)})),
                 )
                     .into_response();
             }
-
-            if stream {
-                let codex_stream = resp.bytes_stream();
-                let openai_stream = async_stream::stream! {
-                    use futures_util::StreamExt;
-                    let mut buffer = String::new();
-                    let mut stream = codex_stream;
-                    while let Some(chunk) = stream.next().await {
-                        match chunk {
-                            Ok(bytes) => {
-                                buffer.push_str(&String::from_utf8_lossy(&bytes));
-                                while let Some(pos) = buffer.find('\n') {
-                                    let line = buffer[..pos].trim().to_string();
-                                    buffer = buffer[pos + 1..].to_string();
-                                    if !line.starts_with("data: ") {
-                                        continue;
-                                    }
-                                    let data = line.strip_prefix("data: ").map(|s| s.trim()).unwrap_or("");
-                                    if data == "[DONE]" {
-                                        yield Ok::<bytes::Bytes, String>(bytes::Bytes::from("data: [DONE]\n\n"));
-                                        continue;
-                                    }
-                                    if let Ok(val) = serde_json::from_str::<Value>(data) {
-                                        // ChatGPT: delta.text | OpenAI: choices[0].delta.content | 或嵌套在 output 等
-                                        let content_delta = val
-                                            .get("delta")
-                                            .and_then(|d| d.get("text").and_then(|t| t.as_str()))
-                                            .or_else(|| val.get("delta").and_then(|d| d.get("content").and_then(|c| c.as_str())))
-                                            .or_else(|| val.get("delta").and_then(|d| d.as_str()))
-                                            .or_else(|| val.get("output").and_then(|o| o.get("delta")).and_then(|d| d.get("text").and_then(|t| t.as_str())))
-                                            .or_else(|| val.get("choices").and_then(|c| c.get(0)).and_then(|c| c.get("delta")).and_then(|d| d.get("content")).and_then(|c| c.as_str()));
-                                        if let Some(text) = content_delta {
-                                            if !text.is_empty() {
-                                                let openai_chunk = serde_json::json!({
-                                                    "choices": [{"delta": {"content": text}, "index": 0}]
-                                                });
-                                                yield Ok(bytes::Bytes::from(format!("data: {}\n\n", openai_chunk)));
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                logger::log_warn(&format!("[ApiProxy] Codex 流读取错误: {}", e));
-                                break;
-                            }
-                        }
-                    }
-                    yield Ok(bytes::Bytes::from("data: [DONE]\n\n"));
-                };
-                return Response::builder()
-                    .status(200)
-                    .header("Content-Type", "text/event-stream")
-                    .header("Cache-Control", "no-cache")
-                    .header("Connection", "keep-alive")
-                    .body(Body::from_stream(openai_stream))
-                    .unwrap()
-                    .into_response();
-            } else {
-                let body = resp.bytes().await.unwrap_or_default();
-                let mut response = Response::new(Body::from(body));
-                *response.status_mut() = StatusCode::OK;
-                response.headers_mut().insert(
-                    axum::http::header::CONTENT_TYPE,
-                    axum::http::header::HeaderValue::from_static("application/json"),
-                );
-                return response.into_response();
-            }
-        } // end of retry loop
-
-        if let Some(err_resp) = last_error_response {
-            return err_resp;
+        };
+        if is_openai_chat {
+            return handle_codex_chat(&state, &cred, &caller, &slot, client_body).await;
         }
+        let model = client_body
+            .get("model")
+            .and_then(|v| v.as_str())
+            .unwrap_or(super::api_proxy_codex::DEFAULT_CODEX_MODEL)
+            .to_string();
+        let chat_body = super::api_proxy_openai::claude_to_openai_chat(&client_body);
+        let resp = handle_codex_chat(&state, &cred, &caller, &slot, chat_body).await;
+        return super::api_proxy_openai::openai_response_to_claude(resp, model).await;
     }
 
-    // ===== Codex / Windsurf / 其他 Provider: 简单转发 =====
+    // ===== Codex / GitHub Copilot / Windsurf / 其他 Provider: 简单转发 =====
+    // GitHub Copilot、Windsurf(Copilot API) 与 Warp 会话池原生即为 OpenAI chat/completions 格式，无需转换
     let upstream_url = if provider == "warp" {
         let config = state.config.read().unwrap();
         format!("{}/{}", config.warp_api_url.trim_end_matches('/'), rest)
+    } else if uses_copilot_api(&provider) {
+        // Copilot API 路径不带 /v1 前缀（/chat/completions、/models）
+        format!("{}/{}", upstream.base_url, rest.strip_prefix("v1/").unwrap_or(&rest))
     } else {
         format!("{}/{}", upstream.base_url, rest)
     };
@@ -3237,7 +4868,7 @@ async fn proxy_handler(
         method, rest, upstream_url, provider
     ));
 
-    let mut req_builder = state.http_client.request(
+    let mut req_builder = state.http_client().request(
         reqwest::Method::from_bytes(method.as_str().as_bytes()).unwrap_or(reqwest::Method::POST),
         &upstream_url,
     );
@@ -3261,6 +4892,13 @@ async fn proxy_handler(
     let auth_value = format!("{}{}", upstream.auth_prefix, cred.access_token);
     req_builder = req_builder.header(upstream.auth_header, &auth_value);
 
+    // Copilot API 要求携带编辑器标识头，否则返回 400
+    if uses_copilot_api(&provider) {
+        for (name, value) in COPILOT_EDITOR_HEADERS {
+            req_builder = req_builder.header(*name, *value);
+        }
+    }
+
     // Codex 上游需要 ChatGPT-Account-Id 头，否则可能 502/404
     if provider == "codex" {
         if let Some(ref acc_id) = cred.chatgpt_account_id {
@@ -3278,6 +4916,7 @@ async fn proxy_handler(
         Ok(resp) => {
             let status = StatusCode::from_u16(resp.status().as_u16())
                 .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+            state.metrics.record_upstream(&provider, &cred.id, &cred.email, status.as_u16());
 
             let mut response_headers = HeaderMap::new();
             for (key, value) in resp.headers().iter() {
@@ -3291,6 +4930,19 @@ async fn proxy_handler(
                 response_headers.insert(key.clone(), value.clone());
             }
 
+            if status.is_success() {
+                state.cooldowns.record_success(&provider, &cred.id);
+            } else if super::api_proxy_cooldown::is_cooldown_status(status.as_u16()) {
+                // 读取错误体推断恢复时间，再原样返回
+                let retry_after = header_str(resp.headers(), "retry-after");
+                let err_bytes = resp.bytes().await.unwrap_or_default();
+                state.trip_cooldown(&provider, &cred, status.as_u16(), retry_after.as_deref(), &String::from_utf8_lossy(&err_bytes));
+                let mut response = Response::new(Body::from(err_bytes));
+                *response.status_mut() = status;
+                *response.headers_mut() = response_headers;
+                return response;
+            }
+
             let is_sse = resp
                 .headers()
                 .get("content-type")
@@ -3355,15 +5007,13 @@ pub async fn start_proxy_server(proxy_config: ApiProxyConfig) -> Result<ProxySta
     }
 
     let port = proxy_config.port;
-    let bind_address = if proxy_config.allow_lan_access {
-        "0.0.0.0"
-    } else {
-        "127.0.0.1"
-    };
+    let allow_lan_access = proxy_config.allow_lan_access;
+    let bind_address = listen_address(allow_lan_access);
+    let tls = super::api_proxy_tls::prepare_tls(&super::api_proxy_tls::load_tls_settings())?;
+    let (listener, actual_port) = bind_listener(bind_address, port, false).await?;
 
+    // 绑定成功后再保存 state 引用到全局，以便运行时更新配置
     let state: SharedState = Arc::new(ProxyServerState::new(proxy_config.clone()));
-
-    // 保存 state 引用到全局，以便运行时更新配置
     {
         let lock = PROXY_STATE.get_or_init(|| RwLock::new(None));
         if let Ok(mut s) = lock.write() {
@@ -3371,32 +5021,83 @@ pub async fn start_proxy_server(proxy_config: ApiProxyConfig) -> Result<ProxySta
         }
     }
 
-    // 构建路由
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    logger::log_info(&format!(
+        "[ApiProxy] 反向代理启动在 {}://{}:{} (providers: {:?})",
+        if tls.is_some() { "https" } else { "http" },
+        bind_address,
+        actual_port,
+        enabled_providers
+    ));
 
-    let app = Router::new()
-        .route("/healthz", get(health_check))
-        .route("/{provider}/{*rest}", any(proxy_handler))
-        .layer(cors)
-        .with_state(state);
+    let tls_fingerprint = tls.as_ref().map(|t| t.fingerprint.clone());
+    serve_listener(ProxyListener::new(listener, tls), state, actual_port);
+    set_proxy_binding(Some((port, allow_lan_access)));
 
-    // 尝试绑定端口
-    let mut actual_port = port;
-    let mut listener = None;
-    for offset in 0..PROXY_PORT_RANGE {
-        let try_port = port + offset;
-        let addr: SocketAddr = format!("{}:{}", bind_address, try_port)
+    Ok(ProxyStatus {
+        running: true,
+        port: proxy_config.port,
+        actual_port: Some(actual_port),
+        enabled_providers,
+        cooldowns: Vec::new(),
+        tls: tls_fingerprint.is_some(),
+        tls_fingerprint,
+        queues: Vec::new(),
+        queued: HashMap::new(),
+        sessions: HashMap::new(),
+    })
+}
+
+/// HTTP 或 HTTPS 监听
+enum ProxyListener {
+    Plain(tokio::net::TcpListener),
+    Tls(super::api_proxy_tls::TlsListener),
+}
+
+impl ProxyListener {
+    fn new(listener: tokio::net::TcpListener, tls: Option<super::api_proxy_tls::TlsMaterial>) -> Self {
+        let fingerprint = tls.as_ref().map(|t| t.fingerprint.clone());
+        set_proxy_tls_fingerprint(fingerprint);
+        match tls {
+            Some(tls) => Self::Tls(super::api_proxy_tls::TlsListener::new(listener, tls.acceptor)),
+            None => Self::Plain(listener),
+        }
+    }
+}
+
+fn listen_address(allow_lan_access: bool) -> &'static str {
+    if allow_lan_access {
+        "0.0.0.0"
+    } else {
+        "127.0.0.1"
+    }
+}
+
+/// 绑定监听端口；端口被占用时依次尝试后续端口
+/// wait_for_release: 重新绑定时旧监听可能尚未释放端口，先在原端口上短暂重试
+async fn bind_listener(
+    bind_address: &str,
+    port: u16,
+    wait_for_release: bool,
+) -> Result<(tokio::net::TcpListener, u16), String> {
+    let addr_for = |p: u16| -> Result<SocketAddr, String> {
+        format!("{}:{}", bind_address, p)
             .parse()
-            .map_err(|e| format!("地址解析失败: {}", e))?;
-        match tokio::net::TcpListener::bind(addr).await {
-            Ok(l) => {
-                actual_port = try_port;
-                listener = Some(l);
-                break;
+            .map_err(|e| format!("地址解析失败: {}", e))
+    };
+
+    if wait_for_release {
+        for _ in 0..20 {
+            if let Ok(l) = tokio::net::TcpListener::bind(addr_for(port)?).await {
+                return Ok((l, port));
             }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+    }
+
+    for offset in 0..PROXY_PORT_RANGE {
+        let try_port = port + offset;
+        match tokio::net::TcpListener::bind(addr_for(try_port)?).await {
+            Ok(l) => return Ok((l, try_port)),
             Err(_) => {
                 if offset == 0 {
                     logger::log_info(&format!(
@@ -3409,17 +5110,48 @@ pub async fn start_proxy_server(proxy_config: ApiProxyConfig) -> Result<ProxySta
         }
     }
 
-    let listener = listener.ok_or(format!(
+    Err(format!(
         "无法绑定端口 {}-{}",
         port,
         port + PROXY_PORT_RANGE - 1
-    ))?;
+    ))
+}
 
-    logger::log_info(&format!(
-        "[ApiProxy] 反向代理启动在 {}:{} (providers: {:?})",
-        bind_address, actual_port, enabled_providers
-    ));
+/// 客户端地址（明文与 TLS 监听通用的 ConnectInfo）
+#[derive(Debug, Clone, Copy)]
+struct PeerAddr(SocketAddr);
+
+impl axum::extract::connect_info::Connected<axum::serve::IncomingStream<'_, tokio::net::TcpListener>> for PeerAddr {
+    fn connect_info(stream: axum::serve::IncomingStream<'_, tokio::net::TcpListener>) -> Self {
+        PeerAddr(*stream.remote_addr())
+    }
+}
+
+impl axum::extract::connect_info::Connected<axum::serve::IncomingStream<'_, super::api_proxy_tls::TlsListener>>
+    for PeerAddr
+{
+    fn connect_info(stream: axum::serve::IncomingStream<'_, super::api_proxy_tls::TlsListener>) -> Self {
+        PeerAddr(*stream.remote_addr())
+    }
+}
+
+/// 在监听上启动服务，并替换全局 shutdown channel
+fn serve_listener(listener: ProxyListener, state: SharedState, actual_port: u16) {
+    // 构建路由
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    let app = Router::new()
+        .route("/healthz", get(health_check))
+        .route("/metrics", get(metrics_handler))
+        .route("/v1/{*rest}", any(unified_handler))
+        .route("/{provider}/{*rest}", any(proxy_handler))
+        .layer(cors)
+        .with_state(state);
 
+    let generation = PROXY_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
     set_proxy_running(true);
     set_proxy_actual_port(Some(actual_port));
 
@@ -3432,27 +5164,124 @@ pub async fn start_proxy_server(proxy_config: ApiProxyConfig) -> Result<ProxySta
         }
     }
 
-    // 启动服务
+    // 启动服务；收到 shutdown 后停止接受新连接，等待进行中的请求（含流式响应）结束
     tokio::spawn(async move {
-        let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        let shutdown = async move {
             let _ = shutdown_rx.changed().await;
-        });
+        };
+        let result = match listener {
+            ProxyListener::Plain(l) => {
+                axum::serve(l, app.into_make_service_with_connect_info::<PeerAddr>())
+                    .with_graceful_shutdown(shutdown)
+                    .await
+            }
+            ProxyListener::Tls(l) => {
+                axum::serve(l, app.into_make_service_with_connect_info::<PeerAddr>())
+                    .with_graceful_shutdown(shutdown)
+                    .await
+            }
+        };
 
-        if let Err(e) = server.await {
+        if let Err(e) = result {
             logger::log_error(&format!("[ApiProxy] 服务异常退出: {}", e));
         }
 
-        set_proxy_running(false);
-        set_proxy_actual_port(None);
-        logger::log_info("[ApiProxy] 反向代理已停止");
+        if PROXY_GENERATION.load(Ordering::SeqCst) == generation {
+            set_proxy_running(false);
+            set_proxy_actual_port(None);
+            set_proxy_binding(None);
+            set_proxy_tls_fingerprint(None);
+            logger::log_info("[ApiProxy] 反向代理已停止");
+        } else {
+            logger::log_info(&format!("[ApiProxy] 端口 {} 上的旧监听已排空", actual_port));
+        }
     });
+}
 
-    Ok(ProxyStatus {
-        running: true,
-        port: proxy_config.port,
-        actual_port: Some(actual_port),
-        enabled_providers,
-    })
+/// 取出当前监听的 shutdown 发送端
+fn take_proxy_shutdown_tx() -> Option<watch::Sender<()>> {
+    PROXY_SHUTDOWN_TX
+        .get()
+        .and_then(|lock| lock.write().ok())
+        .and_then(|mut tx| tx.take())
+}
+
+/// 端口或局域网访问设置变更时重新绑定监听（force 为 true 时总是重新绑定）
+/// 新监听复用同一份运行状态（冷却、指标、轮询计数），旧监听停止接受新连接并等待进行中的流结束
+pub async fn rebind_proxy_if_needed(force: bool) -> Result<ProxyStatus, String> {
+    let Some(state) = running_state() else {
+        return Ok(get_proxy_status());
+    };
+    let (port, allow_lan_access) = {
+        let config = state.config.read().map_err(|e| format!("锁读取失败: {}", e))?;
+        (config.port, config.allow_lan_access)
+    };
+    let current = PROXY_BINDING
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .and_then(|b| *b);
+    if !force && current == Some((port, allow_lan_access)) {
+        return Ok(get_proxy_status());
+    }
+
+    let bind_address = listen_address(allow_lan_access);
+    let tls = super::api_proxy_tls::prepare_tls(&super::api_proxy_tls::load_tls_settings())?;
+    let previous_port = get_proxy_actual_port();
+
+    // 目标端口不被旧监听占用时先绑定新监听，失败则旧监听继续服务
+    if previous_port != Some(port) {
+        let (listener, actual_port) = bind_listener(bind_address, port, false).await?;
+        let previous_tx = take_proxy_shutdown_tx();
+        serve_listener(ProxyListener::new(listener, tls), state, actual_port);
+        set_proxy_binding(Some((port, allow_lan_access)));
+        // 旧监听停止接受连接，进行中的请求不受影响
+        if let Some(tx) = previous_tx {
+            let _ = tx.send(());
+        }
+        logger::log_info(&format!(
+            "[ApiProxy] 监听已切换到 {}:{}，旧连接排空中",
+            bind_address, actual_port
+        ));
+        return Ok(get_proxy_status());
+    }
+
+    // 旧监听占用目标端口，只能先释放；新地址绑定失败时恢复旧监听
+    if let Some(tx) = take_proxy_shutdown_tx() {
+        let _ = tx.send(());
+    }
+    let (listener, actual_port) = match bind_listener(bind_address, port, true).await {
+        Ok(bound) => bound,
+        Err(e) => {
+            if let Some((old_port, old_lan)) = current {
+                let old_address = listen_address(old_lan);
+                let restored = match super::api_proxy_tls::prepare_tls(&super::api_proxy_tls::load_tls_settings()) {
+                    Ok(tls) => bind_listener(old_address, port, true).await.map(|(l, p)| (l, p, tls)),
+                    Err(e) => Err(e),
+                };
+                if let Ok((listener, actual_port, tls)) = restored {
+                    serve_listener(ProxyListener::new(listener, tls), state, actual_port);
+                    set_proxy_binding(Some((old_port, old_lan)));
+                    logger::log_warn(&format!(
+                        "[ApiProxy] 无法切换到 {}:{}，已恢复 {}:{}",
+                        bind_address, port, old_address, actual_port
+                    ));
+                    return Err(e);
+                }
+            }
+            set_proxy_running(false);
+            set_proxy_actual_port(None);
+            set_proxy_binding(None);
+            set_proxy_tls_fingerprint(None);
+            return Err(e);
+        }
+    };
+    serve_listener(ProxyListener::new(listener, tls), state, actual_port);
+    set_proxy_binding(Some((port, allow_lan_access)));
+    logger::log_info(&format!(
+        "[ApiProxy] 监听已切换到 {}:{}，旧连接排空中",
+        bind_address, actual_port
+    ));
+    Ok(get_proxy_status())
 }
 
 /// 停止代理服务
@@ -3470,6 +5299,9 @@ pub fn stop_proxy_server() -> Result<(), String> {
 
     set_proxy_running(false);
     set_proxy_actual_port(None);
+    set_proxy_binding(None);
+    set_proxy_tls_fingerprint(None);
+    super::api_proxy_usage::flush_usage_ledger();
     logger::log_info("[ApiProxy] 正在停止反向代理服务...");
     Ok(())
 }
@@ -3484,17 +5316,61 @@ pub fn get_proxy_status() -> ProxyStatus {
         .map(|(k, _)| k.clone())
         .collect();
 
+    let now = chrono::Utc::now().timestamp();
+    let (cooldowns, queues, queued, sessions) = PROXY_STATE
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .and_then(|guard| {
+            guard.as_ref().map(|state| {
+                (
+                    state.cooldowns.snapshot(now),
+                    state.limiter.snapshot(),
+                    state.limiter.queue_depths(),
+                    state.sessions.active_counts(now),
+                )
+            })
+        })
+        .unwrap_or_default();
+
+    let tls_fingerprint = get_proxy_tls_fingerprint();
     ProxyStatus {
         running: is_proxy_running(),
         port: config.port,
         actual_port: get_proxy_actual_port(),
         enabled_providers,
+        cooldowns,
+        tls: tls_fingerprint.is_some(),
+        tls_fingerprint,
+        queues,
+        queued,
+        sessions,
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_codex_models_response;
+    use super::{
+        build_kiro_payload, convert_claude_to_google, convert_google_response_to_claude,
+        credential_remaining_percentage, parse_codex_models_response, parse_copilot_models_response,
+        order_by_strategy, pick_quota_weighted,
+        quota_aware_candidates, AccountCredential, ApiProxyConfig, GoogleSseConverter,
+        ProxyServerState,
+    };
+    use std::collections::HashSet;
+
+    fn quota_cred(id: &str, quota: &[(&str, i32)], protected: &[&str]) -> AccountCredential {
+        AccountCredential {
+            id: id.to_string(),
+            email: format!("{}@example.com", id),
+            access_token: String::new(),
+            project_id: String::new(),
+            is_gcp_tos: false,
+            profile_arn: None,
+            chatgpt_account_id: None,
+            quota_percentages: quota.iter().map(|(m, p)| (m.to_string(), *p)).collect(),
+            protected_models: protected.iter().map(|m| m.to_string()).collect::<HashSet<_>>(),
+        }
+    }
 
     #[test]
     fn test_parse_codex_models_response_data_model() {
@@ -3522,4 +5398,201 @@ mod tests {
         let body = r#"{}"#;
         assert!(parse_codex_models_response(body).is_err());
     }
+
+    #[test]
+    fn test_parse_copilot_models_response_keeps_chat_models() {
+        let body = r#"{"data":[
+            {"id":"gpt-4.1","capabilities":{"type":"chat"}},
+            {"id":"text-embedding-3-small","capabilities":{"type":"embeddings"}},
+            {"id":"claude-sonnet-4","capabilities":{"type":"chat"}},
+            {"id":"gpt-4.1","capabilities":{"type":"chat"}}
+        ]}"#;
+        let list = parse_copilot_models_response(body).unwrap();
+        assert_eq!(list, ["gpt-4.1", "claude-sonnet-4"]);
+        assert!(parse_copilot_models_response(r#"{"models":[]}"#).is_err());
+    }
+
+    #[test]
+    fn test_build_kiro_payload_accepts_anthropic_messages() {
+        let body = serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "system": [{"type": "text", "text": "be brief"}],
+            "messages": [
+                {"role": "user", "content": "list files"},
+                {"role": "assistant", "content": [
+                    {"type": "tool_use", "id": "tu_1", "name": "ls", "input": {"path": "."}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "tu_1", "content": "a.rs"}
+                ]}
+            ],
+            "tools": [{"name": "ls", "description": "list", "input_schema": {"type": "object", "properties": {"path": {"type": "string"}}}}]
+        });
+        let payload = build_kiro_payload(&body, "claude-sonnet-4-5", None, false).unwrap();
+        let state = &payload["conversationState"];
+        let history = state["history"].as_array().unwrap();
+        assert!(history[0]["userInputMessage"]["content"].as_str().unwrap().starts_with("be brief"));
+        assert_eq!(history[1]["assistantResponseMessage"]["toolUses"][0]["toolUseId"], "tu_1");
+        assert_eq!(history[1]["assistantResponseMessage"]["toolUses"][0]["input"]["path"], ".");
+        let ctx = &state["currentMessage"]["userInputMessage"]["userInputMessageContext"];
+        assert_eq!(ctx["toolResults"][0]["toolUseId"], "tu_1");
+        assert_eq!(ctx["tools"][0]["toolSpecification"]["inputSchema"]["json"]["properties"]["path"]["type"], "string");
+    }
+
+    #[test]
+    fn test_google_sse_function_call_becomes_tool_use() {
+        let mut conv = GoogleSseConverter::new();
+        let mut out = conv.convert(r#"{"response":{"candidates":[{"content":{"parts":[{"text":"checking"}]}}]}}"#);
+        out.extend(conv.convert(
+            r#"{"response":{"candidates":[{"content":{"parts":[{"functionCall":{"id":"call_1","name":"ls","args":{"path":"."}}}]},"finishReason":"STOP"}],"usageMetadata":{"promptTokenCount":12,"candidatesTokenCount":5}}}"#,
+        ));
+        out.extend(conv.finish());
+        let joined = out.join("");
+        assert!(joined.contains(r#""id":"call_1""#) && joined.contains(r#""type":"tool_use""#));
+        assert!(joined.contains(r#""partial_json":"{\"path\":\".\"}""#));
+        assert_eq!(joined.matches("event: content_block_start").count(), 2);
+        assert_eq!(joined.matches("event: content_block_stop").count(), 2);
+        assert!(joined.contains(r#""stop_reason":"tool_use""#));
+        assert!(joined.contains(r#""input_tokens":12"#));
+        assert!(out.last().unwrap().starts_with("event: message_stop"));
+    }
+
+    #[test]
+    fn test_google_non_stream_rebuilds_claude_message() {
+        let google = serde_json::json!({"response": {
+            "candidates": [{"content": {"parts": [
+                {"text": "plan", "thought": true},
+                {"text": "Hel"}, {"text": "lo"},
+                {"functionCall": {"name": "ls", "args": {}}, "thoughtSignature": "sig"}
+            ]}, "finishReason": "STOP"}],
+            "usageMetadata": {"promptTokenCount": 3, "candidatesTokenCount": 4, "thoughtsTokenCount": 2}
+        }});
+        let msg = convert_google_response_to_claude(&google, "m");
+        let content = msg["content"].as_array().unwrap();
+        assert_eq!(content[0]["type"], "thinking");
+        assert_eq!(content[1]["text"], "Hello");
+        assert_eq!(content[2]["signature"], "sig");
+        assert_eq!(content[3]["type"], "tool_use");
+        assert!(content[3]["id"].as_str().unwrap().starts_with("toolu_"));
+        assert_eq!(msg["stop_reason"], "tool_use");
+        assert_eq!(msg["usage"]["output_tokens"], 6);
+    }
+
+    #[test]
+    fn test_claude_tool_round_trip_to_google() {
+        let body = serde_json::json!({
+            "model": "gemini-3-pro",
+            "messages": [
+                {"role": "user", "content": "list"},
+                {"role": "assistant", "content": [
+                    {"type": "thinking", "thinking": "", "signature": "sig"},
+                    {"type": "tool_use", "id": "call_1", "name": "ls", "input": {}}
+                ]},
+                {"role": "user", "content": [{"type": "tool_result", "tool_use_id": "call_1", "content": "a.rs"}]}
+            ]
+        });
+        let (payload, _) = convert_claude_to_google(&body, "p").unwrap();
+        let contents = payload["request"]["contents"].as_array().unwrap();
+        assert_eq!(contents[1]["parts"].as_array().unwrap().len(), 1);
+        assert_eq!(contents[1]["parts"][0]["thoughtSignature"], "sig");
+        assert_eq!(contents[2]["parts"][0]["functionResponse"]["name"], "ls");
+    }
+
+    #[test]
+    fn test_claude_tool_choice_to_google() {
+        let body = |tool_choice: serde_json::Value| {
+            serde_json::json!({
+                "model": "gemini-3-pro",
+                "messages": [{"role": "user", "content": "list"}],
+                "tools": [{"name": "ls", "description": "list", "input_schema": {"type": "object"}}],
+                "tool_choice": tool_choice
+            })
+        };
+        let config = |tool_choice| {
+            let (payload, _) = convert_claude_to_google(&body(tool_choice), "p").unwrap();
+            payload["request"]["toolConfig"]["functionCallingConfig"].clone()
+        };
+        assert_eq!(config(serde_json::json!({"type": "none"}))["mode"], "NONE");
+        assert_eq!(config(serde_json::json!({"type": "any"})), serde_json::json!({"mode": "ANY"}));
+        assert_eq!(
+            config(serde_json::json!({"type": "tool", "name": "ls"})),
+            serde_json::json!({"mode": "ANY", "allowedFunctionNames": ["ls"]})
+        );
+        assert!(config(serde_json::json!({"type": "auto"})).is_null());
+    }
+
+    #[test]
+    fn test_credential_remaining_percentage_prefers_model_match() {
+        let cred = quota_cred("a", &[("gemini-3-pro", 80), ("claude-sonnet-4-5", 20)], &[]);
+        assert_eq!(credential_remaining_percentage(&cred, Some("claude-sonnet-4-5")), Some(20));
+        assert_eq!(credential_remaining_percentage(&cred, Some("claude-sonnet-4-5-thinking")), Some(20));
+        assert_eq!(credential_remaining_percentage(&cred, Some("unknown")), Some(50));
+        assert_eq!(credential_remaining_percentage(&quota_cred("b", &[], &[]), Some("x")), None);
+    }
+
+    #[test]
+    fn test_quota_aware_candidates_skip_protected_and_low() {
+        let creds = vec![
+            quota_cred("low", &[("m", 5)], &[]),
+            quota_cred("protected", &[("m", 90)], &["m"]),
+            quota_cred("ok", &[("m", 60)], &[]),
+            quota_cred("unknown", &[], &[]),
+        ];
+        let candidates = quota_aware_candidates(&creds, Some("m"), 10).unwrap();
+        assert_eq!(candidates, vec![(2, 60), (3, 100)]);
+
+        // 全部低于阈值时退回未保护账号
+        let candidates = quota_aware_candidates(&creds[..2], Some("m"), 10).unwrap();
+        assert_eq!(candidates, vec![(0, 5)]);
+        assert!(quota_aware_candidates(&creds[1..2], Some("m"), 10).is_err());
+    }
+
+    #[test]
+    fn test_pick_quota_weighted_respects_weights() {
+        let candidates = vec![(0, 30), (5, 70)];
+        assert_eq!(pick_quota_weighted(&candidates, 0), 0);
+        assert_eq!(pick_quota_weighted(&candidates, 29), 0);
+        assert_eq!(pick_quota_weighted(&candidates, 30), 5);
+        assert_eq!(pick_quota_weighted(&candidates, 100), 0);
+    }
+
+    #[test]
+    fn test_order_by_strategy_limits_overflow() {
+        let pool = || (0..4).map(|i| quota_cred(&i.to_string(), &[], &[])).collect::<Vec<_>>();
+        let ids = |creds: Vec<AccountCredential>| creds.into_iter().map(|c| c.id).collect::<Vec<_>>();
+
+        assert_eq!(ids(order_by_strategy(pool(), 2, None)), ["2", "3", "0", "1"]);
+        // single 只用首个账号；配额策略只溢出到未被剔除的候选
+        assert_eq!(ids(order_by_strategy(pool(), 0, Some(&[0]))), ["0"]);
+        assert_eq!(ids(order_by_strategy(pool(), 3, Some(&[1, 3]))), ["3", "1"]);
+    }
+
+    #[test]
+    fn test_account_ids_change_applies_live() {
+        let state = ProxyServerState::new(ApiProxyConfig::default());
+        let pool = || vec![quota_cred("a", &[], &[]), quota_cred("b", &[], &[]), quota_cred("c", &[], &[])];
+        let ids = |creds: &[AccountCredential]| creds.iter().map(|c| c.id.clone()).collect::<Vec<_>>();
+
+        let mut creds = pool();
+        state.retain_configured_accounts("kiro", &mut creds);
+        assert_eq!(ids(&creds), ["a", "b", "c"]);
+
+        // 按 ID 或邮箱限定账号，修改后下一次选号即生效
+        let mut config = ApiProxyConfig::default();
+        config.providers.get_mut("kiro").unwrap().account_ids =
+            vec!["b".to_string(), "C@example.com".to_string()];
+        state.apply_config(config);
+        let mut creds = pool();
+        state.retain_configured_accounts("kiro", &mut creds);
+        assert_eq!(ids(&creds), ["b", "c"]);
+
+        let mut creds = pool();
+        state.retain_configured_accounts("codex", &mut creds);
+        assert_eq!(creds.len(), 3);
+
+        state.apply_config(ApiProxyConfig::default());
+        let mut creds = pool();
+        state.retain_configured_accounts("kiro", &mut creds);
+        assert_eq!(creds.len(), 3);
+    }
 }
@@ -0,0 +1,628 @@
+//! API 反向代理 — 请求审计记录
+//! 可选开启：记录每个请求的客户端 body、转换后的上游 payload、状态码、耗时、实际使用的账号与返回内容（含流式输出），
+//! 写入数据目录下的 SQLite 文件，超过大小上限时轮转；写入前对 token 等敏感字段脱敏
+
+use axum::body::Body;
+use axum::response::Response;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::Instant;
+
+use super::config;
+use super::credential_vault::write_private_file;
+use super::logger;
+
+const AUDIT_DB_FILE: &str = "api_proxy_audit.db";
+/// 轮转后的旧文件（只保留一份）
+const AUDIT_DB_ROTATED_FILE: &str = "api_proxy_audit.1.db";
+const AUDIT_SETTINGS_FILE: &str = "api_proxy_audit_settings.json";
+/// 每写入多少条检查一次文件大小
+const ROTATE_CHECK_INTERVAL: u64 = 50;
+const REDACTED: &str = "***";
+
+static AUDIT_DB: std::sync::LazyLock<Mutex<AuditDb>> =
+    std::sync::LazyLock::new(|| Mutex::new(AuditDb::default()));
+static SETTINGS_CACHE: std::sync::LazyLock<RwLock<Option<AuditSettings>>> =
+    std::sync::LazyLock::new(|| RwLock::new(None));
+
+static BEARER_RE: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r"(?i)bearer\s+[A-Za-z0-9._~+/=-]+").unwrap());
+static SECRET_FIELD_RE: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+    regex::Regex::new(r#"(?i)"([a-z_]*token|authorization|api_?key|x-api-key|password|[a-z_]*secret)"\s*:\s*"[^"]*""#)
+        .unwrap()
+});
+static SK_KEY_RE: std::sync::LazyLock<regex::Regex> =
+    std::sync::LazyLock::new(|| regex::Regex::new(r"sk-[A-Za-z0-9_-]{8,}").unwrap());
+
+/// 审计设置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditSettings {
+    /// 是否记录请求（默认关闭）
+    #[serde(default)]
+    pub enabled: bool,
+    /// 单个 body 保存上限（KB），超出部分截断
+    #[serde(default = "default_max_body_kb")]
+    pub max_body_kb: u32,
+    /// 数据库文件大小上限（MB），超出后轮转
+    #[serde(default = "default_max_file_mb")]
+    pub max_file_mb: u32,
+}
+
+fn default_max_body_kb() -> u32 {
+    256
+}
+
+fn default_max_file_mb() -> u32 {
+    50
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_body_kb: default_max_body_kb(),
+            max_file_mb: default_max_file_mb(),
+        }
+    }
+}
+
+/// 审计记录摘要（列表展示）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecordSummary {
+    pub id: String,
+    /// 毫秒时间戳
+    pub created_at: i64,
+    pub provider: String,
+    pub path: String,
+    pub model: String,
+    pub account_email: String,
+    pub api_key_id: String,
+    pub status: u16,
+    /// 收到响应头的耗时
+    pub latency_ms: u64,
+    /// 响应完整结束（或客户端断开）的耗时
+    pub duration_ms: u64,
+}
+
+/// 完整审计记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    #[serde(flatten)]
+    pub summary: AuditRecordSummary,
+    pub account_id: String,
+    pub client_body: String,
+    /// 转换后发往上游的 payload（透传的 Provider 为空）
+    pub upstream_payload: String,
+    /// 返回给客户端的内容（流式为原始 SSE 文本）
+    pub response_body: String,
+    /// client_body 未经脱敏改写且未截断，可原样重放
+    #[serde(default)]
+    pub replayable: bool,
+}
+
+fn settings_path() -> Result<PathBuf, String> {
+    Ok(config::get_data_dir()?.join(AUDIT_SETTINGS_FILE))
+}
+
+/// 读取审计设置
+pub fn load_audit_settings() -> AuditSettings {
+    if let Some(cached) = SETTINGS_CACHE.read().ok().and_then(|c| c.clone()) {
+        return cached;
+    }
+    let settings: AuditSettings = settings_path()
+        .ok()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default();
+    if let Ok(mut cache) = SETTINGS_CACHE.write() {
+        *cache = Some(settings.clone());
+    }
+    settings
+}
+
+/// 保存审计设置（立即生效）
+pub fn save_audit_settings(settings: &AuditSettings) -> Result<(), String> {
+    let content =
+        serde_json::to_string_pretty(settings).map_err(|e| format!("序列化审计设置失败: {}", e))?;
+    write_private_file(&settings_path()?, content.as_bytes())
+        .map_err(|e| format!("写入审计设置失败: {}", e))?;
+    if let Ok(mut cache) = SETTINGS_CACHE.write() {
+        *cache = Some(settings.clone());
+    }
+    Ok(())
+}
+
+// ============================================================================
+// 脱敏
+// ============================================================================
+
+fn is_sensitive_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    key.ends_with("token")
+        || key.ends_with("secret")
+        || matches!(key.as_str(), "authorization" | "api_key" | "apikey" | "x-api-key" | "password")
+}
+
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                if v.is_string() && is_sensitive_key(key) {
+                    *v = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(v);
+                }
+            }
+        }
+        Value::Array(arr) => arr.iter_mut().for_each(redact_json),
+        Value::String(s) if BEARER_RE.is_match(s) || SK_KEY_RE.is_match(s) => {
+            *s = redact_plain(s);
+        }
+        _ => {}
+    }
+}
+
+fn redact_plain(text: &str) -> String {
+    let text = BEARER_RE.replace_all(text, "Bearer ***");
+    let text = SECRET_FIELD_RE.replace_all(&text, "\"$1\":\"***\"");
+    SK_KEY_RE.replace_all(&text, "sk-***").into_owned()
+}
+
+/// 脱敏：JSON 按字段名替换，其他文本（如 SSE）按模式替换
+pub fn redact(text: &str) -> String {
+    match serde_json::from_str::<Value>(text) {
+        Ok(mut value) if value.is_object() || value.is_array() => {
+            redact_json(&mut value);
+            value.to_string()
+        }
+        _ => redact_plain(text),
+    }
+}
+
+/// 脱敏是否改动了内容（JSON 按值比较，忽略重新序列化带来的格式差异）
+fn redaction_changed(original: &str, redacted: &str) -> bool {
+    match (serde_json::from_str::<Value>(original), serde_json::from_str::<Value>(redacted)) {
+        (Ok(a), Ok(b)) => a != b,
+        _ => original != redacted,
+    }
+}
+
+/// 按字节上限截断（保持 UTF-8 边界）
+fn truncate_body(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…[已截断 {} 字节]", &text[..end], text.len() - end)
+}
+
+// ============================================================================
+// SQLite 存储
+// ============================================================================
+
+#[derive(Default)]
+struct AuditDb {
+    conn: Option<Connection>,
+    writes: u64,
+}
+
+fn db_path() -> Result<PathBuf, String> {
+    Ok(config::get_data_dir()?.join(AUDIT_DB_FILE))
+}
+
+/// 数据库文件限制为仅当前用户可读写（SQLite 的日志文件沿用主文件权限）
+fn ensure_private_db_file(path: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+                .map_err(|e| format!("设置审计数据库权限失败: {}", e))?;
+        } else {
+            fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(path)
+                .map_err(|e| format!("创建审计数据库失败: {}", e))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+fn open_db() -> Result<Connection, String> {
+    let data_dir = config::get_data_dir()?;
+    if !data_dir.exists() {
+        fs::create_dir_all(&data_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let path = data_dir.join(AUDIT_DB_FILE);
+    ensure_private_db_file(&path)?;
+    let conn = Connection::open(&path).map_err(|e| format!("打开审计数据库失败: {}", e))?;
+    init_schema(&conn)?;
+    Ok(conn)
+}
+
+fn init_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_records (
+            id TEXT PRIMARY KEY,
+            created_at INTEGER NOT NULL,
+            provider TEXT NOT NULL,
+            path TEXT NOT NULL,
+            model TEXT NOT NULL,
+            account_id TEXT NOT NULL,
+            account_email TEXT NOT NULL,
+            api_key_id TEXT NOT NULL,
+            status INTEGER NOT NULL,
+            latency_ms INTEGER NOT NULL,
+            duration_ms INTEGER NOT NULL,
+            client_body TEXT NOT NULL,
+            upstream_payload TEXT NOT NULL,
+            response_body TEXT NOT NULL,
+            replayable INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_created_at ON audit_records(created_at);",
+    )
+    .map_err(|e| format!("初始化审计数据库失败: {}", e))?;
+    // 旧库补列；旧记录无法确认是否脱敏，默认不可重放
+    if conn.prepare("SELECT replayable FROM audit_records LIMIT 0").is_err() {
+        conn.execute("ALTER TABLE audit_records ADD COLUMN replayable INTEGER NOT NULL DEFAULT 0", [])
+            .map_err(|e| format!("升级审计数据库失败: {}", e))?;
+    }
+    Ok(())
+}
+
+impl AuditDb {
+    fn conn(&mut self) -> Result<&Connection, String> {
+        if self.conn.is_none() {
+            self.conn = Some(open_db()?);
+        }
+        self.conn.as_ref().ok_or_else(|| "审计数据库未打开".to_string())
+    }
+
+    /// 文件超过上限时关闭连接并重命名为 .1.db，下次写入时新建
+    fn rotate_if_needed(&mut self, max_file_mb: u32) -> Result<(), String> {
+        let path = db_path()?;
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        if size <= u64::from(max_file_mb.max(1)) * 1024 * 1024 {
+            return Ok(());
+        }
+        self.conn = None;
+        let rotated = path.with_file_name(AUDIT_DB_ROTATED_FILE);
+        let _ = fs::remove_file(&rotated);
+        fs::rename(&path, &rotated).map_err(|e| format!("轮转审计数据库失败: {}", e))?;
+        logger::log_info(&format!("[ApiProxy] 审计数据库已轮转 ({} 字节)", size));
+        Ok(())
+    }
+}
+
+fn insert_record(conn: &Connection, record: &AuditRecord) -> Result<(), String> {
+    let s = &record.summary;
+    conn.execute(
+        "INSERT INTO audit_records (id, created_at, provider, path, model, account_id, account_email, api_key_id,
+            status, latency_ms, duration_ms, client_body, upstream_payload, response_body, replayable)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        params![
+            s.id,
+            s.created_at,
+            s.provider,
+            s.path,
+            s.model,
+            record.account_id,
+            s.account_email,
+            s.api_key_id,
+            s.status,
+            s.latency_ms as i64,
+            s.duration_ms as i64,
+            record.client_body,
+            record.upstream_payload,
+            record.response_body,
+            record.replayable,
+        ],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("写入审计记录失败: {}", e))
+}
+
+fn write_record(record: &AuditRecord) {
+    let result = (|| -> Result<(), String> {
+        let mut db = AUDIT_DB.lock().map_err(|_| "获取审计数据库锁失败")?;
+        insert_record(db.conn()?, record)?;
+        db.writes += 1;
+        if db.writes % ROTATE_CHECK_INTERVAL == 0 {
+            db.rotate_if_needed(load_audit_settings().max_file_mb)?;
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        logger::log_warn(&format!("[ApiProxy] {}", e));
+    }
+}
+
+fn summary_from_row(row: &rusqlite::Row) -> rusqlite::Result<AuditRecordSummary> {
+    Ok(AuditRecordSummary {
+        id: row.get("id")?,
+        created_at: row.get("created_at")?,
+        provider: row.get("provider")?,
+        path: row.get("path")?,
+        model: row.get("model")?,
+        account_email: row.get("account_email")?,
+        api_key_id: row.get("api_key_id")?,
+        status: row.get("status")?,
+        latency_ms: row.get::<_, i64>("latency_ms")?.max(0) as u64,
+        duration_ms: row.get::<_, i64>("duration_ms")?.max(0) as u64,
+    })
+}
+
+/// 按时间倒序列出审计记录
+pub fn list_records(limit: u32, offset: u32, provider: Option<&str>) -> Result<Vec<AuditRecordSummary>, String> {
+    let mut db = AUDIT_DB.lock().map_err(|_| "获取审计数据库锁失败")?;
+    let conn = db.conn()?;
+    let provider = provider.filter(|p| !p.is_empty()).unwrap_or("");
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, created_at, provider, path, model, account_email, api_key_id, status, latency_ms, duration_ms
+             FROM audit_records WHERE (?1 = '' OR provider = ?1)
+             ORDER BY created_at DESC LIMIT ?2 OFFSET ?3",
+        )
+        .map_err(|e| format!("查询审计记录失败: {}", e))?;
+    let rows = stmt
+        .query_map(params![provider, limit.clamp(1, 1000), offset], summary_from_row)
+        .map_err(|e| format!("查询审计记录失败: {}", e))?;
+    rows.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("读取审计记录失败: {}", e))
+}
+
+/// 读取单条完整记录
+pub fn get_record(id: &str) -> Result<Option<AuditRecord>, String> {
+    let mut db = AUDIT_DB.lock().map_err(|_| "获取审计数据库锁失败")?;
+    db.conn()?
+        .query_row("SELECT * FROM audit_records WHERE id = ?1", params![id], |row| {
+            Ok(AuditRecord {
+                summary: summary_from_row(row)?,
+                account_id: row.get("account_id")?,
+                client_body: row.get("client_body")?,
+                upstream_payload: row.get("upstream_payload")?,
+                response_body: row.get("response_body")?,
+                replayable: row.get("replayable")?,
+            })
+        })
+        .optional()
+        .map_err(|e| format!("读取审计记录失败: {}", e))
+}
+
+/// 清空审计记录（同时删除轮转文件）
+pub fn clear_records() -> Result<(), String> {
+    let mut db = AUDIT_DB.lock().map_err(|_| "获取审计数据库锁失败")?;
+    db.conn()?
+        .execute("DELETE FROM audit_records", [])
+        .map_err(|e| format!("清空审计记录失败: {}", e))?;
+    let _ = fs::remove_file(db_path()?.with_file_name(AUDIT_DB_ROTATED_FILE));
+    Ok(())
+}
+
+// ============================================================================
+// 请求捕获
+// ============================================================================
+
+/// Provider 处理器在请求过程中上报的信息（转换后的 payload、重试后实际使用的账号）
+#[derive(Debug, Default)]
+pub struct AuditNotes {
+    upstream_payload: Option<String>,
+    account: Option<(String, String)>,
+}
+
+tokio::task_local! {
+    static AUDIT_NOTES: RefCell<AuditNotes>;
+}
+
+/// 上报发往上游的 payload（未开启审计时为空操作）
+pub fn note_upstream_payload(payload: &Value) {
+    let _ = AUDIT_NOTES.try_with(|notes| {
+        notes.borrow_mut().upstream_payload = Some(payload.to_string());
+    });
+}
+
+/// 上报实际使用的账号（切换账号重试时覆盖）
+pub fn note_account(account_id: &str, account_email: &str) {
+    let _ = AUDIT_NOTES.try_with(|notes| {
+        notes.borrow_mut().account = Some((account_id.to_string(), account_email.to_string()));
+    });
+}
+
+/// 在审计上下文中执行请求，返回结果与处理器上报的信息
+pub async fn capture<F: std::future::Future>(fut: F) -> (F::Output, AuditNotes) {
+    AUDIT_NOTES
+        .scope(RefCell::new(AuditNotes::default()), async move {
+            let output = fut.await;
+            let notes = AUDIT_NOTES.with(|notes| notes.take());
+            (output, notes)
+        })
+        .await
+}
+
+/// 请求开始时的审计信息
+pub struct AuditStart<'a> {
+    pub provider: &'a str,
+    pub path: &'a str,
+    pub client_body: &'a [u8],
+    pub api_key_id: &'a str,
+    pub account_id: &'a str,
+    pub account_email: &'a str,
+}
+
+/// 进行中的审计记录
+pub struct AuditDraft {
+    record: AuditRecord,
+    started: Instant,
+    max_body_bytes: usize,
+}
+
+/// 开始记录一个请求；未开启审计时返回 None
+pub fn begin(start: AuditStart) -> Option<AuditDraft> {
+    let settings = load_audit_settings();
+    if !settings.enabled {
+        return None;
+    }
+    let max_body_bytes = settings.max_body_kb.max(1) as usize * 1024;
+    let client_body = String::from_utf8_lossy(start.client_body);
+    let redacted_body = redact(&client_body);
+    let replayable = !redaction_changed(&client_body, &redacted_body) && redacted_body.len() <= max_body_bytes;
+    let model = serde_json::from_str::<Value>(&client_body)
+        .ok()
+        .and_then(|v| v.get("model").and_then(|m| m.as_str()).map(|m| m.to_string()))
+        .unwrap_or_default();
+    Some(AuditDraft {
+        record: AuditRecord {
+            summary: AuditRecordSummary {
+                id: uuid::Uuid::new_v4().to_string(),
+                created_at: chrono::Utc::now().timestamp_millis(),
+                provider: start.provider.to_string(),
+                path: start.path.to_string(),
+                model,
+                account_email: start.account_email.to_string(),
+                api_key_id: start.api_key_id.to_string(),
+                status: 0,
+                latency_ms: 0,
+                duration_ms: 0,
+            },
+            account_id: start.account_id.to_string(),
+            client_body: truncate_body(&redacted_body, max_body_bytes),
+            upstream_payload: String::new(),
+            response_body: String::new(),
+            replayable,
+        },
+        started: Instant::now(),
+        max_body_bytes,
+    })
+}
+
+impl AuditDraft {
+    /// 收到响应：记录状态码与上报信息，并包装响应体以捕获输出
+    /// 响应体结束或客户端断开时写入数据库
+    pub fn finish(mut self, response: Response, notes: AuditNotes) -> Response {
+        self.record.summary.status = response.status().as_u16();
+        self.record.summary.latency_ms = self.started.elapsed().as_millis() as u64;
+        if let Some(payload) = notes.upstream_payload {
+            self.record.upstream_payload = truncate_body(&redact(&payload), self.max_body_bytes);
+        }
+        if let Some((account_id, account_email)) = notes.account {
+            self.record.account_id = account_id;
+            self.record.summary.account_email = account_email;
+        }
+
+        let (parts, body) = response.into_parts();
+        let mut pending = PendingRecord { draft: Some(self), output: Vec::new() };
+        let stream = async_stream::stream! {
+            use futures_util::StreamExt;
+            let mut body_stream = body.into_data_stream();
+            while let Some(chunk) = body_stream.next().await {
+                if let Ok(bytes) = &chunk {
+                    pending.push(bytes);
+                }
+                yield chunk;
+            }
+        };
+        Response::from_parts(parts, Body::from_stream(stream))
+    }
+}
+
+/// 捕获中的响应体，Drop 时落盘（覆盖正常结束与客户端断开）
+struct PendingRecord {
+    draft: Option<AuditDraft>,
+    output: Vec<u8>,
+}
+
+impl PendingRecord {
+    fn push(&mut self, bytes: &[u8]) {
+        let limit = self.draft.as_ref().map(|d| d.max_body_bytes).unwrap_or(0);
+        // 多保留一点以便截断提示显示原始长度
+        let room = (limit + 1).saturating_sub(self.output.len());
+        self.output.extend_from_slice(&bytes[..bytes.len().min(room)]);
+    }
+}
+
+impl Drop for PendingRecord {
+    fn drop(&mut self) {
+        let Some(mut draft) = self.draft.take() else {
+            return;
+        };
+        draft.record.summary.duration_ms = draft.started.elapsed().as_millis() as u64;
+        let output = String::from_utf8_lossy(&self.output);
+        draft.record.response_body = truncate_body(&redact(&output), draft.max_body_bytes);
+        write_record(&draft.record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_json_and_sse_text() {
+        let json = redact(r#"{"access_token":"abc","nested":{"refreshToken":"x","model":"m"},"h":"Bearer eyJ.a.b"}"#);
+        let value: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["access_token"], REDACTED);
+        assert_eq!(value["nested"]["refreshToken"], REDACTED);
+        assert_eq!(value["nested"]["model"], "m");
+        assert_eq!(value["h"], "Bearer ***");
+
+        let sse = redact("data: {\"id_token\": \"secret-value\", \"key\": \"sk-abcdefghijkl\"}\n\n");
+        assert!(!sse.contains("secret-value"));
+        assert!(sse.contains("sk-***"));
+        assert_eq!(truncate_body("你好世界", 7), "你好…[已截断 6 字节]");
+
+        let plain = r#"{"model": "m", "messages": []}"#;
+        assert!(!redaction_changed(plain, &redact(plain)));
+        let secret = r#"{"model": "m", "api_key": "k", "messages": [{"content": "sk-abcdefghijkl"}]}"#;
+        assert!(redaction_changed(secret, &redact(secret)));
+    }
+
+    #[test]
+    fn test_insert_and_read_record() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        let record = AuditRecord {
+            summary: AuditRecordSummary {
+                id: "r1".to_string(),
+                created_at: 1,
+                provider: "kiro".to_string(),
+                path: "v1/messages".to_string(),
+                model: "claude-sonnet-4.5".to_string(),
+                account_email: "a@x.com".to_string(),
+                api_key_id: String::new(),
+                status: 200,
+                latency_ms: 12,
+                duration_ms: 34,
+            },
+            account_id: "a1".to_string(),
+            client_body: "{}".to_string(),
+            upstream_payload: "{}".to_string(),
+            response_body: "event: message_stop".to_string(),
+            replayable: true,
+        };
+        insert_record(&conn, &record).unwrap();
+        let summary = conn
+            .query_row("SELECT * FROM audit_records WHERE id = 'r1'", [], summary_from_row)
+            .unwrap();
+        assert_eq!(summary.status, 200);
+        assert_eq!(summary.duration_ms, 34);
+        let replayable: bool = conn
+            .query_row("SELECT replayable FROM audit_records WHERE id = 'r1'", [], |row| row.get(0))
+            .unwrap();
+        assert!(replayable);
+    }
+}
@@ -0,0 +1,543 @@
+//! API 反向代理 — Codex 协议转换
+//! OpenAI chat/completions 请求 → ChatGPT /backend-api/codex/responses 请求，
+//! 以及 /responses SSE 事件 → OpenAI chat.completion(.chunk) 响应
+
+use serde_json::Value;
+
+/// ChatGPT Codex 后端 /responses 地址
+pub const CODEX_RESPONSES_URL: &str = "https://chatgpt.com/backend-api/codex/responses";
+
+/// 默认 Codex 模型
+pub const DEFAULT_CODEX_MODEL: &str = "gpt-5.1-codex";
+
+/// 未提供 system 消息时的默认 instructions
+const DEFAULT_INSTRUCTIONS: &str = "You are a helpful assistant.";
+
+/// OpenAI 消息 content → 纯文本（数组取 text 部分拼接）
+fn chat_content_text(content: Option<&Value>) -> String {
+    match content {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+            .collect::<Vec<_>>()
+            .join(""),
+        _ => String::new(),
+    }
+}
+
+/// OpenAI user 消息 content → Responses input_text / input_image 列表
+fn user_content_parts(content: Option<&Value>) -> Vec<Value> {
+    match content {
+        Some(Value::String(s)) if !s.is_empty() => vec![serde_json::json!({"type": "input_text", "text": s})],
+        Some(Value::Array(parts)) => parts
+            .iter()
+            .filter_map(|p| match p.get("type").and_then(|t| t.as_str()) {
+                Some("text") | Some("input_text") => p
+                    .get("text")
+                    .and_then(|t| t.as_str())
+                    .filter(|t| !t.is_empty())
+                    .map(|t| serde_json::json!({"type": "input_text", "text": t})),
+                Some("image_url") => {
+                    // image_url 可能是字符串或 {url, detail}
+                    let image = p.get("image_url")?;
+                    let url = image.as_str().or_else(|| image.get("url").and_then(|u| u.as_str()))?;
+                    let detail = image.get("detail").and_then(|d| d.as_str()).unwrap_or("auto");
+                    Some(serde_json::json!({"type": "input_image", "image_url": url, "detail": detail}))
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// OpenAI tools → Responses tools（function 定义展开到顶层）
+fn convert_tools(tools: &[Value]) -> Vec<Value> {
+    tools
+        .iter()
+        .filter_map(|t| {
+            let func = t.get("function")?;
+            let mut tool = serde_json::json!({
+                "type": "function",
+                "name": func.get("name")?.as_str()?,
+                "parameters": func.get("parameters").cloned().unwrap_or_else(|| serde_json::json!({"type": "object", "properties": {}})),
+            });
+            if let Some(desc) = func.get("description").and_then(|d| d.as_str()) {
+                tool["description"] = Value::String(desc.to_string());
+            }
+            if let Some(strict) = func.get("strict").and_then(|v| v.as_bool()) {
+                tool["strict"] = Value::Bool(strict);
+            }
+            Some(tool)
+        })
+        .collect()
+}
+
+/// OpenAI tool_choice → Responses tool_choice
+fn convert_tool_choice(choice: &Value) -> Option<Value> {
+    match choice {
+        Value::String(_) => Some(choice.clone()),
+        Value::Object(_) => {
+            let name = choice.pointer("/function/name")?.as_str()?;
+            Some(serde_json::json!({"type": "function", "name": name}))
+        }
+        _ => None,
+    }
+}
+
+/// 将 OpenAI chat/completions 请求体转换为 ChatGPT /responses 请求体
+/// system/developer 消息合并为 instructions；assistant 的 tool_calls 与 tool 消息转为
+/// function_call / function_call_output 项；上游只接受流式请求，非流式由调用方聚合 SSE 结果
+pub fn build_codex_payload(client_body: &Value) -> Result<Value, String> {
+    let model = client_body
+        .get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or(DEFAULT_CODEX_MODEL);
+    let messages = client_body
+        .get("messages")
+        .and_then(|v| v.as_array())
+        .ok_or("缺少 messages")?;
+
+    let mut instructions: Vec<String> = Vec::new();
+    let mut input: Vec<Value> = Vec::new();
+    for m in messages {
+        let content = m.get("content");
+        match m.get("role").and_then(|r| r.as_str()).unwrap_or("") {
+            "system" | "developer" => {
+                let text = chat_content_text(content);
+                if !text.is_empty() {
+                    instructions.push(text);
+                }
+            }
+            "user" => {
+                let parts = user_content_parts(content);
+                if !parts.is_empty() {
+                    input.push(serde_json::json!({"role": "user", "content": parts}));
+                }
+            }
+            "assistant" => {
+                let text = chat_content_text(content);
+                if !text.is_empty() {
+                    input.push(serde_json::json!({
+                        "role": "assistant",
+                        "content": [{"type": "output_text", "text": text}]
+                    }));
+                }
+                for call in m.get("tool_calls").and_then(|v| v.as_array()).into_iter().flatten() {
+                    let Some(name) = call.pointer("/function/name").and_then(|n| n.as_str()) else {
+                        continue;
+                    };
+                    input.push(serde_json::json!({
+                        "type": "function_call",
+                        "call_id": call.get("id").and_then(|v| v.as_str()).unwrap_or_default(),
+                        "name": name,
+                        "arguments": call.pointer("/function/arguments").and_then(|a| a.as_str()).unwrap_or("{}"),
+                    }));
+                }
+            }
+            "tool" => {
+                input.push(serde_json::json!({
+                    "type": "function_call_output",
+                    "call_id": m.get("tool_call_id").and_then(|v| v.as_str()).unwrap_or_default(),
+                    "output": chat_content_text(content),
+                }));
+            }
+            _ => {}
+        }
+    }
+    if input.is_empty() {
+        return Err("messages 无有效内容".to_string());
+    }
+
+    let instructions = if instructions.is_empty() {
+        DEFAULT_INSTRUCTIONS.to_string()
+    } else {
+        instructions.join("\n\n")
+    };
+    let mut payload = serde_json::json!({
+        "model": model,
+        "instructions": instructions,
+        "input": input,
+        "stream": true,
+        "store": false,
+    });
+
+    if let Some(tools) = client_body.get("tools").and_then(|v| v.as_array()) {
+        let tools = convert_tools(tools);
+        if !tools.is_empty() {
+            payload["tools"] = Value::Array(tools);
+            if let Some(choice) = client_body.get("tool_choice").and_then(convert_tool_choice) {
+                payload["tool_choice"] = choice;
+            }
+            if let Some(parallel) = client_body.get("parallel_tool_calls").and_then(|v| v.as_bool()) {
+                payload["parallel_tool_calls"] = Value::Bool(parallel);
+            }
+        }
+    }
+    if let Some(effort) = client_body.get("reasoning_effort").and_then(|v| v.as_str()) {
+        payload["reasoning"] = serde_json::json!({"effort": effort, "summary": "auto"});
+    }
+    Ok(payload)
+}
+
+/// Responses usage → OpenAI usage
+fn codex_usage_to_openai(usage: &Value) -> Value {
+    let input = usage.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+    let output = usage.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+    let cached = usage
+        .pointer("/input_tokens_details/cached_tokens")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    serde_json::json!({
+        "prompt_tokens": input,
+        "completion_tokens": output,
+        "total_tokens": usage.get("total_tokens").and_then(|v| v.as_u64()).unwrap_or(input + output),
+        "prompt_tokens_details": { "cached_tokens": cached }
+    })
+}
+
+/// 流式中的函数调用
+struct CodexToolCall {
+    item_id: String,
+    call_id: String,
+    name: String,
+    arguments: String,
+    /// 是否已通过 arguments.delta 下发过参数
+    streamed_arguments: bool,
+}
+
+/// ChatGPT /responses SSE → OpenAI chat.completion.chunk 的有状态转换器
+/// 同时累积完整结果，供非流式请求聚合为 chat.completion
+pub struct CodexToOpenAiStream {
+    id: String,
+    model: String,
+    created: i64,
+    sent_role: bool,
+    text: String,
+    reasoning: String,
+    tool_calls: Vec<CodexToolCall>,
+    finish_reason: Option<&'static str>,
+    usage: Option<Value>,
+    error: Option<String>,
+    /// 客户端是否请求了 stream_options.include_usage
+    include_usage: bool,
+}
+
+impl CodexToOpenAiStream {
+    pub fn new(model: &str, include_usage: bool) -> Self {
+        Self {
+            id: format!("chatcmpl-{}", uuid::Uuid::new_v4().simple()),
+            model: model.to_string(),
+            created: chrono::Utc::now().timestamp(),
+            sent_role: false,
+            text: String::new(),
+            reasoning: String::new(),
+            tool_calls: Vec::new(),
+            finish_reason: None,
+            usage: None,
+            error: None,
+            include_usage,
+        }
+    }
+
+    fn chunk(&self, delta: Value, finish_reason: Option<&str>) -> String {
+        let chunk = serde_json::json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "delta": delta,
+                "finish_reason": finish_reason
+            }]
+        });
+        format!("data: {}\n\n", chunk)
+    }
+
+    fn push_delta(&mut self, mut delta: Value, out: &mut Vec<String>) {
+        if !self.sent_role {
+            self.sent_role = true;
+            delta["role"] = Value::String("assistant".to_string());
+        }
+        out.push(self.chunk(delta, None));
+    }
+
+    /// 按 item_id 查找函数调用的下标
+    fn tool_index(&self, val: &Value) -> Option<usize> {
+        let item_id = val.get("item_id").and_then(|v| v.as_str())?;
+        self.tool_calls.iter().position(|t| t.item_id == item_id)
+    }
+
+    /// 上游错误信息（response.failed / error 事件）
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// 上游 response.completed 上报的用量（OpenAI 格式）
+    pub fn usage(&self) -> Option<&Value> {
+        self.usage.as_ref()
+    }
+
+    /// 处理一条 /responses SSE data JSON，返回 OpenAI SSE 行
+    pub fn on_event(&mut self, val: &Value) -> Vec<String> {
+        let mut out = Vec::new();
+        match val.get("type").and_then(|v| v.as_str()).unwrap_or("") {
+            "response.output_text.delta" => {
+                if let Some(text) = val.get("delta").and_then(|d| d.as_str()).filter(|t| !t.is_empty()) {
+                    self.text.push_str(text);
+                    self.push_delta(serde_json::json!({"content": text}), &mut out);
+                }
+            }
+            "response.reasoning_summary_text.delta" | "response.reasoning_text.delta" => {
+                if let Some(text) = val.get("delta").and_then(|d| d.as_str()).filter(|t| !t.is_empty()) {
+                    self.reasoning.push_str(text);
+                    self.push_delta(serde_json::json!({"reasoning_content": text}), &mut out);
+                }
+            }
+            "response.output_item.added" => {
+                let item = val.get("item").unwrap_or(&Value::Null);
+                if item.get("type").and_then(|t| t.as_str()) == Some("function_call") {
+                    let call = CodexToolCall {
+                        item_id: item.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                        call_id: item.get("call_id").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                        name: item.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                        arguments: String::new(),
+                        streamed_arguments: false,
+                    };
+                    let delta = serde_json::json!({"tool_calls": [{
+                        "index": self.tool_calls.len(),
+                        "id": call.call_id,
+                        "type": "function",
+                        "function": {"name": call.name, "arguments": ""}
+                    }]});
+                    self.tool_calls.push(call);
+                    self.push_delta(delta, &mut out);
+                }
+            }
+            "response.function_call_arguments.delta" => {
+                let delta = val.get("delta").and_then(|d| d.as_str()).unwrap_or("");
+                if let (Some(index), false) = (self.tool_index(val), delta.is_empty()) {
+                    let call = &mut self.tool_calls[index];
+                    call.arguments.push_str(delta);
+                    call.streamed_arguments = true;
+                    let chunk = serde_json::json!({"tool_calls": [{
+                        "index": index,
+                        "function": {"arguments": delta}
+                    }]});
+                    self.push_delta(chunk, &mut out);
+                }
+            }
+            "response.output_item.done" => {
+                // 未流式下发参数的上游：在完成时一次性补齐
+                let item = val.get("item").unwrap_or(&Value::Null);
+                let item_id = item.get("id").and_then(|v| v.as_str()).unwrap_or_default();
+                if let Some(index) = self.tool_calls.iter().position(|t| t.item_id == item_id) {
+                    let arguments = item.get("arguments").and_then(|a| a.as_str()).unwrap_or_default();
+                    let call = &mut self.tool_calls[index];
+                    if !call.streamed_arguments && !arguments.is_empty() {
+                        call.arguments = arguments.to_string();
+                        call.streamed_arguments = true;
+                        let chunk = serde_json::json!({"tool_calls": [{
+                            "index": index,
+                            "function": {"arguments": arguments}
+                        }]});
+                        self.push_delta(chunk, &mut out);
+                    }
+                }
+            }
+            "response.completed" | "response.done" | "response.incomplete" => {
+                let response = val.get("response").unwrap_or(&Value::Null);
+                if let Some(usage) = response.get("usage").filter(|u| u.is_object()) {
+                    self.usage = Some(codex_usage_to_openai(usage));
+                }
+                let truncated = response
+                    .pointer("/incomplete_details/reason")
+                    .and_then(|v| v.as_str())
+                    == Some("max_output_tokens");
+                self.finish_reason = Some(if truncated {
+                    "length"
+                } else if !self.tool_calls.is_empty() {
+                    "tool_calls"
+                } else {
+                    "stop"
+                });
+            }
+            "response.failed" | "error" => {
+                let message = val
+                    .pointer("/response/error/message")
+                    .or_else(|| val.pointer("/error/message"))
+                    .or_else(|| val.get("message"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("Codex 上游返回错误");
+                self.error = Some(message.to_string());
+                let err = serde_json::json!({"error": {"message": message, "type": "api_error"}});
+                out.push(format!("data: {}\n\n", err));
+            }
+            _ => {
+                // 兼容 OpenAI 风格的上游：choices[0].delta.content
+                if let Some(text) = val
+                    .pointer("/choices/0/delta/content")
+                    .and_then(|c| c.as_str())
+                    .filter(|t| !t.is_empty())
+                {
+                    self.text.push_str(text);
+                    self.push_delta(serde_json::json!({"content": text}), &mut out);
+                }
+            }
+        }
+        out
+    }
+
+    /// 流结束：发送 finish_reason、usage（仅在客户端请求时）与 [DONE]
+    pub fn finish(&mut self) -> Vec<String> {
+        let finish_reason = self.finish_reason.unwrap_or("stop");
+        let mut out = vec![self.chunk(serde_json::json!({}), Some(finish_reason))];
+        if let Some(usage) = self.usage.as_ref().filter(|_| self.include_usage) {
+            let usage_chunk = serde_json::json!({
+                "id": self.id,
+                "object": "chat.completion.chunk",
+                "created": self.created,
+                "model": self.model,
+                "choices": [],
+                "usage": usage
+            });
+            out.push(format!("data: {}\n\n", usage_chunk));
+        }
+        out.push("data: [DONE]\n\n".to_string());
+        out
+    }
+
+    /// 聚合为非流式 chat.completion 响应
+    pub fn into_completion(self) -> Value {
+        let mut message = serde_json::json!({ "role": "assistant", "content": self.text });
+        if !self.reasoning.is_empty() {
+            message["reasoning_content"] = Value::String(self.reasoning);
+        }
+        if !self.tool_calls.is_empty() {
+            if self.text.is_empty() {
+                message["content"] = Value::Null;
+            }
+            message["tool_calls"] = self
+                .tool_calls
+                .iter()
+                .map(|t| {
+                    serde_json::json!({
+                        "id": t.call_id,
+                        "type": "function",
+                        "function": {"name": t.name, "arguments": t.arguments}
+                    })
+                })
+                .collect();
+        }
+        let mut resp = serde_json::json!({
+            "id": self.id,
+            "object": "chat.completion",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "message": message,
+                "finish_reason": self.finish_reason.unwrap_or("stop")
+            }]
+        });
+        if let Some(usage) = self.usage {
+            resp["usage"] = usage;
+        }
+        resp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codex_stream_collects_text_and_usage() {
+        let mut conv = CodexToOpenAiStream::new("gpt-5.1-codex", true);
+        let events = [
+            serde_json::json!({"type": "response.output_text.delta", "delta": "Hel"}),
+            serde_json::json!({"type": "response.output_text.delta", "delta": "lo"}),
+            serde_json::json!({"type": "response.completed", "response": {"usage": {
+                "input_tokens": 10, "output_tokens": 2, "total_tokens": 12,
+                "input_tokens_details": {"cached_tokens": 4}
+            }}}),
+        ];
+        let out: Vec<String> = events.iter().flat_map(|e| conv.on_event(e)).collect();
+        assert_eq!(out.len(), 2);
+        assert!(out[0].contains("\"role\":\"assistant\""));
+        let tail = conv.finish().join("");
+        assert!(tail.contains("\"prompt_tokens\":10"));
+        let completion = conv.into_completion();
+        assert_eq!(completion["choices"][0]["message"]["content"], "Hello");
+        assert_eq!(completion["usage"]["prompt_tokens_details"]["cached_tokens"], 4);
+    }
+
+    #[test]
+    fn test_build_codex_payload_tools_images_and_instructions() {
+        let body = serde_json::json!({
+            "model": "gpt-5.1-codex",
+            "reasoning_effort": "high",
+            "messages": [
+                {"role": "system", "content": "You are a coding agent."},
+                {"role": "user", "content": [
+                    {"type": "text", "text": "What is in this file?"},
+                    {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}}
+                ]},
+                {"role": "assistant", "content": null, "tool_calls": [{
+                    "id": "call_1", "type": "function",
+                    "function": {"name": "read_file", "arguments": "{\"path\":\"a.rs\"}"}
+                }]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "fn main() {}"}
+            ],
+            "tools": [{"type": "function", "function": {
+                "name": "read_file", "description": "Read a file",
+                "parameters": {"type": "object", "properties": {"path": {"type": "string"}}}
+            }}],
+            "tool_choice": {"type": "function", "function": {"name": "read_file"}}
+        });
+        let payload = build_codex_payload(&body).unwrap();
+        assert_eq!(payload["instructions"], "You are a coding agent.");
+        assert_eq!(payload["reasoning"]["effort"], "high");
+        let input = payload["input"].as_array().unwrap();
+        assert_eq!(input.len(), 3);
+        assert_eq!(input[0]["content"][1]["type"], "input_image");
+        assert_eq!(input[0]["content"][1]["image_url"], "data:image/png;base64,AAAA");
+        assert_eq!(input[1]["type"], "function_call");
+        assert_eq!(input[1]["call_id"], "call_1");
+        assert_eq!(input[2]["type"], "function_call_output");
+        assert_eq!(input[2]["output"], "fn main() {}");
+        assert_eq!(payload["tools"][0]["name"], "read_file");
+        assert_eq!(payload["tool_choice"], serde_json::json!({"type": "function", "name": "read_file"}));
+    }
+
+    #[test]
+    fn test_codex_stream_function_call_to_tool_call_chunks() {
+        let mut conv = CodexToOpenAiStream::new("gpt-5.1-codex", false);
+        let events = [
+            serde_json::json!({"type": "response.reasoning_summary_text.delta", "delta": "Need file"}),
+            serde_json::json!({"type": "response.output_item.added", "output_index": 1, "item": {
+                "type": "function_call", "id": "fc_1", "call_id": "call_9", "name": "read_file", "arguments": ""
+            }}),
+            serde_json::json!({"type": "response.function_call_arguments.delta", "item_id": "fc_1", "delta": "{\"path\":"}),
+            serde_json::json!({"type": "response.function_call_arguments.delta", "item_id": "fc_1", "delta": "\"a.rs\"}"}),
+            serde_json::json!({"type": "response.output_item.done", "item": {
+                "type": "function_call", "id": "fc_1", "arguments": "{\"path\":\"a.rs\"}"
+            }}),
+            serde_json::json!({"type": "response.completed", "response": {"usage": {"input_tokens": 5, "output_tokens": 1}}}),
+        ];
+        let out: Vec<String> = events.iter().flat_map(|e| conv.on_event(e)).collect();
+        assert_eq!(out.len(), 4);
+        assert!(out[0].contains("\"reasoning_content\":\"Need file\""));
+        assert!(out[1].contains("\"id\":\"call_9\""));
+        let tail = conv.finish();
+        assert!(tail[0].contains("\"finish_reason\":\"tool_calls\""));
+        assert!(!tail.iter().any(|chunk| chunk.contains("\"usage\"")));
+        let completion = conv.into_completion();
+        let message = &completion["choices"][0]["message"];
+        assert!(message["content"].is_null());
+        assert_eq!(message["tool_calls"][0]["function"]["arguments"], "{\"path\":\"a.rs\"}");
+        assert_eq!(message["reasoning_content"], "Need file");
+    }
+}
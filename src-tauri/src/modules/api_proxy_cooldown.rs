@@ -0,0 +1,325 @@
+//! API 反向代理 — 账号冷却与熔断
+//! 上游返回 429/402/403 时，根据 Retry-After、Codex resets_in_seconds、Google retryDelay、
+//! Kiro 错误体等推断恢复时间，将账号暂时移出账号池；无明确恢复时间时按连续失败次数指数退避
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// 无明确恢复时间时的首次冷却时长（秒）
+const BASE_COOLDOWN_SECS: i64 = 60;
+/// 指数退避的冷却上限（秒）
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+/// 上游明确给出恢复时间时的上限（秒），月度额度最长约一个月
+const MAX_EXPLICIT_COOLDOWN_SECS: i64 = 31 * 24 * 60 * 60;
+
+/// 需要冷却账号的上游状态码
+pub fn is_cooldown_status(status: u16) -> bool {
+    matches!(status, 402 | 403 | 429)
+}
+
+/// 冷却中的账号（通过 get_api_proxy_status 暴露给前端）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountCooldownInfo {
+    pub provider: String,
+    pub account_id: String,
+    pub account_email: String,
+    /// 恢复时间（Unix 秒）
+    pub until: i64,
+    pub remaining_seconds: i64,
+    /// 触发冷却的 HTTP 状态码
+    pub status: u16,
+    pub reason: String,
+    /// 连续失败次数
+    pub strikes: u32,
+}
+
+/// 一次触发冷却的上游失败
+#[derive(Debug, Clone, Copy)]
+pub struct CooldownCause<'a> {
+    pub status: u16,
+    /// 上游给出的恢复等待时间；为空则按连续失败次数指数退避
+    pub reset_seconds: Option<i64>,
+    pub reason: &'a str,
+}
+
+#[derive(Debug, Clone)]
+struct CooldownEntry {
+    account_email: String,
+    until: i64,
+    status: u16,
+    reason: String,
+    strikes: u32,
+}
+
+/// 共享冷却表，键为 provider + 账号 ID
+/// 冷却到期后条目保留（半开状态），再次失败时退避时长继续翻倍，成功一次即清除
+#[derive(Default)]
+pub struct CooldownTable {
+    entries: RwLock<HashMap<(String, String), CooldownEntry>>,
+}
+
+impl CooldownTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 账号是否处于冷却中
+    pub fn is_cooling(&self, provider: &str, account_id: &str, now: i64) -> bool {
+        self.entries
+            .read()
+            .ok()
+            .and_then(|entries| {
+                entries
+                    .get(&(provider.to_string(), account_id.to_string()))
+                    .map(|e| e.until > now)
+            })
+            .unwrap_or(false)
+    }
+
+    /// 记录一次失败并冷却账号，返回恢复时间（Unix 秒）
+    pub fn trip(
+        &self,
+        provider: &str,
+        account_id: &str,
+        account_email: &str,
+        cause: CooldownCause<'_>,
+        now: i64,
+    ) -> i64 {
+        let Ok(mut entries) = self.entries.write() else {
+            return now;
+        };
+        let key = (provider.to_string(), account_id.to_string());
+        let strikes = entries.get(&key).map(|e| e.strikes).unwrap_or(0) + 1;
+        let duration = match cause.reset_seconds {
+            Some(secs) => secs.clamp(1, MAX_EXPLICIT_COOLDOWN_SECS),
+            None => {
+                let factor = 1i64 << (strikes - 1).min(16);
+                (BASE_COOLDOWN_SECS * factor).min(MAX_BACKOFF_SECS)
+            }
+        };
+        let until = now + duration;
+        entries.insert(
+            key,
+            CooldownEntry {
+                account_email: account_email.to_string(),
+                until,
+                status: cause.status,
+                reason: cause.reason.to_string(),
+                strikes,
+            },
+        );
+        until
+    }
+
+    /// 请求成功：清除该账号的冷却与失败计数
+    pub fn record_success(&self, provider: &str, account_id: &str) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.remove(&(provider.to_string(), account_id.to_string()));
+        }
+    }
+
+    /// 指定账号中最早恢复的时间（Unix 秒）
+    pub fn earliest_release<'a>(
+        &self,
+        provider: &str,
+        account_ids: impl Iterator<Item = &'a str>,
+    ) -> Option<i64> {
+        let entries = self.entries.read().ok()?;
+        account_ids
+            .filter_map(|id| entries.get(&(provider.to_string(), id.to_string())))
+            .map(|e| e.until)
+            .min()
+    }
+
+    /// 当前仍在冷却中的账号
+    pub fn snapshot(&self, now: i64) -> Vec<AccountCooldownInfo> {
+        let Ok(entries) = self.entries.read() else {
+            return Vec::new();
+        };
+        let mut list: Vec<AccountCooldownInfo> = entries
+            .iter()
+            .filter(|(_, e)| e.until > now)
+            .map(|((provider, account_id), e)| AccountCooldownInfo {
+                provider: provider.clone(),
+                account_id: account_id.clone(),
+                account_email: e.account_email.clone(),
+                until: e.until,
+                remaining_seconds: e.until - now,
+                status: e.status,
+                reason: e.reason.clone(),
+                strikes: e.strikes,
+            })
+            .collect();
+        list.sort_by(|a, b| a.provider.cmp(&b.provider).then_with(|| a.until.cmp(&b.until)));
+        list
+    }
+}
+
+/// 解析时长字符串："3.5s"、"250ms"、"1h2m3s"
+fn parse_duration_secs(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if let Some(ms) = text.strip_suffix("ms") {
+        return ms.trim().parse::<f64>().ok().map(|v| (v / 1000.0).ceil() as i64);
+    }
+    let mut total = 0.0;
+    let mut number = String::new();
+    let mut matched = false;
+    for ch in text.chars() {
+        match ch {
+            '0'..='9' | '.' => number.push(ch),
+            'h' | 'm' | 's' => {
+                let value: f64 = number.parse().ok()?;
+                total += value
+                    * match ch {
+                        'h' => 3600.0,
+                        'm' => 60.0,
+                        _ => 1.0,
+                    };
+                number.clear();
+                matched = true;
+            }
+            _ => return None,
+        }
+    }
+    if !number.is_empty() {
+        // 纯数字按秒处理
+        total += number.parse::<f64>().ok()?;
+        matched = true;
+    }
+    matched.then(|| total.ceil() as i64)
+}
+
+/// 在 JSON 中递归查找第一个匹配键的值
+fn find_key<'a>(value: &'a Value, keys: &[&str]) -> Option<&'a Value> {
+    match value {
+        Value::Object(map) => {
+            for key in keys {
+                if let Some(v) = map.get(*key) {
+                    return Some(v);
+                }
+            }
+            map.values().find_map(|v| find_key(v, keys))
+        }
+        Value::Array(arr) => arr.iter().find_map(|v| find_key(v, keys)),
+        _ => None,
+    }
+}
+
+/// 距下个 UTC 自然月开始的秒数（Kiro 月度额度在月初重置）
+fn seconds_until_next_month(now: i64) -> i64 {
+    use chrono::{Datelike, TimeZone};
+    let Some(current) = chrono::Utc.timestamp_opt(now, 0).single() else {
+        return MAX_BACKOFF_SECS;
+    };
+    let (year, month) = if current.month() == 12 {
+        (current.year() + 1, 1)
+    } else {
+        (current.year(), current.month() + 1)
+    };
+    chrono::Utc
+        .with_ymd_and_hms(year, month, 1, 0, 0, 0)
+        .single()
+        .map(|next| next.timestamp() - now)
+        .unwrap_or(MAX_BACKOFF_SECS)
+}
+
+/// 从上游错误响应推断账号恢复前需等待的秒数
+/// 依次尝试：Retry-After 头、Codex resets_in_seconds / resets_at、
+/// Google retryDelay / quotaResetDelay / quotaResetTimeStamp、Kiro 月度额度耗尽
+pub fn parse_reset_seconds(retry_after: Option<&str>, body: &str, now: i64) -> Option<i64> {
+    if let Some(header) = retry_after.map(|h| h.trim()).filter(|h| !h.is_empty()) {
+        if let Ok(secs) = header.parse::<i64>() {
+            return Some(secs);
+        }
+        if let Ok(date) = chrono::DateTime::parse_from_rfc2822(header) {
+            return Some(date.timestamp() - now);
+        }
+    }
+
+    let json: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+
+    if let Some(secs) = find_key(&json, &["resets_in_seconds"]).and_then(|v| v.as_f64()) {
+        return Some(secs.ceil() as i64);
+    }
+    if let Some(ts) = find_key(&json, &["resets_at", "reset_at"]).and_then(|v| v.as_i64()) {
+        // 兼容毫秒时间戳
+        let ts = if ts > 1_000_000_000_000 { ts / 1000 } else { ts };
+        return Some(ts - now);
+    }
+    if let Some(delay) = find_key(&json, &["quotaResetDelay", "retryDelay"])
+        .and_then(|v| v.as_str())
+        .and_then(parse_duration_secs)
+    {
+        return Some(delay);
+    }
+    if let Some(ts) = find_key(&json, &["quotaResetTimeStamp"])
+        .and_then(|v| v.as_str())
+        .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+    {
+        return Some(ts.timestamp() - now);
+    }
+
+    // Kiro: {"reason": "MONTHLY_REQUEST_COUNT", "message": "..."}
+    let reason = find_key(&json, &["reason"]).and_then(|v| v.as_str()).unwrap_or("");
+    if reason.contains("MONTHLY") || body.contains("MONTHLY_REQUEST_COUNT") {
+        return Some(seconds_until_next_month(now));
+    }
+
+    None
+}
+
+/// 截取错误体用于冷却原因展示
+pub fn summarize_reason(body: &str) -> String {
+    let json: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    let message = find_key(&json, &["message"])
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| body.trim().to_string());
+    message.chars().take(200).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_reset_seconds_sources() {
+        let now = 1_700_000_000;
+        assert_eq!(parse_reset_seconds(Some("30"), "", now), Some(30));
+        assert_eq!(
+            parse_reset_seconds(None, r#"{"error":{"type":"usage_limit_reached","resets_in_seconds":1200}}"#, now),
+            Some(1200)
+        );
+        assert_eq!(
+            parse_reset_seconds(None, r#"{"error":{"details":[{"@type":"RetryInfo","retryDelay":"3.2s"}]}}"#, now),
+            Some(4)
+        );
+        assert_eq!(
+            parse_reset_seconds(None, r#"{"error":{"details":[{"metadata":{"quotaResetDelay":"1h2m3s"}}]}}"#, now),
+            Some(3723)
+        );
+        let monthly = parse_reset_seconds(None, r#"{"reason":"MONTHLY_REQUEST_COUNT"}"#, now).unwrap();
+        assert!(monthly > 0 && monthly <= 31 * 24 * 3600);
+        assert_eq!(parse_reset_seconds(None, "rate limited", now), None);
+    }
+
+    #[test]
+    fn test_cooldown_table_backoff_and_success() {
+        let table = CooldownTable::new();
+        let now = 1_000;
+        let backoff = CooldownCause { status: 429, reset_seconds: None, reason: "" };
+        assert_eq!(table.trip("codex", "a", "a@x.com", backoff, now), now + 60);
+        assert!(table.is_cooling("codex", "a", now + 59));
+        assert!(!table.is_cooling("codex", "a", now + 60));
+        // 到期后再次失败，退避翻倍
+        assert_eq!(table.trip("codex", "a", "a@x.com", backoff, now + 60), now + 60 + 120);
+        let explicit = CooldownCause { reset_seconds: Some(10), ..backoff };
+        assert_eq!(table.trip("kiro", "a", "a@x.com", explicit, now), now + 10);
+        assert_eq!(table.snapshot(now).len(), 2);
+        table.record_success("codex", "a");
+        assert!(!table.is_cooling("codex", "a", now));
+        assert_eq!(table.earliest_release("kiro", ["a", "b"].into_iter()), Some(now + 10));
+    }
+}
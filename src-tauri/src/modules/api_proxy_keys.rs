@@ -0,0 +1,437 @@
+//! API 反向代理 — 多 API Key 管理
+//! 每个 Key 可单独吊销，限定可用的 Provider 与账号，并限制每分钟请求数与每日 token 用量；
+//! 旧版 ApiProxyConfig.api_key 仍作为不受限的管理员 Key 生效；
+//! Key 只以 SHA-256 落盘（文件仅当前用户可读），明文仅在创建时返回一次
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+
+use super::config;
+use super::credential_vault::write_private_file;
+use super::websocket_auth::{constant_time_eq, sha256_hex};
+
+const API_KEYS_FILE: &str = "api_proxy_keys.json";
+/// 新生成 Key 的前缀
+const API_KEY_PREFIX: &str = "sk-cockpit-";
+
+static KEYS_LOCK: std::sync::LazyLock<Mutex<()>> = std::sync::LazyLock::new(|| Mutex::new(()));
+/// 运行时缓存，避免每个请求读取文件
+static KEYS_CACHE: std::sync::LazyLock<RwLock<Option<Vec<ApiKeyEntry>>>> =
+    std::sync::LazyLock::new(|| RwLock::new(None));
+/// 各 Key 的请求频率与当日 token 计量
+static KEY_METERS: std::sync::LazyLock<Mutex<HashMap<String, KeyMeter>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// 命名 API Key
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiKeyEntry {
+    /// 创建时由后端生成
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    /// 创建时由后端生成，明文只在创建响应中返回一次
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub key: String,
+    /// Key 的 SHA-256，不返回给前端
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub key_hash: String,
+    /// 用于辨认 Key 的前缀与末 4 位
+    #[serde(default)]
+    pub key_hint: String,
+    /// false = 已吊销
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 允许的 Provider（空 = 全部）
+    #[serde(default)]
+    pub providers: Vec<String>,
+    /// 允许的账号 ID 或邮箱（空 = 全部）
+    #[serde(default)]
+    pub account_ids: Vec<String>,
+    /// 每分钟请求上限（0 = 不限）
+    #[serde(default)]
+    pub requests_per_minute: u32,
+    /// 每日 token 上限（输入 + 输出，0 = 不限）
+    #[serde(default)]
+    pub daily_token_limit: u64,
+    #[serde(default)]
+    pub created_at: i64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 本次请求调用方的授权范围，随请求传递给各 Provider 处理器
+#[derive(Debug, Clone, Default)]
+pub struct ApiKeyScope {
+    /// 命名 Key 的 ID；管理员 Key 或未启用鉴权时为空
+    pub key_id: String,
+    /// 允许的账号 ID 或邮箱（空 = 全部）
+    pub account_ids: Vec<String>,
+}
+
+impl ApiKeyScope {
+    /// 账号是否在授权范围内
+    pub fn allows_account(&self, account_id: &str, email: &str) -> bool {
+        self.account_ids.is_empty()
+            || self
+                .account_ids
+                .iter()
+                .any(|a| a == account_id || a.eq_ignore_ascii_case(email))
+    }
+}
+
+/// 鉴权失败原因（映射为 HTTP 状态码）
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiKeyRejection {
+    /// 401：缺少或无效的 Key
+    Unauthorized,
+    /// 403：Key 已吊销或不允许访问该 Provider
+    Forbidden(String),
+    /// 429：超出频率或每日用量限制，附带建议重试秒数
+    RateLimited(String, i64),
+    /// 500：Key 列表读取失败，无法判断是否需要鉴权
+    Unavailable(String),
+}
+
+fn keys_path() -> Result<PathBuf, String> {
+    Ok(config::get_data_dir()?.join(API_KEYS_FILE))
+}
+
+fn load_keys_from_disk() -> Result<Vec<ApiKeyEntry>, String> {
+    let path = keys_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| format!("读取 API Key 列表失败: {}", e))?;
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&content).map_err(|e| format!("解析 API Key 列表失败: {}", e))
+}
+
+fn save_keys(entries: &[ApiKeyEntry]) -> Result<(), String> {
+    let content =
+        serde_json::to_string_pretty(entries).map_err(|e| format!("序列化 API Key 列表失败: {}", e))?;
+    write_private_file(&keys_path()?, content.as_bytes())
+        .map_err(|e| format!("写入 API Key 文件失败: {}", e))?;
+    if let Ok(mut cache) = KEYS_CACHE.write() {
+        *cache = Some(entries.to_vec());
+    }
+    Ok(())
+}
+
+fn key_hint(key: &str) -> String {
+    let tail: String = key.chars().rev().take(4).collect::<Vec<_>>().into_iter().rev().collect();
+    format!("{}…{}", API_KEY_PREFIX, tail)
+}
+
+/// 旧版明文保存的 Key 改为只保存哈希，返回是否有改动
+fn migrate_plaintext_keys(entries: &mut [ApiKeyEntry]) -> bool {
+    let mut changed = false;
+    for entry in entries.iter_mut().filter(|e| !e.key.is_empty()) {
+        entry.key_hash = sha256_hex(&entry.key);
+        entry.key_hint = key_hint(&entry.key);
+        entry.key.clear();
+        changed = true;
+    }
+    changed
+}
+
+/// 读取含哈希的完整列表（优先使用缓存）
+fn load_entries() -> Result<Vec<ApiKeyEntry>, String> {
+    if let Some(cached) = KEYS_CACHE.read().ok().and_then(|c| c.clone()) {
+        return Ok(cached);
+    }
+    let mut entries = load_keys_from_disk()?;
+    if migrate_plaintext_keys(&mut entries) {
+        save_keys(&entries)?;
+    } else if let Ok(mut cache) = KEYS_CACHE.write() {
+        *cache = Some(entries.clone());
+    }
+    Ok(entries)
+}
+
+/// 返回给前端的条目不含哈希
+fn without_hash(entry: ApiKeyEntry) -> ApiKeyEntry {
+    ApiKeyEntry {
+        key_hash: String::new(),
+        ..entry
+    }
+}
+
+/// 列出所有命名 Key（不含 Key 值与哈希）
+pub fn list_api_keys() -> Result<Vec<ApiKeyEntry>, String> {
+    Ok(load_entries()?.into_iter().map(without_hash).collect())
+}
+
+fn normalize(entry: &mut ApiKeyEntry) -> Result<(), String> {
+    entry.name = entry.name.trim().to_string();
+    if entry.name.is_empty() {
+        return Err("API Key 名称不能为空".to_string());
+    }
+    entry.providers.retain(|p| !p.trim().is_empty());
+    entry.account_ids.retain(|a| !a.trim().is_empty());
+    Ok(())
+}
+
+/// 创建命名 Key（自动生成 Key 值，仅在返回值中包含明文）
+pub fn create_api_key(mut entry: ApiKeyEntry) -> Result<ApiKeyEntry, String> {
+    normalize(&mut entry)?;
+    let _lock = KEYS_LOCK.lock().map_err(|_| "获取 API Key 锁失败")?;
+    let mut entries = load_entries()?;
+    let key = format!("{}{}", API_KEY_PREFIX, uuid::Uuid::new_v4().simple());
+    entry.id = uuid::Uuid::new_v4().to_string();
+    entry.key_hash = sha256_hex(&key);
+    entry.key_hint = key_hint(&key);
+    entry.key = String::new();
+    entry.created_at = chrono::Utc::now().timestamp();
+    entries.push(entry.clone());
+    save_keys(&entries)?;
+    Ok(ApiKeyEntry {
+        key,
+        ..without_hash(entry)
+    })
+}
+
+/// 更新命名 Key 的名称、范围、限额与启用状态（Key 值与创建时间保持不变）
+pub fn update_api_key(mut entry: ApiKeyEntry) -> Result<ApiKeyEntry, String> {
+    normalize(&mut entry)?;
+    let _lock = KEYS_LOCK.lock().map_err(|_| "获取 API Key 锁失败")?;
+    let mut entries = load_entries()?;
+    let existing = entries
+        .iter_mut()
+        .find(|e| e.id == entry.id)
+        .ok_or_else(|| format!("未找到 API Key: {}", entry.id))?;
+    entry.key = String::new();
+    entry.key_hash = existing.key_hash.clone();
+    entry.key_hint = existing.key_hint.clone();
+    entry.created_at = existing.created_at;
+    *existing = entry.clone();
+    save_keys(&entries)?;
+    Ok(without_hash(entry))
+}
+
+/// 删除命名 Key
+pub fn delete_api_key(id: &str) -> Result<(), String> {
+    let _lock = KEYS_LOCK.lock().map_err(|_| "获取 API Key 锁失败")?;
+    let mut entries = load_entries()?;
+    let before = entries.len();
+    entries.retain(|e| e.id != id);
+    if entries.len() == before {
+        return Err(format!("未找到 API Key: {}", id));
+    }
+    save_keys(&entries)?;
+    if let Ok(mut meters) = KEY_METERS.lock() {
+        meters.remove(id);
+    }
+    Ok(())
+}
+
+/// 从请求头取出调用方提供的 Key（Authorization: Bearer 或 x-api-key）
+pub fn presented_key<'a>(authorization: Option<&'a str>, x_api_key: Option<&'a str>) -> Option<&'a str> {
+    authorization
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or(x_api_key)
+        .map(|k| k.trim())
+        .filter(|k| !k.is_empty())
+}
+
+/// 单个 Key 的计量：最近一分钟请求时间戳 + 当日 token
+#[derive(Debug, Default)]
+struct KeyMeter {
+    recent_requests: VecDeque<i64>,
+    day: String,
+    tokens_today: u64,
+}
+
+impl KeyMeter {
+    fn roll_day(&mut self, key_id: &str, today: &str) {
+        if self.day != today {
+            self.day = today.to_string();
+            // 重启后从用量账本恢复当日已用 token
+            self.tokens_today = super::api_proxy_usage::tokens_for_api_key_on(key_id, today);
+        }
+    }
+}
+
+/// 校验频率与每日用量限制，通过则计入一次请求
+fn check_limits(
+    meters: &mut HashMap<String, KeyMeter>,
+    entry: &ApiKeyEntry,
+    today: &str,
+    now: i64,
+) -> Result<(), ApiKeyRejection> {
+    let meter = meters.entry(entry.id.clone()).or_default();
+    meter.roll_day(&entry.id, today);
+
+    if entry.daily_token_limit > 0 && meter.tokens_today >= entry.daily_token_limit {
+        return Err(ApiKeyRejection::RateLimited(
+            format!("API Key '{}' 已达每日 token 上限 {}", entry.name, entry.daily_token_limit),
+            seconds_until_tomorrow(),
+        ));
+    }
+
+    while meter.recent_requests.front().is_some_and(|t| *t <= now - 60) {
+        meter.recent_requests.pop_front();
+    }
+    if entry.requests_per_minute > 0 && meter.recent_requests.len() >= entry.requests_per_minute as usize {
+        let retry_after = meter.recent_requests.front().map(|t| t + 60 - now).unwrap_or(60).max(1);
+        return Err(ApiKeyRejection::RateLimited(
+            format!("API Key '{}' 超出每分钟 {} 次请求限制", entry.name, entry.requests_per_minute),
+            retry_after,
+        ));
+    }
+    meter.recent_requests.push_back(now);
+    Ok(())
+}
+
+fn seconds_until_tomorrow() -> i64 {
+    let now = chrono::Local::now();
+    let tomorrow = now.date_naive().succ_opt().and_then(|d| d.and_hms_opt(0, 0, 0));
+    tomorrow
+        .map(|t| (t - now.naive_local()).num_seconds().max(1))
+        .unwrap_or(60)
+}
+
+/// 是否配置了任何 Key（管理员 Key 或命名 Key）；Key 列表读取失败时返回错误
+pub fn has_any_key(admin_key: &str) -> Result<bool, String> {
+    Ok(!admin_key.is_empty() || !load_entries()?.is_empty())
+}
+
+/// 校验请求的 Key，并计入一次请求（每个请求只调用一次）
+/// admin_key: 旧版全局 api_key；未配置任何 Key 时不鉴权
+/// 返回 None 表示管理员 Key 或未启用鉴权，不受范围与限额约束
+/// Key 列表读取失败时拒绝请求，不视为未配置 Key
+pub fn authenticate(admin_key: &str, presented: Option<&str>) -> Result<Option<ApiKeyEntry>, ApiKeyRejection> {
+    let keys = load_entries().map_err(ApiKeyRejection::Unavailable)?;
+    if admin_key.is_empty() && keys.is_empty() {
+        return Ok(None);
+    }
+    let presented = presented.ok_or(ApiKeyRejection::Unauthorized)?;
+    if !admin_key.is_empty() && constant_time_eq(presented, admin_key) {
+        return Ok(None);
+    }
+    let presented_hash = sha256_hex(presented);
+    let entry = keys
+        .into_iter()
+        .find(|e| constant_time_eq(&e.key_hash, &presented_hash))
+        .ok_or(ApiKeyRejection::Unauthorized)?;
+    if !entry.enabled {
+        return Err(ApiKeyRejection::Forbidden(format!("API Key '{}' 已吊销", entry.name)));
+    }
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let now = chrono::Utc::now().timestamp();
+    let mut meters = KEY_METERS
+        .lock()
+        .map_err(|_| ApiKeyRejection::Forbidden("获取 API Key 计量锁失败".to_string()))?;
+    check_limits(&mut meters, &entry, &today, now)?;
+    Ok(Some(entry))
+}
+
+/// 校验 Key 对指定 Provider 的访问权限，返回随请求传递的授权范围
+pub fn scope_for_provider(entry: Option<&ApiKeyEntry>, provider: &str) -> Result<ApiKeyScope, ApiKeyRejection> {
+    let Some(entry) = entry else {
+        return Ok(ApiKeyScope::default());
+    };
+    if !entry.providers.is_empty() && !entry.providers.iter().any(|p| p == provider) {
+        return Err(ApiKeyRejection::Forbidden(format!(
+            "API Key '{}' 无权访问 Provider '{}'",
+            entry.name, provider
+        )));
+    }
+    Ok(ApiKeyScope {
+        key_id: entry.id.clone(),
+        account_ids: entry.account_ids.clone(),
+    })
+}
+
+/// 请求完成后累加该 Key 的当日 token（由用量账本记录时调用）
+pub fn add_key_tokens(key_id: &str, tokens: u64) {
+    if key_id.is_empty() || tokens == 0 {
+        return;
+    }
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    if let Ok(mut meters) = KEY_METERS.lock() {
+        let meter = meters.entry(key_id.to_string()).or_default();
+        meter.roll_day(key_id, &today);
+        meter.tokens_today += tokens;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(rpm: u32, daily: u64) -> ApiKeyEntry {
+        ApiKeyEntry {
+            id: "k1".to_string(),
+            name: "ci".to_string(),
+            key: String::new(),
+            key_hash: sha256_hex("sk-cockpit-test"),
+            key_hint: key_hint("sk-cockpit-test"),
+            enabled: true,
+            providers: vec!["kiro".to_string()],
+            account_ids: Vec::new(),
+            requests_per_minute: rpm,
+            daily_token_limit: daily,
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_check_limits_requests_per_minute_and_daily_tokens() {
+        let mut meters = HashMap::new();
+        let entry = key(2, 0);
+        let day = "2026-01-02";
+        // 预置当日计量，避免读取账本
+        meters.insert(entry.id.clone(), KeyMeter { day: day.to_string(), ..Default::default() });
+        assert!(check_limits(&mut meters, &entry, day, 1000).is_ok());
+        assert!(check_limits(&mut meters, &entry, day, 1010).is_ok());
+        assert_eq!(
+            check_limits(&mut meters, &entry, day, 1030),
+            Err(ApiKeyRejection::RateLimited("API Key 'ci' 超出每分钟 2 次请求限制".to_string(), 30))
+        );
+        assert!(check_limits(&mut meters, &entry, day, 1061).is_ok());
+
+        let capped = key(0, 100);
+        meters.get_mut("k1").unwrap().tokens_today = 100;
+        assert!(matches!(
+            check_limits(&mut meters, &capped, day, 2000),
+            Err(ApiKeyRejection::RateLimited(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_scope_and_provider_checks() {
+        let entry = key(0, 0);
+        assert!(matches!(scope_for_provider(Some(&entry), "codex"), Err(ApiKeyRejection::Forbidden(_))));
+        assert_eq!(scope_for_provider(Some(&entry), "kiro").unwrap().key_id, "k1");
+        assert!(scope_for_provider(None, "codex").unwrap().key_id.is_empty());
+
+        let scope = ApiKeyScope { key_id: "k1".to_string(), account_ids: vec!["A@x.com".to_string()] };
+        assert!(scope.allows_account("id-1", "a@x.com"));
+        assert!(!scope.allows_account("id-2", "b@x.com"));
+        assert_eq!(presented_key(Some("Bearer sk-1"), None), Some("sk-1"));
+        assert_eq!(presented_key(None, Some("sk-2")), Some("sk-2"));
+        assert_eq!(presented_key(Some("Basic x"), None), None);
+    }
+
+    #[test]
+    fn test_legacy_plaintext_keys_are_hashed() {
+        let mut entries = vec![ApiKeyEntry { key: "sk-cockpit-abc123".to_string(), ..key(0, 0) }];
+        assert!(migrate_plaintext_keys(&mut entries));
+        assert!(entries[0].key.is_empty());
+        assert_eq!(entries[0].key_hash, sha256_hex("sk-cockpit-abc123"));
+        assert_eq!(entries[0].key_hint, "sk-cockpit-…c123");
+        assert!(!migrate_plaintext_keys(&mut entries));
+
+        let saved = serde_json::to_string(&entries[0]).unwrap();
+        assert!(!saved.contains("sk-cockpit-abc123"));
+        assert!(!saved.contains("\"key\""));
+    }
+}
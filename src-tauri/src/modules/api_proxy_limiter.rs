@@ -0,0 +1,331 @@
+//! API 反向代理 — 单账号并发限制
+//! 每个上游账号同时进行的请求数受 max_concurrent_per_account 限制；槽位已满时优先换用有空位的账号，
+//! 全部占满则按先来先到排队，超过等待时间的请求返回 429
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// 排队超时后建议客户端的重试间隔（秒）
+pub const QUEUE_RETRY_AFTER_SECS: u64 = 5;
+
+/// 单个账号的并发槽位
+struct AccountSlots {
+    semaphore: Arc<Semaphore>,
+    sizing: Mutex<SlotSizing>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SlotSizing {
+    limit: usize,
+    /// 缩小上限时仍被占用、归还时需销毁的槽位数
+    excess: usize,
+}
+
+fn lock_or_recover<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+impl AccountSlots {
+    fn new(limit: usize) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            sizing: Mutex::new(SlotSizing { limit, excess: 0 }),
+        }
+    }
+
+    /// 在原信号量上调整上限，已占用的槽位继续计数
+    fn resize(&self, limit: usize) {
+        let mut sizing = lock_or_recover(&self.sizing);
+        if sizing.limit == limit {
+            return;
+        }
+        if limit > sizing.limit {
+            let grow = limit - sizing.limit;
+            let cancelled = grow.min(sizing.excess);
+            sizing.excess -= cancelled;
+            self.semaphore.add_permits(grow - cancelled);
+        } else {
+            let shrink = sizing.limit - limit;
+            let forgotten = self.semaphore.forget_permits(shrink);
+            sizing.excess += shrink - forgotten;
+        }
+        sizing.limit = limit;
+    }
+
+    /// 归还槽位时抵消缩容欠账，返回 true 表示该槽位应销毁
+    fn absorb_excess(&self) -> bool {
+        let mut sizing = lock_or_recover(&self.sizing);
+        if sizing.excess == 0 {
+            return false;
+        }
+        sizing.excess -= 1;
+        true
+    }
+
+    fn sizing(&self) -> SlotSizing {
+        *lock_or_recover(&self.sizing)
+    }
+}
+
+/// 账号并发占用情况（通过 get_api_proxy_status 暴露给前端）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSlotInfo {
+    pub provider: String,
+    pub account_id: String,
+    pub in_flight: usize,
+    pub limit: usize,
+}
+
+/// 占用中的并发槽位，随响应体释放
+pub struct SlotPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    slots: Arc<AccountSlots>,
+    /// 排队等待时长（未排队为 0）
+    pub waited: Duration,
+}
+
+impl Drop for SlotPermit {
+    fn drop(&mut self) {
+        if let Some(permit) = self.permit.take() {
+            if self.slots.absorb_excess() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+/// 请求持有的并发槽位；重试换号时先归还旧账号的槽位，再放入新账号的槽位
+#[derive(Clone, Default)]
+pub struct HeldSlot(Arc<Mutex<Option<SlotPermit>>>);
+
+impl HeldSlot {
+    pub fn new(permit: Option<SlotPermit>) -> Self {
+        Self(Arc::new(Mutex::new(permit)))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<SlotPermit>> {
+        lock_or_recover(&self.0)
+    }
+
+    pub fn release(&self) {
+        self.lock().take();
+    }
+
+    pub fn hold(&self, permit: Option<SlotPermit>) {
+        *self.lock() = permit;
+    }
+
+    pub fn is_held(&self) -> bool {
+        self.lock().is_some()
+    }
+}
+
+/// 排队超时
+#[derive(Debug, Clone, Copy)]
+pub struct QueueTimeout {
+    pub waited: Duration,
+}
+
+/// 等待中的请求数计数，离开作用域（获得槽位、超时或客户端断开）时减一
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+pub struct ConcurrencyLimiter {
+    /// (provider, account_id) → 槽位
+    slots: Mutex<HashMap<(String, String), Arc<AccountSlots>>>,
+    /// provider → 排队中的请求数
+    waiting: Mutex<HashMap<String, Arc<AtomicUsize>>>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 获取账号的槽位；上限变化时在原信号量上增减，避免已占用的槽位脱离计数
+    fn account_slots(&self, provider: &str, account_id: &str, limit: usize) -> Arc<AccountSlots> {
+        let slots = lock_or_recover(&self.slots)
+            .entry((provider.to_string(), account_id.to_string()))
+            .or_insert_with(|| Arc::new(AccountSlots::new(limit)))
+            .clone();
+        slots.resize(limit);
+        slots
+    }
+
+    fn waiting_counter(&self, provider: &str) -> Arc<AtomicUsize> {
+        lock_or_recover(&self.waiting)
+            .entry(provider.to_string()).or_default().clone()
+    }
+
+    /// 按顺序尝试立即占用一个账号的槽位，返回账号下标
+    pub fn try_acquire(&self, provider: &str, account_ids: &[&str], limit: usize) -> Option<(usize, SlotPermit)> {
+        account_ids.iter().enumerate().find_map(|(idx, id)| {
+            let slots = self.account_slots(provider, id, limit);
+            let permit = slots.semaphore.clone().try_acquire_owned().ok()?;
+            Some((
+                idx,
+                SlotPermit {
+                    permit: Some(permit),
+                    slots,
+                    waited: Duration::ZERO,
+                },
+            ))
+        })
+    }
+
+    /// 在所有候选账号上排队，任一账号空出槽位即占用；每个账号的等待队列先来先到
+    pub async fn acquire(
+        &self,
+        provider: &str,
+        account_ids: &[&str],
+        limit: usize,
+        timeout: Duration,
+    ) -> Result<(usize, SlotPermit), QueueTimeout> {
+        if let Some(acquired) = self.try_acquire(provider, account_ids, limit) {
+            return Ok(acquired);
+        }
+        let started = Instant::now();
+        if account_ids.is_empty() || timeout.is_zero() {
+            return Err(QueueTimeout { waited: Duration::ZERO });
+        }
+
+        let counter = self.waiting_counter(provider);
+        counter.fetch_add(1, Ordering::Relaxed);
+        let _waiting = WaitingGuard(&counter);
+
+        let waits = account_ids.iter().map(|id| {
+            let slots = self.account_slots(provider, id, limit);
+            Box::pin(async move {
+                let permit = slots.semaphore.clone().acquire_owned().await?;
+                Ok::<_, tokio::sync::AcquireError>((permit, slots))
+            })
+        });
+        match tokio::time::timeout(timeout, futures::future::select_all(waits)).await {
+            Ok((Ok((permit, slots)), idx, _)) => Ok((
+                idx,
+                SlotPermit {
+                    permit: Some(permit),
+                    slots,
+                    waited: started.elapsed(),
+                },
+            )),
+            _ => Err(QueueTimeout { waited: started.elapsed() }),
+        }
+    }
+
+    /// 各 Provider 排队中的请求数
+    pub fn queue_depths(&self) -> HashMap<String, usize> {
+        match self.waiting.lock() {
+            Ok(waiting) => waiting
+                .iter()
+                .map(|(provider, n)| (provider.clone(), n.load(Ordering::Relaxed)))
+                .collect(),
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    /// 有请求进行中的账号
+    pub fn snapshot(&self) -> Vec<AccountSlotInfo> {
+        let Ok(slots) = self.slots.lock() else {
+            return Vec::new();
+        };
+        let mut list: Vec<AccountSlotInfo> = slots
+            .iter()
+            .map(|((provider, account_id), s)| {
+                let sizing = s.sizing();
+                AccountSlotInfo {
+                    provider: provider.clone(),
+                    account_id: account_id.clone(),
+                    in_flight: (sizing.limit + sizing.excess).saturating_sub(s.semaphore.available_permits()),
+                    limit: sizing.limit,
+                }
+            })
+            .filter(|info| info.in_flight > 0)
+            .collect();
+        list.sort_by(|a, b| (&a.provider, &a.account_id).cmp(&(&b.provider, &b.account_id)));
+        list
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_acquire_moves_to_free_account_then_queues() {
+        let limiter = Arc::new(ConcurrencyLimiter::new());
+        let (idx, first) = limiter.acquire("kiro", &["a", "b"], 1, Duration::from_millis(10)).await.unwrap();
+        assert_eq!(idx, 0);
+        let (idx, _second) = limiter.acquire("kiro", &["a", "b"], 1, Duration::from_millis(10)).await.unwrap();
+        assert_eq!(idx, 1);
+        assert_eq!(limiter.snapshot().len(), 2);
+
+        // 两个账号都占满：超时
+        assert!(limiter.acquire("kiro", &["a", "b"], 1, Duration::from_millis(20)).await.is_err());
+        assert_eq!(limiter.queue_depths()["kiro"], 0);
+
+        // 排队中释放槽位即被占用
+        let waiter = {
+            let limiter = limiter.clone();
+            tokio::spawn(async move { limiter.acquire("kiro", &["a", "b"], 1, Duration::from_secs(5)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(limiter.queue_depths()["kiro"], 1);
+        drop(first);
+        let (idx, permit) = waiter.await.unwrap().unwrap();
+        assert_eq!(idx, 0);
+        assert!(permit.waited >= Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn test_held_slot_swaps_account_on_retry() {
+        let limiter = ConcurrencyLimiter::new();
+        let (_, permit) = limiter.acquire("codex", &["a"], 1, Duration::ZERO).await.unwrap();
+        let slot = HeldSlot::new(Some(permit));
+        assert!(limiter.try_acquire("codex", &["a"], 1).is_none());
+
+        // 换号：归还 a 的槽位后占用 b，a 可再被其它请求使用
+        slot.release();
+        let (_, permit) = limiter.acquire("codex", &["b"], 1, Duration::ZERO).await.unwrap();
+        slot.hold(Some(permit));
+        assert!(slot.is_held());
+        assert!(limiter.try_acquire("codex", &["b"], 1).is_none());
+        assert!(limiter.try_acquire("codex", &["a"], 1).is_some());
+
+        drop(slot);
+        assert!(limiter.try_acquire("codex", &["b"], 1).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_limit_change_keeps_held_permits_counted() {
+        let limiter = ConcurrencyLimiter::new();
+        let first = limiter.try_acquire("kiro", &["a"], 2).unwrap().1;
+        let second = limiter.try_acquire("kiro", &["a"], 2).unwrap().1;
+
+        // 上限降为 1：两个占用中的槽位仍计数，归还一个后依旧满额
+        assert!(limiter.try_acquire("kiro", &["a"], 1).is_none());
+        drop(first);
+        assert!(limiter.try_acquire("kiro", &["a"], 1).is_none());
+        assert_eq!(limiter.snapshot()[0].in_flight, 1);
+        drop(second);
+        let third = limiter.try_acquire("kiro", &["a"], 1).unwrap().1;
+        assert!(limiter.try_acquire("kiro", &["a"], 1).is_none());
+
+        // 上限升为 3：在原信号量上扩容
+        assert!(limiter.try_acquire("kiro", &["a"], 3).is_some());
+        drop(third);
+    }
+}
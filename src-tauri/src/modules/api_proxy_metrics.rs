@@ -0,0 +1,353 @@
+//! API 反向代理 — 运行指标
+//! 以 Prometheus 文本格式（GET /metrics）导出请求数、延迟分布、上游状态码、重试、切号、冷却与进行中的流；
+//! 账号标签使用账号 ID，邮箱只以加盐哈希形式出现（盐值保存在数据目录，重启后保持不变），避免抓取端保存或反推明文邮箱
+
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{config, logger};
+
+/// 请求延迟直方图的桶上界（秒）
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+/// 邮箱哈希的盐值文件
+const HASH_SALT_FILE: &str = "api_proxy_metrics_salt";
+
+/// 账号标签：(账号 ID, 邮箱哈希)
+type AccountKey = (String, String);
+
+/// 读取邮箱哈希的盐值，不存在时生成；写入失败时本次运行使用临时盐值
+fn load_or_create_salt() -> String {
+    let path = match config::get_data_dir() {
+        Ok(dir) => dir.join(HASH_SALT_FILE),
+        Err(_) => return random_salt(),
+    };
+    if let Ok(existing) = std::fs::read_to_string(&path) {
+        let existing = existing.trim();
+        if !existing.is_empty() {
+            return existing.to_string();
+        }
+    }
+    let salt = random_salt();
+    if let Err(e) = super::credential_vault::write_private_file(&path, salt.as_bytes()) {
+        logger::log_warn(&format!("[ApiProxy] 保存指标盐值失败，重启后邮箱哈希将变化: {}", e));
+    }
+    salt
+}
+
+fn random_salt() -> String {
+    let mut buf = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[derive(Default, Clone)]
+struct Histogram {
+    /// 各桶（含 +Inf）的非累计计数
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS.len() + 1];
+        }
+        let idx = LATENCY_BUCKETS
+            .iter()
+            .position(|le| seconds <= *le)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[idx] += 1;
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+struct MetricsInner {
+    /// (provider, account, status) → 请求数
+    requests: BTreeMap<(String, AccountKey, u16), u64>,
+    latency: BTreeMap<String, Histogram>,
+    /// (provider, account, status) → 上游响应数（含重试）
+    upstream: BTreeMap<(String, AccountKey, u16), u64>,
+    retries: BTreeMap<(String, AccountKey), u64>,
+    rotations: BTreeMap<String, u64>,
+    cooldowns: BTreeMap<(String, AccountKey), u64>,
+    in_flight_streams: BTreeMap<String, i64>,
+    queue_wait: BTreeMap<String, Histogram>,
+    queue_rejections: BTreeMap<String, u64>,
+}
+
+/// 代理运行指标，随代理服务启动创建
+pub struct ProxyMetrics {
+    inner: Mutex<MetricsInner>,
+    /// 邮箱哈希的盐值
+    salt: String,
+}
+
+impl Default for ProxyMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 进行中的流式响应；响应体被释放（完成或客户端断开）时计数减一
+pub struct StreamGuard {
+    metrics: Arc<ProxyMetrics>,
+    provider: String,
+}
+
+impl Drop for StreamGuard {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.metrics.inner.lock() {
+            if let Some(n) = inner.in_flight_streams.get_mut(&self.provider) {
+                *n -= 1;
+            }
+        }
+    }
+}
+
+impl ProxyMetrics {
+    pub fn new() -> Self {
+        Self::with_salt(load_or_create_salt())
+    }
+
+    pub fn with_salt(salt: String) -> Self {
+        Self {
+            inner: Mutex::new(MetricsInner::default()),
+            salt,
+        }
+    }
+
+    /// 邮箱哈希：加盐 SHA-256 前 12 位十六进制，不区分大小写
+    pub fn hash_email(&self, email: &str) -> String {
+        let digest = Sha256::new()
+            .chain_update(self.salt.as_bytes())
+            .chain_update([0u8])
+            .chain_update(email.trim().to_lowercase().as_bytes())
+            .finalize();
+        digest.iter().take(6).map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn account_key(&self, account_id: &str, email: &str) -> AccountKey {
+        // 部分 Provider 的账号 ID 即邮箱，同样做哈希
+        let id = if account_id.contains('@') {
+            self.hash_email(account_id)
+        } else {
+            account_id.to_string()
+        };
+        let email_hash = if email.is_empty() { String::new() } else { self.hash_email(email) };
+        (id, email_hash)
+    }
+
+    fn with_inner(&self, f: impl FnOnce(&mut MetricsInner)) {
+        if let Ok(mut inner) = self.inner.lock() {
+            f(&mut inner);
+        }
+    }
+
+    /// 客户端请求完成（响应头已返回）；流式请求的延迟为首包时间
+    pub fn record_request(&self, provider: &str, account_id: &str, email: &str, status: u16, elapsed: Duration) {
+        let key = (provider.to_string(), self.account_key(account_id, email), status);
+        self.with_inner(|inner| {
+            *inner.requests.entry(key).or_default() += 1;
+            inner
+                .latency
+                .entry(provider.to_string())
+                .or_default()
+                .observe(elapsed.as_secs_f64());
+        });
+    }
+
+    /// 上游返回的 HTTP 状态码（每次尝试各记一次）
+    pub fn record_upstream(&self, provider: &str, account_id: &str, email: &str, status: u16) {
+        let key = (provider.to_string(), self.account_key(account_id, email), status);
+        self.with_inner(|inner| *inner.upstream.entry(key).or_default() += 1);
+    }
+
+    /// 失败后重试（记在失败的账号上）
+    pub fn record_retry(&self, provider: &str, account_id: &str, email: &str) {
+        let key = (provider.to_string(), self.account_key(account_id, email));
+        self.with_inner(|inner| *inner.retries.entry(key).or_default() += 1);
+    }
+
+    /// 请求中途切换到另一个账号
+    pub fn record_rotation(&self, provider: &str) {
+        self.with_inner(|inner| *inner.rotations.entry(provider.to_string()).or_default() += 1);
+    }
+
+    /// 账号进入冷却
+    pub fn record_cooldown(&self, provider: &str, account_id: &str, email: &str) {
+        let key = (provider.to_string(), self.account_key(account_id, email));
+        self.with_inner(|inner| *inner.cooldowns.entry(key).or_default() += 1);
+    }
+
+    /// 排队后获得账号并发槽位
+    pub fn record_queue_wait(&self, provider: &str, waited: Duration) {
+        self.with_inner(|inner| {
+            inner
+                .queue_wait
+                .entry(provider.to_string())
+                .or_default()
+                .observe(waited.as_secs_f64())
+        });
+    }
+
+    /// 排队超时被拒绝（429）
+    pub fn record_queue_rejection(&self, provider: &str) {
+        self.with_inner(|inner| *inner.queue_rejections.entry(provider.to_string()).or_default() += 1);
+    }
+
+    /// 开始一个流式响应，返回的 guard 需随响应体一起持有
+    pub fn track_stream(self: &Arc<Self>, provider: &str) -> StreamGuard {
+        self.with_inner(|inner| *inner.in_flight_streams.entry(provider.to_string()).or_default() += 1);
+        StreamGuard {
+            metrics: Arc::clone(self),
+            provider: provider.to_string(),
+        }
+    }
+
+    /// 导出 Prometheus 文本格式；cooling / queued: 各 Provider 当前冷却中的账号数与排队中的请求数
+    pub fn render(&self, cooling: &HashMap<String, usize>, queued: &HashMap<String, usize>) -> String {
+        let Ok(inner) = self.inner.lock() else {
+            return String::new();
+        };
+        let mut out = String::new();
+
+        header(&mut out, "cockpit_proxy_requests_total", "counter", "客户端请求数（按最终状态码）");
+        for ((provider, (account, email), status), n) in &inner.requests {
+            let _ = writeln!(
+                out,
+                "cockpit_proxy_requests_total{{provider=\"{}\",account=\"{}\",email_hash=\"{}\",status=\"{}\"}} {}",
+                escape(provider), escape(account), email, status, n
+            );
+        }
+
+        header(&mut out, "cockpit_proxy_request_duration_seconds", "histogram", "请求延迟（流式为首包时间）");
+        write_histograms(&mut out, "cockpit_proxy_request_duration_seconds", &inner.latency);
+
+        header(&mut out, "cockpit_proxy_upstream_responses_total", "counter", "上游响应数（按状态码，含重试）");
+        for ((provider, (account, email), status), n) in &inner.upstream {
+            let _ = writeln!(
+                out,
+                "cockpit_proxy_upstream_responses_total{{provider=\"{}\",account=\"{}\",email_hash=\"{}\",status=\"{}\"}} {}",
+                escape(provider), escape(account), email, status, n
+            );
+        }
+
+        header(&mut out, "cockpit_proxy_retries_total", "counter", "上游失败后的重试次数");
+        for ((provider, (account, email)), n) in &inner.retries {
+            let _ = writeln!(
+                out,
+                "cockpit_proxy_retries_total{{provider=\"{}\",account=\"{}\",email_hash=\"{}\"}} {}",
+                escape(provider), escape(account), email, n
+            );
+        }
+
+        header(&mut out, "cockpit_proxy_account_rotations_total", "counter", "请求中途切换账号的次数");
+        for (provider, n) in &inner.rotations {
+            let _ = writeln!(out, "cockpit_proxy_account_rotations_total{{provider=\"{}\"}} {}", escape(provider), n);
+        }
+
+        header(&mut out, "cockpit_proxy_cooldowns_total", "counter", "账号进入冷却的次数");
+        for ((provider, (account, email)), n) in &inner.cooldowns {
+            let _ = writeln!(
+                out,
+                "cockpit_proxy_cooldowns_total{{provider=\"{}\",account=\"{}\",email_hash=\"{}\"}} {}",
+                escape(provider), escape(account), email, n
+            );
+        }
+
+        header(&mut out, "cockpit_proxy_cooling_accounts", "gauge", "当前冷却中的账号数");
+        let cooling: BTreeMap<_, _> = cooling.iter().collect();
+        for (provider, n) in cooling {
+            let _ = writeln!(out, "cockpit_proxy_cooling_accounts{{provider=\"{}\"}} {}", escape(provider), n);
+        }
+
+        header(&mut out, "cockpit_proxy_in_flight_streams", "gauge", "进行中的流式响应数");
+        for (provider, n) in &inner.in_flight_streams {
+            let _ = writeln!(out, "cockpit_proxy_in_flight_streams{{provider=\"{}\"}} {}", escape(provider), n);
+        }
+
+        header(&mut out, "cockpit_proxy_queue_depth", "gauge", "等待账号并发槽位的请求数");
+        let queued: BTreeMap<_, _> = queued.iter().collect();
+        for (provider, n) in queued {
+            let _ = writeln!(out, "cockpit_proxy_queue_depth{{provider=\"{}\"}} {}", escape(provider), n);
+        }
+
+        header(&mut out, "cockpit_proxy_queue_wait_seconds", "histogram", "排队等待并发槽位的时长");
+        write_histograms(&mut out, "cockpit_proxy_queue_wait_seconds", &inner.queue_wait);
+
+        header(&mut out, "cockpit_proxy_queue_rejections_total", "counter", "排队超时返回 429 的次数");
+        for (provider, n) in &inner.queue_rejections {
+            let _ = writeln!(out, "cockpit_proxy_queue_rejections_total{{provider=\"{}\"}} {}", escape(provider), n);
+        }
+        out
+    }
+}
+
+fn write_histograms(out: &mut String, name: &str, histograms: &BTreeMap<String, Histogram>) {
+    for (provider, hist) in histograms {
+        let provider = escape(provider);
+        let mut cumulative = 0;
+        for (i, le) in LATENCY_BUCKETS.iter().enumerate() {
+            cumulative += hist.buckets[i];
+            let _ = writeln!(out, "{}_bucket{{provider=\"{}\",le=\"{}\"}} {}", name, provider, le, cumulative);
+        }
+        let _ = writeln!(out, "{}_bucket{{provider=\"{}\",le=\"+Inf\"}} {}", name, provider, hist.count);
+        let _ = writeln!(out, "{}_sum{{provider=\"{}\"}} {}", name, provider, hist.sum);
+        let _ = writeln!(out, "{}_count{{provider=\"{}\"}} {}", name, provider, hist.count);
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// 转义标签值中的反斜杠、引号与换行
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_counters_histogram_and_streams() {
+        let metrics = Arc::new(ProxyMetrics::with_salt("test-salt".to_string()));
+        metrics.record_request("codex", "acc-1", "User@Example.com", 200, Duration::from_millis(300));
+        metrics.record_request("codex", "acc-1", "user@example.com", 200, Duration::from_secs(200));
+        metrics.record_upstream("codex", "acc-1", "user@example.com", 429);
+        metrics.record_retry("kiro", "someone@example.com", "someone@example.com");
+        metrics.record_rotation("codex");
+        metrics.record_queue_wait("codex", Duration::from_millis(80));
+        let guard = metrics.track_stream("codex");
+
+        let mut cooling = HashMap::new();
+        cooling.insert("codex".to_string(), 1);
+        let text = metrics.render(&cooling, &cooling);
+        let email_hash = metrics.hash_email("user@example.com");
+        assert_ne!(email_hash, ProxyMetrics::with_salt("other".to_string()).hash_email("user@example.com"));
+        assert!(text.contains(&format!(
+            "cockpit_proxy_requests_total{{provider=\"codex\",account=\"acc-1\",email_hash=\"{}\",status=\"200\"}} 2",
+            email_hash
+        )));
+        assert!(text.contains("cockpit_proxy_request_duration_seconds_bucket{provider=\"codex\",le=\"0.5\"} 1"));
+        assert!(text.contains("cockpit_proxy_request_duration_seconds_bucket{provider=\"codex\",le=\"+Inf\"} 2"));
+        assert!(text.contains("status=\"429\"} 1"));
+        assert!(!text.contains("someone@example.com"));
+        assert!(text.contains("cockpit_proxy_cooling_accounts{provider=\"codex\"} 1"));
+        assert!(text.contains("cockpit_proxy_in_flight_streams{provider=\"codex\"} 1"));
+        assert!(text.contains("cockpit_proxy_queue_depth{provider=\"codex\"} 1"));
+        assert!(text.contains("cockpit_proxy_queue_wait_seconds_bucket{provider=\"codex\",le=\"0.1\"} 1"));
+
+        drop(guard);
+        assert!(metrics.render(&cooling, &HashMap::new()).contains("cockpit_proxy_in_flight_streams{provider=\"codex\"} 0"));
+    }
+}
@@ -0,0 +1,96 @@
+//! API 反向代理 — 模型列表
+//! 汇总各 Provider 的可用模型，以 OpenAI GET /v1/models 格式返回，按 Provider 缓存一段时间
+
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+/// 模型列表缓存有效期
+pub const MODEL_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// 路由别名在模型列表中的 owned_by
+const ROUTE_ALIAS_OWNER: &str = "cockpit-router";
+
+/// 按 Provider 缓存的模型列表
+#[derive(Default)]
+pub struct ModelCatalogCache {
+    entries: RwLock<HashMap<String, (Instant, Vec<String>)>>,
+}
+
+impl ModelCatalogCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 读取未过期的缓存
+    pub fn get(&self, provider: &str, now: Instant) -> Option<Vec<String>> {
+        let entries = self.entries.read().ok()?;
+        let (fetched_at, models) = entries.get(provider)?;
+        (now.duration_since(*fetched_at) < MODEL_CACHE_TTL).then(|| models.clone())
+    }
+
+    pub fn put(&self, provider: &str, models: Vec<String>, now: Instant) {
+        if let Ok(mut entries) = self.entries.write() {
+            entries.insert(provider.to_string(), (now, models));
+        }
+    }
+}
+
+/// 组装 OpenAI 格式模型列表
+/// providers: 按优先级排列的 (Provider, 模型列表)，同名模型只保留第一个；aliases: 路由表中的别名
+pub fn openai_model_list(providers: &[(String, Vec<String>)], aliases: &[String]) -> Value {
+    let mut seen = HashSet::new();
+    let mut data = Vec::new();
+    for (provider, models) in providers {
+        for model in models {
+            if seen.insert(model.clone()) {
+                data.push(serde_json::json!({
+                    "id": model,
+                    "object": "model",
+                    "created": 0,
+                    "owned_by": provider
+                }));
+            }
+        }
+    }
+    for alias in aliases {
+        if seen.insert(alias.clone()) {
+            data.push(serde_json::json!({
+                "id": alias,
+                "object": "model",
+                "created": 0,
+                "owned_by": ROUTE_ALIAS_OWNER
+            }));
+        }
+    }
+    serde_json::json!({ "object": "list", "data": data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_list_dedupes_and_cache_expires() {
+        let list = openai_model_list(
+            &[
+                ("kiro".to_string(), vec!["claude-sonnet-4.5".to_string(), "auto".to_string()]),
+                ("antigravity".to_string(), vec!["claude-sonnet-4.5".to_string(), "gemini-3-pro".to_string()]),
+            ],
+            &["sonnet".to_string(), "auto".to_string()],
+        );
+        let data = list["data"].as_array().unwrap();
+        assert_eq!(data.len(), 4);
+        assert_eq!(data[0]["owned_by"], "kiro");
+        assert_eq!(data[3]["id"], "sonnet");
+        assert_eq!(data[3]["owned_by"], ROUTE_ALIAS_OWNER);
+
+        let cache = ModelCatalogCache::new();
+        let t0 = Instant::now();
+        cache.put("codex", vec!["gpt-5.2".to_string()], t0);
+        assert_eq!(cache.get("codex", t0 + Duration::from_secs(1)).unwrap().len(), 1);
+        assert!(cache.get("codex", t0 + MODEL_CACHE_TTL).is_none());
+        assert!(cache.get("kiro", t0).is_none());
+    }
+}
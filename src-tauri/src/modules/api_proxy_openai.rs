@@ -0,0 +1,1313 @@
+//! API 反向代理 — OpenAI Chat Completions ↔ Claude Messages 兼容层
+//! - OpenAI chat/completions 请求 → Claude Messages，Claude 响应（JSON / SSE）→ OpenAI 格式，
+//!   使 /{provider}/v1/chat/completions 可用于以 Claude 格式输出的 Provider
+//! - Claude Messages 请求 → OpenAI chat/completions，OpenAI 响应 → Claude 格式，
+//!   使 /{provider}/v1/messages 可用于以 OpenAI 格式输出的 Provider
+
+use axum::{
+    body::Body,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::logger;
+
+// ============================================================================
+// 请求转换：OpenAI chat → Claude Messages
+// ============================================================================
+
+/// 将 OpenAI 的 data URL（data:image/png;base64,...）拆分为 (media_type, data)
+fn split_data_url(url: &str) -> Option<(String, String)> {
+    let rest = url.strip_prefix("data:")?;
+    let (header, data) = rest.split_once(',')?;
+    let media_type = header.split(';').next().unwrap_or("image/jpeg");
+    let media_type = if media_type.is_empty() { "image/jpeg" } else { media_type };
+    Some((media_type.to_string(), data.to_string()))
+}
+
+/// 将 OpenAI 消息 content 转换为 Claude content blocks
+fn openai_content_to_claude_blocks(content: &Value) -> Vec<Value> {
+    match content {
+        Value::String(s) => {
+            if s.is_empty() {
+                vec![]
+            } else {
+                vec![serde_json::json!({"type": "text", "text": s})]
+            }
+        }
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|part| match part.get("type").and_then(|v| v.as_str()) {
+                Some("text") | Some("input_text") => part
+                    .get("text")
+                    .and_then(|v| v.as_str())
+                    .filter(|t| !t.is_empty())
+                    .map(|t| serde_json::json!({"type": "text", "text": t})),
+                Some("image_url") => {
+                    let url = part
+                        .get("image_url")
+                        .and_then(|iu| iu.get("url").or(Some(iu)))
+                        .and_then(|u| u.as_str())?;
+                    if let Some((media_type, data)) = split_data_url(url) {
+                        Some(serde_json::json!({
+                            "type": "image",
+                            "source": { "type": "base64", "media_type": media_type, "data": data }
+                        }))
+                    } else {
+                        Some(serde_json::json!({
+                            "type": "image",
+                            "source": { "type": "url", "url": url }
+                        }))
+                    }
+                }
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// 提取 OpenAI 消息 content 中的纯文本
+fn openai_content_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(|p| p.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join(""),
+        _ => String::new(),
+    }
+}
+
+/// 将 reasoning_effort 映射为 Claude thinking 预算
+fn reasoning_effort_to_budget(effort: &str) -> Option<u64> {
+    match effort {
+        "minimal" | "low" => Some(4096),
+        "medium" => Some(16384),
+        "high" => Some(32768),
+        _ => None,
+    }
+}
+
+/// 将 OpenAI chat/completions 请求体转换为 Claude Messages 请求体
+pub fn openai_chat_to_claude(body: &Value) -> Value {
+    let empty = Vec::new();
+    let messages = body
+        .get("messages")
+        .and_then(|v| v.as_array())
+        .unwrap_or(&empty);
+
+    let mut system_parts: Vec<String> = Vec::new();
+    let mut claude_messages: Vec<Value> = Vec::new();
+
+    for msg in messages {
+        let role = msg.get("role").and_then(|v| v.as_str()).unwrap_or("user");
+        let content = msg.get("content").unwrap_or(&Value::Null);
+        match role {
+            "system" | "developer" => {
+                let text = openai_content_text(content);
+                if !text.is_empty() {
+                    system_parts.push(text);
+                }
+            }
+            "assistant" => {
+                let mut blocks = openai_content_to_claude_blocks(content);
+                if let Some(tool_calls) = msg.get("tool_calls").and_then(|v| v.as_array()) {
+                    for tc in tool_calls {
+                        let func = tc.get("function").unwrap_or(&Value::Null);
+                        let arguments = func.get("arguments").and_then(|v| v.as_str()).unwrap_or("{}");
+                        let input: Value = serde_json::from_str(arguments)
+                            .unwrap_or_else(|_| serde_json::json!({}));
+                        blocks.push(serde_json::json!({
+                            "type": "tool_use",
+                            "id": tc.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                            "name": func.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                            "input": input
+                        }));
+                    }
+                }
+                if blocks.is_empty() {
+                    continue;
+                }
+                claude_messages.push(serde_json::json!({"role": "assistant", "content": blocks}));
+            }
+            "tool" | "function" => {
+                let tool_result = serde_json::json!({
+                    "type": "tool_result",
+                    "tool_use_id": msg.get("tool_call_id").and_then(|v| v.as_str()).unwrap_or(""),
+                    "content": openai_content_text(content)
+                });
+                // 连续的 tool 消息合并进同一条 user 消息（Claude 要求 user/assistant 交替）
+                if let Some(last) = claude_messages.last_mut() {
+                    let last_is_tool_results = last.get("role").and_then(|v| v.as_str()) == Some("user")
+                        && last
+                            .get("content")
+                            .and_then(|c| c.as_array())
+                            .map(|arr| arr.iter().all(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result")))
+                            .unwrap_or(false);
+                    if last_is_tool_results {
+                        if let Some(arr) = last.get_mut("content").and_then(|c| c.as_array_mut()) {
+                            arr.push(tool_result);
+                            continue;
+                        }
+                    }
+                }
+                claude_messages.push(serde_json::json!({"role": "user", "content": [tool_result]}));
+            }
+            _ => {
+                let blocks = openai_content_to_claude_blocks(content);
+                if blocks.is_empty() {
+                    continue;
+                }
+                claude_messages.push(serde_json::json!({"role": "user", "content": blocks}));
+            }
+        }
+    }
+
+    let mut claude_body = serde_json::json!({
+        "model": body.get("model").cloned().unwrap_or(Value::Null),
+        "messages": claude_messages,
+        "stream": body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false),
+    });
+
+    if !system_parts.is_empty() {
+        claude_body["system"] = Value::String(system_parts.join("\n"));
+    }
+    if let Some(max_tokens) = body
+        .get("max_completion_tokens")
+        .or_else(|| body.get("max_tokens"))
+        .and_then(|v| v.as_u64())
+    {
+        claude_body["max_tokens"] = Value::from(max_tokens);
+    }
+    for key in ["temperature", "top_p"] {
+        if let Some(v) = body.get(key).filter(|v| v.is_number()) {
+            claude_body[key] = v.clone();
+        }
+    }
+    match body.get("stop") {
+        Some(Value::String(s)) => claude_body["stop_sequences"] = serde_json::json!([s]),
+        Some(Value::Array(arr)) => claude_body["stop_sequences"] = Value::Array(arr.clone()),
+        _ => {}
+    }
+
+    if let Some(tools) = body.get("tools").and_then(|v| v.as_array()) {
+        let claude_tools: Vec<Value> = tools
+            .iter()
+            .filter_map(|tool| {
+                let func = tool.get("function")?;
+                Some(serde_json::json!({
+                    "name": func.get("name").cloned().unwrap_or(Value::Null),
+                    "description": func.get("description").and_then(|v| v.as_str()).unwrap_or(""),
+                    "input_schema": func.get("parameters").cloned()
+                        .unwrap_or_else(|| serde_json::json!({"type": "object", "properties": {}}))
+                }))
+            })
+            .collect();
+        if !claude_tools.is_empty() {
+            claude_body["tools"] = Value::Array(claude_tools);
+        }
+    }
+    match body.get("tool_choice") {
+        Some(Value::String(s)) if s == "required" => {
+            claude_body["tool_choice"] = serde_json::json!({"type": "any"});
+        }
+        Some(Value::String(s)) if s == "auto" => {
+            claude_body["tool_choice"] = serde_json::json!({"type": "auto"});
+        }
+        Some(Value::String(s)) if s == "none" => {
+            claude_body["tool_choice"] = serde_json::json!({"type": "none"});
+        }
+        Some(Value::Object(obj)) => {
+            if let Some(name) = obj.get("function").and_then(|f| f.get("name")).and_then(|v| v.as_str()) {
+                claude_body["tool_choice"] = serde_json::json!({"type": "tool", "name": name});
+            }
+        }
+        _ => {}
+    }
+
+    if let Some(budget) = body
+        .get("reasoning_effort")
+        .and_then(|v| v.as_str())
+        .and_then(reasoning_effort_to_budget)
+    {
+        claude_body["thinking"] = serde_json::json!({"type": "enabled", "budget_tokens": budget});
+    }
+
+    claude_body
+}
+
+// ============================================================================
+// 响应转换：Claude → OpenAI
+// ============================================================================
+
+/// Claude stop_reason → OpenAI finish_reason
+fn claude_stop_reason_to_finish_reason(stop_reason: &str) -> &'static str {
+    match stop_reason {
+        "max_tokens" => "length",
+        "tool_use" => "tool_calls",
+        _ => "stop",
+    }
+}
+
+/// Claude usage → OpenAI usage
+fn claude_usage_to_openai(usage: &Value) -> Value {
+    let input = usage.get("input_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+    let output = usage.get("output_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+    serde_json::json!({
+        "prompt_tokens": input,
+        "completion_tokens": output,
+        "total_tokens": input + output
+    })
+}
+
+fn new_completion_id() -> String {
+    format!("chatcmpl-{}", uuid::Uuid::new_v4().simple())
+}
+
+/// 将完整的 Claude message 响应转换为 OpenAI chat.completion 响应
+pub fn claude_message_to_openai(message: &Value, model: &str) -> Value {
+    let mut text = String::new();
+    let mut reasoning = String::new();
+    let mut tool_calls: Vec<Value> = Vec::new();
+
+    if let Some(blocks) = message.get("content").and_then(|v| v.as_array()) {
+        for block in blocks {
+            match block.get("type").and_then(|v| v.as_str()) {
+                Some("text") => text.push_str(block.get("text").and_then(|v| v.as_str()).unwrap_or("")),
+                Some("thinking") => {
+                    reasoning.push_str(block.get("thinking").and_then(|v| v.as_str()).unwrap_or(""))
+                }
+                Some("tool_use") => {
+                    let input = block.get("input").cloned().unwrap_or_else(|| serde_json::json!({}));
+                    tool_calls.push(serde_json::json!({
+                        "id": block.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                        "type": "function",
+                        "function": {
+                            "name": block.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                            "arguments": input.to_string()
+                        }
+                    }));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut out_message = serde_json::json!({
+        "role": "assistant",
+        "content": if text.is_empty() && !tool_calls.is_empty() { Value::Null } else { Value::String(text) }
+    });
+    if !reasoning.is_empty() {
+        out_message["reasoning_content"] = Value::String(reasoning);
+    }
+    let finish_reason = if !tool_calls.is_empty() {
+        out_message["tool_calls"] = Value::Array(tool_calls);
+        "tool_calls"
+    } else {
+        claude_stop_reason_to_finish_reason(
+            message.get("stop_reason").and_then(|v| v.as_str()).unwrap_or("end_turn"),
+        )
+    };
+
+    let mut resp = serde_json::json!({
+        "id": new_completion_id(),
+        "object": "chat.completion",
+        "created": chrono::Utc::now().timestamp(),
+        "model": model,
+        "choices": [{
+            "index": 0,
+            "message": out_message,
+            "finish_reason": finish_reason
+        }]
+    });
+    if let Some(usage) = message.get("usage") {
+        resp["usage"] = claude_usage_to_openai(usage);
+    }
+    resp
+}
+
+/// Claude SSE → OpenAI chat.completion.chunk 的有状态转换器
+pub struct ClaudeToOpenAiStream {
+    id: String,
+    model: String,
+    created: i64,
+    include_usage: bool,
+    /// Claude content block index → OpenAI tool_calls index
+    tool_indices: HashMap<u64, usize>,
+    finish_reason: Option<String>,
+    input_tokens: u64,
+    output_tokens: u64,
+    has_usage: bool,
+    finished: bool,
+}
+
+impl ClaudeToOpenAiStream {
+    pub fn new(model: &str, include_usage: bool) -> Self {
+        Self {
+            id: new_completion_id(),
+            model: model.to_string(),
+            created: chrono::Utc::now().timestamp(),
+            include_usage,
+            tool_indices: HashMap::new(),
+            finish_reason: None,
+            input_tokens: 0,
+            output_tokens: 0,
+            has_usage: false,
+            finished: false,
+        }
+    }
+
+    fn chunk(&self, delta: Value, finish_reason: Option<&str>) -> String {
+        let chunk = serde_json::json!({
+            "id": self.id,
+            "object": "chat.completion.chunk",
+            "created": self.created,
+            "model": self.model,
+            "choices": [{
+                "index": 0,
+                "delta": delta,
+                "finish_reason": finish_reason
+            }]
+        });
+        format!("data: {}\n\n", chunk)
+    }
+
+    fn merge_usage(&mut self, usage: &Value) {
+        if let Some(v) = usage.get("input_tokens").and_then(|v| v.as_u64()) {
+            self.input_tokens = self.input_tokens.max(v);
+            self.has_usage = true;
+        }
+        if let Some(v) = usage.get("output_tokens").and_then(|v| v.as_u64()) {
+            self.output_tokens = self.output_tokens.max(v);
+            self.has_usage = true;
+        }
+    }
+
+    /// 处理一条 Claude SSE data JSON，返回需要发送给客户端的 OpenAI SSE 行
+    pub fn on_event(&mut self, event: &Value) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.finished {
+            return out;
+        }
+        match event.get("type").and_then(|v| v.as_str()).unwrap_or("") {
+            "message_start" => {
+                if let Some(usage) = event.get("message").and_then(|m| m.get("usage")) {
+                    self.merge_usage(usage);
+                }
+                out.push(self.chunk(serde_json::json!({"role": "assistant", "content": ""}), None));
+            }
+            "content_block_start" => {
+                let block = event.get("content_block").unwrap_or(&Value::Null);
+                if block.get("type").and_then(|v| v.as_str()) == Some("tool_use") {
+                    let block_index = event.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+                    let tool_index = self.tool_indices.len();
+                    self.tool_indices.insert(block_index, tool_index);
+                    // 部分上游在 start 中就带完整 input
+                    let initial_args = block
+                        .get("input")
+                        .filter(|v| v.as_object().map(|o| !o.is_empty()).unwrap_or(false))
+                        .map(|v| v.to_string())
+                        .unwrap_or_default();
+                    out.push(self.chunk(
+                        serde_json::json!({
+                            "tool_calls": [{
+                                "index": tool_index,
+                                "id": block.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                                "type": "function",
+                                "function": {
+                                    "name": block.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                                    "arguments": initial_args
+                                }
+                            }]
+                        }),
+                        None,
+                    ));
+                }
+            }
+            "content_block_delta" => {
+                let delta = event.get("delta").unwrap_or(&Value::Null);
+                match delta.get("type").and_then(|v| v.as_str()) {
+                    Some("text_delta") => {
+                        if let Some(text) = delta.get("text").and_then(|v| v.as_str()).filter(|t| !t.is_empty()) {
+                            out.push(self.chunk(serde_json::json!({"content": text}), None));
+                        }
+                    }
+                    Some("thinking_delta") => {
+                        if let Some(text) = delta.get("thinking").and_then(|v| v.as_str()).filter(|t| !t.is_empty()) {
+                            out.push(self.chunk(serde_json::json!({"reasoning_content": text}), None));
+                        }
+                    }
+                    Some("input_json_delta") => {
+                        let block_index = event.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+                        let partial = delta.get("partial_json").and_then(|v| v.as_str()).unwrap_or("");
+                        if let Some(tool_index) = self.tool_indices.get(&block_index).copied() {
+                            if !partial.is_empty() {
+                                out.push(self.chunk(
+                                    serde_json::json!({
+                                        "tool_calls": [{
+                                            "index": tool_index,
+                                            "function": { "arguments": partial }
+                                        }]
+                                    }),
+                                    None,
+                                ));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            "message_delta" => {
+                if let Some(reason) = event
+                    .get("delta")
+                    .and_then(|d| d.get("stop_reason"))
+                    .and_then(|v| v.as_str())
+                {
+                    self.finish_reason = Some(claude_stop_reason_to_finish_reason(reason).to_string());
+                }
+                if let Some(usage) = event.get("usage") {
+                    self.merge_usage(usage);
+                }
+            }
+            "message_stop" => {
+                out.extend(self.finish());
+            }
+            "error" => {
+                let message = event
+                    .get("error")
+                    .and_then(|e| e.get("message"))
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("upstream error");
+                let err = serde_json::json!({"error": {"message": message, "type": "api_error"}});
+                out.push(format!("data: {}\n\n", err));
+            }
+            _ => {}
+        }
+        out
+    }
+
+    /// 流结束：补发 finish_reason、usage 与 [DONE]（幂等）
+    pub fn finish(&mut self) -> Vec<String> {
+        if self.finished {
+            return Vec::new();
+        }
+        self.finished = true;
+        let finish_reason = self.finish_reason.clone().unwrap_or_else(|| {
+            if self.tool_indices.is_empty() { "stop".to_string() } else { "tool_calls".to_string() }
+        });
+        let mut out = vec![self.chunk(serde_json::json!({}), Some(&finish_reason))];
+        if self.include_usage && self.has_usage {
+            let usage_chunk = serde_json::json!({
+                "id": self.id,
+                "object": "chat.completion.chunk",
+                "created": self.created,
+                "model": self.model,
+                "choices": [],
+                "usage": {
+                    "prompt_tokens": self.input_tokens,
+                    "completion_tokens": self.output_tokens,
+                    "total_tokens": self.input_tokens + self.output_tokens
+                }
+            });
+            out.push(format!("data: {}\n\n", usage_chunk));
+        }
+        out.push("data: [DONE]\n\n".to_string());
+        out
+    }
+}
+
+/// 从上游/内部错误响应体中提取错误信息，转换为 OpenAI 错误格式
+pub fn openai_error_from_body(status: StatusCode, body: &[u8]) -> Value {
+    let parsed: Option<Value> = serde_json::from_slice(body).ok();
+    let (err_type, message) = match parsed.as_ref().and_then(|v| v.get("error")) {
+        Some(Value::Object(obj)) => (
+            obj.get("type").and_then(|v| v.as_str()).unwrap_or("api_error").to_string(),
+            obj.get("message")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| Value::Object(obj.clone()).to_string()),
+        ),
+        Some(Value::String(s)) => ("api_error".to_string(), s.clone()),
+        _ => ("api_error".to_string(), String::from_utf8_lossy(body).to_string()),
+    };
+    serde_json::json!({
+        "error": {
+            "message": message,
+            "type": err_type,
+            "code": status.as_u16()
+        }
+    })
+}
+
+/// 将 Claude 格式的代理响应（JSON 或 SSE）包装为 OpenAI chat/completions 响应
+pub async fn claude_response_to_openai(resp: Response, model: String, include_usage: bool) -> Response {
+    let status = resp.status();
+    let is_sse = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.contains("text/event-stream"))
+        .unwrap_or(false);
+
+    if !status.is_success() {
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap_or_default();
+        return (status, Json(openai_error_from_body(status, &body))).into_response();
+    }
+
+    if !is_sse {
+        let body = match axum::body::to_bytes(resp.into_body(), usize::MAX).await {
+            Ok(b) => b,
+            Err(e) => {
+                return (
+                    StatusCode::BAD_GATEWAY,
+                    Json(serde_json::json!({"error": {"message": format!("读取响应失败: {}", e), "type": "api_error"}})),
+                )
+                    .into_response();
+            }
+        };
+        let message: Value = match serde_json::from_slice(&body) {
+            Ok(v) => v,
+            Err(e) => {
+                logger::log_warn(&format!("[ApiProxy] Claude→OpenAI 响应解析失败: {}", e));
+                return (
+                    StatusCode::BAD_GATEWAY,
+                    Json(serde_json::json!({"error": {"message": format!("解析响应失败: {}", e), "type": "api_error"}})),
+                )
+                    .into_response();
+            }
+        };
+        return (StatusCode::OK, Json(claude_message_to_openai(&message, &model))).into_response();
+    }
+
+    let mut claude_stream = resp.into_body().into_data_stream();
+    let openai_stream = async_stream::stream! {
+        use futures_util::StreamExt;
+        let mut converter = ClaudeToOpenAiStream::new(&model, include_usage);
+        let mut buffer = String::new();
+        while let Some(chunk) = claude_stream.next().await {
+            match chunk {
+                Ok(bytes) => {
+                    buffer.push_str(&String::from_utf8_lossy(&bytes));
+                    while let Some(pos) = buffer.find('\n') {
+                        let line = buffer[..pos].trim().to_string();
+                        buffer = buffer[pos + 1..].to_string();
+                        let data = match line.strip_prefix("data:") {
+                            Some(d) => d.trim(),
+                            None => continue,
+                        };
+                        if data.is_empty() || data == "[DONE]" {
+                            continue;
+                        }
+                        if let Ok(event) = serde_json::from_str::<Value>(data) {
+                            for out in converter.on_event(&event) {
+                                yield Ok::<bytes::Bytes, String>(bytes::Bytes::from(out));
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    logger::log_warn(&format!("[ApiProxy] Claude→OpenAI 流读取错误: {}", e));
+                    break;
+                }
+            }
+        }
+        for out in converter.finish() {
+            yield Ok(bytes::Bytes::from(out));
+        }
+    };
+
+    Response::builder()
+        .status(200)
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .header("Connection", "keep-alive")
+        .body(Body::from_stream(openai_stream))
+        .unwrap()
+        .into_response()
+}
+
+// ============================================================================
+// 请求转换：Claude Messages → OpenAI chat
+// ============================================================================
+
+/// 提取 Claude content（字符串或 block 数组）中的纯文本
+fn claude_content_text(content: &Value) -> String {
+    match content {
+        Value::String(s) => s.clone(),
+        Value::Array(blocks) => blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(|v| v.as_str()))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// 将 Claude thinking 预算映射为 reasoning_effort
+fn thinking_budget_to_effort(budget: u64) -> &'static str {
+    if budget >= 32768 {
+        "high"
+    } else if budget >= 16384 {
+        "medium"
+    } else {
+        "low"
+    }
+}
+
+/// 将 Claude Messages 请求体转换为 OpenAI chat/completions 请求体
+pub fn claude_to_openai_chat(body: &Value) -> Value {
+    let mut messages: Vec<Value> = Vec::new();
+
+    if let Some(system) = body.get("system") {
+        let text = claude_content_text(system);
+        if !text.is_empty() {
+            messages.push(serde_json::json!({"role": "system", "content": text}));
+        }
+    }
+
+    let empty = Vec::new();
+    for msg in body.get("messages").and_then(|v| v.as_array()).unwrap_or(&empty) {
+        let role = msg.get("role").and_then(|v| v.as_str()).unwrap_or("user");
+        let blocks = match msg.get("content") {
+            Some(Value::String(s)) => {
+                messages.push(serde_json::json!({"role": role, "content": s}));
+                continue;
+            }
+            Some(Value::Array(arr)) => arr,
+            _ => continue,
+        };
+
+        if role == "assistant" {
+            let mut text = String::new();
+            let mut tool_calls: Vec<Value> = Vec::new();
+            for block in blocks {
+                match block.get("type").and_then(|v| v.as_str()) {
+                    Some("text") => text.push_str(block.get("text").and_then(|v| v.as_str()).unwrap_or("")),
+                    Some("tool_use") => tool_calls.push(serde_json::json!({
+                        "id": block.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                        "type": "function",
+                        "function": {
+                            "name": block.get("name").and_then(|v| v.as_str()).unwrap_or(""),
+                            "arguments": block.get("input").cloned().unwrap_or_else(|| serde_json::json!({})).to_string()
+                        }
+                    })),
+                    _ => {}
+                }
+            }
+            let mut out = serde_json::json!({
+                "role": "assistant",
+                "content": if text.is_empty() && !tool_calls.is_empty() { Value::Null } else { Value::String(text) }
+            });
+            if !tool_calls.is_empty() {
+                out["tool_calls"] = Value::Array(tool_calls);
+            }
+            messages.push(out);
+            continue;
+        }
+
+        // user：tool_result 拆为独立的 tool 消息（须紧跟 assistant 的 tool_calls），其余内容合并为一条 user 消息
+        let mut parts: Vec<Value> = Vec::new();
+        for block in blocks {
+            match block.get("type").and_then(|v| v.as_str()) {
+                Some("tool_result") => {
+                    let mut text = claude_content_text(block.get("content").unwrap_or(&Value::Null));
+                    if block.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false) {
+                        text = format!("[error] {}", text);
+                    }
+                    messages.push(serde_json::json!({
+                        "role": "tool",
+                        "tool_call_id": block.get("tool_use_id").and_then(|v| v.as_str()).unwrap_or(""),
+                        "content": text
+                    }));
+                }
+                Some("text") => {
+                    if let Some(text) = block.get("text").and_then(|v| v.as_str()) {
+                        parts.push(serde_json::json!({"type": "text", "text": text}));
+                    }
+                }
+                Some("image") => {
+                    let source = block.get("source").unwrap_or(&Value::Null);
+                    let url = match source.get("type").and_then(|v| v.as_str()) {
+                        Some("base64") => format!(
+                            "data:{};base64,{}",
+                            source.get("media_type").and_then(|v| v.as_str()).unwrap_or("image/jpeg"),
+                            source.get("data").and_then(|v| v.as_str()).unwrap_or("")
+                        ),
+                        _ => source.get("url").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    };
+                    if !url.is_empty() {
+                        parts.push(serde_json::json!({"type": "image_url", "image_url": {"url": url}}));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !parts.is_empty() {
+            messages.push(serde_json::json!({"role": "user", "content": parts}));
+        }
+    }
+
+    let mut chat_body = serde_json::json!({
+        "model": body.get("model").cloned().unwrap_or(Value::Null),
+        "messages": messages,
+        "stream": body.get("stream").and_then(|v| v.as_bool()).unwrap_or(false),
+    });
+    if let Some(max_tokens) = body.get("max_tokens").and_then(|v| v.as_u64()) {
+        chat_body["max_tokens"] = Value::from(max_tokens);
+    }
+    for key in ["temperature", "top_p"] {
+        if let Some(v) = body.get(key).filter(|v| v.is_number()) {
+            chat_body[key] = v.clone();
+        }
+    }
+    if let Some(stop) = body.get("stop_sequences").and_then(|v| v.as_array()) {
+        chat_body["stop"] = Value::Array(stop.clone());
+    }
+    if let Some(tools) = body.get("tools").and_then(|v| v.as_array()) {
+        let openai_tools: Vec<Value> = tools
+            .iter()
+            .filter(|t| t.get("name").is_some())
+            .map(|t| {
+                serde_json::json!({
+                    "type": "function",
+                    "function": {
+                        "name": t.get("name").cloned().unwrap_or(Value::Null),
+                        "description": t.get("description").and_then(|v| v.as_str()).unwrap_or(""),
+                        "parameters": t.get("input_schema").cloned()
+                            .unwrap_or_else(|| serde_json::json!({"type": "object", "properties": {}}))
+                    }
+                })
+            })
+            .collect();
+        if !openai_tools.is_empty() {
+            chat_body["tools"] = Value::Array(openai_tools);
+        }
+    }
+    if let Some(choice) = body.get("tool_choice") {
+        match choice.get("type").and_then(|v| v.as_str()) {
+            Some("any") => chat_body["tool_choice"] = Value::String("required".to_string()),
+            Some("auto") => chat_body["tool_choice"] = Value::String("auto".to_string()),
+            Some("none") => chat_body["tool_choice"] = Value::String("none".to_string()),
+            Some("tool") => {
+                chat_body["tool_choice"] = serde_json::json!({
+                    "type": "function",
+                    "function": {"name": choice.get("name").cloned().unwrap_or(Value::Null)}
+                });
+            }
+            _ => {}
+        }
+    }
+    if let Some(thinking) = body.get("thinking") {
+        if thinking.get("type").and_then(|v| v.as_str()) == Some("enabled") {
+            let budget = thinking.get("budget_tokens").and_then(|v| v.as_u64()).unwrap_or(16384);
+            chat_body["reasoning_effort"] = Value::String(thinking_budget_to_effort(budget).to_string());
+        }
+    }
+
+    chat_body
+}
+
+// ============================================================================
+// 响应转换：OpenAI → Claude
+// ============================================================================
+
+/// OpenAI finish_reason → Claude stop_reason
+fn finish_reason_to_claude_stop_reason(finish_reason: &str) -> &'static str {
+    match finish_reason {
+        "length" => "max_tokens",
+        "tool_calls" | "function_call" => "tool_use",
+        _ => "end_turn",
+    }
+}
+
+/// OpenAI usage → Claude usage
+fn openai_usage_to_claude(usage: &Value) -> Value {
+    let prompt = usage.get("prompt_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+    let cached = usage
+        .pointer("/prompt_tokens_details/cached_tokens")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    serde_json::json!({
+        "input_tokens": prompt.saturating_sub(cached),
+        "output_tokens": usage.get("completion_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+        "cache_read_input_tokens": cached
+    })
+}
+
+fn new_message_id() -> String {
+    format!("msg_{}", uuid::Uuid::new_v4().simple())
+}
+
+/// 将完整的 OpenAI chat.completion 响应转换为 Claude message 响应
+pub fn openai_completion_to_claude(completion: &Value, model: &str) -> Value {
+    let choice = completion.pointer("/choices/0").unwrap_or(&Value::Null);
+    let message = choice.get("message").unwrap_or(&Value::Null);
+    let mut content: Vec<Value> = Vec::new();
+
+    if let Some(reasoning) = message.get("reasoning_content").and_then(|v| v.as_str()).filter(|s| !s.is_empty()) {
+        content.push(serde_json::json!({"type": "thinking", "thinking": reasoning, "signature": ""}));
+    }
+    if let Some(text) = message.get("content").and_then(|v| v.as_str()).filter(|s| !s.is_empty()) {
+        content.push(serde_json::json!({"type": "text", "text": text}));
+    }
+    if let Some(tool_calls) = message.get("tool_calls").and_then(|v| v.as_array()) {
+        for tc in tool_calls {
+            let arguments = tc.pointer("/function/arguments").and_then(|v| v.as_str()).unwrap_or("{}");
+            content.push(serde_json::json!({
+                "type": "tool_use",
+                "id": tc.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                "name": tc.pointer("/function/name").and_then(|v| v.as_str()).unwrap_or(""),
+                "input": serde_json::from_str::<Value>(arguments).unwrap_or_else(|_| serde_json::json!({}))
+            }));
+        }
+    }
+
+    let stop_reason = finish_reason_to_claude_stop_reason(
+        choice.get("finish_reason").and_then(|v| v.as_str()).unwrap_or("stop"),
+    );
+    serde_json::json!({
+        "id": new_message_id(),
+        "type": "message",
+        "role": "assistant",
+        "model": model,
+        "content": content,
+        "stop_reason": stop_reason,
+        "stop_sequence": null,
+        "usage": completion
+            .get("usage")
+            .map(openai_usage_to_claude)
+            .unwrap_or_else(|| serde_json::json!({"input_tokens": 0, "output_tokens": 0}))
+    })
+}
+
+/// 当前打开的 Claude 内容块
+enum OpenBlock {
+    Text,
+    Thinking,
+    /// OpenAI tool_calls index
+    Tool(u64),
+}
+
+/// OpenAI chat.completion.chunk → Claude SSE 的有状态转换器
+pub struct OpenAiToClaudeStream {
+    model: String,
+    started: bool,
+    next_index: u64,
+    open_block: Option<OpenBlock>,
+    stop_reason: Option<&'static str>,
+    usage: Option<Value>,
+    has_tool_use: bool,
+    finished: bool,
+}
+
+fn claude_sse(event_type: &str, data: Value) -> String {
+    format!("event: {}\ndata: {}\n\n", event_type, data)
+}
+
+impl OpenAiToClaudeStream {
+    pub fn new(model: &str) -> Self {
+        Self {
+            model: model.to_string(),
+            started: false,
+            next_index: 0,
+            open_block: None,
+            stop_reason: None,
+            usage: None,
+            has_tool_use: false,
+            finished: false,
+        }
+    }
+
+    fn ensure_started(&mut self, out: &mut Vec<String>) {
+        if self.started {
+            return;
+        }
+        self.started = true;
+        out.push(claude_sse(
+            "message_start",
+            serde_json::json!({
+                "type": "message_start",
+                "message": {
+                    "id": new_message_id(),
+                    "type": "message",
+                    "role": "assistant",
+                    "model": self.model,
+                    "content": [],
+                    "stop_reason": null,
+                    "stop_sequence": null,
+                    "usage": {"input_tokens": 0, "output_tokens": 0}
+                }
+            }),
+        ));
+    }
+
+    fn close_block(&mut self, out: &mut Vec<String>) {
+        if self.open_block.take().is_some() {
+            out.push(claude_sse(
+                "content_block_stop",
+                serde_json::json!({"type": "content_block_stop", "index": self.next_index - 1}),
+            ));
+        }
+    }
+
+    fn open_block(&mut self, block: OpenBlock, content_block: Value, out: &mut Vec<String>) {
+        self.close_block(out);
+        out.push(claude_sse(
+            "content_block_start",
+            serde_json::json!({
+                "type": "content_block_start",
+                "index": self.next_index,
+                "content_block": content_block
+            }),
+        ));
+        self.next_index += 1;
+        self.open_block = Some(block);
+    }
+
+    fn delta(&self, delta: Value) -> String {
+        claude_sse(
+            "content_block_delta",
+            serde_json::json!({"type": "content_block_delta", "index": self.next_index - 1, "delta": delta}),
+        )
+    }
+
+    /// 处理一条 OpenAI SSE data JSON，返回 Claude SSE 事件
+    pub fn on_chunk(&mut self, chunk: &Value) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.finished {
+            return out;
+        }
+        if let Some(err) = chunk.get("error") {
+            let message = err.get("message").and_then(|v| v.as_str()).map(|s| s.to_string())
+                .unwrap_or_else(|| err.to_string());
+            out.push(claude_sse(
+                "error",
+                serde_json::json!({"type": "error", "error": {"type": "api_error", "message": message}}),
+            ));
+            return out;
+        }
+        self.ensure_started(&mut out);
+
+        if let Some(usage) = chunk.get("usage").filter(|u| u.is_object()) {
+            self.usage = Some(openai_usage_to_claude(usage));
+        }
+        let choice = match chunk.pointer("/choices/0") {
+            Some(c) => c,
+            None => return out,
+        };
+        let delta = choice.get("delta").unwrap_or(&Value::Null);
+
+        if let Some(reasoning) = delta.get("reasoning_content").and_then(|v| v.as_str()).filter(|s| !s.is_empty()) {
+            if !matches!(self.open_block, Some(OpenBlock::Thinking)) {
+                self.open_block(
+                    OpenBlock::Thinking,
+                    serde_json::json!({"type": "thinking", "thinking": "", "signature": ""}),
+                    &mut out,
+                );
+            }
+            out.push(self.delta(serde_json::json!({"type": "thinking_delta", "thinking": reasoning})));
+        }
+
+        if let Some(text) = delta.get("content").and_then(|v| v.as_str()).filter(|s| !s.is_empty()) {
+            if !matches!(self.open_block, Some(OpenBlock::Text)) {
+                self.open_block(OpenBlock::Text, serde_json::json!({"type": "text", "text": ""}), &mut out);
+            }
+            out.push(self.delta(serde_json::json!({"type": "text_delta", "text": text})));
+        }
+
+        if let Some(tool_calls) = delta.get("tool_calls").and_then(|v| v.as_array()) {
+            for tc in tool_calls {
+                let tool_index = tc.get("index").and_then(|v| v.as_u64()).unwrap_or(0);
+                let is_current = matches!(self.open_block, Some(OpenBlock::Tool(i)) if i == tool_index);
+                if !is_current {
+                    self.has_tool_use = true;
+                    self.open_block(
+                        OpenBlock::Tool(tool_index),
+                        serde_json::json!({
+                            "type": "tool_use",
+                            "id": tc.get("id").and_then(|v| v.as_str()).unwrap_or(""),
+                            "name": tc.pointer("/function/name").and_then(|v| v.as_str()).unwrap_or(""),
+                            "input": {}
+                        }),
+                        &mut out,
+                    );
+                }
+                if let Some(args) = tc.pointer("/function/arguments").and_then(|v| v.as_str()).filter(|s| !s.is_empty()) {
+                    out.push(self.delta(serde_json::json!({"type": "input_json_delta", "partial_json": args})));
+                }
+            }
+        }
+
+        if let Some(reason) = choice.get("finish_reason").and_then(|v| v.as_str()) {
+            self.stop_reason = Some(finish_reason_to_claude_stop_reason(reason));
+        }
+        out
+    }
+
+    /// 流结束：关闭内容块，发送 message_delta（stop_reason + usage）与 message_stop（幂等）
+    pub fn finish(&mut self) -> Vec<String> {
+        let mut out = Vec::new();
+        if self.finished {
+            return out;
+        }
+        self.ensure_started(&mut out);
+        self.finished = true;
+        self.close_block(&mut out);
+        let stop_reason = self
+            .stop_reason
+            .unwrap_or(if self.has_tool_use { "tool_use" } else { "end_turn" });
+        let usage = self
+            .usage
+            .clone()
+            .unwrap_or_else(|| serde_json::json!({"output_tokens": 0}));
+        out.push(claude_sse(
+            "message_delta",
+            serde_json::json!({
+                "type": "message_delta",
+                "delta": {"stop_reason": stop_reason, "stop_sequence": null},
+                "usage": usage
+            }),
+        ));
+        out.push(claude_sse("message_stop", serde_json::json!({"type": "message_stop"})));
+        out
+    }
+}
+
+/// 将 OpenAI 格式的代理响应（JSON 或 SSE）包装为 Claude Messages 响应
+pub async fn openai_response_to_claude(resp: Response, model: String) -> Response {
+    let status = resp.status();
+    let is_sse = resp
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.contains("text/event-stream"))
+        .unwrap_or(false);
+
+    if !status.is_success() {
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap_or_default();
+        let err = openai_error_from_body(status, &body);
+        return (
+            status,
+            Json(serde_json::json!({
+                "type": "error",
+                "error": {
+                    "type": err.pointer("/error/type").cloned().unwrap_or(Value::Null),
+                    "message": err.pointer("/error/message").cloned().unwrap_or(Value::Null)
+                }
+            })),
+        )
+            .into_response();
+    }
+
+    if !is_sse {
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap_or_default();
+        return match serde_json::from_slice::<Value>(&body) {
+            Ok(completion) => (StatusCode::OK, Json(openai_completion_to_claude(&completion, &model))).into_response(),
+            Err(e) => (
+                StatusCode::BAD_GATEWAY,
+                Json(serde_json::json!({
+                    "type": "error",
+                    "error": {"type": "api_error", "message": format!("解析响应失败: {}", e)}
+                })),
+            )
+                .into_response(),
+        };
+    }
+
+    let mut openai_stream = resp.into_body().into_data_stream();
+    let claude_stream = async_stream::stream! {
+        use futures_util::StreamExt;
+        let mut converter = OpenAiToClaudeStream::new(&model);
+        let mut buffer = String::new();
+        while let Some(chunk) = openai_stream.next().await {
+            match chunk {
+                Ok(bytes) => {
+                    buffer.push_str(&String::from_utf8_lossy(&bytes));
+                    while let Some(pos) = buffer.find('\n') {
+                        let line = buffer[..pos].trim().to_string();
+                        buffer = buffer[pos + 1..].to_string();
+                        let data = match line.strip_prefix("data:") {
+                            Some(d) => d.trim(),
+                            None => continue,
+                        };
+                        if data.is_empty() || data == "[DONE]" {
+                            continue;
+                        }
+                        if let Ok(chunk) = serde_json::from_str::<Value>(data) {
+                            for out in converter.on_chunk(&chunk) {
+                                yield Ok::<bytes::Bytes, String>(bytes::Bytes::from(out));
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    logger::log_warn(&format!("[ApiProxy] OpenAI→Claude 流读取错误: {}", e));
+                    break;
+                }
+            }
+        }
+        for out in converter.finish() {
+            yield Ok(bytes::Bytes::from(out));
+        }
+    };
+
+    Response::builder()
+        .status(200)
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .header("Connection", "keep-alive")
+        .body(Body::from_stream(claude_stream))
+        .unwrap()
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_openai_chat_to_claude_tools_round_trip() {
+        let body = serde_json::json!({
+            "model": "claude-sonnet-4-5",
+            "stream": true,
+            "messages": [
+                {"role": "system", "content": "be brief"},
+                {"role": "user", "content": "weather?"},
+                {"role": "assistant", "content": null, "tool_calls": [{
+                    "id": "call_1", "type": "function",
+                    "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+                }]},
+                {"role": "tool", "tool_call_id": "call_1", "content": "sunny"}
+            ],
+            "tools": [{"type": "function", "function": {
+                "name": "get_weather", "description": "d",
+                "parameters": {"type": "object", "properties": {"city": {"type": "string"}}}
+            }}]
+        });
+        let claude = openai_chat_to_claude(&body);
+        assert_eq!(claude["system"], "be brief");
+        assert_eq!(claude["stream"], true);
+        let msgs = claude["messages"].as_array().unwrap();
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(msgs[1]["content"][0]["type"], "tool_use");
+        assert_eq!(msgs[1]["content"][0]["input"]["city"], "Paris");
+        assert_eq!(msgs[2]["content"][0]["type"], "tool_result");
+        assert_eq!(msgs[2]["content"][0]["tool_use_id"], "call_1");
+        assert_eq!(claude["tools"][0]["input_schema"]["type"], "object");
+        assert!(claude.get("tool_choice").is_none());
+
+        let mut none_body = body.clone();
+        none_body["tool_choice"] = serde_json::json!("none");
+        let claude = openai_chat_to_claude(&none_body);
+        assert_eq!(claude["tool_choice"]["type"], "none");
+    }
+
+    #[test]
+    fn test_openai_image_url_to_claude_base64() {
+        let body = serde_json::json!({
+            "messages": [{"role": "user", "content": [
+                {"type": "text", "text": "what is this"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}}
+            ]}]
+        });
+        let claude = openai_chat_to_claude(&body);
+        let block = &claude["messages"][0]["content"][1];
+        assert_eq!(block["source"]["media_type"], "image/png");
+        assert_eq!(block["source"]["data"], "AAAA");
+    }
+
+    #[test]
+    fn test_claude_stream_tool_use_to_openai_chunks() {
+        let mut conv = ClaudeToOpenAiStream::new("m", true);
+        let events = [
+            serde_json::json!({"type": "message_start", "message": {"usage": {"input_tokens": 7}}}),
+            serde_json::json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "hi"}}),
+            serde_json::json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "t1", "name": "f", "input": {}}}),
+            serde_json::json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"a\":1}"}}),
+            serde_json::json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 3}}),
+            serde_json::json!({"type": "message_stop"}),
+        ];
+        let out: Vec<String> = events.iter().flat_map(|e| conv.on_event(e)).collect();
+        let joined = out.join("");
+        assert!(joined.contains("\"content\":\"hi\""));
+        assert!(joined.contains("\"arguments\":\"{\\\"a\\\":1}\""));
+        assert!(joined.contains("\"finish_reason\":\"tool_calls\""));
+        assert!(joined.contains("\"total_tokens\":10"));
+        assert!(joined.ends_with("data: [DONE]\n\n"));
+        assert!(conv.finish().is_empty());
+    }
+
+    #[test]
+    fn test_claude_message_to_openai_non_stream() {
+        let msg = serde_json::json!({
+            "content": [
+                {"type": "thinking", "thinking": "hmm"},
+                {"type": "text", "text": "ok"}
+            ],
+            "stop_reason": "max_tokens",
+            "usage": {"input_tokens": 2, "output_tokens": 5}
+        });
+        let resp = claude_message_to_openai(&msg, "m");
+        assert_eq!(resp["choices"][0]["message"]["content"], "ok");
+        assert_eq!(resp["choices"][0]["message"]["reasoning_content"], "hmm");
+        assert_eq!(resp["choices"][0]["finish_reason"], "length");
+        assert_eq!(resp["usage"]["total_tokens"], 7);
+    }
+
+    #[test]
+    fn test_claude_to_openai_chat_splits_tool_results() {
+        let body = serde_json::json!({
+            "model": "gpt-5.1-codex",
+            "system": [{"type": "text", "text": "sys"}],
+            "max_tokens": 100,
+            "messages": [
+                {"role": "user", "content": "list files"},
+                {"role": "assistant", "content": [
+                    {"type": "text", "text": "ok"},
+                    {"type": "tool_use", "id": "tu_1", "name": "ls", "input": {"path": "."}}
+                ]},
+                {"role": "user", "content": [
+                    {"type": "tool_result", "tool_use_id": "tu_1", "content": [{"type": "text", "text": "a.rs"}]},
+                    {"type": "text", "text": "next?"}
+                ]}
+            ],
+            "tools": [{"name": "ls", "description": "list", "input_schema": {"type": "object"}}]
+        });
+        let chat = claude_to_openai_chat(&body);
+        let msgs = chat["messages"].as_array().unwrap();
+        assert_eq!(msgs[0]["role"], "system");
+        assert_eq!(msgs[2]["tool_calls"][0]["function"]["arguments"], "{\"path\":\".\"}");
+        assert_eq!(msgs[3]["role"], "tool");
+        assert_eq!(msgs[3]["content"], "a.rs");
+        assert_eq!(msgs[4]["role"], "user");
+        assert_eq!(chat["tools"][0]["function"]["name"], "ls");
+    }
+
+    #[test]
+    fn test_openai_stream_to_claude_events() {
+        let mut conv = OpenAiToClaudeStream::new("m");
+        let chunks = [
+            serde_json::json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": "hi"}}]}),
+            serde_json::json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "id": "c1", "function": {"name": "f", "arguments": "{\"x\""}}]}}]}),
+            serde_json::json!({"choices": [{"index": 0, "delta": {"tool_calls": [{"index": 0, "function": {"arguments": ":1}"}}]}}]}),
+            serde_json::json!({"choices": [{"index": 0, "delta": {}, "finish_reason": "tool_calls"}]}),
+            serde_json::json!({"choices": [], "usage": {"prompt_tokens": 9, "completion_tokens": 4}}),
+        ];
+        let mut out: Vec<String> = chunks.iter().flat_map(|c| conv.on_chunk(c)).collect();
+        out.extend(conv.finish());
+        let joined = out.join("");
+        assert!(out[0].starts_with("event: message_start"));
+        assert_eq!(joined.matches("event: content_block_start").count(), 2);
+        assert_eq!(joined.matches("event: content_block_stop").count(), 2);
+        assert!(joined.contains("\"partial_json\":\":1}\""));
+        assert!(joined.contains("\"stop_reason\":\"tool_use\""));
+        assert!(joined.contains("\"input_tokens\":9"));
+        assert!(out.last().unwrap().starts_with("event: message_stop"));
+    }
+}
@@ -276,6 +276,7 @@ async fn run_provider_flow(
         }
         "codex" => {
             let accounts: Vec<_> = codex_account::list_accounts()
+                .unwrap_or_default()
                 .into_iter()
                 .filter(|a| !a.tokens.access_token.is_empty())
                 .collect();
@@ -363,6 +364,7 @@ async fn run_provider_flow(
         }
         "kiro" => {
             let accounts: Vec<_> = kiro_account::list_accounts()
+                .unwrap_or_default()
                 .into_iter()
                 .filter(|a| !a.access_token.is_empty())
                 .collect();
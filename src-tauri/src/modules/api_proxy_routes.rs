@@ -0,0 +1,159 @@
+//! API 反向代理 — 模型路由表
+//! 统一入口 /v1/* 根据请求中的 model 选择 Provider：模型名或别名（如 sonnet、gpt-5-codex）映射到
+//! 有序的 Provider + 上游模型 ID 列表，首选池没有可用账号时依次回退
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+
+use super::config;
+
+const MODEL_ROUTES_FILE: &str = "api_proxy_routes.json";
+
+static ROUTES_LOCK: std::sync::LazyLock<Mutex<()>> = std::sync::LazyLock::new(|| Mutex::new(()));
+/// 运行时缓存，避免每个请求读取文件
+static ROUTES_CACHE: std::sync::LazyLock<RwLock<Option<Vec<ModelRoute>>>> =
+    std::sync::LazyLock::new(|| RwLock::new(None));
+
+/// 路由目标：Provider + 上游模型 ID
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteTarget {
+    pub provider: String,
+    /// 上游模型 ID（为空则沿用请求中的模型名）
+    #[serde(default)]
+    pub model: String,
+}
+
+/// 一条路由：别名 → 按顺序尝试的目标
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelRoute {
+    /// 模型名或别名，不区分大小写；以 * 结尾表示前缀匹配（如 claude-*）
+    pub alias: String,
+    pub targets: Vec<RouteTarget>,
+}
+
+fn routes_path() -> Result<PathBuf, String> {
+    Ok(config::get_data_dir()?.join(MODEL_ROUTES_FILE))
+}
+
+fn load_routes_from_disk() -> Result<Vec<ModelRoute>, String> {
+    let path = routes_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| format!("读取模型路由表失败: {}", e))?;
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&content).map_err(|e| format!("解析模型路由表失败: {}", e))
+}
+
+/// 加载模型路由表
+pub fn list_model_routes() -> Result<Vec<ModelRoute>, String> {
+    if let Some(cached) = ROUTES_CACHE.read().ok().and_then(|c| c.clone()) {
+        return Ok(cached);
+    }
+    let routes = load_routes_from_disk()?;
+    if let Ok(mut cache) = ROUTES_CACHE.write() {
+        *cache = Some(routes.clone());
+    }
+    Ok(routes)
+}
+
+/// 保存模型路由表（整表替换）
+pub fn save_model_routes(mut routes: Vec<ModelRoute>) -> Result<(), String> {
+    for route in routes.iter_mut() {
+        route.alias = route.alias.trim().to_string();
+        if route.alias.is_empty() {
+            return Err("路由别名不能为空".to_string());
+        }
+        route.targets.retain(|t| !t.provider.trim().is_empty());
+        if route.targets.is_empty() {
+            return Err(format!("路由 '{}' 至少需要一个目标 Provider", route.alias));
+        }
+    }
+
+    let _lock = ROUTES_LOCK.lock().map_err(|_| "获取模型路由表锁失败")?;
+    let data_dir = config::get_data_dir()?;
+    if !data_dir.exists() {
+        fs::create_dir_all(&data_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let path = data_dir.join(MODEL_ROUTES_FILE);
+    let temp_path = data_dir.join(format!("{}.tmp", MODEL_ROUTES_FILE));
+    let content =
+        serde_json::to_string_pretty(&routes).map_err(|e| format!("序列化模型路由表失败: {}", e))?;
+    fs::write(&temp_path, content).map_err(|e| format!("写入临时路由文件失败: {}", e))?;
+    fs::rename(temp_path, path).map_err(|e| format!("替换路由文件失败: {}", e))?;
+    if let Ok(mut cache) = ROUTES_CACHE.write() {
+        *cache = Some(routes);
+    }
+    Ok(())
+}
+
+/// 未配置路由时按模型名前缀推断 Provider（保持请求中的模型名）
+fn default_targets(model: &str) -> Vec<RouteTarget> {
+    let lower = model.to_lowercase();
+    let providers: &[&str] = if lower.starts_with("claude") {
+        &["kiro", "antigravity"]
+    } else if lower.starts_with("gemini") {
+        &["antigravity"]
+    } else if lower.starts_with("gpt")
+        || lower.contains("codex")
+        || ["o1", "o3", "o4"].iter().any(|p| lower.starts_with(p))
+    {
+        &["codex"]
+    } else {
+        &[]
+    };
+    providers
+        .iter()
+        .map(|p| RouteTarget { provider: p.to_string(), model: String::new() })
+        .collect()
+}
+
+/// 解析模型对应的有序路由目标：精确别名优先，其次最长前缀通配，最后按模型名推断
+pub fn resolve_targets(routes: &[ModelRoute], model: &str) -> Vec<RouteTarget> {
+    let lower = model.to_lowercase();
+    if let Some(route) = routes.iter().find(|r| r.alias.to_lowercase() == lower) {
+        return route.targets.clone();
+    }
+    let wildcard = routes
+        .iter()
+        .filter_map(|r| {
+            let prefix = r.alias.strip_suffix('*')?.to_lowercase();
+            lower.starts_with(&prefix).then_some((prefix.len(), r))
+        })
+        .max_by_key(|(len, _)| *len);
+    if let Some((_, route)) = wildcard {
+        return route.targets.clone();
+    }
+    default_targets(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(provider: &str, model: &str) -> RouteTarget {
+        RouteTarget { provider: provider.to_string(), model: model.to_string() }
+    }
+
+    #[test]
+    fn test_resolve_targets_alias_wildcard_and_defaults() {
+        let routes = vec![
+            ModelRoute {
+                alias: "sonnet".to_string(),
+                targets: vec![target("kiro", "claude-sonnet-4-5"), target("antigravity", "claude-sonnet-4-5")],
+            },
+            ModelRoute { alias: "claude-*".to_string(), targets: vec![target("antigravity", "")] },
+            ModelRoute { alias: "claude-opus-*".to_string(), targets: vec![target("kiro", "")] },
+        ];
+        assert_eq!(resolve_targets(&routes, "Sonnet")[0], target("kiro", "claude-sonnet-4-5"));
+        assert_eq!(resolve_targets(&routes, "claude-opus-4-1"), vec![target("kiro", "")]);
+        assert_eq!(resolve_targets(&routes, "claude-haiku-4-5"), vec![target("antigravity", "")]);
+        assert_eq!(resolve_targets(&routes, "gpt-5-codex"), vec![target("codex", "")]);
+        assert_eq!(resolve_targets(&[], "claude-sonnet-4-5").len(), 2);
+        assert!(resolve_targets(&routes, "llama-3").is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+//! API 反向代理 — 会话粘滞
+//! 同一会话固定使用同一账号，避免中途换号导致提示缓存失效、thoughtSignature 等思考签名校验失败；
+//! 会话由请求头 X-Session-Id 标识，缺省时取 system 与首条用户消息（含之前的系统消息）的哈希。
+//! 绑定的账号冷却（额度不足或鉴权失败）或不再可用时才改绑其它账号
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// 客户端显式指定会话的请求头
+pub const SESSION_HEADER: &str = "x-session-id";
+
+/// 绑定数超过该值时清理过期条目
+const PRUNE_THRESHOLD: usize = 4096;
+
+/// 会话标识：优先使用请求头，否则对会话开头做哈希；无法识别会话时返回 None
+pub fn session_key(header: Option<&str>, body: &Value) -> Option<String> {
+    if let Some(id) = header.map(str::trim).filter(|s| !s.is_empty()) {
+        return Some(format!("h:{}", id));
+    }
+
+    // Anthropic/OpenAI: messages；Gemini: contents；Responses API: input
+    // 会话后续轮次的开头不变：取到第一条用户消息为止
+    let head: Vec<&Value> = ["messages", "contents", "input"]
+        .iter()
+        .find_map(|field| body.get(*field).and_then(|v| v.as_array()))
+        .map(|items| {
+            let end = items
+                .iter()
+                .position(|item| item.get("role").and_then(|r| r.as_str()) == Some("user"))
+                .unwrap_or(0);
+            items.iter().take(end + 1).collect()
+        })
+        .unwrap_or_default();
+    if head.is_empty() {
+        return None;
+    }
+    let system = body
+        .get("system")
+        .or_else(|| body.get("systemInstruction"))
+        .or_else(|| body.get("instructions"));
+    let seed = serde_json::json!({ "system": system, "head": head });
+    let digest = Sha256::digest(seed.to_string().as_bytes());
+    Some(format!("m:{}", digest.iter().take(8).map(|b| format!("{:02x}", b)).collect::<String>()))
+}
+
+struct Binding {
+    account_id: String,
+    expires_at: i64,
+}
+
+/// 会话 → 账号绑定表，键为 (provider, 调用方 Key ID, 会话标识)
+/// 每次使用都会顺延过期时间，闲置超过 TTL 的会话重新按策略选号
+#[derive(Default)]
+pub struct SessionAffinity {
+    bindings: Mutex<HashMap<(String, String, String), Binding>>,
+}
+
+impl SessionAffinity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 会话当前绑定的账号 ID
+    pub fn pinned(&self, provider: &str, key_id: &str, session: &str, now: i64) -> Option<String> {
+        let bindings = self.bindings.lock().ok()?;
+        bindings
+            .get(&(provider.to_string(), key_id.to_string(), session.to_string()))
+            .filter(|b| b.expires_at > now)
+            .map(|b| b.account_id.clone())
+    }
+
+    /// 绑定（或续期）会话到账号
+    pub fn pin(&self, provider: &str, key_id: &str, session: &str, account_id: &str, now: i64, ttl_secs: u64) {
+        let Ok(mut bindings) = self.bindings.lock() else {
+            return;
+        };
+        if bindings.len() >= PRUNE_THRESHOLD {
+            bindings.retain(|_, b| b.expires_at > now);
+        }
+        bindings.insert(
+            (provider.to_string(), key_id.to_string(), session.to_string()),
+            Binding {
+                account_id: account_id.to_string(),
+                expires_at: now + ttl_secs.min(i64::MAX as u64) as i64,
+            },
+        );
+    }
+
+    /// 各 Provider 未过期的会话数
+    pub fn active_counts(&self, now: i64) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        if let Ok(bindings) = self.bindings.lock() {
+            for ((provider, _, _), b) in bindings.iter() {
+                if b.expires_at > now {
+                    *counts.entry(provider.clone()).or_default() += 1;
+                }
+            }
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_key_and_pinning() {
+        let first = serde_json::json!({
+            "messages": [{"role": "system", "content": "be brief"}, {"role": "user", "content": "hi"}],
+        });
+        let mut later = first.clone();
+        later["messages"].as_array_mut().unwrap().extend([
+            serde_json::json!({"role": "assistant", "content": "hello"}),
+            serde_json::json!({"role": "user", "content": "more"}),
+        ]);
+        let key = session_key(None, &first).unwrap();
+        assert_eq!(session_key(None, &later).as_deref(), Some(key.as_str()));
+        let other = serde_json::json!({"messages": [{"role": "user", "content": "hello there"}]});
+        assert_ne!(session_key(None, &other).as_deref(), Some(key.as_str()));
+        assert_eq!(session_key(Some(" abc "), &first).as_deref(), Some("h:abc"));
+        assert_eq!(session_key(None, &serde_json::json!({"model": "x"})), None);
+
+        let affinity = SessionAffinity::new();
+        affinity.pin("codex", "", &key, "acc-1", 100, 60);
+        assert_eq!(affinity.pinned("codex", "", &key, 159).as_deref(), Some("acc-1"));
+        assert_eq!(affinity.pinned("codex", "other-key", &key, 159), None);
+        assert_eq!(affinity.pinned("codex", "", &key, 160), None);
+        assert_eq!(affinity.active_counts(120)["codex"], 1);
+    }
+}
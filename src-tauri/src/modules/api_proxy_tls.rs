@@ -0,0 +1,242 @@
+//! API 反向代理 — HTTPS 监听
+//! 开启后代理以 TLS 提供服务：默认生成并持久化自签名证书，也可指定 PEM 证书与私钥文件；
+//! 证书 SHA-256 指纹通过 ProxyStatus 暴露，供局域网客户端固定（pinning）
+
+use rcgen::generate_simple_self_signed;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use super::config;
+use super::logger;
+
+const TLS_SETTINGS_FILE: &str = "api_proxy_tls.json";
+/// 自签名证书目录（位于数据目录下）
+const SELF_SIGNED_DIR: &str = "api_proxy_tls";
+const SELF_SIGNED_CERT_FILE: &str = "cert.pem";
+const SELF_SIGNED_KEY_FILE: &str = "key.pem";
+
+/// 单个连接的 TLS 握手超时
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// HTTPS 设置
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsSettings {
+    /// 是否以 HTTPS 提供服务（默认关闭）
+    #[serde(default)]
+    pub enabled: bool,
+    /// PEM 证书链路径；与 key_path 同时为空时使用自签名证书
+    #[serde(default)]
+    pub cert_path: String,
+    /// PEM 私钥路径
+    #[serde(default)]
+    pub key_path: String,
+}
+
+/// 已加载的证书
+pub struct TlsMaterial {
+    pub acceptor: TlsAcceptor,
+    /// 叶子证书 SHA-256 指纹（AA:BB:... 格式）
+    pub fingerprint: String,
+}
+
+fn settings_path() -> Result<PathBuf, String> {
+    Ok(config::get_data_dir()?.join(TLS_SETTINGS_FILE))
+}
+
+/// 加载 HTTPS 设置
+pub fn load_tls_settings() -> TlsSettings {
+    settings_path()
+        .ok()
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// 保存 HTTPS 设置（运行中的代理需重新绑定监听后生效）
+pub fn save_tls_settings(settings: &TlsSettings) -> Result<(), String> {
+    let cert_path = settings.cert_path.trim();
+    let key_path = settings.key_path.trim();
+    if cert_path.is_empty() != key_path.is_empty() {
+        return Err("证书与私钥路径需同时填写".to_string());
+    }
+    let data_dir = config::get_data_dir()?;
+    if !data_dir.exists() {
+        fs::create_dir_all(&data_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let content =
+        serde_json::to_string_pretty(settings).map_err(|e| format!("序列化 HTTPS 设置失败: {}", e))?;
+    fs::write(data_dir.join(TLS_SETTINGS_FILE), content).map_err(|e| format!("写入 HTTPS 设置失败: {}", e))
+}
+
+/// 证书 SHA-256 指纹
+pub fn sha256_fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// 读取持久化的自签名证书，不存在时生成（SAN: localhost / 127.0.0.1 / ::1）
+fn load_or_generate_self_signed() -> Result<(String, String), String> {
+    let dir = config::get_data_dir()?.join(SELF_SIGNED_DIR);
+    let cert_path = dir.join(SELF_SIGNED_CERT_FILE);
+    let key_path = dir.join(SELF_SIGNED_KEY_FILE);
+    if let (Ok(cert), Ok(key)) = (fs::read_to_string(&cert_path), fs::read_to_string(&key_path)) {
+        return Ok((cert, key));
+    }
+
+    let certified = generate_simple_self_signed(vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ])
+    .map_err(|e| format!("生成自签名证书失败: {}", e))?;
+    let cert_pem = certified.cert.pem();
+    let key_pem = certified.key_pair.serialize_pem();
+
+    fs::create_dir_all(&dir).map_err(|e| format!("创建证书目录失败: {}", e))?;
+    fs::write(&cert_path, &cert_pem).map_err(|e| format!("写入证书失败: {}", e))?;
+    // 私钥仅当前用户可读
+    super::credential_vault::write_private_file(&key_path, key_pem.as_bytes())
+        .map_err(|e| format!("写入私钥失败: {}", e))?;
+    logger::log_info(&format!("[ApiProxy] 已生成自签名证书: {}", cert_path.display()));
+    Ok((cert_pem, key_pem))
+}
+
+/// 由 PEM 文本构建 TLS 配置
+fn build_material(cert_pem: &str, key_pem: &str) -> Result<TlsMaterial, String> {
+    let certs: Vec<CertificateDer<'static>> = CertificateDer::pem_slice_iter(cert_pem.as_bytes())
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("解析证书失败: {}", e))?;
+    let leaf = certs.first().ok_or("证书文件中没有证书")?;
+    let fingerprint = sha256_fingerprint(leaf.as_ref());
+    let key = PrivateKeyDer::from_pem_slice(key_pem.as_bytes()).map_err(|e| format!("解析私钥失败: {}", e))?;
+
+    let mut server_config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("创建 TLS 配置失败: {}", e))?;
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(TlsMaterial {
+        acceptor: TlsAcceptor::from(Arc::new(server_config)),
+        fingerprint,
+    })
+}
+
+/// 按设置准备证书；未开启 HTTPS 时返回 None
+pub fn prepare_tls(settings: &TlsSettings) -> Result<Option<TlsMaterial>, String> {
+    if !settings.enabled {
+        return Ok(None);
+    }
+    let cert_path = settings.cert_path.trim();
+    let key_path = settings.key_path.trim();
+    let (cert_pem, key_pem) = if cert_path.is_empty() && key_path.is_empty() {
+        load_or_generate_self_signed()?
+    } else {
+        (
+            fs::read_to_string(cert_path).map_err(|e| format!("读取证书 {} 失败: {}", cert_path, e))?,
+            fs::read_to_string(key_path).map_err(|e| format!("读取私钥 {} 失败: {}", key_path, e))?,
+        )
+    };
+    build_material(&cert_pem, &key_pem).map(Some)
+}
+
+/// 删除持久化的自签名证书，下次以 HTTPS 启动时重新生成（指纹随之变化）
+pub fn reset_self_signed_cert() -> Result<(), String> {
+    let dir = config::get_data_dir()?.join(SELF_SIGNED_DIR);
+    for name in [SELF_SIGNED_CERT_FILE, SELF_SIGNED_KEY_FILE] {
+        let path = dir.join(name);
+        if path.exists() {
+            fs::remove_file(&path).map_err(|e| format!("删除 {} 失败: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+/// axum 使用的 TLS 监听：握手在后台并发进行，慢连接不会阻塞其它连接的接入
+pub struct TlsListener {
+    tcp: TcpListener,
+    acceptor: TlsAcceptor,
+    handshakes: JoinSet<Option<(TlsStream<TcpStream>, SocketAddr)>>,
+}
+
+impl TlsListener {
+    pub fn new(tcp: TcpListener, acceptor: TlsAcceptor) -> Self {
+        Self {
+            tcp,
+            acceptor,
+            handshakes: JoinSet::new(),
+        }
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            tokio::select! {
+                accepted = self.tcp.accept() => match accepted {
+                    Ok((stream, addr)) => {
+                        let acceptor = self.acceptor.clone();
+                        self.handshakes.spawn(async move {
+                            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                                Ok(Ok(tls)) => Some((tls, addr)),
+                                Ok(Err(e)) => {
+                                    logger::log_warn(&format!("[ApiProxy] TLS 握手失败 ({}): {}", addr, e));
+                                    None
+                                }
+                                Err(_) => {
+                                    logger::log_warn(&format!("[ApiProxy] TLS 握手超时 ({})", addr));
+                                    None
+                                }
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        logger::log_warn(&format!("[ApiProxy] accept 失败: {}", e));
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                },
+                Some(done) = self.handshakes.join_next(), if !self.handshakes.is_empty() => {
+                    if let Ok(Some(conn)) = done {
+                        return conn;
+                    }
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        self.tcp.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_material_from_generated_pem() {
+        let certified = generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let material = build_material(&certified.cert.pem(), &certified.key_pair.serialize_pem()).unwrap();
+        assert_eq!(material.fingerprint, sha256_fingerprint(certified.cert.der()));
+        assert_eq!(material.fingerprint.len(), 32 * 3 - 1);
+        assert!(build_material("not a cert", &certified.key_pair.serialize_pem()).is_err());
+    }
+}
@@ -0,0 +1,326 @@
+//! API 反向代理 — Token 用量账本
+//! 从各上游响应中提取 token 用量（Google usageMetadata、Codex response.completed、Kiro 计量事件），
+//! 按 日期 × Provider × 账号 × 模型 × API Key 聚合；账本常驻内存，有改动时延迟 FLUSH_DELAY 合并写盘
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::config;
+use super::logger;
+
+const USAGE_LEDGER_FILE: &str = "api_proxy_usage.json";
+/// 账本保留天数
+const MAX_LEDGER_DAYS: i64 = 90;
+
+/// 记录用量后延迟写盘的时长，期间的请求合并为一次写入
+const FLUSH_DELAY: Duration = Duration::from_secs(5);
+
+/// 内存中的账本
+#[derive(Default)]
+struct LedgerState {
+    /// None = 尚未从磁盘加载
+    entries: Option<Vec<UsageLedgerEntry>>,
+    /// 有未写盘的改动
+    dirty: bool,
+    flush_scheduled: bool,
+}
+
+static LEDGER: std::sync::LazyLock<Mutex<LedgerState>> =
+    std::sync::LazyLock::new(|| Mutex::new(LedgerState::default()));
+
+/// 单次请求的 token 用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    /// Kiro 计量事件上报的 credit 消耗
+    pub credits: f64,
+}
+
+impl TokenUsage {
+    /// 从 Claude 格式 usage 解析
+    pub fn from_claude(usage: &Value) -> Self {
+        let get = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        Self {
+            input_tokens: get("input_tokens"),
+            output_tokens: get("output_tokens"),
+            cache_read_tokens: get("cache_read_input_tokens"),
+            cache_creation_tokens: get("cache_creation_input_tokens"),
+            credits: 0.0,
+        }
+    }
+
+    /// 从 OpenAI 格式 usage 解析（prompt_tokens 含缓存命中部分）
+    pub fn from_openai(usage: &Value) -> Self {
+        let prompt = usage.get("prompt_tokens").and_then(|v| v.as_u64()).unwrap_or(0);
+        let cached = usage
+            .pointer("/prompt_tokens_details/cached_tokens")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+        Self {
+            input_tokens: prompt.saturating_sub(cached),
+            output_tokens: usage.get("completion_tokens").and_then(|v| v.as_u64()).unwrap_or(0),
+            cache_read_tokens: cached,
+            cache_creation_tokens: 0,
+            credits: 0.0,
+        }
+    }
+}
+
+/// 账本条目：某天某账号在某模型上的累计用量
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UsageLedgerEntry {
+    /// 本地日期 YYYY-MM-DD
+    pub date: String,
+    pub provider: String,
+    pub account_id: String,
+    pub account_email: String,
+    pub model: String,
+    /// 调用方的命名 API Key ID（管理员 Key 或未鉴权时为空）
+    #[serde(default)]
+    pub api_key_id: String,
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cache_read_tokens: u64,
+    pub cache_creation_tokens: u64,
+    #[serde(default)]
+    pub credits: f64,
+}
+
+fn ledger_path() -> Result<PathBuf, String> {
+    Ok(config::get_data_dir()?.join(USAGE_LEDGER_FILE))
+}
+
+fn load_ledger_from_disk() -> Result<Vec<UsageLedgerEntry>, String> {
+    let path = ledger_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| format!("读取用量账本失败: {}", e))?;
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&content).map_err(|e| format!("解析用量账本失败: {}", e))
+}
+
+fn save_usage_ledger(entries: &[UsageLedgerEntry]) -> Result<(), String> {
+    let data_dir = config::get_data_dir()?;
+    if !data_dir.exists() {
+        fs::create_dir_all(&data_dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let path = data_dir.join(USAGE_LEDGER_FILE);
+    let temp_path = data_dir.join(format!("{}.tmp", USAGE_LEDGER_FILE));
+    let content =
+        serde_json::to_string_pretty(entries).map_err(|e| format!("序列化用量账本失败: {}", e))?;
+    fs::write(&temp_path, content).map_err(|e| format!("写入临时账本文件失败: {}", e))?;
+    fs::rename(temp_path, path).map_err(|e| format!("替换账本文件失败: {}", e))
+}
+
+/// 一次请求的归属：Provider、账号、模型与调用方 API Key
+#[derive(Debug, Clone, Copy)]
+pub struct UsageSource<'a> {
+    pub provider: &'a str,
+    pub account_id: &'a str,
+    pub account_email: &'a str,
+    pub model: &'a str,
+    pub api_key_id: &'a str,
+}
+
+/// 将一次请求的用量累加到账本（同一 日期/Provider/账号/模型/API Key 合并为一条）
+fn merge_usage(entries: &mut Vec<UsageLedgerEntry>, date: &str, source: &UsageSource, usage: &TokenUsage) {
+    let existing = entries.iter_mut().find(|e| {
+        e.date == date
+            && e.provider == source.provider
+            && e.account_id == source.account_id
+            && e.model == source.model
+            && e.api_key_id == source.api_key_id
+    });
+    let entry = match existing {
+        Some(e) => e,
+        None => {
+            entries.push(UsageLedgerEntry {
+                date: date.to_string(),
+                provider: source.provider.to_string(),
+                account_id: source.account_id.to_string(),
+                account_email: source.account_email.to_string(),
+                model: source.model.to_string(),
+                api_key_id: source.api_key_id.to_string(),
+                requests: 0,
+                input_tokens: 0,
+                output_tokens: 0,
+                cache_read_tokens: 0,
+                cache_creation_tokens: 0,
+                credits: 0.0,
+            });
+            entries.last_mut().unwrap()
+        }
+    };
+    if !source.account_email.is_empty() {
+        entry.account_email = source.account_email.to_string();
+    }
+    entry.requests += 1;
+    entry.input_tokens += usage.input_tokens;
+    entry.output_tokens += usage.output_tokens;
+    entry.cache_read_tokens += usage.cache_read_tokens;
+    entry.cache_creation_tokens += usage.cache_creation_tokens;
+    entry.credits += usage.credits;
+}
+
+/// 删除早于 cutoff（YYYY-MM-DD）的条目
+fn prune_before(entries: &mut Vec<UsageLedgerEntry>, cutoff: &str) {
+    entries.retain(|e| e.date.as_str() >= cutoff);
+}
+
+fn date_days_ago(days: i64) -> String {
+    (chrono::Local::now() - chrono::Duration::days(days))
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// 在内存账本上执行操作，首次使用时从磁盘加载
+/// 加载失败时返回错误且不缓存，避免以空账本覆盖磁盘上的历史记录
+fn with_ledger<R>(f: impl FnOnce(&mut LedgerState, &mut Vec<UsageLedgerEntry>) -> R) -> Result<R, String> {
+    let mut state = LEDGER.lock().map_err(|_| "获取用量账本锁失败")?;
+    let mut entries = match state.entries.take() {
+        Some(entries) => entries,
+        None => load_ledger_from_disk()?,
+    };
+    let result = f(&mut state, &mut entries);
+    state.entries = Some(entries);
+    Ok(result)
+}
+
+/// 将未写盘的改动写入磁盘（失败时保留改动，下次记录用量时重试）
+pub fn flush_usage_ledger() {
+    let Ok(mut state) = LEDGER.lock() else {
+        return;
+    };
+    state.flush_scheduled = false;
+    if !state.dirty {
+        return;
+    }
+    let Some(entries) = state.entries.as_ref() else {
+        return;
+    };
+    match save_usage_ledger(entries) {
+        Ok(()) => state.dirty = false,
+        Err(e) => logger::log_warn(&format!("[ApiProxy] 写入用量账本失败: {}", e)),
+    }
+}
+
+fn schedule_flush(state: &mut LedgerState) {
+    state.dirty = true;
+    if state.flush_scheduled {
+        return;
+    }
+    state.flush_scheduled = true;
+    std::thread::spawn(|| {
+        std::thread::sleep(FLUSH_DELAY);
+        flush_usage_ledger();
+    });
+}
+
+/// 记录一次代理请求的用量（失败仅记录日志，不影响请求）
+pub fn record_usage(source: &UsageSource, usage: &TokenUsage) {
+    super::api_proxy_keys::add_key_tokens(source.api_key_id, usage.input_tokens + usage.output_tokens);
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    let result = with_ledger(|state, entries| {
+        merge_usage(entries, &today, source, usage);
+        prune_before(entries, &date_days_ago(MAX_LEDGER_DAYS));
+        schedule_flush(state);
+    });
+    if let Err(e) = result {
+        logger::log_warn(&format!("[ApiProxy] 记录用量失败，本次用量未写入账本: {}", e));
+    }
+}
+
+/// 某个 API Key 在指定日期（YYYY-MM-DD）已用的 token（输入 + 输出）
+pub fn tokens_for_api_key_on(api_key_id: &str, date: &str) -> u64 {
+    with_ledger(|_, entries| {
+        entries
+            .iter()
+            .filter(|e| e.date == date && e.api_key_id == api_key_id)
+            .map(|e| e.input_tokens + e.output_tokens)
+            .sum()
+    })
+    .unwrap_or(0)
+}
+
+/// 查询用量账本
+/// days: 仅返回最近 N 天（含今天）；provider / api_key_id: 仅返回指定 Provider / API Key
+pub fn query_usage_ledger(
+    days: Option<u32>,
+    provider: Option<&str>,
+    api_key_id: Option<&str>,
+) -> Result<Vec<UsageLedgerEntry>, String> {
+    let mut entries = with_ledger(|_, entries| entries.clone())?;
+    if let Some(days) = days {
+        let cutoff = date_days_ago(i64::from(days.max(1)) - 1);
+        prune_before(&mut entries, &cutoff);
+    }
+    if let Some(provider) = provider.filter(|p| !p.is_empty()) {
+        entries.retain(|e| e.provider == provider);
+    }
+    if let Some(api_key_id) = api_key_id.filter(|k| !k.is_empty()) {
+        entries.retain(|e| e.api_key_id == api_key_id);
+    }
+    entries.sort_by(|a, b| {
+        b.date
+            .cmp(&a.date)
+            .then_with(|| a.provider.cmp(&b.provider))
+            .then_with(|| a.account_email.cmp(&b.account_email))
+            .then_with(|| a.model.cmp(&b.model))
+    });
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_usage_aggregates_same_key() {
+        let mut entries = Vec::new();
+        let usage = TokenUsage { input_tokens: 10, output_tokens: 5, ..Default::default() };
+        let sonnet = UsageSource {
+            provider: "kiro",
+            account_id: "a1",
+            account_email: "a@x.com",
+            model: "claude-sonnet-4-5",
+            api_key_id: "",
+        };
+        let haiku = UsageSource { model: "claude-haiku-4-5", ..sonnet };
+        let keyed = UsageSource { api_key_id: "k1", ..sonnet };
+        merge_usage(&mut entries, "2026-01-02", &sonnet, &usage);
+        merge_usage(&mut entries, "2026-01-02", &sonnet, &usage);
+        merge_usage(&mut entries, "2026-01-02", &haiku, &usage);
+        merge_usage(&mut entries, "2026-01-02", &keyed, &usage);
+        merge_usage(&mut entries, "2026-01-01", &sonnet, &usage);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].requests, 2);
+        assert_eq!(entries[0].input_tokens, 20);
+        assert_eq!(entries[2].api_key_id, "k1");
+        prune_before(&mut entries, "2026-01-02");
+        assert_eq!(entries.len(), 3);
+    }
+
+    #[test]
+    fn test_token_usage_from_openai_splits_cache() {
+        let usage = TokenUsage::from_openai(&serde_json::json!({
+            "prompt_tokens": 100, "completion_tokens": 7,
+            "prompt_tokens_details": {"cached_tokens": 40}
+        }));
+        assert_eq!(usage.input_tokens, 60);
+        assert_eq!(usage.cache_read_tokens, 40);
+        assert_eq!(usage.output_tokens, 7);
+    }
+}
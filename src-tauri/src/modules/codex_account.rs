@@ -2,9 +2,9 @@ use crate::models::codex::{
     CodexAccount, CodexAccountIndex, CodexAccountSummary, CodexAuthFile, CodexAuthTokens,
     CodexJwtPayload, CodexTokens,
 };
-use crate::modules::{codex_oauth, logger};
+use crate::modules::platform_store::{PlatformAccountStore, PlatformQuotaAlert};
+use crate::modules::{codex_oauth, config, logger};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
-use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -21,9 +21,81 @@ pub struct ImportProgressPayload {
     pub current_file: String,
 }
 
-static CODEX_QUOTA_ALERT_LAST_SENT: std::sync::LazyLock<Mutex<HashMap<String, i64>>> =
-    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
-const CODEX_QUOTA_ALERT_COOLDOWN_SECONDS: i64 = 300;
+static CODEX_ACCOUNT_INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+pub struct CodexStore;
+
+impl PlatformAccountStore for CodexStore {
+    type Account = CodexAccount;
+    type Summary = CodexAccountSummary;
+    type Index = CodexAccountIndex;
+
+    const LABEL: &'static str = "Codex";
+    const INDEX_FILE: &'static str = "codex_accounts.json";
+    const ACCOUNTS_DIR: &'static str = "codex_accounts";
+
+    fn index_lock() -> &'static Mutex<()> {
+        &CODEX_ACCOUNT_INDEX_LOCK
+    }
+    fn index_entries(index: &mut CodexAccountIndex) -> &mut Vec<CodexAccountSummary> {
+        &mut index.accounts
+    }
+    fn summary_id(summary: &CodexAccountSummary) -> &str {
+        &summary.id
+    }
+    fn summarize(account: &CodexAccount) -> CodexAccountSummary {
+        CodexAccountSummary {
+            id: account.id.clone(),
+            email: account.email.clone(),
+            account_id: account.account_id.clone(),
+            organization_id: account.organization_id.clone(),
+            plan_type: account.plan_type.clone(),
+            created_at: account.created_at,
+            last_used: account.last_used,
+        }
+    }
+    fn account_id(account: &CodexAccount) -> &str {
+        &account.id
+    }
+    fn set_tags(account: &mut CodexAccount, tags: Vec<String>) {
+        account.tags = Some(tags);
+    }
+    /// Codex 账号存放在系统本地数据目录
+    fn data_dir() -> Result<PathBuf, String> {
+        let data_dir = dirs::data_local_dir()
+            .or_else(dirs::home_dir)
+            .ok_or("无法获取用户目录")?
+            .join("com.antigravity.cockpit-tools");
+        fs::create_dir_all(&data_dir).map_err(|e| format!("创建数据目录失败: {}", e))?;
+        Ok(data_dir)
+    }
+    /// 删除的是当前账号时清除 current_account_id
+    fn on_index_remove(index: &mut CodexAccountIndex, account_id: &str) {
+        if index.current_account_id.as_deref() == Some(account_id) {
+            index.current_account_id = None;
+        }
+    }
+}
+
+impl PlatformQuotaAlert for CodexStore {
+    const PLATFORM: &'static str = "codex";
+
+    fn quota_alert_settings(cfg: &config::UserConfig) -> (bool, i32) {
+        (cfg.codex_quota_alert_enabled, cfg.codex_quota_alert_threshold)
+    }
+    fn quota_metrics(account: &CodexAccount) -> Vec<(String, i32)> {
+        extract_quota_metrics(account)
+    }
+    fn resolve_current_account_id(accounts: &[CodexAccount]) -> Option<String> {
+        resolve_current_account_id(accounts)
+    }
+    fn display_email(account: &CodexAccount) -> String {
+        account.email.clone()
+    }
+    fn last_used(account: &CodexAccount) -> i64 {
+        account.last_used
+    }
+}
 
 /// 获取 Codex 数据目录
 pub fn get_codex_home() -> PathBuf {
@@ -35,23 +107,9 @@ pub fn get_auth_json_path() -> PathBuf {
     get_codex_home().join("auth.json")
 }
 
-/// 获取我们的多账号存储路径
-fn get_accounts_storage_path() -> PathBuf {
-    let data_dir = dirs::data_local_dir()
-        .unwrap_or_else(|| dirs::home_dir().expect("无法获取用户目录"))
-        .join("com.antigravity.cockpit-tools");
-    fs::create_dir_all(&data_dir).ok();
-    data_dir.join("codex_accounts.json")
-}
-
 /// 获取账号详情存储目录
-fn get_accounts_dir() -> PathBuf {
-    let data_dir = dirs::data_local_dir()
-        .unwrap_or_else(|| dirs::home_dir().expect("无法获取用户目录"))
-        .join("com.antigravity.cockpit-tools")
-        .join("codex_accounts");
-    fs::create_dir_all(&data_dir).ok();
-    data_dir
+pub fn get_accounts_dir() -> Result<PathBuf, String> {
+    CodexStore::accounts_dir()
 }
 
 /// 解析 JWT Token 的 payload
@@ -240,66 +298,19 @@ pub fn extract_user_info(
     Ok((email, user_id, plan_type, account_id, organization_id))
 }
 
-/// 读取账号索引
-pub fn load_account_index() -> CodexAccountIndex {
-    let path = get_accounts_storage_path();
-    if !path.exists() {
-        return CodexAccountIndex::new();
-    }
-
-    match fs::read_to_string(&path) {
-        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|_| CodexAccountIndex::new()),
-        Err(_) => CodexAccountIndex::new(),
-    }
-}
-
-/// 保存账号索引
-pub fn save_account_index(index: &CodexAccountIndex) -> Result<(), String> {
-    let path = get_accounts_storage_path();
-    let content = serde_json::to_string_pretty(index).map_err(|e| format!("序列化失败: {}", e))?;
-    fs::write(&path, content).map_err(|e| format!("写入文件失败: {}", e))?;
-    Ok(())
-}
-
 /// 读取单个账号详情
-pub fn load_account(account_id: &str) -> Option<CodexAccount> {
-    let path = get_accounts_dir().join(format!("{}.json", account_id));
-    if !path.exists() {
-        return None;
-    }
-
-    match fs::read_to_string(&path) {
-        Ok(content) => serde_json::from_str(&content).ok(),
-        Err(_) => None,
-    }
+pub fn load_account(account_id: &str) -> Result<Option<CodexAccount>, String> {
+    CodexStore::load_account(account_id)
 }
 
 /// 保存单个账号详情
 pub fn save_account(account: &CodexAccount) -> Result<(), String> {
-    let path = get_accounts_dir().join(format!("{}.json", &account.id));
-    let content =
-        serde_json::to_string_pretty(account).map_err(|e| format!("序列化失败: {}", e))?;
-    fs::write(&path, content).map_err(|e| format!("写入文件失败: {}", e))?;
-    Ok(())
-}
-
-/// 删除单个账号
-pub fn delete_account_file(account_id: &str) -> Result<(), String> {
-    let path = get_accounts_dir().join(format!("{}.json", account_id));
-    if path.exists() {
-        fs::remove_file(&path).map_err(|e| format!("删除文件失败: {}", e))?;
-    }
-    Ok(())
+    CodexStore::save_account_file(account)
 }
 
 /// 列出所有账号
-pub fn list_accounts() -> Vec<CodexAccount> {
-    let index = load_account_index();
-    index
-        .accounts
-        .iter()
-        .filter_map(|summary| load_account(&summary.id))
-        .collect()
+pub fn list_accounts() -> Result<Vec<CodexAccount>, String> {
+    CodexStore::list_accounts()
 }
 
 /// 添加或更新账号
@@ -342,7 +353,7 @@ fn upsert_account_internal(
     let account = if let Some(pos) = existing {
         // 更新现有账号
         let existing_id = index.accounts[pos].id.clone();
-        let mut acc = load_account(&existing_id)
+        let mut acc = load_account(&existing_id)?
             .unwrap_or_else(|| CodexAccount::new(existing_id, email.clone(), tokens.clone()));
         acc.tokens = tokens;
         acc.user_id = user_id;
@@ -359,37 +370,11 @@ fn upsert_account_internal(
         acc.account_id = account_id.clone();
         acc.organization_id = organization_id.clone();
 
-        index.accounts.retain(|item| item.id != existing_id);
-        index.accounts.push(CodexAccountSummary {
-            id: existing_id.clone(),
-            email: email.clone(),
-            account_id: account_id.clone(),
-            organization_id: organization_id.clone(),
-            plan_type: plan_type.clone(),
-            created_at: acc.created_at,
-            last_used: acc.last_used,
-        });
         acc
     };
 
     // 更新索引中的摘要信息
-    if let Some(summary) = index.accounts.iter_mut().find(|a| a.id == account.id) {
-        summary.email = account.email.clone();
-        summary.account_id = account.account_id.clone();
-        summary.organization_id = account.organization_id.clone();
-        summary.plan_type = account.plan_type.clone();
-        summary.last_used = account.last_used;
-    } else {
-        index.accounts.push(CodexAccountSummary {
-            id: account.id.clone(),
-            email: account.email.clone(),
-            account_id: account.account_id.clone(),
-            organization_id: account.organization_id.clone(),
-            plan_type: account.plan_type.clone(),
-            created_at: account.created_at,
-            last_used: account.last_used,
-        });
-    }
+    CodexStore::refresh_summary(index, &account);
 
     logger::log_info(&format!(
         "Codex 账号已保存: email={}, account_id={:?}, organization_id={:?}",
@@ -404,37 +389,22 @@ fn upsert_account_with_hints(
     account_id_hint: Option<String>,
     organization_id_hint: Option<String>,
 ) -> Result<CodexAccount, String> {
-    let mut index = load_account_index();
+    let _lock = CodexStore::lock_index()?;
+    let mut index = CodexStore::load_index();
     let account = upsert_account_internal(&mut index, tokens, account_id_hint, organization_id_hint)?;
     save_account(&account)?;
-    save_account_index(&index)?;
+    CodexStore::save_index(&index)?;
     Ok(account)
 }
 
 /// 删除账号
 pub fn remove_account(account_id: &str) -> Result<(), String> {
-    let mut index = load_account_index();
-
-    // 从索引中移除
-    index.accounts.retain(|a| a.id != account_id);
-
-    // 如果删除的是当前账号，清除 current_account_id
-    if index.current_account_id.as_deref() == Some(account_id) {
-        index.current_account_id = None;
-    }
-
-    save_account_index(&index)?;
-    delete_account_file(account_id)?;
-
-    Ok(())
+    CodexStore::remove_account(account_id)
 }
 
 /// 批量删除账号
 pub fn remove_accounts(account_ids: &[String]) -> Result<(), String> {
-    for id in account_ids {
-        remove_account(id)?;
-    }
-    Ok(())
+    CodexStore::remove_accounts(account_ids)
 }
 
 /// 获取当前激活的账号（基于 auth.json）
@@ -466,7 +436,7 @@ pub fn get_current_account() -> Option<CodexAccount> {
     );
 
     // 在我们的账号列表中查找
-    let accounts = list_accounts();
+    let accounts = list_accounts().ok()?;
     if let Some(account_id) = current_account_id.as_deref() {
         if let Some(account) = accounts.iter().find(|account| {
             account.email.eq_ignore_ascii_case(&email)
@@ -527,7 +497,7 @@ pub fn write_auth_file_to_dir(base_dir: &Path, account: &CodexAccount) -> Result
 /// 准备账号注入：如有必要刷新 Token 并写回存储
 pub async fn prepare_account_for_injection(account_id: &str) -> Result<CodexAccount, String> {
     let mut account =
-        load_account(account_id).ok_or_else(|| format!("账号不存在: {}", account_id))?;
+        load_account(account_id)?.ok_or_else(|| format!("账号不存在: {}", account_id))?;
     if codex_oauth::is_token_expired(&account.tokens.access_token) {
         logger::log_info(&format!("账号 {} 的 Token 已过期，尝试刷新", account.email));
         if let Some(ref refresh_token) = account.tokens.refresh_token {
@@ -551,13 +521,16 @@ pub async fn prepare_account_for_injection(account_id: &str) -> Result<CodexAcco
 
 /// 切换账号（写入 auth.json）
 pub fn switch_account(account_id: &str) -> Result<CodexAccount, String> {
-    let account = load_account(account_id).ok_or_else(|| format!("账号不存在: {}", account_id))?;
+    let account = load_account(account_id)?.ok_or_else(|| format!("账号不存在: {}", account_id))?;
     write_auth_file_to_dir(&get_codex_home(), &account)?;
 
     // 更新索引中的 current_account_id
-    let mut index = load_account_index();
-    index.current_account_id = Some(account_id.to_string());
-    save_account_index(&index)?;
+    {
+        let _lock = CodexStore::lock_index()?;
+        let mut index = CodexStore::load_index();
+        index.current_account_id = Some(account_id.to_string());
+        CodexStore::save_index(&index)?;
+    }
 
     // 更新账号的 last_used
     let mut updated_account = account.clone();
@@ -665,12 +638,13 @@ pub fn import_from_json_internal(index: &mut CodexAccountIndex, json_content: &s
 
 /// 从 JSON 字符串导入账号
 pub fn import_from_json(json_content: &str) -> Result<Vec<CodexAccount>, String> {
-    let mut index = load_account_index();
+    let _lock = CodexStore::lock_index()?;
+    let mut index = CodexStore::load_index();
     let result = import_from_json_internal(&mut index, json_content)?;
     for acc in &result {
         let _ = save_account(acc);
     }
-    save_account_index(&index)?;
+    CodexStore::save_index(&index)?;
     Ok(result)
 }
 
@@ -707,7 +681,7 @@ where
     }
 
     // 共享状态
-    let index = Arc::new(Mutex::new(load_account_index()));
+    let index = Arc::new(Mutex::new(CodexStore::load_index()));
     let imported_accounts = Arc::new(Mutex::new(Vec::new()));
     let current_count = Arc::new(AtomicUsize::new(0));
     let success_count = Arc::new(AtomicUsize::new(0));
@@ -774,7 +748,7 @@ where
     }
 
     let final_index = index.lock().unwrap().clone();
-    let _ = save_account_index(&final_index);
+    let _ = CodexStore::save_index(&final_index);
     let final_accounts = imported_accounts.lock().unwrap().clone();
 
     Ok(final_accounts)
@@ -782,26 +756,11 @@ where
 
 /// 导出账号为 JSON
 pub fn export_accounts(account_ids: &[String]) -> Result<String, String> {
-    let accounts: Vec<CodexAccount> = account_ids
-        .iter()
-        .filter_map(|id| load_account(id))
-        .collect();
-
-    serde_json::to_string_pretty(&accounts).map_err(|e| format!("序列化失败: {}", e))
+    CodexStore::export_accounts(account_ids)
 }
 
 pub fn update_account_tags(account_id: &str, tags: Vec<String>) -> Result<CodexAccount, String> {
-    let mut account =
-        load_account(account_id).ok_or_else(|| format!("账号不存在: {}", account_id))?;
-
-    account.tags = Some(tags);
-    save_account(&account)?;
-
-    Ok(account)
-}
-
-fn normalize_quota_alert_threshold(raw: i32) -> i32 {
-    raw.clamp(0, 100)
+    CodexStore::update_tags(account_id, tags)
 }
 
 fn format_codex_quota_metric_label(window_minutes: Option<i64>, fallback: &str) -> String {
@@ -835,7 +794,7 @@ fn format_codex_quota_metric_label(window_minutes: Option<i64>, fallback: &str)
     format!("{}m", minutes)
 }
 
-fn extract_quota_metrics(account: &CodexAccount) -> Vec<(String, i32)> {
+pub fn extract_quota_metrics(account: &CodexAccount) -> Vec<(String, i32)> {
     let Some(quota) = account.quota.as_ref() else {
         return Vec::new();
     };
@@ -868,40 +827,7 @@ fn extract_quota_metrics(account: &CodexAccount) -> Vec<(String, i32)> {
     metrics
 }
 
-fn average_quota_percentage(metrics: &[(String, i32)]) -> f64 {
-    if metrics.is_empty() {
-        return 0.0;
-    }
-    let sum: i32 = metrics.iter().map(|(_, pct)| *pct).sum();
-    sum as f64 / metrics.len() as f64
-}
-
-fn build_quota_alert_cooldown_key(account_id: &str, threshold: i32) -> String {
-    format!("codex:{}:{}", account_id, threshold)
-}
-
-fn should_emit_quota_alert(cooldown_key: &str, now: i64) -> bool {
-    let Ok(mut state) = CODEX_QUOTA_ALERT_LAST_SENT.lock() else {
-        return true;
-    };
-
-    if let Some(last_sent) = state.get(cooldown_key) {
-        if now - *last_sent < CODEX_QUOTA_ALERT_COOLDOWN_SECONDS {
-            return false;
-        }
-    }
-
-    state.insert(cooldown_key.to_string(), now);
-    true
-}
-
-fn clear_quota_alert_cooldown(account_id: &str, threshold: i32) {
-    if let Ok(mut state) = CODEX_QUOTA_ALERT_LAST_SENT.lock() {
-        state.remove(&build_quota_alert_cooldown_key(account_id, threshold));
-    }
-}
-
-fn resolve_current_account_id(accounts: &[CodexAccount]) -> Option<String> {
+pub fn resolve_current_account_id(accounts: &[CodexAccount]) -> Option<String> {
     if let Some(account) = get_current_account() {
         return Some(account.id);
     }
@@ -921,85 +847,9 @@ fn resolve_current_account_id(accounts: &[CodexAccount]) -> Option<String> {
         .map(|account| account.id.clone())
 }
 
-fn pick_quota_alert_recommendation(
-    accounts: &[CodexAccount],
-    current_id: &str,
-) -> Option<CodexAccount> {
-    let mut candidates: Vec<CodexAccount> = accounts
-        .iter()
-        .filter(|account| account.id != current_id)
-        .filter(|account| !extract_quota_metrics(account).is_empty())
-        .cloned()
-        .collect();
-
-    if candidates.is_empty() {
-        return None;
-    }
-
-    candidates.sort_by(|a, b| {
-        let avg_a = average_quota_percentage(&extract_quota_metrics(a));
-        let avg_b = average_quota_percentage(&extract_quota_metrics(b));
-        avg_b
-            .partial_cmp(&avg_a)
-            .unwrap_or(std::cmp::Ordering::Equal)
-            .then_with(|| a.last_used.cmp(&b.last_used))
-    });
-
-    candidates.into_iter().next()
-}
-
 pub fn run_quota_alert_if_needed(
 ) -> Result<Option<crate::modules::account::QuotaAlertPayload>, String> {
-    let cfg = crate::modules::config::get_user_config();
-    if !cfg.codex_quota_alert_enabled {
-        return Ok(None);
-    }
-
-    let threshold = normalize_quota_alert_threshold(cfg.codex_quota_alert_threshold);
-    let accounts = list_accounts();
-    let current_id = match resolve_current_account_id(&accounts) {
-        Some(id) => id,
-        None => return Ok(None),
-    };
-
-    let current = match accounts.iter().find(|account| account.id == current_id) {
-        Some(account) => account,
-        None => return Ok(None),
-    };
-
-    let metrics = extract_quota_metrics(current);
-    let low_models: Vec<(String, i32)> = metrics
-        .into_iter()
-        .filter(|(_, pct)| *pct <= threshold)
-        .collect();
-
-    if low_models.is_empty() {
-        clear_quota_alert_cooldown(&current_id, threshold);
-        return Ok(None);
-    }
-
-    let now = chrono::Utc::now().timestamp();
-    let cooldown_key = build_quota_alert_cooldown_key(&current_id, threshold);
-    if !should_emit_quota_alert(&cooldown_key, now) {
-        return Ok(None);
-    }
-
-    let recommendation = pick_quota_alert_recommendation(&accounts, &current_id);
-    let lowest_percentage = low_models.iter().map(|(_, pct)| *pct).min().unwrap_or(0);
-    let payload = crate::modules::account::QuotaAlertPayload {
-        platform: "codex".to_string(),
-        current_account_id: current_id,
-        current_email: current.email.clone(),
-        threshold,
-        lowest_percentage,
-        low_models: low_models.into_iter().map(|(name, _)| name).collect(),
-        recommended_account_id: recommendation.as_ref().map(|account| account.id.clone()),
-        recommended_email: recommendation.as_ref().map(|account| account.email.clone()),
-        triggered_at: now,
-    };
-
-    crate::modules::account::dispatch_quota_alert(&payload);
-    Ok(Some(payload))
+    CodexStore::run_quota_alert_if_needed()
 }
 
 #[cfg(test)]
@@ -1,338 +1,88 @@
-use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
-use chrono::Utc;
-use uuid::Uuid;
-
-use crate::models::{DefaultInstanceSettings, InstanceProfile, InstanceStore};
+use crate::models::DefaultInstanceSettings;
 use crate::modules;
-use crate::modules::instance::InstanceDefaults;
-use crate::modules::instance_store;
-
-pub use crate::modules::instance_store::{CreateInstanceParams, UpdateInstanceParams};
-
-static CODEX_INSTANCE_STORE_LOCK: std::sync::LazyLock<Mutex<()>> =
-    std::sync::LazyLock::new(|| Mutex::new(()));
+use crate::modules::instance_manager::PlatformInstanceManager;
 
-const CODEX_INSTANCES_FILE: &str = "codex_instances.json";
+static CODEX_INSTANCE_STORE_LOCK: Mutex<()> = Mutex::new(());
 
-fn instances_path() -> Result<PathBuf, String> {
-    let data_dir = modules::account::get_data_dir()?;
-    Ok(data_dir.join(CODEX_INSTANCES_FILE))
-}
+pub struct CodexInstances;
 
-pub fn load_instance_store() -> Result<InstanceStore, String> {
-    let path = instances_path()?;
-    instance_store::load_instance_store(&path, CODEX_INSTANCES_FILE)
-}
+impl PlatformInstanceManager for CodexInstances {
+    const LABEL: &'static str = "Codex";
+    const INSTANCES_FILE: &'static str = "codex_instances.json";
+    const INSTANCES_DIR: &'static str = "codex";
+    const FOLLOW_LOCAL_ACCOUNT: bool = true;
 
-pub fn save_instance_store(store: &InstanceStore) -> Result<(), String> {
-    let path = instances_path()?;
-    instance_store::save_instance_store(&path, CODEX_INSTANCES_FILE, store)
-}
-
-pub fn load_default_settings() -> Result<DefaultInstanceSettings, String> {
-    let store = load_instance_store()?;
-    Ok(store.default_settings)
-}
-
-pub fn update_default_settings(
-    bind_account_id: Option<Option<String>>,
-    extra_args: Option<String>,
-    follow_local_account: Option<bool>,
-) -> Result<DefaultInstanceSettings, String> {
-    let _lock = CODEX_INSTANCE_STORE_LOCK
-        .lock()
-        .map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let settings = &mut store.default_settings;
-
-    if follow_local_account == Some(true) {
-        settings.follow_local_account = true;
-        settings.bind_account_id = None;
+    fn store_lock() -> &'static Mutex<()> {
+        &CODEX_INSTANCE_STORE_LOCK
     }
-
-    if let Some(bind) = bind_account_id {
-        settings.bind_account_id = bind;
-        settings.follow_local_account = false;
-    }
-
-    if follow_local_account == Some(false) && settings.bind_account_id.is_none() {
-        settings.follow_local_account = false;
+    fn default_user_data_dir() -> Result<PathBuf, String> {
+        get_default_codex_home()
     }
+    fn instances_root_dir() -> Result<PathBuf, String> {
+        #[cfg(target_os = "macos")]
+        {
+            return modules::instance_manager::default_instances_root_dir(Self::INSTANCES_DIR);
+        }
 
-    if let Some(args) = extra_args {
-        settings.extra_args = args.trim().to_string();
+        #[allow(unreachable_code)]
+        Err("Codex 多开实例仅支持 macOS".to_string())
     }
-
-    let updated = settings.clone();
-    save_instance_store(&store)?;
-    Ok(updated)
-}
-
-pub fn get_default_codex_home() -> Result<PathBuf, String> {
-    #[cfg(target_os = "macos")]
-    {
-        return Ok(modules::codex_account::get_codex_home());
+    fn collect_process_entries() -> Vec<(u32, Option<String>)> {
+        modules::process::collect_codex_process_entries()
     }
-
-    #[allow(unreachable_code)]
-    Err("Codex 多开实例仅支持 macOS".to_string())
-}
-
-pub fn get_default_instances_root_dir() -> Result<PathBuf, String> {
-    #[cfg(target_os = "macos")]
-    {
-        let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
-        return Ok(home.join(".antigravity_cockpit/instances/codex"));
+    fn resolve_pid_from_entries(
+        last_pid: Option<u32>,
+        codex_home: Option<&str>,
+        entries: &[(u32, Option<String>)],
+    ) -> Option<u32> {
+        modules::process::resolve_codex_pid_from_entries(last_pid, codex_home, entries)
     }
-
-    #[allow(unreachable_code)]
-    Err("Codex 多开实例仅支持 macOS".to_string())
-}
-
-pub fn get_instance_defaults() -> Result<InstanceDefaults, String> {
-    let root_dir = get_default_instances_root_dir()?;
-    let default_user_data_dir = get_default_codex_home()?;
-    Ok(InstanceDefaults {
-        root_dir: root_dir.to_string_lossy().to_string(),
-        default_user_data_dir: default_user_data_dir.to_string_lossy().to_string(),
-    })
-}
-
-pub fn create_instance(params: CreateInstanceParams) -> Result<InstanceProfile, String> {
-    let _lock = CODEX_INSTANCE_STORE_LOCK
-        .lock()
-        .map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-
-    let name = instance_store::normalize_name(&params.name)?;
-    let user_data_dir = params.user_data_dir.trim().to_string();
-    if user_data_dir.is_empty() {
-        return Err("实例目录不能为空".to_string());
+    fn resolve_pid(last_pid: Option<u32>, codex_home: Option<&str>) -> Option<u32> {
+        modules::process::resolve_codex_pid(last_pid, codex_home)
     }
-
-    instance_store::ensure_unique(&store, &name, &user_data_dir, None)?;
-
-    let user_dir_path = PathBuf::from(&user_data_dir);
-    let init_mode = params
-        .init_mode
-        .as_deref()
-        .unwrap_or("copy")
-        .to_ascii_lowercase();
-    let create_empty = init_mode == "empty";
-
-    if create_empty {
-        if user_dir_path.exists() {
-            let mut has_entries = false;
-            if let Ok(mut iter) = fs::read_dir(&user_dir_path) {
-                if iter.next().is_some() {
-                    has_entries = true;
-                }
-            }
-            if has_entries {
-                let resolved_path = instance_store::display_path(&user_dir_path);
-                return Err(format!("空白实例需要目标目录为空: {}", resolved_path));
-            }
-        }
-        fs::create_dir_all(&user_dir_path).map_err(|e| format!("创建实例目录失败: {}", e))?;
-    } else {
-        let source_dir = match params.copy_source_instance_id.as_deref() {
-            Some("__default__") | None => get_default_codex_home()?,
-            Some(source_id) => {
-                let source_instance = store
-                    .instances
-                    .iter()
-                    .find(|item| item.id == source_id)
-                    .ok_or("复制来源实例不存在")?;
-                PathBuf::from(&source_instance.user_data_dir)
-            }
-        };
-
-        if user_dir_path.exists() {
-            let mut has_entries = false;
-            if let Ok(mut iter) = fs::read_dir(&user_dir_path) {
-                if iter.next().is_some() {
-                    has_entries = true;
-                }
-            }
-            if has_entries {
-                let resolved_path = instance_store::display_path(&user_dir_path);
-                modules::logger::log_info(&format!(
-                    "[Codex Instance] 复制来源实例需要空目录，但目标已存在: {}",
-                    resolved_path
-                ));
-                return Err(format!("复制来源实例需要目标目录为空: {}", resolved_path));
-            }
-        }
-
-        if !source_dir.exists() {
-            return Err("未找到复制来源目录，请先确保来源实例已初始化".to_string());
-        }
-
-        instance_store::copy_dir_recursive(&source_dir, &user_dir_path)?;
+    fn start(codex_home: &str, extra_args: &[String], _new_window: bool) -> Result<u32, String> {
+        modules::process::start_codex_with_args(codex_home, extra_args)
     }
-
-    let instance = InstanceProfile {
-        id: Uuid::new_v4().to_string(),
-        name,
-        user_data_dir,
-        extra_args: params.extra_args.trim().to_string(),
-        bind_account_id: if create_empty {
-            None
-        } else {
-            params.bind_account_id
-        },
-        created_at: Utc::now().timestamp_millis(),
-        last_launched_at: None,
-        last_pid: None,
-    };
-
-    store.instances.push(instance.clone());
-    save_instance_store(&store)?;
-    Ok(instance)
-}
-
-pub fn update_instance(params: UpdateInstanceParams) -> Result<InstanceProfile, String> {
-    let _lock = CODEX_INSTANCE_STORE_LOCK
-        .lock()
-        .map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let index = store
-        .instances
-        .iter()
-        .position(|instance| instance.id == params.instance_id)
-        .ok_or("实例不存在")?;
-
-    let current_id = store.instances[index].id.clone();
-    let current_dir = store.instances[index].user_data_dir.clone();
-    let next_name = params
-        .name
-        .as_ref()
-        .map(|name| instance_store::normalize_name(name))
-        .transpose()?;
-
-    if let Some(ref normalized) = next_name {
-        instance_store::ensure_unique(&store, normalized, &current_dir, Some(&current_id))?;
+    fn start_default(_extra_args: &[String], _new_window: bool) -> Result<u32, String> {
+        modules::process::start_codex_default()
     }
-
-    let instance = &mut store.instances[index];
-    if let Some(normalized) = next_name {
-        instance.name = normalized;
+    fn focus(last_pid: Option<u32>, codex_home: Option<&str>) -> Result<u32, String> {
+        modules::process::focus_codex_instance(last_pid, codex_home)
     }
-    if let Some(ref extra_args) = params.extra_args {
-        instance.extra_args = extra_args.trim().to_string();
+    fn close(codex_homes: &[String], timeout_secs: u64) -> Result<(), String> {
+        modules::process::close_codex_instances(codex_homes, timeout_secs)
     }
-    if let Some(bind) = params.bind_account_id.clone() {
-        instance.bind_account_id = bind;
+    async fn inject_account(profile_dir: &Path, account_id: &str) -> Result<(), String> {
+        inject_account_to_profile(profile_dir, account_id).await
     }
-
-    let updated = instance.clone();
-    save_instance_store(&store)?;
-    Ok(updated)
-}
-
-pub fn delete_instance(instance_id: &str) -> Result<(), String> {
-    let _lock = CODEX_INSTANCE_STORE_LOCK
-        .lock()
-        .map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let index = store
-        .instances
-        .iter()
-        .position(|instance| instance.id == instance_id)
-        .ok_or("实例不存在")?;
-    let user_data_dir = store.instances[index].user_data_dir.clone();
-
-    if !user_data_dir.trim().is_empty() {
-        let dir_path = PathBuf::from(&user_data_dir);
-        modules::instance::delete_instance_directory(&dir_path)?;
+    fn resolve_local_account_id() -> Option<String> {
+        modules::codex_account::get_current_account().map(|account| account.id)
     }
-
-    store.instances.remove(index);
-    save_instance_store(&store)?;
-    Ok(())
 }
 
-#[allow(dead_code)]
-pub fn update_instance_last_launched(instance_id: &str) -> Result<InstanceProfile, String> {
-    let _lock = CODEX_INSTANCE_STORE_LOCK
-        .lock()
-        .map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let mut updated = None;
-    for instance in &mut store.instances {
-        if instance.id == instance_id {
-            instance.last_launched_at = Some(Utc::now().timestamp_millis());
-            updated = Some(instance.clone());
-            break;
-        }
-    }
-    let updated = updated.ok_or("实例不存在")?;
-    save_instance_store(&store)?;
-    Ok(updated)
+pub fn load_default_settings() -> Result<DefaultInstanceSettings, String> {
+    CodexInstances::load_default_settings()
 }
 
-pub fn update_instance_after_start(instance_id: &str, pid: u32) -> Result<InstanceProfile, String> {
-    let _lock = CODEX_INSTANCE_STORE_LOCK
-        .lock()
-        .map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let mut updated = None;
-    for instance in &mut store.instances {
-        if instance.id == instance_id {
-            instance.last_launched_at = Some(Utc::now().timestamp_millis());
-            instance.last_pid = Some(pid);
-            updated = Some(instance.clone());
-            break;
-        }
-    }
-    let updated = updated.ok_or("实例不存在")?;
-    save_instance_store(&store)?;
-    Ok(updated)
+pub fn update_default_settings(
+    bind_account_id: Option<Option<String>>,
+    extra_args: Option<String>,
+    follow_local_account: Option<bool>,
+) -> Result<DefaultInstanceSettings, String> {
+    CodexInstances::update_default_settings(bind_account_id, extra_args, follow_local_account)
 }
 
-pub fn update_instance_pid(instance_id: &str, pid: Option<u32>) -> Result<InstanceProfile, String> {
-    let _lock = CODEX_INSTANCE_STORE_LOCK
-        .lock()
-        .map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let mut updated = None;
-    for instance in &mut store.instances {
-        if instance.id == instance_id {
-            instance.last_pid = pid;
-            updated = Some(instance.clone());
-            break;
-        }
+pub fn get_default_codex_home() -> Result<PathBuf, String> {
+    #[cfg(target_os = "macos")]
+    {
+        return Ok(modules::codex_account::get_codex_home());
     }
-    let updated = updated.ok_or("实例不存在")?;
-    save_instance_store(&store)?;
-    Ok(updated)
-}
 
-pub fn update_default_pid(pid: Option<u32>) -> Result<DefaultInstanceSettings, String> {
-    let _lock = CODEX_INSTANCE_STORE_LOCK
-        .lock()
-        .map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    store.default_settings.last_pid = pid;
-    let updated = store.default_settings.clone();
-    save_instance_store(&store)?;
-    Ok(updated)
-}
-
-pub fn clear_all_pids() -> Result<(), String> {
-    let _lock = CODEX_INSTANCE_STORE_LOCK
-        .lock()
-        .map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    store.default_settings.last_pid = None;
-    for instance in &mut store.instances {
-        instance.last_pid = None;
-    }
-    save_instance_store(&store)?;
-    Ok(())
+    #[allow(unreachable_code)]
+    Err("Codex 多开实例仅支持 macOS".to_string())
 }
 
 pub async fn inject_account_to_profile(profile_dir: &Path, account_id: &str) -> Result<(), String> {
@@ -247,7 +247,7 @@ pub async fn fetch_quota(account: &CodexAccount) -> Result<CodexQuota, String> {
 
 /// 刷新账号配额并保存（包含 token 自动刷新）
 pub async fn refresh_account_quota(account_id: &str) -> Result<CodexQuota, String> {
-    let mut account = codex_account::load_account(account_id)
+    let mut account = codex_account::load_account(account_id)?
         .ok_or_else(|| format!("账号不存在: {}", account_id))?;
 
     // 检查 token 是否过期，如果过期则刷新
@@ -306,7 +306,7 @@ pub async fn refresh_all_quotas() -> Result<Vec<(String, Result<CodexQuota, Stri
     use tokio::sync::Semaphore;
 
     const MAX_CONCURRENT: usize = 5;
-    let accounts = codex_account::list_accounts();
+    let accounts = codex_account::list_accounts()?;
 
     let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT));
     let tasks: Vec<_> = accounts
@@ -333,5 +333,10 @@ pub async fn refresh_all_quotas() -> Result<Vec<(String, Result<CodexQuota, Stri
         }
     }
 
+    if results.iter().any(|(_, r)| r.is_ok()) {
+        crate::modules::websocket::broadcast_quota_updated(
+            crate::modules::websocket_platforms::Platform::Codex,
+        );
+    }
     Ok(results)
 }
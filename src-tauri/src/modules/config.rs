@@ -33,6 +33,12 @@ pub struct ServerStatus {
     pub pid: u32,
     /// 启动时间戳
     pub started_at: i64,
+    /// 连接后需先配对/认证（旧版本服务无此字段）
+    #[serde(default)]
+    pub auth_required: bool,
+    /// 配对共享密钥文件路径
+    #[serde(default)]
+    pub secret_file: Option<String>,
 }
 
 /// 用户配置（持久化存储）
@@ -540,6 +546,13 @@ pub fn init_server_status(actual_port: u16) -> Result<(), String> {
         version: env!("CARGO_PKG_VERSION").to_string(),
         pid: std::process::id(),
         started_at: chrono::Utc::now().timestamp(),
+        auth_required: true,
+        secret_file: Some(
+            get_data_dir()?
+                .join(crate::modules::websocket_auth::SECRET_FILE)
+                .to_string_lossy()
+                .to_string(),
+        ),
     };
 
     save_server_status(&status)?;
@@ -0,0 +1,634 @@
+//! 账号凭据加密存储
+//! 开启后各平台账号文件中的 Token / API Key 等敏感字段以 AES-256-GCM 加密落盘，其余字段保持明文；
+//! 密钥来自主密码（PBKDF2-HMAC-SHA256 派生，每次启动需解锁）或系统钥匙串（不可用时退回数据目录下的密钥文件）。
+//! 凭据库锁定期间账号无法读取与保存，反向代理返回 503
+
+use aes_gcm::aead::generic_array::GenericArray;
+use aes_gcm::aead::{Aead, AeadCore, OsRng};
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::Sha256;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::{account, codex_account, config, github_copilot_account, kiro_account, logger, warp_account, windsurf_account};
+
+const VAULT_FILE: &str = "credential_vault.json";
+/// 钥匙串不可用时存放随机密钥的文件
+const KEY_FILE: &str = "credential_vault.key";
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+const KEYRING_SERVICE: &str = "com.antigravity.cockpit-tools";
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+const KEYRING_USER: &str = "credential-vault";
+
+/// 加密字段的值格式：前缀 + base64(nonce || 密文)，明文为原字段值的 JSON
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+const NONCE_LEN: usize = 12;
+const PBKDF2_ITERATIONS: u32 = 600_000;
+const MIN_PASSPHRASE_LEN: usize = 8;
+/// 用于校验主密码的已知明文
+const VERIFIER_PLAINTEXT: &str = "cockpit-tools-credential-vault";
+
+const LOCKED_ERROR: &str = "凭据库已锁定，请先在设置中解锁";
+
+/// 需要加密的字段名（各平台账号结构中的 Token、API Key 与原始授权数据）
+const SENSITIVE_FIELDS: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "id_token",
+    "auth_token",
+    "client_secret",
+    "github_access_token",
+    "copilot_token",
+    "windsurf_api_key",
+    "windsurf_auth_token",
+    "windsurf_auth_status_raw",
+    "kiro_auth_token_raw",
+    "openai_api_key",
+];
+
+/// 密钥来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultMode {
+    /// 主密码派生
+    Passphrase,
+    /// 系统钥匙串（不可用时为数据目录下的密钥文件）
+    Keyring,
+}
+
+/// 凭据库元数据（credential_vault.json），不含密钥本身
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultMeta {
+    mode: VaultMode,
+    /// 主密码模式的 PBKDF2 盐（base64）
+    #[serde(default)]
+    salt: String,
+    #[serde(default)]
+    iterations: u32,
+    /// 钥匙串模式下密钥是否退回存放在密钥文件
+    #[serde(default)]
+    key_file_fallback: bool,
+    /// 已知明文的密文，用于校验密钥
+    verifier: String,
+}
+
+/// 凭据库状态（通过 vault_get_status 暴露给前端）
+#[derive(Debug, Clone, Serialize)]
+pub struct VaultStatus {
+    pub enabled: bool,
+    pub mode: Option<VaultMode>,
+    pub locked: bool,
+    /// 密钥实际存放位置：passphrase / keyring / file
+    pub key_source: Option<String>,
+}
+
+#[derive(Default)]
+struct VaultRuntime {
+    meta: Option<VaultMeta>,
+    key: Option<[u8; 32]>,
+}
+
+static RUNTIME: OnceLock<RwLock<VaultRuntime>> = OnceLock::new();
+
+fn runtime() -> &'static RwLock<VaultRuntime> {
+    RUNTIME.get_or_init(|| RwLock::new(load_runtime()))
+}
+
+fn read_runtime() -> RwLockReadGuard<'static, VaultRuntime> {
+    runtime().read().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn write_runtime() -> RwLockWriteGuard<'static, VaultRuntime> {
+    runtime().write().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn vault_path() -> Result<PathBuf, String> {
+    Ok(config::get_data_dir()?.join(VAULT_FILE))
+}
+
+/// 启动时读取元数据；钥匙串模式自动解锁，主密码模式保持锁定
+fn load_runtime() -> VaultRuntime {
+    let meta = vault_path()
+        .ok()
+        .filter(|p| p.exists())
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|content| match serde_json::from_str::<VaultMeta>(&content) {
+            Ok(meta) => Some(meta),
+            Err(e) => {
+                logger::log_error(&format!("[Vault] 解析凭据库配置失败: {}", e));
+                None
+            }
+        });
+    let key = meta.as_ref().filter(|m| m.mode == VaultMode::Keyring).and_then(|m| {
+        match load_stored_key(m).and_then(|key| verify_key(m, &key).map(|_| key)) {
+            Ok(key) => Some(key),
+            Err(e) => {
+                logger::log_error(&format!("[Vault] 读取凭据库密钥失败: {}", e));
+                None
+            }
+        }
+    });
+    VaultRuntime { meta, key }
+}
+
+fn save_meta(meta: &VaultMeta) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(meta).map_err(|e| format!("序列化凭据库配置失败: {}", e))?;
+    write_private_file(&vault_path()?, content.as_bytes())
+}
+
+/// 写入仅当前用户可读的文件：临时文件创建时即为 0600（Unix），写完后原子替换目标文件
+pub(crate) fn write_private_file(path: &Path, content: &[u8]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("无效的文件路径: {}", path.display()))?;
+    let temp_path = path.with_file_name(format!("{}.tmp", file_name.to_string_lossy()));
+    // 残留的临时文件可能权限过宽，删除后以 create_new 重新创建
+    let _ = fs::remove_file(&temp_path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&temp_path).and_then(|mut file| {
+        file.write_all(content)?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(format!("写入 {} 失败: {}", temp_path.display(), e));
+    }
+    fs::rename(&temp_path, path).map_err(|e| format!("替换 {} 失败: {}", path.display(), e))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+fn encrypt_bytes(key: &[u8; 32], plaintext: &[u8]) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|e| format!("凭据加密失败: {}", e))?;
+    let mut packed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+    packed.extend_from_slice(nonce.as_slice());
+    packed.extend_from_slice(&ciphertext);
+    Ok(general_purpose::STANDARD.encode(packed))
+}
+
+fn decrypt_bytes(key: &[u8; 32], encoded: &str) -> Result<Vec<u8>, String> {
+    let packed = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("密文格式无效: {}", e))?;
+    if packed.len() <= NONCE_LEN {
+        return Err("密文长度无效".to_string());
+    }
+    let (nonce, ciphertext) = packed.split_at(NONCE_LEN);
+    Aes256Gcm::new(GenericArray::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "凭据解密失败：密钥不匹配或数据已损坏".to_string())
+}
+
+fn verify_key(meta: &VaultMeta, key: &[u8; 32]) -> Result<(), String> {
+    match decrypt_bytes(key, &meta.verifier) {
+        Ok(plain) if plain == VERIFIER_PLAINTEXT.as_bytes() => Ok(()),
+        _ => Err("密钥校验失败".to_string()),
+    }
+}
+
+/// 加密对象中的敏感字段（已加密或为 null 的跳过）
+fn seal_value(value: &mut Value, key: &[u8; 32]) -> Result<(), String> {
+    match value {
+        Value::Object(map) => {
+            for (name, field) in map.iter_mut() {
+                if SENSITIVE_FIELDS.contains(&name.as_str()) {
+                    if field.is_null() || is_encrypted(field) {
+                        continue;
+                    }
+                    let plain = serde_json::to_vec(field).map_err(|e| format!("序列化凭据失败: {}", e))?;
+                    *field = Value::String(format!("{}{}", ENCRYPTED_PREFIX, encrypt_bytes(key, &plain)?));
+                } else {
+                    seal_value(field, key)?;
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                seal_value(item, key)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// 还原所有加密字段；key 为 None（锁定）且存在加密字段时报错
+fn open_value(value: &mut Value, key: Option<&[u8; 32]>) -> Result<(), String> {
+    match value {
+        Value::String(s) if s.starts_with(ENCRYPTED_PREFIX) => {
+            let key = key.ok_or(LOCKED_ERROR)?;
+            let plain = decrypt_bytes(key, &s[ENCRYPTED_PREFIX.len()..])?;
+            *value = serde_json::from_slice(&plain).map_err(|e| format!("解析解密后的凭据失败: {}", e))?;
+        }
+        Value::Object(map) => {
+            for field in map.values_mut() {
+                open_value(field, key)?;
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                open_value(item, key)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn is_encrypted(value: &Value) -> bool {
+    value.as_str().is_some_and(|s| s.starts_with(ENCRYPTED_PREFIX))
+}
+
+fn contains_encrypted(value: &Value) -> bool {
+    match value {
+        Value::Object(map) => map.values().any(contains_encrypted),
+        Value::Array(items) => items.iter().any(contains_encrypted),
+        other => is_encrypted(other),
+    }
+}
+
+/// 序列化账号数据用于落盘：凭据库开启时加密敏感字段，锁定时拒绝写入
+pub fn seal_json<T: Serialize>(data: &T) -> Result<String, String> {
+    let mut value = serde_json::to_value(data).map_err(|e| format!("序列化失败: {}", e))?;
+    {
+        let runtime = read_runtime();
+        if runtime.meta.is_some() {
+            let key = runtime.key.as_ref().ok_or("凭据库已锁定，无法保存账号凭据")?;
+            seal_value(&mut value, key)?;
+        }
+    }
+    serde_json::to_string_pretty(&value).map_err(|e| format!("序列化失败: {}", e))
+}
+
+/// 解析账号文件：兼容明文与加密两种格式
+pub fn open_json<T: DeserializeOwned>(content: &str) -> Result<T, String> {
+    let mut value: Value = serde_json::from_str(content).map_err(|e| format!("解析失败: {}", e))?;
+    if contains_encrypted(&value) {
+        open_value(&mut value, read_runtime().key.as_ref())?;
+    }
+    serde_json::from_value(value).map_err(|e| format!("解析失败: {}", e))
+}
+
+/// 凭据库已开启且未解锁
+pub fn is_locked() -> bool {
+    let runtime = read_runtime();
+    runtime.meta.is_some() && runtime.key.is_none()
+}
+
+pub fn get_status() -> VaultStatus {
+    let runtime = read_runtime();
+    let key_source = runtime.meta.as_ref().map(|m| match m.mode {
+        VaultMode::Passphrase => "passphrase".to_string(),
+        VaultMode::Keyring if m.key_file_fallback => "file".to_string(),
+        VaultMode::Keyring => "keyring".to_string(),
+    });
+    VaultStatus {
+        enabled: runtime.meta.is_some(),
+        mode: runtime.meta.as_ref().map(|m| m.mode),
+        locked: runtime.meta.is_some() && runtime.key.is_none(),
+        key_source,
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn keyring_entry() -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER).map_err(|e| format!("打开系统钥匙串失败: {}", e))
+}
+
+/// 将随机密钥存入系统钥匙串并读回校验
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn store_key_in_keyring(encoded: &str) -> Result<(), String> {
+    let entry = keyring_entry()?;
+    entry
+        .set_password(encoded)
+        .map_err(|e| format!("写入系统钥匙串失败: {}", e))?;
+    match entry.get_password() {
+        Ok(stored) if stored == encoded => Ok(()),
+        Ok(_) => Err("系统钥匙串读回的密钥不一致".to_string()),
+        Err(e) => Err(format!("读取系统钥匙串失败: {}", e)),
+    }
+}
+
+/// Linux 等平台的钥匙串（keyutils）重启后不保留，直接使用密钥文件
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn store_key_in_keyring(_encoded: &str) -> Result<(), String> {
+    Err("当前平台不支持持久化的系统钥匙串".to_string())
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn read_key_from_keyring() -> Result<String, String> {
+    keyring_entry()?
+        .get_password()
+        .map_err(|e| format!("读取系统钥匙串失败: {}", e))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn read_key_from_keyring() -> Result<String, String> {
+    Err("当前平台不支持持久化的系统钥匙串".to_string())
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn delete_key_from_keyring() {
+    if let Ok(entry) = keyring_entry() {
+        let _ = entry.delete_credential();
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn delete_key_from_keyring() {}
+
+fn load_stored_key(meta: &VaultMeta) -> Result<[u8; 32], String> {
+    let encoded = if meta.key_file_fallback {
+        fs::read_to_string(config::get_data_dir()?.join(KEY_FILE)).map_err(|e| format!("读取密钥文件失败: {}", e))?
+    } else {
+        read_key_from_keyring()?
+    };
+    let bytes = general_purpose::STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| format!("密钥格式无效: {}", e))?;
+    bytes.try_into().map_err(|_| "密钥长度无效".to_string())
+}
+
+/// 各平台账号文件所在目录
+fn account_dirs() -> Result<Vec<PathBuf>, String> {
+    Ok(vec![
+        account::get_accounts_dir()?,
+        codex_account::get_accounts_dir()?,
+        github_copilot_account::get_accounts_dir()?,
+        windsurf_account::get_accounts_dir()?,
+        kiro_account::get_accounts_dir()?,
+        warp_account::get_accounts_dir()?,
+    ])
+}
+
+/// 按当前密钥重写单个账号文件，返回是否有改动
+fn rewrite_file(path: &Path, key: Option<&[u8; 32]>, encrypt: bool) -> Result<bool, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("读取失败: {}", e))?;
+    let mut value: Value = serde_json::from_str(&content).map_err(|e| format!("解析失败: {}", e))?;
+    let original = value.clone();
+    open_value(&mut value, key)?;
+    if encrypt {
+        seal_value(&mut value, key.ok_or(LOCKED_ERROR)?)?;
+    }
+    if value == original {
+        return Ok(false);
+    }
+    let content = serde_json::to_string_pretty(&value).map_err(|e| format!("序列化失败: {}", e))?;
+    write_private_file(path, content.as_bytes())?;
+    Ok(true)
+}
+
+/// 重写所有账号文件（encrypt=false 时还原为明文），返回改动的文件数；单个文件失败不影响其余文件
+fn rewrite_all(key: Option<&[u8; 32]>, encrypt: bool) -> Result<usize, String> {
+    let mut changed = 0;
+    let mut failed = 0;
+    for dir in account_dirs()? {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|e| e.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match rewrite_file(&path, key, encrypt) {
+                Ok(true) => changed += 1,
+                Ok(false) => {}
+                Err(e) => {
+                    failed += 1;
+                    logger::log_error(&format!("[Vault] 迁移 {} 失败: {}", path.display(), e));
+                }
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} 个账号文件迁移失败，详见日志", failed));
+    }
+    Ok(changed)
+}
+
+/// 开启凭据库并加密现有账号文件，返回加密的文件数
+/// mode 为 Passphrase 时需提供主密码；Keyring 模式生成随机密钥存入系统钥匙串，不可用时存为密钥文件
+pub fn enable(mode: VaultMode, passphrase: Option<&str>) -> Result<usize, String> {
+    let mut runtime = write_runtime();
+    if runtime.meta.is_some() {
+        return Err("凭据库已开启".to_string());
+    }
+
+    let mut salt = [0u8; 16];
+    let (key, meta) = match mode {
+        VaultMode::Passphrase => {
+            let passphrase = passphrase.unwrap_or_default();
+            if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+                return Err(format!("主密码至少 {} 个字符", MIN_PASSPHRASE_LEN));
+            }
+            rand::thread_rng().fill_bytes(&mut salt);
+            let key = derive_key(passphrase, &salt, PBKDF2_ITERATIONS);
+            let meta = VaultMeta {
+                mode,
+                salt: general_purpose::STANDARD.encode(salt),
+                iterations: PBKDF2_ITERATIONS,
+                key_file_fallback: false,
+                verifier: encrypt_bytes(&key, VERIFIER_PLAINTEXT.as_bytes())?,
+            };
+            (key, meta)
+        }
+        VaultMode::Keyring => {
+            let mut key = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            let encoded = general_purpose::STANDARD.encode(key);
+            let key_file_fallback = match store_key_in_keyring(&encoded) {
+                Ok(()) => false,
+                Err(e) => {
+                    logger::log_warn(&format!("[Vault] {}，改用密钥文件", e));
+                    write_private_file(&config::get_data_dir()?.join(KEY_FILE), encoded.as_bytes())?;
+                    true
+                }
+            };
+            let meta = VaultMeta {
+                mode,
+                salt: String::new(),
+                iterations: 0,
+                key_file_fallback,
+                verifier: encrypt_bytes(&key, VERIFIER_PLAINTEXT.as_bytes())?,
+            };
+            (key, meta)
+        }
+    };
+
+    // 先加密账号文件，全部成功后才写入凭据库配置；失败时还原已加密的文件
+    let migrated = rewrite_all(Some(&key), true).and_then(|changed| save_meta(&meta).map(|_| changed));
+    let changed = match migrated {
+        Ok(changed) => changed,
+        Err(e) => {
+            if let Err(restore_err) = rewrite_all(Some(&key), false) {
+                // 仍有文件处于加密状态：保留密钥并保持开启，避免账号无法读取
+                logger::log_error(&format!("[Vault] 开启失败后还原账号文件失败: {}", restore_err));
+                let _ = save_meta(&meta);
+                runtime.meta = Some(meta);
+                runtime.key = Some(key);
+                return Err(format!("{}；部分账号文件已加密，凭据库保持开启", e));
+            }
+            discard_stored_key(&meta);
+            return Err(e);
+        }
+    };
+    runtime.meta = Some(meta);
+    runtime.key = Some(key);
+    logger::log_info(&format!("[Vault] 凭据库已开启 ({:?})", mode));
+    Ok(changed)
+}
+
+/// 删除钥匙串模式保存的密钥（钥匙串条目或密钥文件）
+fn discard_stored_key(meta: &VaultMeta) {
+    if meta.mode != VaultMode::Keyring {
+        return;
+    }
+    if meta.key_file_fallback {
+        if let Ok(dir) = config::get_data_dir() {
+            let _ = fs::remove_file(dir.join(KEY_FILE));
+        }
+    } else {
+        delete_key_from_keyring();
+    }
+}
+
+/// 解锁凭据库；钥匙串模式无需主密码
+pub fn unlock(passphrase: Option<&str>) -> Result<(), String> {
+    let mut runtime = write_runtime();
+    let meta = runtime.meta.clone().ok_or("凭据库未开启")?;
+    let key = match meta.mode {
+        VaultMode::Passphrase => {
+            let salt = general_purpose::STANDARD
+                .decode(&meta.salt)
+                .map_err(|e| format!("凭据库配置损坏: {}", e))?;
+            let key = derive_key(passphrase.unwrap_or_default(), &salt, meta.iterations);
+            verify_key(&meta, &key).map_err(|_| "主密码错误".to_string())?;
+            key
+        }
+        VaultMode::Keyring => {
+            let key = load_stored_key(&meta)?;
+            verify_key(&meta, &key)?;
+            key
+        }
+    };
+    runtime.key = Some(key);
+    logger::log_info("[Vault] 凭据库已解锁");
+    Ok(())
+}
+
+/// 锁定凭据库（清除内存中的密钥）
+pub fn lock() -> Result<(), String> {
+    let mut runtime = write_runtime();
+    if runtime.meta.is_none() {
+        return Err("凭据库未开启".to_string());
+    }
+    runtime.key = None;
+    logger::log_info("[Vault] 凭据库已锁定");
+    Ok(())
+}
+
+/// 加密尚未加密的账号文件（如关闭凭据库期间或旧版本写入的文件），返回加密的文件数
+pub fn migrate() -> Result<usize, String> {
+    let runtime = read_runtime();
+    if runtime.meta.is_none() {
+        return Err("凭据库未开启".to_string());
+    }
+    let key = runtime.key.ok_or(LOCKED_ERROR)?;
+    rewrite_all(Some(&key), true)
+}
+
+/// 关闭凭据库：解密所有账号文件并删除密钥，返回解密的文件数；需先解锁
+pub fn disable() -> Result<usize, String> {
+    let mut runtime = write_runtime();
+    let meta = runtime.meta.clone().ok_or("凭据库未开启")?;
+    let key = runtime.key.ok_or(LOCKED_ERROR)?;
+    let changed = rewrite_all(Some(&key), false)?;
+
+    fs::remove_file(vault_path()?).map_err(|e| format!("删除凭据库配置失败: {}", e))?;
+    discard_stored_key(&meta);
+    *runtime = VaultRuntime::default();
+    logger::log_info("[Vault] 凭据库已关闭，账号文件已还原为明文");
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open_sensitive_fields() {
+        let key = derive_key("correct horse", b"salt-salt-salt-1", 1_000);
+        let account = serde_json::json!({
+            "id": "a1",
+            "email": "user@example.com",
+            "token": {"access_token": "ya29.x", "refresh_token": "1//r", "expires_in": 3600},
+            "kiro_auth_token_raw": {"accessToken": "t"},
+            "refresh_token": null,
+        });
+
+        let mut sealed = account.clone();
+        seal_value(&mut sealed, &key).unwrap();
+        assert_eq!(sealed["email"], "user@example.com");
+        assert_eq!(sealed["token"]["expires_in"], 3600);
+        assert!(is_encrypted(&sealed["token"]["access_token"]));
+        assert!(is_encrypted(&sealed["kiro_auth_token_raw"]));
+        assert!(sealed["refresh_token"].is_null());
+        assert!(!sealed.to_string().contains("ya29.x"));
+
+        // 重复加密不改变已加密字段
+        let mut twice = sealed.clone();
+        seal_value(&mut twice, &key).unwrap();
+        assert_eq!(twice, sealed);
+
+        assert_eq!(open_value(&mut sealed.clone(), None).unwrap_err(), LOCKED_ERROR);
+        let wrong = derive_key("wrong", b"salt-salt-salt-1", 1_000);
+        assert!(open_value(&mut sealed.clone(), Some(&wrong)).is_err());
+        open_value(&mut sealed, Some(&key)).unwrap();
+        assert_eq!(sealed, account);
+    }
+
+    #[test]
+    fn test_write_private_file_replaces_atomically() {
+        let dir = std::env::temp_dir().join(format!("vault-write-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("secret.json");
+        write_private_file(&path, b"first").unwrap();
+        // 残留的临时文件不影响写入
+        fs::write(dir.join("secret.json.tmp"), b"stale").unwrap();
+        write_private_file(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert!(!dir.join("secret.json.tmp").exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,181 @@
+//! 无界面守护模式（`--daemon`）
+//! 不创建窗口与托盘，在后台运行 API 反向代理、唤醒调度、各平台定时刷新与 WebSocket 服务；
+//! 配置读取数据目录下的文件，原本发往前端的事件改为写入日志
+
+use std::future::Future;
+use std::time::Duration;
+
+use crate::modules::{self, config::UserConfig, credential_vault::VaultMode, logger};
+
+/// 自动刷新关闭时重新检查配置的间隔
+const DISABLED_RECHECK_SECS: u64 = 60;
+/// 凭据库主密码（主密码模式下用于启动时解锁）
+const VAULT_PASSPHRASE_ENV: &str = "COCKPIT_VAULT_PASSPHRASE";
+
+/// 守护模式入口，收到 Ctrl+C / SIGTERM 后停止代理并退出
+pub fn run() {
+    tauri::async_runtime::block_on(async {
+        match logger::get_log_dir() {
+            Ok(dir) => logger::log_info(&format!("[Daemon] 以无界面模式启动，日志目录: {}", dir.display())),
+            Err(_) => logger::log_info("[Daemon] 以无界面模式启动"),
+        }
+
+        tauri::async_runtime::spawn(async {
+            modules::websocket::start_server().await;
+        });
+
+        unlock_vault_from_env();
+
+        let proxy_config = modules::api_proxy::load_proxy_config();
+        if proxy_config.enabled {
+            match modules::api_proxy::start_proxy_server(proxy_config).await {
+                Ok(status) => logger::log_info(&format!(
+                    "[Daemon] API 反向代理已启动: port={}",
+                    status.actual_port.unwrap_or(0)
+                )),
+                Err(e) => logger::log_error(&format!("[Daemon] API 反向代理启动失败: {}", e)),
+            }
+        } else {
+            logger::log_info("[Daemon] API 反向代理未启用，跳过");
+        }
+
+        match modules::wakeup_scheduler::restore_synced_tasks() {
+            Ok(count) => logger::log_info(&format!("[Daemon] 已恢复 {} 个唤醒任务", count)),
+            Err(e) => logger::log_warn(&format!("[Daemon] 恢复唤醒任务失败: {}", e)),
+        }
+        modules::wakeup_scheduler::ensure_started();
+
+        spawn_refresh_loop("Antigravity", |c| c.auto_refresh_minutes, refresh_antigravity);
+        spawn_refresh_loop("Codex", |c| c.codex_auto_refresh_minutes, refresh_codex);
+        spawn_refresh_loop("GitHub Copilot", |c| c.ghcp_auto_refresh_minutes, refresh_github_copilot);
+        spawn_refresh_loop("Windsurf", |c| c.windsurf_auto_refresh_minutes, refresh_windsurf);
+        spawn_refresh_loop("Kiro", |c| c.kiro_auto_refresh_minutes, refresh_kiro);
+
+        wait_for_shutdown().await;
+        logger::log_info("[Daemon] 收到退出信号，正在停止");
+        if let Err(e) = modules::api_proxy::stop_proxy_server() {
+            logger::log_warn(&format!("[Daemon] 停止 API 反向代理失败: {}", e));
+        }
+    });
+}
+
+/// 主密码模式的凭据库无法交互解锁，从环境变量读取主密码
+fn unlock_vault_from_env() {
+    if !modules::credential_vault::is_locked() {
+        return;
+    }
+    let passphrase = std::env::var(VAULT_PASSPHRASE_ENV).ok();
+    if passphrase.is_none() && modules::credential_vault::get_status().mode == Some(VaultMode::Passphrase) {
+        logger::log_warn(&format!(
+            "[Daemon] 凭据库已锁定，账号不可用；可通过环境变量 {} 提供主密码",
+            VAULT_PASSPHRASE_ENV
+        ));
+        return;
+    }
+    match modules::credential_vault::unlock(passphrase.as_deref()) {
+        Ok(()) => logger::log_info("[Daemon] 凭据库已解锁"),
+        Err(e) => logger::log_error(&format!("[Daemon] 凭据库解锁失败: {}", e)),
+    }
+}
+
+/// 按用户配置的间隔（分钟，≤0 表示关闭）定时刷新；每轮重新读取配置，修改后无需重启
+fn spawn_refresh_loop<F, Fut>(platform: &'static str, interval: fn(&UserConfig) -> i32, refresh: F)
+where
+    F: Fn() -> Fut + Send + 'static,
+    Fut: Future<Output = Result<String, String>> + Send,
+{
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let minutes = interval(&modules::config::get_user_config());
+            if minutes <= 0 {
+                tokio::time::sleep(Duration::from_secs(DISABLED_RECHECK_SECS)).await;
+                continue;
+            }
+            tokio::time::sleep(Duration::from_secs(minutes as u64 * 60)).await;
+            match refresh().await {
+                Ok(summary) => logger::log_info(&format!("[Daemon] {} 定时刷新完成: {}", platform, summary)),
+                Err(e) => logger::log_warn(&format!("[Daemon] {} 定时刷新失败: {}", platform, e)),
+            }
+        }
+    });
+}
+
+fn summarize<T>(results: &[(String, Result<T, String>)]) -> (usize, String) {
+    let success = results.iter().filter(|(_, r)| r.is_ok()).count();
+    (success, format!("success={}, failed={}", success, results.len() - success))
+}
+
+async fn refresh_antigravity() -> Result<String, String> {
+    let stats = modules::account::refresh_all_quotas_logic().await?;
+    match modules::account::run_auto_switch_if_needed().await {
+        Ok(Some(account)) => logger::log_info(&format!("[AutoSwitch] 自动切号完成: {}", account.email)),
+        Ok(None) => {
+            if let Err(e) = modules::account::run_quota_alert_if_needed() {
+                logger::log_warn(&format!("[QuotaAlert] 预警检查失败: {}", e));
+            }
+        }
+        Err(e) => logger::log_warn(&format!("[AutoSwitch] 自动切号执行失败: {}", e)),
+    }
+    Ok(format!("success={}, failed={}", stats.success, stats.failed))
+}
+
+async fn refresh_codex() -> Result<String, String> {
+    let (success, summary) = summarize(&modules::codex_quota::refresh_all_quotas().await?);
+    if success > 0 {
+        if let Err(e) = modules::codex_account::run_quota_alert_if_needed() {
+            logger::log_warn(&format!("[QuotaAlert][Codex] 预警检查失败: {}", e));
+        }
+    }
+    Ok(summary)
+}
+
+async fn refresh_github_copilot() -> Result<String, String> {
+    let (success, summary) = summarize(&modules::github_copilot_account::refresh_all_tokens().await?);
+    if success > 0 {
+        if let Err(e) = modules::github_copilot_account::run_quota_alert_if_needed() {
+            logger::log_warn(&format!("[QuotaAlert][GitHub Copilot] 预警检查失败: {}", e));
+        }
+    }
+    Ok(summary)
+}
+
+async fn refresh_windsurf() -> Result<String, String> {
+    let (success, summary) = summarize(&modules::windsurf_account::refresh_all_tokens().await?);
+    if success > 0 {
+        if let Err(e) = modules::windsurf_account::run_quota_alert_if_needed() {
+            logger::log_warn(&format!("[QuotaAlert][Windsurf] 预警检查失败: {}", e));
+        }
+    }
+    Ok(summary)
+}
+
+async fn refresh_kiro() -> Result<String, String> {
+    let (success, summary) = summarize(&modules::kiro_account::refresh_all_tokens().await?);
+    if success > 0 {
+        if let Err(e) = modules::kiro_account::run_quota_alert_if_needed() {
+            logger::log_warn(&format!("[QuotaAlert][Kiro] 预警检查失败: {}", e));
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(unix)]
+async fn wait_for_shutdown() {
+    use tokio::signal::unix::{signal, SignalKind};
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = sigterm.recv() => {}
+            }
+        }
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_shutdown() {
+    let _ = tokio::signal::ctrl_c().await;
+}
@@ -1,137 +1,100 @@
 use crate::models::github_copilot::{
-    GitHubCopilotAccount, GitHubCopilotAccountIndex, GitHubCopilotOAuthCompletePayload,
+    GitHubCopilotAccount, GitHubCopilotAccountIndex, GitHubCopilotAccountSummary,
+    GitHubCopilotOAuthCompletePayload,
 };
-use crate::modules::{account, github_copilot_oauth, logger};
+use crate::modules::platform_store::{
+    calc_remaining_percent, clamp_percent, PlatformAccountStore, PlatformQuotaAlert,
+};
+use crate::modules::{config, github_copilot_oauth, logger};
 use std::collections::HashMap;
-use std::fs;
 use std::path::PathBuf;
 use std::sync::Mutex;
 
-const ACCOUNTS_INDEX_FILE: &str = "github_copilot_accounts.json";
-const ACCOUNTS_DIR: &str = "github_copilot_accounts";
-static GHCP_ACCOUNT_INDEX_LOCK: std::sync::LazyLock<Mutex<()>> =
-    std::sync::LazyLock::new(|| Mutex::new(()));
-static GHCP_QUOTA_ALERT_LAST_SENT: std::sync::LazyLock<Mutex<HashMap<String, i64>>> =
-    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
-const GHCP_QUOTA_ALERT_COOLDOWN_SECONDS: i64 = 300;
-
-fn now_ts() -> i64 {
-    chrono::Utc::now().timestamp()
-}
-
-fn get_data_dir() -> Result<PathBuf, String> {
-    account::get_data_dir()
-}
-
-fn get_accounts_dir() -> Result<PathBuf, String> {
-    let base = get_data_dir()?;
-    let dir = base.join(ACCOUNTS_DIR);
-    if !dir.exists() {
-        fs::create_dir_all(&dir).map_err(|e| format!("创建 GitHub Copilot 账号目录失败: {}", e))?;
-    }
-    Ok(dir)
-}
+static GHCP_ACCOUNT_INDEX_LOCK: Mutex<()> = Mutex::new(());
 
-fn get_accounts_index_path() -> Result<PathBuf, String> {
-    Ok(get_data_dir()?.join(ACCOUNTS_INDEX_FILE))
-}
+pub struct GitHubCopilotStore;
 
-pub fn accounts_index_path_string() -> Result<String, String> {
-    Ok(get_accounts_index_path()?.to_string_lossy().to_string())
-}
+impl PlatformAccountStore for GitHubCopilotStore {
+    type Account = GitHubCopilotAccount;
+    type Summary = GitHubCopilotAccountSummary;
+    type Index = GitHubCopilotAccountIndex;
 
-/// Load a single account by ID (public wrapper)
-pub fn load_account(account_id: &str) -> Option<GitHubCopilotAccount> {
-    load_account_file(account_id)
-}
+    const LABEL: &'static str = "GitHub Copilot";
+    const INDEX_FILE: &'static str = "github_copilot_accounts.json";
+    const ACCOUNTS_DIR: &'static str = "github_copilot_accounts";
 
-fn load_account_file(account_id: &str) -> Option<GitHubCopilotAccount> {
-    let account_path = get_accounts_dir()
-        .ok()
-        .map(|dir| dir.join(format!("{}.json", account_id)))?;
-    if !account_path.exists() {
-        return None;
+    fn index_lock() -> &'static Mutex<()> {
+        &GHCP_ACCOUNT_INDEX_LOCK
     }
-    let content = fs::read_to_string(account_path).ok()?;
-    serde_json::from_str(&content).ok()
-}
-
-fn save_account_file(account: &GitHubCopilotAccount) -> Result<(), String> {
-    let path = get_accounts_dir()?.join(format!("{}.json", account.id));
-    let content =
-        serde_json::to_string_pretty(account).map_err(|e| format!("序列化账号失败: {}", e))?;
-    fs::write(path, content).map_err(|e| format!("保存账号失败: {}", e))
-}
-
-fn delete_account_file(account_id: &str) -> Result<(), String> {
-    let path = get_accounts_dir()?.join(format!("{}.json", account_id));
-    if path.exists() {
-        fs::remove_file(path).map_err(|e| format!("删除账号失败: {}", e))?;
+    fn index_entries(index: &mut GitHubCopilotAccountIndex) -> &mut Vec<GitHubCopilotAccountSummary> {
+        &mut index.accounts
+    }
+    fn summary_id(summary: &GitHubCopilotAccountSummary) -> &str {
+        &summary.id
+    }
+    fn summarize(account: &GitHubCopilotAccount) -> GitHubCopilotAccountSummary {
+        account.summary()
+    }
+    fn account_id(account: &GitHubCopilotAccount) -> &str {
+        &account.id
+    }
+    fn set_tags(account: &mut GitHubCopilotAccount, tags: Vec<String>) {
+        account.tags = Some(tags);
+        account.last_used = now_ts();
     }
-    Ok(())
 }
 
-fn load_account_index() -> GitHubCopilotAccountIndex {
-    let path = match get_accounts_index_path() {
-        Ok(p) => p,
-        Err(_) => return GitHubCopilotAccountIndex::new(),
-    };
+impl PlatformQuotaAlert for GitHubCopilotStore {
+    const PLATFORM: &'static str = "github_copilot";
 
-    if !path.exists() {
-        return GitHubCopilotAccountIndex::new();
+    fn quota_alert_settings(cfg: &config::UserConfig) -> (bool, i32) {
+        (cfg.ghcp_quota_alert_enabled, cfg.ghcp_quota_alert_threshold)
     }
-
-    match fs::read_to_string(path) {
-        Ok(content) => {
-            serde_json::from_str(&content).unwrap_or_else(|_| GitHubCopilotAccountIndex::new())
-        }
-        Err(_) => GitHubCopilotAccountIndex::new(),
+    fn quota_metrics(account: &GitHubCopilotAccount) -> Vec<(String, i32)> {
+        extract_quota_metrics(account)
+    }
+    fn resolve_current_account_id(accounts: &[GitHubCopilotAccount]) -> Option<String> {
+        resolve_current_account_id(accounts)
+    }
+    fn display_email(account: &GitHubCopilotAccount) -> String {
+        account
+            .github_email
+            .clone()
+            .filter(|text| !text.trim().is_empty())
+            .unwrap_or_else(|| account.github_login.clone())
+    }
+    fn last_used(account: &GitHubCopilotAccount) -> i64 {
+        account.last_used
     }
 }
 
-fn save_account_index(index: &GitHubCopilotAccountIndex) -> Result<(), String> {
-    let path = get_accounts_index_path()?;
-    let content =
-        serde_json::to_string_pretty(index).map_err(|e| format!("序列化账号索引失败: {}", e))?;
-    fs::write(path, content).map_err(|e| format!("写入账号索引失败: {}", e))
+fn now_ts() -> i64 {
+    chrono::Utc::now().timestamp()
 }
 
-fn refresh_summary(index: &mut GitHubCopilotAccountIndex, account: &GitHubCopilotAccount) {
-    if let Some(summary) = index.accounts.iter_mut().find(|item| item.id == account.id) {
-        *summary = account.summary();
-        return;
-    }
-    index.accounts.push(account.summary());
+pub fn get_accounts_dir() -> Result<PathBuf, String> {
+    GitHubCopilotStore::accounts_dir()
 }
 
-fn upsert_account_record(account: GitHubCopilotAccount) -> Result<GitHubCopilotAccount, String> {
-    let _lock = GHCP_ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|_| "获取 GitHub Copilot 账号锁失败".to_string())?;
-    let mut index = load_account_index();
-    save_account_file(&account)?;
-    refresh_summary(&mut index, &account);
-    save_account_index(&index)?;
-    Ok(account)
+pub fn accounts_index_path_string() -> Result<String, String> {
+    Ok(GitHubCopilotStore::index_path()?.to_string_lossy().to_string())
 }
 
-pub fn list_accounts() -> Vec<GitHubCopilotAccount> {
-    let index = load_account_index();
-    index
-        .accounts
-        .iter()
-        .filter_map(|summary| load_account_file(&summary.id))
-        .collect()
+/// Load a single account by ID (public wrapper)
+pub fn load_account(account_id: &str) -> Result<Option<GitHubCopilotAccount>, String> {
+    GitHubCopilotStore::load_account(account_id)
+}
+
+pub fn list_accounts() -> Result<Vec<GitHubCopilotAccount>, String> {
+    GitHubCopilotStore::list_accounts()
 }
 
 pub fn upsert_account(
     payload: GitHubCopilotOAuthCompletePayload,
 ) -> Result<GitHubCopilotAccount, String> {
-    let _lock = GHCP_ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|_| "获取 GitHub Copilot 账号锁失败".to_string())?;
+    let _lock = GitHubCopilotStore::lock_index()?;
     let now = now_ts();
-    let mut index = load_account_index();
+    let mut index = GitHubCopilotStore::load_index();
     let generated_id = format!(
         "ghcp_{:x}",
         md5::compute(format!("{}:{}", payload.github_login, payload.github_id))
@@ -143,7 +106,7 @@ pub fn upsert_account(
         .map(|item| item.id.clone())
         .unwrap_or(generated_id);
 
-    let existing = load_account_file(&account_id);
+    let existing = GitHubCopilotStore::load_account(&account_id)?;
     let tags = existing.as_ref().and_then(|acc| acc.tags.clone());
     let created_at = existing.as_ref().map(|acc| acc.created_at).unwrap_or(now);
 
@@ -189,9 +152,9 @@ pub fn upsert_account(
     account.created_at = created_at;
     account.last_used = now;
 
-    save_account_file(&account)?;
-    refresh_summary(&mut index, &account);
-    save_account_index(&index)?;
+    GitHubCopilotStore::save_account_file(&account)?;
+    GitHubCopilotStore::refresh_summary(&mut index, &account);
+    GitHubCopilotStore::save_index(&index)?;
 
     logger::log_info(&format!(
         "GitHub Copilot 账号已保存: id={}, login={}",
@@ -201,7 +164,7 @@ pub fn upsert_account(
 }
 
 pub async fn refresh_account_token(account_id: &str) -> Result<GitHubCopilotAccount, String> {
-    let mut account = load_account_file(account_id).ok_or_else(|| "账号不存在".to_string())?;
+    let mut account = load_account(account_id)?.ok_or_else(|| "账号不存在".to_string())?;
     let bundle = github_copilot_oauth::refresh_copilot_token(&account.github_access_token).await?;
 
     account.copilot_token = bundle.token;
@@ -215,9 +178,7 @@ pub async fn refresh_account_token(account_id: &str) -> Result<GitHubCopilotAcco
     account.copilot_limited_user_reset_date = bundle.limited_user_reset_date;
     account.last_used = now_ts();
 
-    let updated = account.clone();
-    upsert_account_record(account)?;
-    Ok(updated)
+    GitHubCopilotStore::upsert_account_record(account)
 }
 
 pub async fn refresh_all_tokens(
@@ -227,7 +188,7 @@ pub async fn refresh_all_tokens(
     use tokio::sync::Semaphore;
 
     const MAX_CONCURRENT: usize = 5;
-    let accounts = list_accounts();
+    let accounts = list_accounts()?;
     let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT));
     let tasks: Vec<_> = accounts
         .into_iter()
@@ -253,71 +214,35 @@ pub async fn refresh_all_tokens(
         }
     }
 
+    if results.iter().any(|(_, r)| r.is_ok()) {
+        crate::modules::websocket::broadcast_quota_updated(
+            crate::modules::websocket_platforms::Platform::GithubCopilot,
+        );
+    }
     Ok(results)
 }
 
 pub fn remove_account(account_id: &str) -> Result<(), String> {
-    let _lock = GHCP_ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|_| "获取 GitHub Copilot 账号锁失败".to_string())?;
-    let mut index = load_account_index();
-    index.accounts.retain(|item| item.id != account_id);
-    save_account_index(&index)?;
-    delete_account_file(account_id)?;
-    Ok(())
+    GitHubCopilotStore::remove_account(account_id)
 }
 
 pub fn remove_accounts(account_ids: &[String]) -> Result<(), String> {
-    for id in account_ids {
-        remove_account(id)?;
-    }
-    Ok(())
+    GitHubCopilotStore::remove_accounts(account_ids)
 }
 
 pub fn update_account_tags(
     account_id: &str,
     tags: Vec<String>,
 ) -> Result<GitHubCopilotAccount, String> {
-    let mut account = load_account_file(account_id).ok_or_else(|| "账号不存在".to_string())?;
-    account.tags = Some(tags);
-    account.last_used = now_ts();
-    let updated = account.clone();
-    upsert_account_record(account)?;
-    Ok(updated)
+    GitHubCopilotStore::update_tags(account_id, tags)
 }
 
 pub fn import_from_json(json_content: &str) -> Result<Vec<GitHubCopilotAccount>, String> {
-    if let Ok(account) = serde_json::from_str::<GitHubCopilotAccount>(json_content) {
-        let saved = upsert_account_record(account)?;
-        return Ok(vec![saved]);
-    }
-
-    if let Ok(accounts) = serde_json::from_str::<Vec<GitHubCopilotAccount>>(json_content) {
-        let mut result = Vec::new();
-        for account in accounts {
-            let saved = upsert_account_record(account)?;
-            result.push(saved);
-        }
-        return Ok(result);
-    }
-
-    Err("无法解析 JSON 内容".to_string())
+    GitHubCopilotStore::import_from_json(json_content)
 }
 
 pub fn export_accounts(account_ids: &[String]) -> Result<String, String> {
-    let accounts: Vec<GitHubCopilotAccount> = account_ids
-        .iter()
-        .filter_map(|id| load_account_file(id))
-        .collect();
-    serde_json::to_string_pretty(&accounts).map_err(|e| format!("序列化失败: {}", e))
-}
-
-fn normalize_quota_alert_threshold(raw: i32) -> i32 {
-    raw.clamp(0, 100)
-}
-
-fn clamp_percent(value: f64) -> i32 {
-    value.round().clamp(0.0, 100.0) as i32
+    GitHubCopilotStore::export_accounts(account_ids)
 }
 
 fn parse_token_map(token: &str) -> HashMap<String, String> {
@@ -350,13 +275,6 @@ fn get_json_number(value: &serde_json::Value) -> Option<f64> {
     .filter(|value| value.is_finite())
 }
 
-fn calc_remaining_percent(remaining: f64, total: f64) -> Option<i32> {
-    if total <= 0.0 {
-        return None;
-    }
-    Some(clamp_percent((remaining.max(0.0) / total) * 100.0))
-}
-
 fn extract_limited_metrics(account: &GitHubCopilotAccount) -> Vec<(String, i32)> {
     let Some(limited) = account
         .copilot_limited_user_quotas
@@ -410,7 +328,7 @@ fn extract_premium_metric(account: &GitHubCopilotAccount) -> Option<(String, i32
     Some(("Premium Interactions".to_string(), percent_remaining))
 }
 
-fn extract_quota_metrics(account: &GitHubCopilotAccount) -> Vec<(String, i32)> {
+pub fn extract_quota_metrics(account: &GitHubCopilotAccount) -> Vec<(String, i32)> {
     let mut metrics = extract_limited_metrics(account);
     if let Some(premium) = extract_premium_metric(account) {
         metrics.push(premium);
@@ -418,15 +336,19 @@ fn extract_quota_metrics(account: &GitHubCopilotAccount) -> Vec<(String, i32)> {
     metrics
 }
 
-fn average_quota_percentage(metrics: &[(String, i32)]) -> f64 {
-    if metrics.is_empty() {
-        return 0.0;
-    }
-    let sum: i32 = metrics.iter().map(|(_, pct)| *pct).sum();
-    sum as f64 / metrics.len() as f64
+/// API 反向代理使用的剩余配额：取聊天相关指标（Chat Messages / Premium Interactions）中最紧的一个，
+/// 与模型无关；无配额数据时返回空
+pub fn proxy_quota_percentages(account: &GitHubCopilotAccount) -> Vec<(String, i32)> {
+    extract_quota_metrics(account)
+        .into_iter()
+        .filter(|(name, _)| name != "Inline Suggestions")
+        .map(|(_, pct)| pct)
+        .min()
+        .map(|pct| vec![("*".to_string(), pct)])
+        .unwrap_or_default()
 }
 
-fn resolve_current_account_id(accounts: &[GitHubCopilotAccount]) -> Option<String> {
+pub fn resolve_current_account_id(accounts: &[GitHubCopilotAccount]) -> Option<String> {
     if let Ok(settings) = crate::modules::github_copilot_instance::load_default_settings() {
         if let Some(bind_id) = settings.bind_account_id {
             let trimmed = bind_id.trim();
@@ -442,116 +364,7 @@ fn resolve_current_account_id(accounts: &[GitHubCopilotAccount]) -> Option<Strin
         .map(|account| account.id.clone())
 }
 
-fn display_email(account: &GitHubCopilotAccount) -> String {
-    account
-        .github_email
-        .clone()
-        .filter(|text| !text.trim().is_empty())
-        .unwrap_or_else(|| account.github_login.clone())
-}
-
-fn build_quota_alert_cooldown_key(account_id: &str, threshold: i32) -> String {
-    format!("github_copilot:{}:{}", account_id, threshold)
-}
-
-fn should_emit_quota_alert(cooldown_key: &str, now: i64) -> bool {
-    let Ok(mut state) = GHCP_QUOTA_ALERT_LAST_SENT.lock() else {
-        return true;
-    };
-
-    if let Some(last_sent) = state.get(cooldown_key) {
-        if now - *last_sent < GHCP_QUOTA_ALERT_COOLDOWN_SECONDS {
-            return false;
-        }
-    }
-
-    state.insert(cooldown_key.to_string(), now);
-    true
-}
-
-fn clear_quota_alert_cooldown(account_id: &str, threshold: i32) {
-    if let Ok(mut state) = GHCP_QUOTA_ALERT_LAST_SENT.lock() {
-        state.remove(&build_quota_alert_cooldown_key(account_id, threshold));
-    }
-}
-
-fn pick_quota_alert_recommendation(
-    accounts: &[GitHubCopilotAccount],
-    current_id: &str,
-) -> Option<GitHubCopilotAccount> {
-    let mut candidates: Vec<GitHubCopilotAccount> = accounts
-        .iter()
-        .filter(|account| account.id != current_id)
-        .filter(|account| !extract_quota_metrics(account).is_empty())
-        .cloned()
-        .collect();
-
-    if candidates.is_empty() {
-        return None;
-    }
-
-    candidates.sort_by(|a, b| {
-        let avg_a = average_quota_percentage(&extract_quota_metrics(a));
-        let avg_b = average_quota_percentage(&extract_quota_metrics(b));
-        avg_b
-            .partial_cmp(&avg_a)
-            .unwrap_or(std::cmp::Ordering::Equal)
-            .then_with(|| a.last_used.cmp(&b.last_used))
-    });
-
-    candidates.into_iter().next()
-}
-
 pub fn run_quota_alert_if_needed(
 ) -> Result<Option<crate::modules::account::QuotaAlertPayload>, String> {
-    let cfg = crate::modules::config::get_user_config();
-    if !cfg.ghcp_quota_alert_enabled {
-        return Ok(None);
-    }
-
-    let threshold = normalize_quota_alert_threshold(cfg.ghcp_quota_alert_threshold);
-    let accounts = list_accounts();
-    let current_id = match resolve_current_account_id(&accounts) {
-        Some(id) => id,
-        None => return Ok(None),
-    };
-
-    let current = match accounts.iter().find(|account| account.id == current_id) {
-        Some(account) => account,
-        None => return Ok(None),
-    };
-
-    let metrics = extract_quota_metrics(current);
-    let low_models: Vec<(String, i32)> = metrics
-        .into_iter()
-        .filter(|(_, pct)| *pct <= threshold)
-        .collect();
-
-    if low_models.is_empty() {
-        clear_quota_alert_cooldown(&current_id, threshold);
-        return Ok(None);
-    }
-
-    let now = chrono::Utc::now().timestamp();
-    let cooldown_key = build_quota_alert_cooldown_key(&current_id, threshold);
-    if !should_emit_quota_alert(&cooldown_key, now) {
-        return Ok(None);
-    }
-
-    let recommendation = pick_quota_alert_recommendation(&accounts, &current_id);
-    let lowest_percentage = low_models.iter().map(|(_, pct)| *pct).min().unwrap_or(0);
-    let payload = crate::modules::account::QuotaAlertPayload {
-        platform: "github_copilot".to_string(),
-        current_account_id: current_id,
-        current_email: display_email(current),
-        threshold,
-        lowest_percentage,
-        low_models: low_models.into_iter().map(|(name, _)| name).collect(),
-        recommended_account_id: recommendation.as_ref().map(|account| account.id.clone()),
-        recommended_email: recommendation.as_ref().map(display_email),
-        triggered_at: now,
-    };
-
-    crate::modules::account::dispatch_quota_alert(&payload);
-    Ok(Some(payload))
+    GitHubCopilotStore::run_quota_alert_if_needed()
 }
@@ -2,46 +2,90 @@ use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
-use chrono::Utc;
-use serde::{Deserialize, Serialize};
-use uuid::Uuid;
+use base64::{engine::general_purpose, Engine as _};
+use rusqlite::Connection;
 
-use crate::models::{DefaultInstanceSettings, InstanceProfile, InstanceStore};
+use crate::models::DefaultInstanceSettings;
 use crate::modules;
-use crate::modules::instance_store;
+use crate::modules::instance_manager::PlatformInstanceManager;
 
-pub use crate::modules::instance_store::{CreateInstanceParams, UpdateInstanceParams};
+static INSTANCE_STORE_LOCK: Mutex<()> = Mutex::new(());
 
-static INSTANCE_STORE_LOCK: std::sync::LazyLock<Mutex<()>> =
-    std::sync::LazyLock::new(|| Mutex::new(()));
+pub struct AntigravityInstances;
 
-const INSTANCES_FILE: &str = "instances.json";
+impl PlatformInstanceManager for AntigravityInstances {
+    const LABEL: &'static str = "Antigravity";
+    const INSTANCES_FILE: &'static str = "instances.json";
+    const INSTANCES_DIR: &'static str = "antigravity";
+    const FOLLOW_LOCAL_ACCOUNT: bool = true;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct InstanceDefaults {
-    pub root_dir: String,
-    pub default_user_data_dir: String,
+    fn store_lock() -> &'static Mutex<()> {
+        &INSTANCE_STORE_LOCK
+    }
+    fn default_user_data_dir() -> Result<PathBuf, String> {
+        get_default_user_data_dir()
+    }
+    fn collect_process_entries() -> Vec<(u32, Option<String>)> {
+        modules::process::collect_antigravity_process_entries()
+    }
+    fn resolve_pid_from_entries(
+        last_pid: Option<u32>,
+        user_data_dir: Option<&str>,
+        entries: &[(u32, Option<String>)],
+    ) -> Option<u32> {
+        modules::process::resolve_antigravity_pid_from_entries(last_pid, user_data_dir, entries)
+    }
+    fn resolve_pid(last_pid: Option<u32>, user_data_dir: Option<&str>) -> Option<u32> {
+        modules::process::resolve_antigravity_pid(last_pid, user_data_dir)
+    }
+    fn start(user_data_dir: &str, extra_args: &[String], _new_window: bool) -> Result<u32, String> {
+        modules::process::start_antigravity_with_args(user_data_dir, extra_args)
+    }
+    fn start_default(_extra_args: &[String], _new_window: bool) -> Result<u32, String> {
+        modules::process::start_antigravity()
+    }
+    fn focus(last_pid: Option<u32>, user_data_dir: Option<&str>) -> Result<u32, String> {
+        modules::process::focus_antigravity_instance(last_pid, user_data_dir)
+    }
+    fn close(user_data_dirs: &[String], timeout_secs: u64) -> Result<(), String> {
+        modules::process::close_antigravity_instances(user_data_dirs, timeout_secs)
+    }
+    async fn inject_account(profile_dir: &Path, account_id: &str) -> Result<(), String> {
+        let _ = modules::prepare_account_for_injection(account_id).await?;
+        inject_account_to_profile(profile_dir, account_id)
+    }
+    fn resolve_local_account_id() -> Option<String> {
+        resolve_local_account_id()
+    }
 }
 
-fn instances_path() -> Result<PathBuf, String> {
-    let data_dir = modules::account::get_data_dir()?;
-    Ok(data_dir.join(INSTANCES_FILE))
-}
+/// 从 Antigravity 本地数据库的 refresh_token 反查当前登录账号
+fn resolve_local_account_id() -> Option<String> {
+    let db_path = modules::db::get_db_path().ok()?;
+    let conn = Connection::open(&db_path).ok()?;
+    let state_data: String = conn
+        .query_row(
+            "SELECT value FROM ItemTable WHERE key = ?",
+            ["jetskiStateSync.agentManagerInitState"],
+            |row| row.get(0),
+        )
+        .ok()?;
 
-pub fn load_instance_store() -> Result<InstanceStore, String> {
-    let path = instances_path()?;
-    instance_store::load_instance_store(&path, INSTANCES_FILE)
-}
+    let blob = general_purpose::STANDARD.decode(&state_data).ok()?;
+    let local_refresh_token = match crate::utils::protobuf::extract_refresh_token(&blob) {
+        Some(token) if !token.is_empty() => token,
+        _ => return None,
+    };
 
-pub fn save_instance_store(store: &InstanceStore) -> Result<(), String> {
-    let path = instances_path()?;
-    instance_store::save_instance_store(&path, INSTANCES_FILE, store)
+    let accounts = modules::list_accounts().ok()?;
+    accounts
+        .into_iter()
+        .find(|account| account.token.refresh_token == local_refresh_token)
+        .map(|account| account.id)
 }
 
 pub fn load_default_settings() -> Result<DefaultInstanceSettings, String> {
-    let store = load_instance_store()?;
-    Ok(store.default_settings)
+    AntigravityInstances::load_default_settings()
 }
 
 pub fn update_default_settings(
@@ -49,31 +93,11 @@ pub fn update_default_settings(
     extra_args: Option<String>,
     follow_local_account: Option<bool>,
 ) -> Result<DefaultInstanceSettings, String> {
-    let _lock = INSTANCE_STORE_LOCK.lock().map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let settings = &mut store.default_settings;
-
-    if follow_local_account == Some(true) {
-        settings.follow_local_account = true;
-        settings.bind_account_id = None;
-    }
-
-    if let Some(bind) = bind_account_id {
-        settings.bind_account_id = bind;
-        settings.follow_local_account = false;
-    }
-
-    if follow_local_account == Some(false) && settings.bind_account_id.is_none() {
-        settings.follow_local_account = false;
-    }
-
-    if let Some(args) = extra_args {
-        settings.extra_args = args.trim().to_string();
-    }
+    AntigravityInstances::update_default_settings(bind_account_id, extra_args, follow_local_account)
+}
 
-    let updated = settings.clone();
-    save_instance_store(&store)?;
-    Ok(updated)
+pub fn update_default_pid(pid: Option<u32>) -> Result<DefaultInstanceSettings, String> {
+    AntigravityInstances::update_default_pid(pid)
 }
 
 pub fn get_default_user_data_dir() -> Result<PathBuf, String> {
@@ -100,64 +124,6 @@ pub fn get_default_user_data_dir() -> Result<PathBuf, String> {
     Err("无法确定 Antigravity 默认目录".to_string())
 }
 
-pub fn get_default_instances_root_dir() -> Result<PathBuf, String> {
-    #[cfg(target_os = "macos")]
-    {
-        let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
-        return Ok(home.join(".antigravity_cockpit/instances/antigravity"));
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        let appdata =
-            std::env::var("APPDATA").map_err(|_| "无法获取 APPDATA 环境变量".to_string())?;
-        return Ok(PathBuf::from(appdata).join(".antigravity_cockpit\\instances\\antigravity"));
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-        let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
-        return Ok(home.join(".antigravity_cockpit/instances/antigravity"));
-    }
-
-    #[allow(unreachable_code)]
-    Err("无法确定默认实例目录".to_string())
-}
-
-pub fn get_instance_defaults() -> Result<InstanceDefaults, String> {
-    let root_dir = get_default_instances_root_dir()?;
-    let default_user_data_dir = get_default_user_data_dir()?;
-    Ok(InstanceDefaults {
-        root_dir: root_dir.to_string_lossy().to_string(),
-        default_user_data_dir: default_user_data_dir.to_string_lossy().to_string(),
-    })
-}
-
-fn is_ignored_entry_name(name: &str) -> bool {
-    matches!(name, ".DS_Store" | "Thumbs.db" | "desktop.ini")
-}
-
-pub fn is_profile_initialized(profile_dir: &Path) -> bool {
-    if !profile_dir.exists() {
-        return false;
-    }
-    if !profile_dir.is_dir() {
-        return false;
-    }
-    let Ok(entries) = fs::read_dir(profile_dir) else {
-        return false;
-    };
-    for entry in entries.flatten() {
-        let file_name = entry.file_name();
-        let name = file_name.to_string_lossy();
-        if is_ignored_entry_name(&name) {
-            continue;
-        }
-        return true;
-    }
-    false
-}
-
 fn ensure_profile_global_storage(profile_dir: &Path) -> Result<PathBuf, String> {
     let global_storage = profile_dir.join("User").join("globalStorage");
     if !global_storage.exists() {
@@ -216,255 +182,3 @@ pub fn inject_account_to_profile(profile_dir: &Path, account_id: &str) -> Result
     )
     .map(|_| ())
 }
-
-pub fn create_instance(params: CreateInstanceParams) -> Result<InstanceProfile, String> {
-    let _lock = INSTANCE_STORE_LOCK.lock().map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-
-    let name = instance_store::normalize_name(&params.name)?;
-    let user_data_dir = params.user_data_dir.trim().to_string();
-    if user_data_dir.is_empty() {
-        return Err("实例目录不能为空".to_string());
-    }
-
-    instance_store::ensure_unique(&store, &name, &user_data_dir, None)?;
-
-    let user_dir_path = PathBuf::from(&user_data_dir);
-
-    let init_mode = params
-        .init_mode
-        .as_deref()
-        .unwrap_or("copy")
-        .to_ascii_lowercase();
-    let create_empty = init_mode == "empty";
-
-    if create_empty {
-        if user_dir_path.exists() {
-            let mut has_entries = false;
-            if let Ok(mut iter) = fs::read_dir(&user_dir_path) {
-                if iter.next().is_some() {
-                    has_entries = true;
-                }
-            }
-            if has_entries {
-                let resolved_path = instance_store::display_path(&user_dir_path);
-                return Err(format!("空白实例需要目标目录为空: {}", resolved_path));
-            }
-        }
-        fs::create_dir_all(&user_dir_path).map_err(|e| format!("创建实例目录失败: {}", e))?;
-    } else {
-        let source_dir = match params.copy_source_instance_id.as_deref() {
-            Some("__default__") | None => get_default_user_data_dir()?,
-            Some(source_id) => {
-                let source_instance = store
-                    .instances
-                    .iter()
-                    .find(|item| item.id == source_id)
-                    .ok_or("复制来源实例不存在")?;
-                PathBuf::from(&source_instance.user_data_dir)
-            }
-        };
-
-        if user_dir_path.exists() {
-            let mut has_entries = false;
-            if let Ok(mut iter) = fs::read_dir(&user_dir_path) {
-                if iter.next().is_some() {
-                    has_entries = true;
-                }
-            }
-            if has_entries {
-                let resolved_path = instance_store::display_path(&user_dir_path);
-                modules::logger::log_info(&format!(
-                    "[Instance] 复制来源实例需要空目录，但目标已存在: {}",
-                    resolved_path
-                ));
-                return Err(format!("复制来源实例需要目标目录为空: {}", resolved_path));
-            }
-        }
-
-        instance_store::copy_dir_recursive(&source_dir, &user_dir_path)?;
-    }
-
-    let instance = InstanceProfile {
-        id: Uuid::new_v4().to_string(),
-        name,
-        user_data_dir,
-        extra_args: params.extra_args.trim().to_string(),
-        bind_account_id: if create_empty {
-            None
-        } else {
-            params.bind_account_id
-        },
-        created_at: Utc::now().timestamp_millis(),
-        last_launched_at: None,
-        last_pid: None,
-    };
-
-    store.instances.push(instance.clone());
-    save_instance_store(&store)?;
-    Ok(instance)
-}
-
-pub fn update_instance(params: UpdateInstanceParams) -> Result<InstanceProfile, String> {
-    let _lock = INSTANCE_STORE_LOCK.lock().map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let index = store
-        .instances
-        .iter()
-        .position(|instance| instance.id == params.instance_id)
-        .ok_or("实例不存在")?;
-
-    let current_id = store.instances[index].id.clone();
-    let current_dir = store.instances[index].user_data_dir.clone();
-    let next_name = params
-        .name
-        .as_ref()
-        .map(|name| instance_store::normalize_name(name))
-        .transpose()?;
-
-    if let Some(ref normalized) = next_name {
-        instance_store::ensure_unique(&store, normalized, &current_dir, Some(&current_id))?;
-    }
-
-    let instance = &mut store.instances[index];
-    if let Some(normalized) = next_name {
-        instance.name = normalized;
-    }
-    if let Some(ref extra_args) = params.extra_args {
-        instance.extra_args = extra_args.trim().to_string();
-    }
-    if let Some(bind) = params.bind_account_id.clone() {
-        instance.bind_account_id = bind;
-    }
-
-    let updated = instance.clone();
-    save_instance_store(&store)?;
-    Ok(updated)
-}
-
-pub fn delete_instance(instance_id: &str) -> Result<(), String> {
-    let _lock = INSTANCE_STORE_LOCK.lock().map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let index = store
-        .instances
-        .iter()
-        .position(|instance| instance.id == instance_id)
-        .ok_or("实例不存在")?;
-    let user_data_dir = store.instances[index].user_data_dir.clone();
-
-    if !user_data_dir.trim().is_empty() {
-        let dir_path = PathBuf::from(&user_data_dir);
-        delete_instance_directory(&dir_path)?;
-    }
-
-    store.instances.remove(index);
-    save_instance_store(&store)?;
-    Ok(())
-}
-
-pub fn delete_instance_directory(dir_path: &Path) -> Result<(), String> {
-    if !dir_path.exists() {
-        return Ok(());
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
-        let trash_dir = home.join(".Trash");
-        fs::create_dir_all(&trash_dir).map_err(|err| format!("创建废纸篓目录失败: {}", err))?;
-        let base_name = dir_path
-            .file_name()
-            .map(|name| name.to_string_lossy().to_string())
-            .filter(|name| !name.is_empty())
-            .ok_or("实例目录无效")?;
-        let mut target = trash_dir.join(&base_name);
-        if target.exists() {
-            let suffix = Utc::now().timestamp_millis();
-            target = trash_dir.join(format!("{}-{}", base_name, suffix));
-        }
-        match fs::rename(dir_path, &target) {
-            Ok(()) => Ok(()),
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
-            Err(err) => Err(format!("移动实例目录到废纸篓失败: {}", err)),
-        }
-    }
-
-    #[cfg(not(target_os = "macos"))]
-    {
-        match fs::remove_dir_all(dir_path) {
-            Ok(()) => Ok(()),
-            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
-            Err(err) => Err(format!("删除实例目录失败: {}", err)),
-        }
-    }
-}
-
-#[allow(dead_code)]
-pub fn update_instance_last_launched(instance_id: &str) -> Result<InstanceProfile, String> {
-    let _lock = INSTANCE_STORE_LOCK.lock().map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let mut updated = None;
-    for instance in &mut store.instances {
-        if instance.id == instance_id {
-            instance.last_launched_at = Some(Utc::now().timestamp_millis());
-            updated = Some(instance.clone());
-            break;
-        }
-    }
-    let updated = updated.ok_or("实例不存在")?;
-    save_instance_store(&store)?;
-    Ok(updated)
-}
-
-pub fn update_instance_after_start(instance_id: &str, pid: u32) -> Result<InstanceProfile, String> {
-    let _lock = INSTANCE_STORE_LOCK.lock().map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let mut updated = None;
-    for instance in &mut store.instances {
-        if instance.id == instance_id {
-            instance.last_launched_at = Some(Utc::now().timestamp_millis());
-            instance.last_pid = Some(pid);
-            updated = Some(instance.clone());
-            break;
-        }
-    }
-    let updated = updated.ok_or("实例不存在")?;
-    save_instance_store(&store)?;
-    Ok(updated)
-}
-
-pub fn update_instance_pid(instance_id: &str, pid: Option<u32>) -> Result<InstanceProfile, String> {
-    let _lock = INSTANCE_STORE_LOCK.lock().map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    let mut updated = None;
-    for instance in &mut store.instances {
-        if instance.id == instance_id {
-            instance.last_pid = pid;
-            updated = Some(instance.clone());
-            break;
-        }
-    }
-    let updated = updated.ok_or("实例不存在")?;
-    save_instance_store(&store)?;
-    Ok(updated)
-}
-
-pub fn update_default_pid(pid: Option<u32>) -> Result<DefaultInstanceSettings, String> {
-    let _lock = INSTANCE_STORE_LOCK.lock().map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    store.default_settings.last_pid = pid;
-    let updated = store.default_settings.clone();
-    save_instance_store(&store)?;
-    Ok(updated)
-}
-
-pub fn clear_all_pids() -> Result<(), String> {
-    let _lock = INSTANCE_STORE_LOCK.lock().map_err(|_| "无法获取实例锁")?;
-    let mut store = load_instance_store()?;
-    store.default_settings.last_pid = None;
-    for instance in &mut store.instances {
-        instance.last_pid = None;
-    }
-    save_instance_store(&store)?;
-    Ok(())
-}
@@ -0,0 +1,829 @@
+//! 多平台多开实例管理
+//! 各平台实例统一以「<平台>_instances.json」存储，实例增删改、PID 记录、默认实例设置，
+//! 以及启动/停止/定位窗口/全部关闭流程在此统一实现；
+//! 新平台只需实现 PlatformInstanceManager 中的启动器与账号注入钩子
+
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::{DefaultInstanceSettings, InstanceProfile, InstanceProfileView, InstanceStore};
+use crate::modules::{account, instance_store, logger, process};
+
+pub use crate::modules::instance_store::{CreateInstanceParams, UpdateInstanceParams};
+
+pub const DEFAULT_INSTANCE_ID: &str = "__default__";
+
+const CLOSE_TIMEOUT_SECS: u64 = 20;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceDefaults {
+    pub root_dir: String,
+    pub default_user_data_dir: String,
+}
+
+/// 支持多开实例的平台，作为统一实例命令的 platform 参数
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstancePlatform {
+    Antigravity,
+    Codex,
+    GithubCopilot,
+    Windsurf,
+    Kiro,
+}
+
+fn is_ignored_entry_name(name: &str) -> bool {
+    matches!(name, ".DS_Store" | "Thumbs.db" | "desktop.ini")
+}
+
+pub fn is_profile_initialized(profile_dir: &Path) -> bool {
+    if !profile_dir.exists() {
+        return false;
+    }
+    if !profile_dir.is_dir() {
+        return false;
+    }
+    let Ok(entries) = fs::read_dir(profile_dir) else {
+        return false;
+    };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let name = file_name.to_string_lossy();
+        if is_ignored_entry_name(&name) {
+            continue;
+        }
+        return true;
+    }
+    false
+}
+
+fn dir_has_entries(path: &Path) -> bool {
+    fs::read_dir(path)
+        .map(|mut iter| iter.next().is_some())
+        .unwrap_or(false)
+}
+
+pub fn delete_instance_directory(dir_path: &Path) -> Result<(), String> {
+    if !dir_path.exists() {
+        return Ok(());
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
+        let trash_dir = home.join(".Trash");
+        fs::create_dir_all(&trash_dir).map_err(|err| format!("创建废纸篓目录失败: {}", err))?;
+        let base_name = dir_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .filter(|name| !name.is_empty())
+            .ok_or("实例目录无效")?;
+        let mut target = trash_dir.join(&base_name);
+        if target.exists() {
+            let suffix = Utc::now().timestamp_millis();
+            target = trash_dir.join(format!("{}-{}", base_name, suffix));
+        }
+        match fs::rename(dir_path, &target) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(format!("移动实例目录到废纸篓失败: {}", err)),
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        match fs::remove_dir_all(dir_path) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(format!("删除实例目录失败: {}", err)),
+        }
+    }
+}
+
+/// 多开实例默认根目录：~/.antigravity_cockpit/instances/<dir_name>（Windows 位于 %APPDATA% 下）
+pub fn default_instances_root_dir(dir_name: &str) -> Result<PathBuf, String> {
+    #[cfg(target_os = "windows")]
+    {
+        let appdata =
+            std::env::var("APPDATA").map_err(|_| "无法获取 APPDATA 环境变量".to_string())?;
+        return Ok(PathBuf::from(appdata)
+            .join(".antigravity_cockpit")
+            .join("instances")
+            .join(dir_name));
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    {
+        let home = dirs::home_dir().ok_or("无法获取用户主目录")?;
+        return Ok(home
+            .join(".antigravity_cockpit")
+            .join("instances")
+            .join(dir_name));
+    }
+
+    #[allow(unreachable_code)]
+    Err("无法确定默认实例目录".to_string())
+}
+
+/// 平台多开实例管理
+pub trait PlatformInstanceManager {
+    /// 平台显示名，用于日志
+    const LABEL: &'static str;
+    const INSTANCES_FILE: &'static str;
+    /// 实例根目录名，见 default_instances_root_dir
+    const INSTANCES_DIR: &'static str;
+    /// 默认实例是否支持「跟随当前账号」
+    const FOLLOW_LOCAL_ACCOUNT: bool = false;
+    /// 启动前是否按实例目录关闭残留进程（注入前需确保本地数据库可写）
+    const CLOSE_BEFORE_START: bool = false;
+
+    fn store_lock() -> &'static Mutex<()>;
+    fn default_user_data_dir() -> Result<PathBuf, String>;
+
+    fn collect_process_entries() -> Vec<(u32, Option<String>)>;
+    fn resolve_pid_from_entries(
+        last_pid: Option<u32>,
+        user_data_dir: Option<&str>,
+        entries: &[(u32, Option<String>)],
+    ) -> Option<u32>;
+    fn resolve_pid(last_pid: Option<u32>, user_data_dir: Option<&str>) -> Option<u32>;
+    fn start(user_data_dir: &str, extra_args: &[String], new_window: bool) -> Result<u32, String>;
+    fn start_default(extra_args: &[String], new_window: bool) -> Result<u32, String>;
+    fn focus(last_pid: Option<u32>, user_data_dir: Option<&str>) -> Result<u32, String>;
+    fn close(user_data_dirs: &[String], timeout_secs: u64) -> Result<(), String>;
+
+    /// 将账号凭据写入实例目录
+    fn inject_account(
+        profile_dir: &Path,
+        account_id: &str,
+    ) -> impl Future<Output = Result<(), String>> + Send;
+
+    /// 默认实例跟随当前账号时，解析本地当前登录的账号 ID
+    fn resolve_local_account_id() -> Option<String> {
+        None
+    }
+
+    fn is_running(pid: u32, _user_data_dir: Option<&str>) -> bool {
+        process::is_pid_running(pid)
+    }
+
+    fn data_dir() -> Result<PathBuf, String> {
+        account::get_data_dir()
+    }
+
+    fn instances_root_dir() -> Result<PathBuf, String> {
+        default_instances_root_dir(Self::INSTANCES_DIR)
+    }
+
+    fn lock_store() -> Result<MutexGuard<'static, ()>, String> {
+        Self::store_lock()
+            .lock()
+            .map_err(|_| "无法获取实例锁".to_string())
+    }
+
+    fn instances_path() -> Result<PathBuf, String> {
+        Ok(Self::data_dir()?.join(Self::INSTANCES_FILE))
+    }
+
+    fn load_instance_store() -> Result<InstanceStore, String> {
+        let path = Self::instances_path()?;
+        instance_store::load_instance_store(&path, Self::INSTANCES_FILE)
+    }
+
+    fn save_instance_store(store: &InstanceStore) -> Result<(), String> {
+        let path = Self::instances_path()?;
+        instance_store::save_instance_store(&path, Self::INSTANCES_FILE, store)
+    }
+
+    fn load_default_settings() -> Result<DefaultInstanceSettings, String> {
+        Ok(Self::load_instance_store()?.default_settings)
+    }
+
+    fn update_default_settings(
+        bind_account_id: Option<Option<String>>,
+        extra_args: Option<String>,
+        follow_local_account: Option<bool>,
+    ) -> Result<DefaultInstanceSettings, String> {
+        let _lock = Self::lock_store()?;
+        let mut store = Self::load_instance_store()?;
+        let settings = &mut store.default_settings;
+
+        match follow_local_account {
+            Some(true) if Self::FOLLOW_LOCAL_ACCOUNT => {
+                settings.follow_local_account = true;
+                settings.bind_account_id = None;
+            }
+            // 不支持「跟随当前账号」的平台直接忽略开启请求
+            Some(_) => settings.follow_local_account = false,
+            None => {}
+        }
+
+        if let Some(bind) = bind_account_id {
+            settings.bind_account_id = bind;
+            settings.follow_local_account = false;
+        }
+
+        if let Some(args) = extra_args {
+            settings.extra_args = args.trim().to_string();
+        }
+
+        let updated = settings.clone();
+        Self::save_instance_store(&store)?;
+        Ok(updated)
+    }
+
+    fn get_instance_defaults() -> Result<InstanceDefaults, String> {
+        let root_dir = Self::instances_root_dir()?;
+        let default_user_data_dir = Self::default_user_data_dir()?;
+        Ok(InstanceDefaults {
+            root_dir: root_dir.to_string_lossy().to_string(),
+            default_user_data_dir: default_user_data_dir.to_string_lossy().to_string(),
+        })
+    }
+
+    fn find_instance(instance_id: &str) -> Result<InstanceProfile, String> {
+        Self::load_instance_store()?
+            .instances
+            .into_iter()
+            .find(|item| item.id == instance_id)
+            .ok_or_else(|| "实例不存在".to_string())
+    }
+
+    fn create_instance(params: CreateInstanceParams) -> Result<InstanceProfile, String> {
+        let _lock = Self::lock_store()?;
+        let mut store = Self::load_instance_store()?;
+
+        let name = instance_store::normalize_name(&params.name)?;
+        let user_data_dir = params.user_data_dir.trim().to_string();
+        if user_data_dir.is_empty() {
+            return Err("实例目录不能为空".to_string());
+        }
+
+        instance_store::ensure_unique(&store, &name, &user_data_dir, None)?;
+
+        let user_dir_path = PathBuf::from(&user_data_dir);
+        let create_empty = params
+            .init_mode
+            .as_deref()
+            .unwrap_or("copy")
+            .eq_ignore_ascii_case("empty");
+
+        if create_empty {
+            if user_dir_path.exists() && dir_has_entries(&user_dir_path) {
+                let resolved_path = instance_store::display_path(&user_dir_path);
+                return Err(format!("空白实例需要目标目录为空: {}", resolved_path));
+            }
+            fs::create_dir_all(&user_dir_path).map_err(|e| format!("创建实例目录失败: {}", e))?;
+        } else {
+            let source_dir = match params.copy_source_instance_id.as_deref() {
+                Some(DEFAULT_INSTANCE_ID) | None => Self::default_user_data_dir()?,
+                Some(source_id) => {
+                    let source_instance = store
+                        .instances
+                        .iter()
+                        .find(|item| item.id == source_id)
+                        .ok_or("复制来源实例不存在")?;
+                    PathBuf::from(&source_instance.user_data_dir)
+                }
+            };
+
+            if user_dir_path.exists() && dir_has_entries(&user_dir_path) {
+                let resolved_path = instance_store::display_path(&user_dir_path);
+                logger::log_info(&format!(
+                    "[{} Instance] 复制来源实例需要空目录，但目标已存在: {}",
+                    Self::LABEL,
+                    resolved_path
+                ));
+                return Err(format!("复制来源实例需要目标目录为空: {}", resolved_path));
+            }
+
+            if !source_dir.exists() {
+                return Err("未找到复制来源目录，请先确保来源实例已初始化".to_string());
+            }
+
+            instance_store::copy_dir_recursive(&source_dir, &user_dir_path)?;
+        }
+
+        let instance = InstanceProfile {
+            id: Uuid::new_v4().to_string(),
+            name,
+            user_data_dir,
+            extra_args: params.extra_args.trim().to_string(),
+            bind_account_id: if create_empty {
+                None
+            } else {
+                params.bind_account_id
+            },
+            created_at: Utc::now().timestamp_millis(),
+            last_launched_at: None,
+            last_pid: None,
+        };
+
+        store.instances.push(instance.clone());
+        Self::save_instance_store(&store)?;
+        Ok(instance)
+    }
+
+    fn update_instance(params: UpdateInstanceParams) -> Result<InstanceProfile, String> {
+        let _lock = Self::lock_store()?;
+        let mut store = Self::load_instance_store()?;
+        let index = store
+            .instances
+            .iter()
+            .position(|instance| instance.id == params.instance_id)
+            .ok_or("实例不存在")?;
+
+        let current_id = store.instances[index].id.clone();
+        let current_dir = store.instances[index].user_data_dir.clone();
+        let next_name = params
+            .name
+            .as_ref()
+            .map(|name| instance_store::normalize_name(name))
+            .transpose()?;
+
+        if let Some(ref normalized) = next_name {
+            instance_store::ensure_unique(&store, normalized, &current_dir, Some(&current_id))?;
+        }
+
+        let instance = &mut store.instances[index];
+        if let Some(normalized) = next_name {
+            instance.name = normalized;
+        }
+        if let Some(ref extra_args) = params.extra_args {
+            instance.extra_args = extra_args.trim().to_string();
+        }
+        if let Some(bind) = params.bind_account_id {
+            instance.bind_account_id = bind;
+        }
+
+        let updated = instance.clone();
+        Self::save_instance_store(&store)?;
+        Ok(updated)
+    }
+
+    fn delete_instance(instance_id: &str) -> Result<(), String> {
+        if instance_id == DEFAULT_INSTANCE_ID {
+            return Err("默认实例不可删除".to_string());
+        }
+        let _lock = Self::lock_store()?;
+        let mut store = Self::load_instance_store()?;
+        let index = store
+            .instances
+            .iter()
+            .position(|instance| instance.id == instance_id)
+            .ok_or("实例不存在")?;
+        let user_data_dir = store.instances[index].user_data_dir.clone();
+
+        if !user_data_dir.trim().is_empty() {
+            delete_instance_directory(Path::new(&user_data_dir))?;
+        }
+
+        store.instances.remove(index);
+        Self::save_instance_store(&store)?;
+        Ok(())
+    }
+
+    fn modify_instance(
+        instance_id: &str,
+        apply: impl FnOnce(&mut InstanceProfile),
+    ) -> Result<InstanceProfile, String> {
+        let _lock = Self::lock_store()?;
+        let mut store = Self::load_instance_store()?;
+        let instance = store
+            .instances
+            .iter_mut()
+            .find(|instance| instance.id == instance_id)
+            .ok_or("实例不存在")?;
+        apply(instance);
+        let updated = instance.clone();
+        Self::save_instance_store(&store)?;
+        Ok(updated)
+    }
+
+    fn update_instance_after_start(instance_id: &str, pid: u32) -> Result<InstanceProfile, String> {
+        Self::modify_instance(instance_id, |instance| {
+            instance.last_launched_at = Some(Utc::now().timestamp_millis());
+            instance.last_pid = Some(pid);
+        })
+    }
+
+    fn update_instance_pid(instance_id: &str, pid: Option<u32>) -> Result<InstanceProfile, String> {
+        Self::modify_instance(instance_id, |instance| instance.last_pid = pid)
+    }
+
+    fn update_default_pid(pid: Option<u32>) -> Result<DefaultInstanceSettings, String> {
+        let _lock = Self::lock_store()?;
+        let mut store = Self::load_instance_store()?;
+        store.default_settings.last_pid = pid;
+        let updated = store.default_settings.clone();
+        Self::save_instance_store(&store)?;
+        Ok(updated)
+    }
+
+    fn clear_all_pids() -> Result<(), String> {
+        let _lock = Self::lock_store()?;
+        let mut store = Self::load_instance_store()?;
+        store.default_settings.last_pid = None;
+        for instance in &mut store.instances {
+            instance.last_pid = None;
+        }
+        Self::save_instance_store(&store)
+    }
+
+    fn default_account_id(settings: &DefaultInstanceSettings) -> Option<String> {
+        if Self::FOLLOW_LOCAL_ACCOUNT && settings.follow_local_account {
+            Self::resolve_local_account_id()
+        } else {
+            settings.bind_account_id.clone()
+        }
+    }
+
+    fn default_view(
+        settings: &DefaultInstanceSettings,
+        last_pid: Option<u32>,
+        running: bool,
+    ) -> Result<InstanceProfileView, String> {
+        let default_dir = Self::default_user_data_dir()?;
+        Ok(InstanceProfileView {
+            id: DEFAULT_INSTANCE_ID.to_string(),
+            name: String::new(),
+            user_data_dir: default_dir.to_string_lossy().to_string(),
+            extra_args: settings.extra_args.clone(),
+            bind_account_id: Self::default_account_id(settings),
+            created_at: 0,
+            last_launched_at: None,
+            last_pid,
+            running,
+            initialized: is_profile_initialized(&default_dir),
+            is_default: true,
+            follow_local_account: Self::FOLLOW_LOCAL_ACCOUNT && settings.follow_local_account,
+        })
+    }
+
+    fn instance_view(instance: InstanceProfile, running: bool) -> InstanceProfileView {
+        let initialized = is_profile_initialized(Path::new(&instance.user_data_dir));
+        InstanceProfileView::from_profile(instance, running, initialized)
+    }
+
+    /// 实例列表（末尾附带默认实例），运行状态按当前进程实时解析
+    fn list_instance_views() -> Result<Vec<InstanceProfileView>, String> {
+        let store = Self::load_instance_store()?;
+        let process_entries = Self::collect_process_entries();
+        let mut result: Vec<InstanceProfileView> = store
+            .instances
+            .into_iter()
+            .map(|instance| {
+                let resolved_pid = Self::resolve_pid_from_entries(
+                    instance.last_pid,
+                    Some(&instance.user_data_dir),
+                    &process_entries,
+                );
+                let mut view = Self::instance_view(instance, resolved_pid.is_some());
+                view.last_pid = resolved_pid;
+                view
+            })
+            .collect();
+
+        let default_pid =
+            Self::resolve_pid_from_entries(store.default_settings.last_pid, None, &process_entries);
+        result.push(Self::default_view(
+            &store.default_settings,
+            default_pid,
+            default_pid.is_some(),
+        )?);
+        Ok(result)
+    }
+
+    fn create_instance_view(params: CreateInstanceParams) -> Result<InstanceProfileView, String> {
+        let instance = Self::create_instance(params)?;
+        Ok(Self::instance_view(instance, false))
+    }
+
+    fn update_instance_view(
+        params: UpdateInstanceParams,
+        follow_local_account: Option<bool>,
+    ) -> Result<InstanceProfileView, String> {
+        if params.instance_id == DEFAULT_INSTANCE_ID {
+            let updated = Self::update_default_settings(
+                params.bind_account_id,
+                params.extra_args,
+                follow_local_account,
+            )?;
+            let running = updated
+                .last_pid
+                .map(|pid| Self::is_running(pid, None))
+                .unwrap_or(false);
+            return Self::default_view(&updated, updated.last_pid, running);
+        }
+
+        let wants_bind = params
+            .bind_account_id
+            .as_ref()
+            .and_then(|next| next.as_ref())
+            .is_some();
+        if wants_bind {
+            if let Ok(target) = Self::find_instance(&params.instance_id) {
+                if !is_profile_initialized(Path::new(&target.user_data_dir)) {
+                    return Err(
+                        "INSTANCE_NOT_INITIALIZED:请先启动一次实例创建数据后，再进行账号绑定"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+
+        let instance = Self::update_instance(params)?;
+        let running = instance
+            .last_pid
+            .map(|pid| Self::is_running(pid, Some(&instance.user_data_dir)))
+            .unwrap_or(false);
+        Ok(Self::instance_view(instance, running))
+    }
+
+    /// 启动前准备实例目录：按需关闭残留进程并注入绑定账号
+    fn prepare_profile_for_start(
+        user_data_dir: &str,
+        account_id: Option<&str>,
+    ) -> impl Future<Output = Result<(), String>> + Send {
+        async move {
+            if Self::CLOSE_BEFORE_START {
+                Self::close(&[user_data_dir.to_string()], CLOSE_TIMEOUT_SECS)?;
+            }
+            let Some(account_id) = account_id.map(str::trim).filter(|id| !id.is_empty()) else {
+                return Ok(());
+            };
+            logger::log_info(&format!(
+                "{} 实例启动检测到绑定账号，准备注入: account_id={}, user_data_dir={}",
+                Self::LABEL,
+                account_id,
+                user_data_dir
+            ));
+            Self::inject_account(Path::new(user_data_dir), account_id).await?;
+            logger::log_info(&format!("{} 账号注入完成: {}", Self::LABEL, account_id));
+            Ok(())
+        }
+    }
+
+    /// 启动实例；已在运行时先关闭再以绑定账号重新启动
+    fn start_instance(
+        instance_id: String,
+    ) -> impl Future<Output = Result<InstanceProfileView, String>> + Send {
+        async move {
+            logger::log_info(&format!("开始启动 {} 实例: {}", Self::LABEL, instance_id));
+            if instance_id == DEFAULT_INSTANCE_ID {
+                let default_dir = Self::default_user_data_dir()?;
+                let settings = Self::load_default_settings()?;
+                if let Some(pid) = Self::resolve_pid(settings.last_pid, None) {
+                    process::close_pid(pid, CLOSE_TIMEOUT_SECS)?;
+                    Self::update_default_pid(None)?;
+                }
+                let account_id = Self::default_account_id(&settings);
+                Self::prepare_profile_for_start(
+                    &default_dir.to_string_lossy(),
+                    account_id.as_deref(),
+                )
+                .await?;
+                let extra_args = process::parse_extra_args(&settings.extra_args);
+                let pid = Self::start_default(&extra_args, true)?;
+                logger::log_info(&format!("{} 默认实例已启动: pid={}", Self::LABEL, pid));
+                Self::update_default_pid(Some(pid))?;
+                return Self::default_view(&settings, Some(pid), Self::is_running(pid, None));
+            }
+
+            let instance = Self::find_instance(&instance_id)?;
+            if let Some(pid) = Self::resolve_pid(instance.last_pid, Some(&instance.user_data_dir)) {
+                process::close_pid(pid, CLOSE_TIMEOUT_SECS)?;
+                Self::update_instance_pid(&instance.id, None)?;
+            }
+            Self::prepare_profile_for_start(
+                &instance.user_data_dir,
+                instance.bind_account_id.as_deref(),
+            )
+            .await?;
+
+            let extra_args = process::parse_extra_args(&instance.extra_args);
+            let pid = Self::start(&instance.user_data_dir, &extra_args, true)?;
+            logger::log_info(&format!(
+                "{} 实例已启动: instance_id={}, pid={}",
+                Self::LABEL,
+                instance.id,
+                pid
+            ));
+            let updated = Self::update_instance_after_start(&instance.id, pid)?;
+            let running = Self::is_running(pid, Some(&updated.user_data_dir));
+            Ok(Self::instance_view(updated, running))
+        }
+    }
+
+    fn stop_instance(instance_id: &str) -> Result<InstanceProfileView, String> {
+        if instance_id == DEFAULT_INSTANCE_ID {
+            let settings = Self::load_default_settings()?;
+            if let Some(pid) = Self::resolve_pid(settings.last_pid, None) {
+                process::close_pid(pid, CLOSE_TIMEOUT_SECS)?;
+            }
+            let updated = Self::update_default_pid(None)?;
+            return Self::default_view(&updated, None, false);
+        }
+
+        let instance = Self::find_instance(instance_id)?;
+        if let Some(pid) = Self::resolve_pid(instance.last_pid, Some(&instance.user_data_dir)) {
+            process::close_pid(pid, CLOSE_TIMEOUT_SECS)?;
+        }
+        let updated = Self::update_instance_pid(&instance.id, None)?;
+        Ok(Self::instance_view(updated, false))
+    }
+
+    /// 定位实例窗口；定位失败时回退为在当前窗口启动实例
+    fn open_instance_window(instance_id: &str) -> Result<(), String> {
+        if instance_id == DEFAULT_INSTANCE_ID {
+            let settings = Self::load_default_settings()?;
+            if let Err(err) = Self::focus(settings.last_pid, None) {
+                logger::log_warn(&format!(
+                    "定位 {} 默认实例窗口失败，回退为启动实例: {}",
+                    Self::LABEL,
+                    err
+                ));
+                let extra_args = process::parse_extra_args(&settings.extra_args);
+                let pid = Self::start_default(&extra_args, false)?;
+                Self::update_default_pid(Some(pid))?;
+            }
+            return Ok(());
+        }
+
+        let instance = Self::find_instance(instance_id)?;
+        if let Err(err) = Self::focus(instance.last_pid, Some(&instance.user_data_dir)) {
+            logger::log_warn(&format!(
+                "定位 {} 实例窗口失败，回退为启动实例: instance_id={}, err={}",
+                Self::LABEL,
+                instance.id,
+                err
+            ));
+            let extra_args = process::parse_extra_args(&instance.extra_args);
+            let pid = Self::start(&instance.user_data_dir, &extra_args, false)?;
+            Self::update_instance_after_start(&instance.id, pid)?;
+        }
+        Ok(())
+    }
+
+    fn close_all_instances() -> Result<(), String> {
+        let store = Self::load_instance_store()?;
+        let default_dir = Self::default_user_data_dir()?;
+        let mut target_dirs = vec![default_dir.to_string_lossy().to_string()];
+        for instance in &store.instances {
+            let dir = instance.user_data_dir.trim();
+            if !dir.is_empty() {
+                target_dirs.push(dir.to_string());
+            }
+        }
+
+        Self::close(&target_dirs, CLOSE_TIMEOUT_SECS)?;
+        let _ = Self::clear_all_pids();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestInstances;
+
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    fn test_root() -> PathBuf {
+        std::env::temp_dir().join(format!("instance_manager_test_{}", std::process::id()))
+    }
+
+    impl PlatformInstanceManager for TestInstances {
+        const LABEL: &'static str = "Test";
+        const INSTANCES_FILE: &'static str = "test_instances.json";
+        const INSTANCES_DIR: &'static str = "test";
+        const FOLLOW_LOCAL_ACCOUNT: bool = true;
+
+        fn store_lock() -> &'static Mutex<()> {
+            &TEST_LOCK
+        }
+        fn default_user_data_dir() -> Result<PathBuf, String> {
+            Ok(test_root().join("default"))
+        }
+        fn data_dir() -> Result<PathBuf, String> {
+            let dir = test_root();
+            fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            Ok(dir)
+        }
+        fn collect_process_entries() -> Vec<(u32, Option<String>)> {
+            Vec::new()
+        }
+        fn resolve_pid_from_entries(
+            _last_pid: Option<u32>,
+            _user_data_dir: Option<&str>,
+            _entries: &[(u32, Option<String>)],
+        ) -> Option<u32> {
+            None
+        }
+        fn resolve_pid(_last_pid: Option<u32>, _user_data_dir: Option<&str>) -> Option<u32> {
+            None
+        }
+        fn start(_dir: &str, _args: &[String], _new_window: bool) -> Result<u32, String> {
+            Err("unsupported".to_string())
+        }
+        fn start_default(_args: &[String], _new_window: bool) -> Result<u32, String> {
+            Err("unsupported".to_string())
+        }
+        fn focus(_last_pid: Option<u32>, _dir: Option<&str>) -> Result<u32, String> {
+            Err("unsupported".to_string())
+        }
+        fn close(_dirs: &[String], _timeout_secs: u64) -> Result<(), String> {
+            Ok(())
+        }
+        async fn inject_account(_profile_dir: &Path, _account_id: &str) -> Result<(), String> {
+            Ok(())
+        }
+        fn resolve_local_account_id() -> Option<String> {
+            Some("local".to_string())
+        }
+    }
+
+    #[test]
+    fn test_instance_lifecycle_and_default_settings() {
+        let root = test_root();
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(TestInstances::default_user_data_dir().unwrap().join("User")).unwrap();
+
+        let copied = TestInstances::create_instance_view(CreateInstanceParams {
+            name: " Work ".to_string(),
+            user_data_dir: root.join("work").to_string_lossy().to_string(),
+            extra_args: " --verbose ".to_string(),
+            bind_account_id: Some("acc".to_string()),
+            copy_source_instance_id: None,
+            init_mode: None,
+        })
+        .unwrap();
+        assert_eq!(copied.name, "Work");
+        assert_eq!(copied.extra_args, "--verbose");
+        assert!(copied.initialized);
+
+        let empty = TestInstances::create_instance(CreateInstanceParams {
+            name: "Empty".to_string(),
+            user_data_dir: root.join("empty").to_string_lossy().to_string(),
+            extra_args: String::new(),
+            bind_account_id: Some("acc".to_string()),
+            copy_source_instance_id: None,
+            init_mode: Some("EMPTY".to_string()),
+        })
+        .unwrap();
+        assert_eq!(empty.bind_account_id, None);
+
+        let rebind = TestInstances::update_instance_view(
+            UpdateInstanceParams {
+                instance_id: empty.id.clone(),
+                name: None,
+                extra_args: None,
+                bind_account_id: Some(Some("acc".to_string())),
+            },
+            None,
+        );
+        assert!(rebind.unwrap_err().starts_with("INSTANCE_NOT_INITIALIZED"));
+
+        TestInstances::update_instance_after_start(&copied.id, 42).unwrap();
+        let listed = TestInstances::list_instance_views().unwrap();
+        assert_eq!(listed.len(), 3);
+        assert!(listed.last().unwrap().is_default);
+        assert!(!listed[0].running);
+
+        let default = TestInstances::update_instance_view(
+            UpdateInstanceParams {
+                instance_id: DEFAULT_INSTANCE_ID.to_string(),
+                name: None,
+                extra_args: None,
+                bind_account_id: None,
+            },
+            Some(true),
+        )
+        .unwrap();
+        assert!(default.follow_local_account);
+        assert_eq!(default.bind_account_id.as_deref(), Some("local"));
+        let settings =
+            TestInstances::update_default_settings(Some(Some("b".into())), None, None).unwrap();
+        assert!(!settings.follow_local_account);
+
+        TestInstances::clear_all_pids().unwrap();
+        assert_eq!(
+            TestInstances::find_instance(&copied.id).unwrap().last_pid,
+            None
+        );
+        assert!(TestInstances::delete_instance(DEFAULT_INSTANCE_ID).is_err());
+        TestInstances::delete_instance(&copied.id).unwrap();
+        assert!(!root.join("work").exists());
+        assert!(TestInstances::find_instance(&copied.id).is_err());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -6,18 +6,17 @@ use std::path::PathBuf;
 use std::sync::Mutex;
 use std::time::Instant;
 
-use crate::models::kiro::{KiroAccount, KiroAccountIndex, KiroOAuthCompletePayload};
-use crate::modules::{account, kiro_oauth, logger};
+use crate::models::kiro::{
+    KiroAccount, KiroAccountIndex, KiroAccountSummary, KiroOAuthCompletePayload,
+};
+use crate::modules::platform_store::{self, PlatformAccountStore, PlatformQuotaAlert};
+use crate::modules::{config, kiro_oauth, logger};
 
-const ACCOUNTS_INDEX_FILE: &str = "kiro_accounts.json";
-const ACCOUNTS_DIR: &str = "kiro_accounts";
 const LOCAL_AUTH_TOKEN_FILE_NAME: &str = "kiro-auth-token.json";
 const LOCAL_USAGE_DB_KEY: &str = "kiro.kiroAgent";
-const KIRO_QUOTA_ALERT_COOLDOWN_SECONDS: i64 = 10 * 60;
 
 lazy_static::lazy_static! {
     static ref KIRO_ACCOUNT_INDEX_LOCK: Mutex<()> = Mutex::new(());
-    static ref KIRO_QUOTA_ALERT_LAST_SENT: Mutex<HashMap<String, i64>> = Mutex::new(HashMap::new());
     static ref KIRO_REFRESH_LOCKS: Mutex<HashMap<String, std::sync::Arc<tokio::sync::Mutex<()>>>> = Mutex::new(HashMap::new());
 }
 
@@ -59,25 +58,80 @@ fn is_banned_account(account: &KiroAccount) -> bool {
     is_banned_status(account.status.as_deref()) || is_banned_reason(account.status_reason.as_deref())
 }
 
-fn get_data_dir() -> Result<PathBuf, String> {
-    account::get_data_dir()
+pub struct KiroStore;
+
+impl PlatformAccountStore for KiroStore {
+    type Account = KiroAccount;
+    type Summary = KiroAccountSummary;
+    type Index = KiroAccountIndex;
+
+    const LABEL: &'static str = "Kiro";
+    const INDEX_FILE: &'static str = "kiro_accounts.json";
+    const ACCOUNTS_DIR: &'static str = "kiro_accounts";
+
+    fn index_lock() -> &'static Mutex<()> {
+        &KIRO_ACCOUNT_INDEX_LOCK
+    }
+    fn index_entries(index: &mut KiroAccountIndex) -> &mut Vec<KiroAccountSummary> {
+        &mut index.accounts
+    }
+    fn summary_id(summary: &KiroAccountSummary) -> &str {
+        &summary.id
+    }
+    fn summarize(account: &KiroAccount) -> KiroAccountSummary {
+        account.summary()
+    }
+    fn account_id(account: &KiroAccount) -> &str {
+        &account.id
+    }
+    fn set_tags(account: &mut KiroAccount, tags: Vec<String>) {
+        account.tags = Some(tags);
+        account.last_used = now_ts();
+    }
+    fn validate_account_id(account_id: &str) -> Result<String, String> {
+        normalize_account_id(account_id)
+    }
+    fn list_accounts() -> Result<Vec<KiroAccount>, String> {
+        list_accounts()
+    }
 }
 
-fn get_accounts_dir() -> Result<PathBuf, String> {
-    let base = get_data_dir()?;
-    let dir = base.join(ACCOUNTS_DIR);
-    if !dir.exists() {
-        fs::create_dir_all(&dir).map_err(|e| format!("创建 Kiro 账号目录失败: {}", e))?;
+impl PlatformQuotaAlert for KiroStore {
+    const PLATFORM: &'static str = "kiro";
+    const QUOTA_ALERT_COOLDOWN_SECONDS: i64 = 10 * 60;
+
+    fn quota_alert_settings(cfg: &config::UserConfig) -> (bool, i32) {
+        (cfg.kiro_quota_alert_enabled, cfg.kiro_quota_alert_threshold)
+    }
+    fn quota_metrics(account: &KiroAccount) -> Vec<(String, i32)> {
+        extract_quota_metrics(account)
+    }
+    fn resolve_current_account_id(accounts: &[KiroAccount]) -> Option<String> {
+        resolve_current_account_id(accounts)
+    }
+    fn display_email(account: &KiroAccount) -> String {
+        let trimmed = account.email.trim();
+        if trimmed.is_empty() {
+            account.id.clone()
+        } else {
+            trimmed.to_string()
+        }
+    }
+    fn last_used(account: &KiroAccount) -> i64 {
+        account.last_used
+    }
+    /// 已封禁账号不参与预警与推荐
+    fn alert_eligible(account: &KiroAccount) -> bool {
+        !is_banned_account(account)
     }
-    Ok(dir)
 }
 
-fn get_accounts_index_path() -> Result<PathBuf, String> {
-    Ok(get_data_dir()?.join(ACCOUNTS_INDEX_FILE))
+pub fn get_accounts_dir() -> Result<PathBuf, String> {
+    KiroStore::accounts_dir()
 }
 
 pub fn accounts_index_path_string() -> Result<String, String> {
-    Ok(get_accounts_index_path()?.to_string_lossy().to_string())
+    Ok(KiroStore::index_path()?.to_string_lossy().to_string())
 }
 
 fn normalize_account_id(account_id: &str) -> Result<String, String> {
@@ -101,75 +155,8 @@ fn normalize_account_id(account_id: &str) -> Result<String, String> {
     Ok(trimmed.to_string())
 }
 
-fn resolve_account_file_path(account_id: &str) -> Result<PathBuf, String> {
-    let normalized = normalize_account_id(account_id)?;
-    Ok(get_accounts_dir()?.join(format!("{}.json", normalized)))
-}
-
-pub fn load_account(account_id: &str) -> Option<KiroAccount> {
-    let account_path = resolve_account_file_path(account_id).ok()?;
-    if !account_path.exists() {
-        return None;
-    }
-    let content = fs::read_to_string(account_path).ok()?;
-    serde_json::from_str(&content).ok()
-}
-
-fn save_account_file(account: &KiroAccount) -> Result<(), String> {
-    let path = resolve_account_file_path(account.id.as_str())?;
-    let content =
-        serde_json::to_string_pretty(account).map_err(|e| format!("序列化账号失败: {}", e))?;
-    fs::write(path, content).map_err(|e| format!("保存账号失败: {}", e))
-}
-
-fn delete_account_file(account_id: &str) -> Result<(), String> {
-    let path = resolve_account_file_path(account_id)?;
-    if path.exists() {
-        fs::remove_file(path).map_err(|e| format!("删除账号文件失败: {}", e))?;
-    }
-    Ok(())
-}
-
-fn load_account_index() -> KiroAccountIndex {
-    let path = match get_accounts_index_path() {
-        Ok(p) => p,
-        Err(_) => return KiroAccountIndex::new(),
-    };
-
-    if !path.exists() {
-        return KiroAccountIndex::new();
-    }
-
-    match fs::read_to_string(path) {
-        Ok(content) => serde_json::from_str(&content).unwrap_or_else(|_| KiroAccountIndex::new()),
-        Err(_) => KiroAccountIndex::new(),
-    }
-}
-
-fn save_account_index(index: &KiroAccountIndex) -> Result<(), String> {
-    let path = get_accounts_index_path()?;
-    let content =
-        serde_json::to_string_pretty(index).map_err(|e| format!("序列化账号索引失败: {}", e))?;
-    fs::write(path, content).map_err(|e| format!("写入账号索引失败: {}", e))
-}
-
-fn refresh_summary(index: &mut KiroAccountIndex, account: &KiroAccount) {
-    if let Some(summary) = index.accounts.iter_mut().find(|item| item.id == account.id) {
-        *summary = account.summary();
-        return;
-    }
-    index.accounts.push(account.summary());
-}
-
-fn upsert_account_record(account: KiroAccount) -> Result<KiroAccount, String> {
-    let _lock = KIRO_ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|_| "获取 Kiro 账号锁失败".to_string())?;
-    let mut index = load_account_index();
-    save_account_file(&account)?;
-    refresh_summary(&mut index, &account);
-    save_account_index(&index)?;
-    Ok(account)
+pub fn load_account(account_id: &str) -> Result<Option<KiroAccount>, String> {
+    KiroStore::load_account(account_id)
 }
 
 fn normalize_non_empty(value: Option<&str>) -> Option<String> {
@@ -449,7 +436,7 @@ fn choose_primary_account_index(
         .unwrap_or(group[0])
 }
 
-fn normalize_account_index(index: &mut KiroAccountIndex) -> Vec<KiroAccount> {
+fn normalize_account_index(index: &mut KiroAccountIndex) -> Result<Vec<KiroAccount>, String> {
     let mut loaded_accounts = Vec::new();
     let mut seen_summary_ids = HashSet::new();
 
@@ -457,7 +444,7 @@ fn normalize_account_index(index: &mut KiroAccountIndex) -> Vec<KiroAccount> {
         if !seen_summary_ids.insert(summary.id.clone()) {
             continue;
         }
-        if let Some(account) = load_account(&summary.id) {
+        if let Some(account) = load_account(&summary.id)? {
             loaded_accounts.push(account);
         }
     }
@@ -467,7 +454,7 @@ fn normalize_account_index(index: &mut KiroAccountIndex) -> Vec<KiroAccount> {
             .iter()
             .map(|account| account.summary())
             .collect();
-        return loaded_accounts;
+        return Ok(loaded_accounts);
     }
 
     let preferred_bound_id = crate::modules::kiro_instance::load_default_settings()
@@ -541,7 +528,7 @@ fn normalize_account_index(index: &mut KiroAccountIndex) -> Vec<KiroAccount> {
 
     if !removed_ids.is_empty() {
         for account in &normalized_accounts {
-            if let Err(err) = save_account_file(account) {
+            if let Err(err) = KiroStore::save_account_file(account) {
                 logger::log_warn(&format!(
                     "[Kiro Account] 保存去重账号失败: id={}, error={}",
                     account.id, err
@@ -549,7 +536,7 @@ fn normalize_account_index(index: &mut KiroAccountIndex) -> Vec<KiroAccount> {
             }
         }
         for account_id in &removed_ids {
-            if let Err(err) = delete_account_file(account_id) {
+            if let Err(err) = KiroStore::delete_account_file(account_id) {
                 logger::log_warn(&format!(
                     "[Kiro Account] 删除重复账号文件失败: id={}, error={}",
                     account_id, err
@@ -566,16 +553,17 @@ fn normalize_account_index(index: &mut KiroAccountIndex) -> Vec<KiroAccount> {
         .iter()
         .map(|account| account.summary())
         .collect();
-    normalized_accounts
+    Ok(normalized_accounts)
 }
 
-pub fn list_accounts() -> Vec<KiroAccount> {
-    let mut index = load_account_index();
-    let accounts = normalize_account_index(&mut index);
-    if let Err(err) = save_account_index(&index) {
+/// 凭据库锁定时账号文件无法读取，直接返回错误，不据此改写索引
+pub fn list_accounts() -> Result<Vec<KiroAccount>, String> {
+    let mut index = KiroStore::load_index();
+    let accounts = normalize_account_index(&mut index)?;
+    if let Err(err) = KiroStore::save_index(&index) {
         logger::log_warn(&format!("[Kiro Account] 保存账号索引失败: {}", err));
     }
-    accounts
+    Ok(accounts)
 }
 
 fn apply_payload(account: &mut KiroAccount, payload: KiroOAuthCompletePayload) {
@@ -615,11 +603,9 @@ fn apply_payload(account: &mut KiroAccount, payload: KiroOAuthCompletePayload) {
 }
 
 pub fn upsert_account(payload: KiroOAuthCompletePayload) -> Result<KiroAccount, String> {
-    let _lock = KIRO_ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|_| "获取 Kiro 账号锁失败".to_string())?;
+    let _lock = KiroStore::lock_index()?;
     let now = now_ts();
-    let mut index = load_account_index();
+    let mut index = KiroStore::load_index();
     let incoming_profile_arn = normalize_identity(payload_profile_arn(&payload).as_deref());
     let incoming_user_id = normalize_identity(payload.user_id.as_deref());
     let incoming_email = normalize_email_identity(Some(payload.email.as_str()));
@@ -634,10 +620,8 @@ pub fn upsert_account(payload: KiroOAuthCompletePayload) -> Result<KiroAccount,
         .to_lowercase();
     let generated_id = format!("kiro_{:x}", md5::compute(identity_seed.as_bytes()));
 
-    let account_id = index
-        .accounts
-        .iter()
-        .filter_map(|item| load_account(&item.id))
+    let account_id = KiroStore::load_indexed_accounts()?
+        .into_iter()
         .find(|account| {
             let existing_profile_arn = normalize_identity(account_profile_arn(account).as_deref());
             let existing_user = normalize_identity(account.user_id.as_deref());
@@ -657,7 +641,7 @@ pub fn upsert_account(payload: KiroOAuthCompletePayload) -> Result<KiroAccount,
         .map(|account| account.id)
         .unwrap_or(generated_id);
 
-    let existing = load_account(&account_id);
+    let existing = load_account(&account_id)?;
     let tags = existing.as_ref().and_then(|acc| acc.tags.clone());
     let created_at = existing.as_ref().map(|acc| acc.created_at).unwrap_or(now);
 
@@ -700,9 +684,9 @@ pub fn upsert_account(payload: KiroOAuthCompletePayload) -> Result<KiroAccount,
     account.created_at = created_at;
     account.last_used = now;
 
-    save_account_file(&account)?;
-    refresh_summary(&mut index, &account);
-    save_account_index(&index)?;
+    KiroStore::save_account_file(&account)?;
+    KiroStore::refresh_summary(&mut index, &account);
+    KiroStore::save_index(&index)?;
 
     logger::log_info(&format!(
         "Kiro 账号已保存: id={}, email={}",
@@ -722,7 +706,7 @@ pub async fn refresh_account_token(account_id: &str) -> Result<KiroAccount, Stri
     let _guard = lock.lock().await;
 
     let started_at = Instant::now();
-    let mut account = load_account(account_id).ok_or_else(|| "账号不存在".to_string())?;
+    let mut account = load_account(account_id)?.ok_or_else(|| "账号不存在".to_string())?;
     logger::log_info(&format!(
         "[Kiro Refresh] 开始刷新账号: id={}, email={}",
         account.id, account.email
@@ -738,7 +722,7 @@ pub async fn refresh_account_token(account_id: &str) -> Result<KiroAccount, Stri
             account.last_used = now_ts();
 
             let updated = account.clone();
-            upsert_account_record(account)?;
+            KiroStore::upsert_account_record(account)?;
             
             // AUTH-1: Token 刷新后回写到 Kiro IDE 本地文件
             if let Err(e) = crate::modules::kiro_instance::write_local_auth_token_file(&updated) {
@@ -780,7 +764,7 @@ pub async fn refresh_all_tokens() -> Result<Vec<(String, Result<KiroAccount, Str
     use tokio::sync::Semaphore;
 
     const MAX_CONCURRENT: usize = 5;
-    let accounts = list_accounts();
+    let accounts = list_accounts()?;
     let total = accounts.len();
     let active_accounts: Vec<KiroAccount> = accounts
         .into_iter()
@@ -819,43 +803,38 @@ pub async fn refresh_all_tokens() -> Result<Vec<(String, Result<KiroAccount, Str
         }
     }
 
+    if results.iter().any(|(_, r)| r.is_ok()) {
+        crate::modules::websocket::broadcast_quota_updated(
+            crate::modules::websocket_platforms::Platform::Kiro,
+        );
+    }
     Ok(results)
 }
 
 pub fn remove_account(account_id: &str) -> Result<(), String> {
-    let _lock = KIRO_ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|_| "获取 Kiro 账号锁失败".to_string())?;
-    let mut index = load_account_index();
-    index.accounts.retain(|item| item.id != account_id);
-    save_account_index(&index)?;
-    delete_account_file(account_id)?;
-    Ok(())
+    KiroStore::remove_account(account_id)
 }
 
 pub fn remove_accounts(account_ids: &[String]) -> Result<(), String> {
-    for id in account_ids {
-        remove_account(id)?;
-    }
-    Ok(())
+    KiroStore::remove_accounts(account_ids)
 }
 
 pub fn update_account_tags(account_id: &str, tags: Vec<String>) -> Result<KiroAccount, String> {
-    let mut account = load_account(account_id).ok_or_else(|| "账号不存在".to_string())?;
-    account.tags = Some(tags);
-    account.last_used = now_ts();
-    let updated = account.clone();
-    upsert_account_record(account)?;
-    Ok(updated)
+    KiroStore::update_tags(account_id, tags)
 }
 
-/// 基于 contextUsagePercentage 估算并更新当前 Kiro 账号的使用额度
-pub fn update_account_quota(account_id: &str, percentage: f64) -> Result<(), String> {
-    if let Some(mut account) = load_account(account_id) {
-        account.credits_total = Some(10000.0);
-        let p = if percentage < 0.0 { 0.0 } else if percentage > 100.0 { 100.0 } else { percentage };
-        account.credits_used = Some((p * 100.0).round() as f64);
-        upsert_account_record(account)?;
+/// 代理请求结束后按计量事件累加已用 Prompt Credits
+/// 仅在账号已有真实额度数据时更新，下次刷新配额时以服务端数据为准
+pub fn add_credits_used(account_id: &str, credits: f64) -> Result<(), String> {
+    if !credits.is_finite() || credits <= 0.0 {
+        return Ok(());
+    }
+    if let Some(mut account) = load_account(account_id)? {
+        if account.credits_total.is_none() {
+            return Ok(());
+        }
+        account.credits_used = Some(account.credits_used.unwrap_or(0.0) + credits);
+        KiroStore::upsert_account_record(account)?;
     }
     Ok(())
 }
@@ -1041,18 +1020,8 @@ fn payloads_from_import_json_value(value: Value) -> Result<Vec<KiroOAuthComplete
 }
 
 pub fn import_from_json(json_content: &str) -> Result<Vec<KiroAccount>, String> {
-    if let Ok(account) = serde_json::from_str::<KiroAccount>(json_content) {
-        let saved = upsert_account_record(account)?;
-        return Ok(vec![saved]);
-    }
-
-    if let Ok(accounts) = serde_json::from_str::<Vec<KiroAccount>>(json_content) {
-        let mut result = Vec::new();
-        for account in accounts {
-            let saved = upsert_account_record(account)?;
-            result.push(saved);
-        }
-        return Ok(result);
+    if KiroStore::parse_accounts_json(json_content).is_some() {
+        return KiroStore::import_from_json(json_content);
     }
 
     if let Ok(value) = serde_json::from_str::<Value>(json_content) {
@@ -1070,36 +1039,15 @@ pub fn import_from_json(json_content: &str) -> Result<Vec<KiroAccount>, String>
 }
 
 pub fn export_accounts(account_ids: &[String]) -> Result<String, String> {
-    let accounts: Vec<KiroAccount> = account_ids
-        .iter()
-        .filter_map(|id| load_account(id))
-        .collect();
-    serde_json::to_string_pretty(&accounts).map_err(|e| format!("序列化失败: {}", e))
-}
-
-fn normalize_quota_alert_threshold(raw: i32) -> i32 {
-    raw.clamp(0, 100)
-}
-
-fn clamp_percent(value: f64) -> i32 {
-    value.round().clamp(0.0, 100.0) as i32
+    KiroStore::export_accounts(account_ids)
 }
 
 fn calc_remaining_percent(total: Option<f64>, used: Option<f64>) -> Option<i32> {
     let total = total?;
-    if !total.is_finite() || total <= 0.0 {
-        return None;
-    }
-
-    let used = used.unwrap_or(0.0);
-    if !used.is_finite() {
-        return None;
-    }
-    let remaining = (total - used).max(0.0);
-    Some(clamp_percent((remaining / total) * 100.0))
+    platform_store::calc_remaining_percent(total - used.unwrap_or(0.0), total)
 }
 
-fn extract_quota_metrics(account: &KiroAccount) -> Vec<(String, i32)> {
+pub fn extract_quota_metrics(account: &KiroAccount) -> Vec<(String, i32)> {
     let mut metrics = Vec::new();
 
     if let Some(pct) = calc_remaining_percent(account.credits_total, account.credits_used) {
@@ -1112,15 +1060,7 @@ fn extract_quota_metrics(account: &KiroAccount) -> Vec<(String, i32)> {
     metrics
 }
 
-fn average_quota_percentage(metrics: &[(String, i32)]) -> f64 {
-    if metrics.is_empty() {
-        return 0.0;
-    }
-    let sum: i32 = metrics.iter().map(|(_, pct)| *pct).sum();
-    sum as f64 / metrics.len() as f64
-}
-
-fn resolve_current_account_id(accounts: &[KiroAccount]) -> Option<String> {
+pub fn resolve_current_account_id(accounts: &[KiroAccount]) -> Option<String> {
     if let Ok(settings) = crate::modules::kiro_instance::load_default_settings() {
         if let Some(bind_id) = settings.bind_account_id {
             let trimmed = bind_id.trim();
@@ -1136,123 +1076,9 @@ fn resolve_current_account_id(accounts: &[KiroAccount]) -> Option<String> {
         .map(|account| account.id.clone())
 }
 
-fn display_email(account: &KiroAccount) -> String {
-    let trimmed = account.email.trim();
-    if trimmed.is_empty() {
-        account.id.clone()
-    } else {
-        trimmed.to_string()
-    }
-}
-
-fn build_quota_alert_cooldown_key(account_id: &str, threshold: i32) -> String {
-    format!("kiro:{}:{}", account_id, threshold)
-}
-
-fn should_emit_quota_alert(cooldown_key: &str, now: i64) -> bool {
-    let Ok(mut state) = KIRO_QUOTA_ALERT_LAST_SENT.lock() else {
-        return true;
-    };
-
-    if let Some(last_sent) = state.get(cooldown_key) {
-        if now - *last_sent < KIRO_QUOTA_ALERT_COOLDOWN_SECONDS {
-            return false;
-        }
-    }
-
-    state.insert(cooldown_key.to_string(), now);
-    true
-}
-
-fn clear_quota_alert_cooldown(account_id: &str, threshold: i32) {
-    if let Ok(mut state) = KIRO_QUOTA_ALERT_LAST_SENT.lock() {
-        state.remove(&build_quota_alert_cooldown_key(account_id, threshold));
-    }
-}
-
-fn pick_quota_alert_recommendation(
-    accounts: &[KiroAccount],
-    current_id: &str,
-) -> Option<KiroAccount> {
-    let mut candidates: Vec<KiroAccount> = accounts
-        .iter()
-        .filter(|account| account.id != current_id)
-        .filter(|account| !is_banned_account(account))
-        .filter(|account| !extract_quota_metrics(account).is_empty())
-        .cloned()
-        .collect();
-
-    if candidates.is_empty() {
-        return None;
-    }
-
-    candidates.sort_by(|a, b| {
-        let avg_a = average_quota_percentage(&extract_quota_metrics(a));
-        let avg_b = average_quota_percentage(&extract_quota_metrics(b));
-        avg_b
-            .partial_cmp(&avg_a)
-            .unwrap_or(std::cmp::Ordering::Equal)
-            .then_with(|| a.last_used.cmp(&b.last_used))
-    });
-
-    candidates.into_iter().next()
-}
-
 pub fn run_quota_alert_if_needed(
 ) -> Result<Option<crate::modules::account::QuotaAlertPayload>, String> {
-    let cfg = crate::modules::config::get_user_config();
-    if !cfg.kiro_quota_alert_enabled {
-        return Ok(None);
-    }
-
-    let threshold = normalize_quota_alert_threshold(cfg.kiro_quota_alert_threshold);
-    let accounts = list_accounts();
-    let current_id = match resolve_current_account_id(&accounts) {
-        Some(id) => id,
-        None => return Ok(None),
-    };
-
-    let current = match accounts.iter().find(|account| account.id == current_id) {
-        Some(account) => account,
-        None => return Ok(None),
-    };
-    if is_banned_account(current) {
-        return Ok(None);
-    }
-
-    let metrics = extract_quota_metrics(current);
-    let low_models: Vec<(String, i32)> = metrics
-        .into_iter()
-        .filter(|(_, pct)| *pct <= threshold)
-        .collect();
-
-    if low_models.is_empty() {
-        clear_quota_alert_cooldown(&current_id, threshold);
-        return Ok(None);
-    }
-
-    let now = chrono::Utc::now().timestamp();
-    let cooldown_key = build_quota_alert_cooldown_key(&current_id, threshold);
-    if !should_emit_quota_alert(&cooldown_key, now) {
-        return Ok(None);
-    }
-
-    let recommendation = pick_quota_alert_recommendation(&accounts, &current_id);
-    let lowest_percentage = low_models.iter().map(|(_, pct)| *pct).min().unwrap_or(0);
-    let payload = crate::modules::account::QuotaAlertPayload {
-        platform: "kiro".to_string(),
-        current_account_id: current_id,
-        current_email: display_email(current),
-        threshold,
-        lowest_percentage,
-        low_models: low_models.into_iter().map(|(name, _)| name).collect(),
-        recommended_account_id: recommendation.as_ref().map(|account| account.id.clone()),
-        recommended_email: recommendation.as_ref().map(display_email),
-        triggered_at: now,
-    };
-
-    crate::modules::account::dispatch_quota_alert(&payload);
-    Ok(Some(payload))
+    KiroStore::run_quota_alert_if_needed()
 }
 
 pub fn get_default_kiro_data_dir() -> Result<PathBuf, String> {
@@ -1,12 +1,26 @@
 pub mod account;
 pub mod announcement;
 pub mod api_proxy;
+pub mod api_proxy_audit;
+pub mod api_proxy_codex;
+pub mod api_proxy_cooldown;
+pub mod api_proxy_keys;
+pub mod api_proxy_limiter;
+pub mod api_proxy_metrics;
+pub mod api_proxy_models;
+pub mod api_proxy_routes;
+pub mod api_proxy_sessions;
+pub mod api_proxy_tls;
+pub mod api_proxy_openai;
+pub mod api_proxy_usage;
 
 pub mod codex_account;
 pub mod codex_instance;
 pub mod codex_oauth;
 pub mod codex_quota;
 pub mod config;
+pub mod credential_vault;
+pub mod daemon;
 pub mod db;
 pub mod device;
 pub mod fingerprint;
@@ -16,6 +30,7 @@ pub mod github_copilot_oauth;
 pub mod group_settings;
 pub mod import;
 pub mod instance;
+pub mod instance_manager;
 pub mod instance_store;
 pub mod kiro_account;
 pub mod kiro_instance;
@@ -24,6 +39,7 @@ pub mod logger;
 pub mod oauth;
 pub mod oauth_server;
 pub mod opencode_auth;
+pub mod platform_store;
 pub mod process;
 pub mod quota;
 pub mod quota_cache;
@@ -33,11 +49,14 @@ pub mod tray_layout;
 pub mod update_checker;
 pub mod vscode_inject;
 pub mod wakeup;
+pub mod wakeup_cron;
 pub mod wakeup_gateway;
 pub mod wakeup_history;
 pub mod wakeup_scheduler;
 pub mod wakeup_verification;
 pub mod websocket;
+pub mod websocket_auth;
+pub mod websocket_platforms;
 pub mod windsurf_account;
 pub mod windsurf_instance;
 pub mod windsurf_oauth;
@@ -0,0 +1,485 @@
+//! 多平台账号存储
+//! 各平台账号按「索引文件 + 账号目录/<id>.json」存储（账号文件经凭据库加密），
+//! 索引读写、去重更新、标签、导入导出与配额预警在此统一实现；
+//! 新平台只需实现 PlatformAccountStore（及可选的 PlatformQuotaAlert）中与平台相关的部分
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::modules::{account, config, credential_vault};
+
+static QUOTA_ALERT_LAST_SENT: std::sync::LazyLock<Mutex<HashMap<String, i64>>> =
+    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn clamp_percent(value: f64) -> i32 {
+    value.round().clamp(0.0, 100.0) as i32
+}
+
+/// 剩余量占总量的百分比；总量无效时返回 None
+pub fn calc_remaining_percent(remaining: f64, total: f64) -> Option<i32> {
+    if !total.is_finite() || total <= 0.0 || !remaining.is_finite() {
+        return None;
+    }
+    Some(clamp_percent((remaining.max(0.0) / total) * 100.0))
+}
+
+pub fn average_quota_percentage(metrics: &[(String, i32)]) -> f64 {
+    if metrics.is_empty() {
+        return 0.0;
+    }
+    let sum: i32 = metrics.iter().map(|(_, pct)| *pct).sum();
+    sum as f64 / metrics.len() as f64
+}
+
+/// 标签去空白、转小写并去重，与 Antigravity 账号标签规则一致；为空时返回 None
+pub fn normalize_tags(tags: Vec<String>) -> Option<Vec<String>> {
+    let mut result = Vec::new();
+    let mut seen = HashSet::new();
+    for tag in tags {
+        let normalized = tag.trim().to_lowercase();
+        if !normalized.is_empty() && seen.insert(normalized.clone()) {
+            result.push(normalized);
+        }
+    }
+    if result.is_empty() {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+/// 平台账号存储
+pub trait PlatformAccountStore {
+    type Account: Serialize + DeserializeOwned + Clone;
+    type Summary;
+    type Index: Serialize + DeserializeOwned + Default;
+
+    /// 平台显示名，用于错误信息
+    const LABEL: &'static str;
+    const INDEX_FILE: &'static str;
+    const ACCOUNTS_DIR: &'static str;
+
+    fn index_lock() -> &'static Mutex<()>;
+    fn index_entries(index: &mut Self::Index) -> &mut Vec<Self::Summary>;
+    fn summary_id(summary: &Self::Summary) -> &str;
+    fn summarize(account: &Self::Account) -> Self::Summary;
+    fn account_id(account: &Self::Account) -> &str;
+    /// 写入标签（各平台沿用原有处理：是否规范化、是否更新 last_used）
+    fn set_tags(account: &mut Self::Account, tags: Vec<String>);
+
+    fn data_dir() -> Result<PathBuf, String> {
+        account::get_data_dir()
+    }
+
+    /// 校验账号 ID，防止目录穿越
+    fn validate_account_id(account_id: &str) -> Result<String, String> {
+        if account_id.is_empty() {
+            return Err("账号 ID 不能为空".to_string());
+        }
+        if account_id.contains('/') || account_id.contains('\\') || account_id.contains("..") {
+            return Err("账号 ID 非法".to_string());
+        }
+        Ok(account_id.to_string())
+    }
+
+    /// 从索引移除账号后的附加处理（如清除当前账号标记）
+    fn on_index_remove(_index: &mut Self::Index, _account_id: &str) {}
+
+    fn lock_index() -> Result<MutexGuard<'static, ()>, String> {
+        Self::index_lock()
+            .lock()
+            .map_err(|_| format!("获取 {} 账号锁失败", Self::LABEL))
+    }
+
+    fn accounts_dir() -> Result<PathBuf, String> {
+        let dir = Self::data_dir()?.join(Self::ACCOUNTS_DIR);
+        if !dir.exists() {
+            fs::create_dir_all(&dir)
+                .map_err(|e| format!("创建 {} 账号目录失败: {}", Self::LABEL, e))?;
+        }
+        Ok(dir)
+    }
+
+    fn index_path() -> Result<PathBuf, String> {
+        Ok(Self::data_dir()?.join(Self::INDEX_FILE))
+    }
+
+    fn account_file_path(account_id: &str) -> Result<PathBuf, String> {
+        let account_id = Self::validate_account_id(account_id)?;
+        Ok(Self::accounts_dir()?.join(format!("{}.json", account_id)))
+    }
+
+    /// 读取索引；文件不存在或损坏时返回空索引
+    fn load_index() -> Self::Index {
+        Self::index_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_index(index: &Self::Index) -> Result<(), String> {
+        let path = Self::index_path()?;
+        let content = serde_json::to_string_pretty(index)
+            .map_err(|e| format!("序列化账号索引失败: {}", e))?;
+        fs::write(path, content).map_err(|e| format!("写入账号索引失败: {}", e))
+    }
+
+    /// 读取账号文件；文件不存在时返回 None，凭据库锁定或解密失败时返回错误
+    fn load_account(account_id: &str) -> Result<Option<Self::Account>, String> {
+        let path = Self::account_file_path(account_id)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("读取 {} 账号失败: {}", Self::LABEL, e))?;
+        credential_vault::open_json(&content).map(Some)
+    }
+
+    fn save_account_file(account: &Self::Account) -> Result<(), String> {
+        let path = Self::account_file_path(Self::account_id(account))?;
+        let content = credential_vault::seal_json(account)?;
+        credential_vault::write_private_file(&path, content.as_bytes())
+            .map_err(|e| format!("保存账号失败: {}", e))
+    }
+
+    fn delete_account_file(account_id: &str) -> Result<(), String> {
+        let path = Self::account_file_path(account_id)?;
+        if path.exists() {
+            fs::remove_file(path).map_err(|e| format!("删除账号文件失败: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// 用账号摘要更新索引中同 ID 的条目，不存在时追加
+    fn refresh_summary(index: &mut Self::Index, account: &Self::Account) {
+        let summary = Self::summarize(account);
+        let entries = Self::index_entries(index);
+        match entries
+            .iter_mut()
+            .find(|item| Self::summary_id(item) == Self::account_id(account))
+        {
+            Some(existing) => *existing = summary,
+            None => entries.push(summary),
+        }
+    }
+
+    /// 保存账号文件并同步索引摘要
+    fn upsert_account_record(account: Self::Account) -> Result<Self::Account, String> {
+        let _lock = Self::lock_index()?;
+        let mut index = Self::load_index();
+        Self::save_account_file(&account)?;
+        Self::refresh_summary(&mut index, &account);
+        Self::save_index(&index)?;
+        Ok(account)
+    }
+
+    /// 按索引顺序读取账号文件，缺失的账号跳过；任一账号无法解密时返回错误
+    fn load_indexed_accounts() -> Result<Vec<Self::Account>, String> {
+        let mut index = Self::load_index();
+        let mut accounts = Vec::new();
+        for summary in Self::index_entries(&mut index).iter() {
+            if let Some(account) = Self::load_account(Self::summary_id(summary))? {
+                accounts.push(account);
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn list_accounts() -> Result<Vec<Self::Account>, String> {
+        Self::load_indexed_accounts()
+    }
+
+    fn remove_account(account_id: &str) -> Result<(), String> {
+        let _lock = Self::lock_index()?;
+        let mut index = Self::load_index();
+        Self::index_entries(&mut index).retain(|item| Self::summary_id(item) != account_id);
+        Self::on_index_remove(&mut index, account_id);
+        Self::save_index(&index)?;
+        Self::delete_account_file(account_id)
+    }
+
+    fn remove_accounts(account_ids: &[String]) -> Result<(), String> {
+        for id in account_ids {
+            Self::remove_account(id)?;
+        }
+        Ok(())
+    }
+
+    fn update_tags(account_id: &str, tags: Vec<String>) -> Result<Self::Account, String> {
+        let mut account = Self::load_account(account_id)?
+            .ok_or_else(|| format!("{} 账号不存在", Self::LABEL))?;
+        Self::set_tags(&mut account, tags);
+        Self::upsert_account_record(account)
+    }
+
+    /// 解析导出格式（单个账号对象或账号数组）
+    fn parse_accounts_json(json_content: &str) -> Option<Vec<Self::Account>> {
+        if let Ok(account) = serde_json::from_str::<Self::Account>(json_content) {
+            return Some(vec![account]);
+        }
+        serde_json::from_str::<Vec<Self::Account>>(json_content).ok()
+    }
+
+    fn import_from_json(json_content: &str) -> Result<Vec<Self::Account>, String> {
+        let accounts = Self::parse_accounts_json(json_content)
+            .ok_or_else(|| "无法解析 JSON 内容".to_string())?;
+        accounts
+            .into_iter()
+            .map(Self::upsert_account_record)
+            .collect()
+    }
+
+    fn export_accounts(account_ids: &[String]) -> Result<String, String> {
+        let mut accounts: Vec<Self::Account> = Vec::new();
+        for id in account_ids {
+            accounts.extend(Self::load_account(id)?);
+        }
+        serde_json::to_string_pretty(&accounts).map_err(|e| format!("序列化失败: {}", e))
+    }
+}
+
+/// 平台配额预警：当前账号任一配额低于阈值时通知，并推荐平均剩余配额最高的其他账号
+pub trait PlatformQuotaAlert: PlatformAccountStore {
+    /// 配额预警 payload 中的平台标识
+    const PLATFORM: &'static str;
+    const QUOTA_ALERT_COOLDOWN_SECONDS: i64 = 300;
+
+    /// (是否开启, 阈值)
+    fn quota_alert_settings(cfg: &config::UserConfig) -> (bool, i32);
+    fn quota_metrics(account: &Self::Account) -> Vec<(String, i32)>;
+    fn resolve_current_account_id(accounts: &[Self::Account]) -> Option<String>;
+    fn display_email(account: &Self::Account) -> String;
+    fn last_used(account: &Self::Account) -> i64;
+
+    /// 是否参与预警（作为当前账号或推荐账号）
+    fn alert_eligible(_account: &Self::Account) -> bool {
+        true
+    }
+
+    fn pick_quota_alert_recommendation<'a>(
+        accounts: &'a [Self::Account],
+        current_id: &str,
+    ) -> Option<&'a Self::Account> {
+        accounts
+            .iter()
+            .filter(|account| Self::account_id(account) != current_id)
+            .filter(|account| Self::alert_eligible(account))
+            .map(|account| (account, Self::quota_metrics(account)))
+            .filter(|(_, metrics)| !metrics.is_empty())
+            .map(|(account, metrics)| (account, average_quota_percentage(&metrics)))
+            .min_by(|(a, avg_a), (b, avg_b)| {
+                avg_b
+                    .partial_cmp(avg_a)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then_with(|| Self::last_used(a).cmp(&Self::last_used(b)))
+            })
+            .map(|(account, _)| account)
+    }
+
+    fn run_quota_alert_if_needed() -> Result<Option<account::QuotaAlertPayload>, String> {
+        let cfg = config::get_user_config();
+        let (enabled, threshold) = Self::quota_alert_settings(&cfg);
+        if !enabled {
+            return Ok(None);
+        }
+
+        let threshold = threshold.clamp(0, 100);
+        let accounts = Self::list_accounts()?;
+        let Some(current_id) = Self::resolve_current_account_id(&accounts) else {
+            return Ok(None);
+        };
+        let Some(current) = accounts
+            .iter()
+            .find(|account| Self::account_id(account) == current_id)
+        else {
+            return Ok(None);
+        };
+        if !Self::alert_eligible(current) {
+            return Ok(None);
+        }
+
+        let low_models: Vec<(String, i32)> = Self::quota_metrics(current)
+            .into_iter()
+            .filter(|(_, pct)| *pct <= threshold)
+            .collect();
+        let cooldown_key = format!("{}:{}:{}", Self::PLATFORM, current_id, threshold);
+        if low_models.is_empty() {
+            if let Ok(mut state) = QUOTA_ALERT_LAST_SENT.lock() {
+                state.remove(&cooldown_key);
+            }
+            return Ok(None);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        if !should_emit_quota_alert(&cooldown_key, now, Self::QUOTA_ALERT_COOLDOWN_SECONDS) {
+            return Ok(None);
+        }
+
+        let recommendation = Self::pick_quota_alert_recommendation(&accounts, &current_id);
+        let lowest_percentage = low_models.iter().map(|(_, pct)| *pct).min().unwrap_or(0);
+        let payload = account::QuotaAlertPayload {
+            platform: Self::PLATFORM.to_string(),
+            current_account_id: current_id.clone(),
+            current_email: Self::display_email(current),
+            threshold,
+            lowest_percentage,
+            low_models: low_models.into_iter().map(|(name, _)| name).collect(),
+            recommended_account_id: recommendation.map(|a| Self::account_id(a).to_string()),
+            recommended_email: recommendation.map(Self::display_email),
+            triggered_at: now,
+        };
+
+        account::dispatch_quota_alert(&payload);
+        Ok(Some(payload))
+    }
+}
+
+fn should_emit_quota_alert(cooldown_key: &str, now: i64, cooldown_seconds: i64) -> bool {
+    let Ok(mut state) = QUOTA_ALERT_LAST_SENT.lock() else {
+        return true;
+    };
+    if let Some(last_sent) = state.get(cooldown_key) {
+        if now - *last_sent < cooldown_seconds {
+            return false;
+        }
+    }
+    state.insert(cooldown_key.to_string(), now);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct TestAccount {
+        id: String,
+        tags: Option<Vec<String>>,
+        remaining: i32,
+        last_used: i64,
+    }
+
+    #[derive(Default, Serialize, Deserialize)]
+    struct TestIndex {
+        accounts: Vec<String>,
+    }
+
+    struct TestStore;
+
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    impl PlatformAccountStore for TestStore {
+        type Account = TestAccount;
+        type Summary = String;
+        type Index = TestIndex;
+
+        const LABEL: &'static str = "Test";
+        const INDEX_FILE: &'static str = "test_accounts.json";
+        const ACCOUNTS_DIR: &'static str = "test_accounts";
+
+        fn index_lock() -> &'static Mutex<()> {
+            &TEST_LOCK
+        }
+        fn index_entries(index: &mut TestIndex) -> &mut Vec<String> {
+            &mut index.accounts
+        }
+        fn summary_id(summary: &String) -> &str {
+            summary
+        }
+        fn summarize(account: &TestAccount) -> String {
+            account.id.clone()
+        }
+        fn account_id(account: &TestAccount) -> &str {
+            &account.id
+        }
+        fn set_tags(account: &mut TestAccount, tags: Vec<String>) {
+            account.tags = normalize_tags(tags);
+        }
+        fn data_dir() -> Result<PathBuf, String> {
+            let dir = std::env::temp_dir().join(format!("platform_store_test_{}", std::process::id()));
+            fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            Ok(dir)
+        }
+    }
+
+    impl PlatformQuotaAlert for TestStore {
+        const PLATFORM: &'static str = "test";
+
+        fn quota_alert_settings(_cfg: &config::UserConfig) -> (bool, i32) {
+            (true, 20)
+        }
+        fn quota_metrics(account: &TestAccount) -> Vec<(String, i32)> {
+            vec![("credits".to_string(), account.remaining)]
+        }
+        fn resolve_current_account_id(_accounts: &[TestAccount]) -> Option<String> {
+            Some("a".to_string())
+        }
+        fn display_email(account: &TestAccount) -> String {
+            account.id.clone()
+        }
+        fn last_used(account: &TestAccount) -> i64 {
+            account.last_used
+        }
+    }
+
+    fn account(id: &str, remaining: i32, last_used: i64) -> TestAccount {
+        TestAccount {
+            id: id.to_string(),
+            tags: None,
+            remaining,
+            last_used,
+        }
+    }
+
+    #[test]
+    fn test_store_roundtrip_tags_and_recommendation() {
+        assert_eq!(calc_remaining_percent(25.0, 100.0), Some(25));
+        assert_eq!(calc_remaining_percent(-5.0, 100.0), Some(0));
+        assert_eq!(calc_remaining_percent(5.0, 0.0), None);
+        assert_eq!(
+            normalize_tags(vec![" Work ".into(), "work".into(), "".into(), "B".into()]),
+            Some(vec!["work".to_string(), "b".to_string()])
+        );
+        assert_eq!(normalize_tags(vec!["  ".into()]), None);
+        assert!(TestStore::account_file_path("../x").is_err());
+
+        let imported = TestStore::import_from_json(
+            &serde_json::to_string(&vec![account("a", 10, 3), account("b", 80, 1)]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(imported.len(), 2);
+        TestStore::upsert_account_record(account("b", 90, 2)).unwrap();
+        let listed = TestStore::list_accounts().unwrap();
+        assert_eq!(listed.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(listed[1].remaining, 90);
+
+        let tagged = TestStore::update_tags("a", vec!["X".into(), "x".into()]).unwrap();
+        assert_eq!(tagged.tags, Some(vec!["x".to_string()]));
+        assert!(TestStore::export_accounts(&["a".to_string()]).unwrap().contains("\"x\""));
+
+        let accounts = vec![account("a", 10, 3), account("b", 60, 5), account("c", 60, 1), account("d", 40, 0)];
+        let picked = TestStore::pick_quota_alert_recommendation(&accounts, "a").unwrap();
+        assert_eq!(picked.id, "c");
+
+        // 未解锁时无法解密的账号让列表与导出报错，而不是被静默跳过
+        fs::write(
+            TestStore::account_file_path("a").unwrap(),
+            r#"{"id":"a","tags":null,"remaining":10,"last_used":3,"access_token":"enc:v1:AAAA"}"#,
+        )
+        .unwrap();
+        assert!(TestStore::load_account("a").is_err());
+        assert!(TestStore::list_accounts().is_err());
+        assert!(TestStore::export_accounts(&["a".to_string()]).is_err());
+
+        TestStore::remove_accounts(&["a".to_string(), "b".to_string()]).unwrap();
+        assert!(TestStore::list_accounts().unwrap().is_empty());
+        assert!(TestStore::load_account("a").unwrap().is_none());
+        let _ = fs::remove_dir_all(TestStore::data_dir().unwrap());
+    }
+}
@@ -311,19 +311,27 @@ fn collect_platform_account_counts() -> HashMap<PlatformId, usize> {
     );
     counts.insert(
         PlatformId::Codex,
-        crate::modules::codex_account::list_accounts().len(),
+        crate::modules::codex_account::list_accounts()
+            .map(|accounts| accounts.len())
+            .unwrap_or(0),
     );
     counts.insert(
         PlatformId::GitHubCopilot,
-        crate::modules::github_copilot_account::list_accounts().len(),
+        crate::modules::github_copilot_account::list_accounts()
+            .map(|accounts| accounts.len())
+            .unwrap_or(0),
     );
     counts.insert(
         PlatformId::Windsurf,
-        crate::modules::windsurf_account::list_accounts().len(),
+        crate::modules::windsurf_account::list_accounts()
+            .map(|accounts| accounts.len())
+            .unwrap_or(0),
     );
     counts.insert(
         PlatformId::Kiro,
-        crate::modules::kiro_account::list_accounts().len(),
+        crate::modules::kiro_account::list_accounts()
+            .map(|accounts| accounts.len())
+            .unwrap_or(0),
     );
     counts
 }
@@ -488,7 +496,7 @@ fn build_codex_display_info(lang: &str) -> AccountDisplayInfo {
 }
 
 fn build_github_copilot_display_info(lang: &str) -> AccountDisplayInfo {
-    let accounts = crate::modules::github_copilot_account::list_accounts();
+    let accounts = crate::modules::github_copilot_account::list_accounts().unwrap_or_default();
     let Some(account) = resolve_github_copilot_current_account(&accounts) else {
         return AccountDisplayInfo {
             account: format!("📧 {}", get_text("not_logged_in", lang)),
@@ -515,7 +523,7 @@ fn build_github_copilot_display_info(lang: &str) -> AccountDisplayInfo {
 }
 
 fn build_windsurf_display_info(lang: &str) -> AccountDisplayInfo {
-    let accounts = crate::modules::windsurf_account::list_accounts();
+    let accounts = crate::modules::windsurf_account::list_accounts().unwrap_or_default();
     let Some(account) = resolve_windsurf_current_account(&accounts) else {
         return AccountDisplayInfo {
             account: format!("📧 {}", get_text("not_logged_in", lang)),
@@ -545,7 +553,7 @@ fn build_windsurf_display_info(lang: &str) -> AccountDisplayInfo {
 }
 
 fn build_kiro_display_info(lang: &str) -> AccountDisplayInfo {
-    let accounts = crate::modules::kiro_account::list_accounts();
+    let accounts = crate::modules::kiro_account::list_accounts().unwrap_or_default();
     let Some(account) = resolve_kiro_current_account(&accounts) else {
         return AccountDisplayInfo {
             account: format!("📧 {}", get_text("not_logged_in", lang)),
@@ -0,0 +1,246 @@
+//! 唤醒任务的 Crontab 表达式解析与触发时间计算
+//! 标准 5 段格式：分 时 日 月 周。支持列表、范围与步长的任意组合（如 `1-5,10`、`0-30/15`），
+//! 月份与星期的英文缩写，以及 @hourly / @daily / @weekly / @monthly / @yearly；
+//! 日与周同时限定时满足其一即触发（与 Vixie cron 一致）
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, TimeZone};
+
+/// 向后搜索的最大天数（覆盖只在闰年 2 月 29 日触发的表达式）
+const MAX_SEARCH_DAYS: i64 = 366 * 8;
+
+/// 预览条数上限
+const MAX_PREVIEW_RUNS: usize = 50;
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+struct FieldSpec {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    /// 英文缩写，下标 + min 即对应取值
+    names: &'static [&'static str],
+}
+
+const FIELDS: [FieldSpec; 5] = [
+    FieldSpec { name: "分钟", min: 0, max: 59, names: &[] },
+    FieldSpec { name: "小时", min: 0, max: 23, names: &[] },
+    FieldSpec { name: "日", min: 1, max: 31, names: &[] },
+    FieldSpec { name: "月", min: 1, max: 12, names: MONTH_NAMES },
+    // 0 与 7 均表示周日
+    FieldSpec { name: "星期", min: 0, max: 7, names: WEEKDAY_NAMES },
+];
+
+/// 解析后的 Crontab 表达式，各字段以位图表示允许的取值
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// 日字段为 * 开头（不限定）
+    any_day_of_month: bool,
+    /// 周字段为 * 开头（不限定）
+    any_day_of_week: bool,
+}
+
+/// 解析 Crontab 表达式，错误信息可直接展示给用户
+pub fn parse_crontab(expr: &str) -> Result<CronSchedule, String> {
+    let expr = expr.trim();
+    let expanded = match expr.to_ascii_lowercase().as_str() {
+        "@hourly" => "0 * * * *",
+        "@daily" | "@midnight" => "0 0 * * *",
+        "@weekly" => "0 0 * * 0",
+        "@monthly" => "0 0 1 * *",
+        "@yearly" | "@annually" => "0 0 1 1 *",
+        other if other.starts_with('@') => return Err(format!("不支持的预设表达式: {}", expr)),
+        _ => expr,
+    };
+
+    let fields: Vec<&str> = expanded.split_whitespace().collect();
+    if fields.len() != FIELDS.len() {
+        return Err(format!(
+            "Crontab 表达式需要 5 个字段（分 时 日 月 周），当前为 {} 个",
+            fields.len()
+        ));
+    }
+
+    let mut days_of_week = parse_field(fields[4], &FIELDS[4])?;
+    if days_of_week & (1 << 7) != 0 {
+        days_of_week = (days_of_week | 1) & !(1 << 7);
+    }
+    Ok(CronSchedule {
+        minutes: parse_field(fields[0], &FIELDS[0])?,
+        hours: parse_field(fields[1], &FIELDS[1])?,
+        days_of_month: parse_field(fields[2], &FIELDS[2])?,
+        months: parse_field(fields[3], &FIELDS[3])?,
+        days_of_week,
+        any_day_of_month: fields[2].starts_with('*'),
+        any_day_of_week: fields[4].starts_with('*'),
+    })
+}
+
+fn parse_field(field: &str, spec: &FieldSpec) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for item in field.split(',') {
+        if item.is_empty() {
+            return Err(format!("{}字段 \"{}\" 含有空的列表项", spec.name, field));
+        }
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("{}字段的步长 \"{}\" 无效", spec.name, step))?;
+                if step == 0 {
+                    return Err(format!("{}字段的步长不能为 0", spec.name));
+                }
+                (range, Some(step))
+            }
+            None => (item, None),
+        };
+
+        let (start, end) = if range == "*" {
+            (spec.min, spec.max)
+        } else if let Some((from, to)) = range.split_once('-') {
+            (parse_value(from, spec)?, parse_value(to, spec)?)
+        } else {
+            let value = parse_value(range, spec)?;
+            // 单值带步长（如 5/15）表示从该值到最大值
+            (value, if step.is_some() { spec.max } else { value })
+        };
+        if start > end {
+            return Err(format!("{}字段的范围 {} 起始值大于结束值", spec.name, range));
+        }
+
+        let step = step.unwrap_or(1);
+        let mut value = start;
+        while value <= end {
+            mask |= 1 << value;
+            value += step;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(token: &str, spec: &FieldSpec) -> Result<u32, String> {
+    let lower = token.to_ascii_lowercase();
+    let value = match spec.names.iter().position(|name| *name == lower) {
+        Some(pos) => pos as u32 + spec.min,
+        None => token
+            .parse::<u32>()
+            .map_err(|_| format!("{}字段的取值 \"{}\" 无效", spec.name, token))?,
+    };
+    if value < spec.min || value > spec.max {
+        return Err(format!(
+            "{}字段的取值 {} 超出范围 {}-{}",
+            spec.name, value, spec.min, spec.max
+        ));
+    }
+    Ok(value)
+}
+
+fn has_bit(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+impl CronSchedule {
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        if !has_bit(self.months, date.month()) {
+            return false;
+        }
+        let dom = has_bit(self.days_of_month, date.day());
+        let dow = has_bit(self.days_of_week, date.weekday().num_days_from_sunday());
+        if self.any_day_of_month || self.any_day_of_week {
+            dom && dow
+        } else {
+            dom || dow
+        }
+    }
+
+    /// after 之后（不含）的下一次触发时间
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let first_day = after.date_naive();
+        for offset in 0..MAX_SEARCH_DAYS {
+            let date = first_day + Duration::days(offset);
+            if !self.matches_day(date) {
+                continue;
+            }
+            for hour in (0..24).filter(|h| has_bit(self.hours, *h)) {
+                for minute in (0..60).filter(|m| has_bit(self.minutes, *m)) {
+                    let Some(naive) = date.and_hms_opt(hour, minute, 0) else {
+                        continue;
+                    };
+                    // 夏令时跳过的时刻不存在；重复的时刻取较早的一次
+                    let Some(candidate) = Local.from_local_datetime(&naive).earliest() else {
+                        continue;
+                    };
+                    if candidate > after {
+                        return Some(candidate);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// after 之后接下来的 count 次触发时间
+    pub fn upcoming(&self, after: DateTime<Local>, count: usize) -> Vec<DateTime<Local>> {
+        let mut runs = Vec::with_capacity(count);
+        let mut cursor = after;
+        while runs.len() < count {
+            let Some(next) = self.next_after(cursor) else {
+                break;
+            };
+            runs.push(next);
+            cursor = next;
+        }
+        runs
+    }
+}
+
+/// 校验表达式并返回接下来 count 次触发时间（毫秒时间戳），供前端预览
+pub fn preview_next_runs(expr: &str, count: usize) -> Result<Vec<i64>, String> {
+    let schedule = parse_crontab(expr)?;
+    let runs = schedule.upcoming(Local::now(), count.clamp(1, MAX_PREVIEW_RUNS));
+    if runs.is_empty() {
+        return Err("该表达式在未来 8 年内不会触发".to_string());
+    }
+    Ok(runs.iter().map(|run| run.timestamp_millis()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn test_weekdays_lists_steps_and_validation() {
+        // 2026-10-16 为周五：工作日表达式应跳过周末
+        let weekdays = parse_crontab("0 6 * * 1-5").unwrap();
+        assert_eq!(weekdays.next_after(local(2026, 10, 16, 7, 0)), Some(local(2026, 10, 19, 6, 0)));
+        assert_eq!(parse_crontab("0 6 * * mon-fri").unwrap(), weekdays);
+
+        let mixed = parse_crontab("0-30/15,45 9 1-5,10 * *").unwrap();
+        let runs = mixed.upcoming(local(2026, 10, 5, 9, 30), 3);
+        assert_eq!(runs, vec![local(2026, 10, 5, 9, 45), local(2026, 10, 10, 9, 0), local(2026, 10, 10, 9, 15)]);
+
+        // 日与周同时限定：13 号或周五
+        let either = parse_crontab("0 0 13 * 5").unwrap();
+        assert_eq!(either.next_after(local(2026, 10, 12, 0, 0)), Some(local(2026, 10, 13, 0, 0)));
+        assert_eq!(either.next_after(local(2026, 10, 13, 0, 0)), Some(local(2026, 10, 16, 0, 0)));
+
+        assert_eq!(parse_crontab("0 0 * * 7").unwrap(), parse_crontab("@weekly").unwrap());
+        let leap_day = parse_crontab("0 0 29 2 *").unwrap();
+        assert_eq!(leap_day.next_after(local(2026, 3, 1, 0, 0)), Some(local(2028, 2, 29, 0, 0)));
+        assert!(parse_crontab("61 * * * *").unwrap_err().contains("分钟"));
+        assert!(parse_crontab("0 6 * *").is_err());
+        assert!(parse_crontab("*/0 * * * *").is_err());
+        assert!(parse_crontab("0 0 31 2 *").unwrap().next_after(local(2026, 1, 1, 0, 0)).is_none());
+    }
+}
@@ -1,10 +1,10 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
 use std::sync::{Mutex, OnceLock};
 use std::time::Duration;
 
 use chrono::{DateTime, Datelike, Local, TimeZone, Timelike};
 use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Emitter};
 use tokio::time::sleep;
 
 use crate::modules;
@@ -12,8 +12,17 @@ use crate::modules;
 const DEFAULT_PROMPT: &str = "hi";
 const RESET_TRIGGER_COOLDOWN_MS: i64 = 10 * 60 * 1000;
 const RESET_SAFETY_MARGIN_MS: i64 = 2 * 60 * 1000;
-
-#[derive(Debug, Clone, Deserialize)]
+const SCHEDULER_STATE_FILE: &str = "wakeup_scheduler_state.json";
+/// 前端最近一次同步的任务列表，供无界面（--daemon）模式启动时恢复
+const SYNCED_TASKS_FILE: &str = "wakeup_tasks.json";
+/// 超过预定时间多久视为错过（应用关闭或系统休眠），调度循环间隔为 30 秒
+const MISSED_RUN_GRACE_SECS: i64 = 2 * 60;
+/// "全部补跑"策略最多补跑的次数，更早的错过执行直接跳过
+const MAX_CATCH_UP_RUNS: usize = 24;
+/// 统计错过次数时的最大遍历次数
+const MAX_MISSED_SCAN: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct WakeupTaskInput {
     pub id: String,
@@ -24,7 +33,7 @@ pub struct WakeupTaskInput {
     pub schedule: ScheduleConfig,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ScheduleConfig {
     pub repeat_mode: String,
@@ -44,6 +53,28 @@ pub struct ScheduleConfig {
     pub time_window_start: Option<String>,
     pub time_window_end: Option<String>,
     pub fallback_times: Option<Vec<String>>,
+    pub catch_up_policy: Option<String>,
+}
+
+/// 错过执行（应用关闭或系统休眠期间）后的补跑策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CatchUpPolicy {
+    /// 跳过错过的执行，等待下一次
+    Skip,
+    /// 无论错过几次，只补跑一次
+    RunOnce,
+    /// 每次错过的执行都补跑（最多 MAX_CATCH_UP_RUNS 次）
+    RunAll,
+}
+
+impl CatchUpPolicy {
+    fn parse(value: Option<&str>) -> Self {
+        match value {
+            Some("skip") => CatchUpPolicy::Skip,
+            Some("all") => CatchUpPolicy::RunAll,
+            _ => CatchUpPolicy::RunOnce,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -76,9 +107,11 @@ struct ScheduleConfigNormalized {
     time_window_start: Option<String>,
     time_window_end: Option<String>,
     fallback_times: Vec<String>,
+    catch_up_policy: CatchUpPolicy,
 }
 
-#[derive(Default, Debug, Clone)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct ResetState {
     last_reset_trigger_timestamps: HashMap<String, String>,
     last_reset_trigger_at: HashMap<String, i64>,
@@ -94,13 +127,105 @@ struct SchedulerState {
     last_fallback_run_at: HashMap<String, i64>,
     /// 记录每个任务的实际执行时间，不会被前端 sync_state 覆盖
     last_executed_at: HashMap<String, i64>,
+    /// 每个任务已处理（执行或按策略跳过）到的预定时间，下一次执行从该时间之后计算
+    schedule_cursor: HashMap<String, i64>,
+}
+
+/// 持久化到数据目录的调度状态，重启后恢复，避免配额重置任务重复触发或错过执行无法识别
+#[derive(Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PersistedSchedulerState {
+    #[serde(default)]
+    reset_states: HashMap<String, ResetState>,
+    #[serde(default)]
+    last_fallback_run_at: HashMap<String, i64>,
+    #[serde(default)]
+    last_executed_at: HashMap<String, i64>,
+    #[serde(default)]
+    schedule_cursor: HashMap<String, i64>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SyncedTasks {
+    enabled: bool,
+    tasks: Vec<WakeupTaskInput>,
 }
 
 static STATE: OnceLock<Mutex<SchedulerState>> = OnceLock::new();
 static STARTED: OnceLock<Mutex<bool>> = OnceLock::new();
 
 fn state() -> &'static Mutex<SchedulerState> {
-    STATE.get_or_init(|| Mutex::new(SchedulerState::default()))
+    STATE.get_or_init(|| {
+        let persisted = load_persisted_state();
+        Mutex::new(SchedulerState {
+            reset_states: persisted.reset_states,
+            last_fallback_run_at: persisted.last_fallback_run_at,
+            last_executed_at: persisted.last_executed_at,
+            schedule_cursor: persisted.schedule_cursor,
+            ..SchedulerState::default()
+        })
+    })
+}
+
+fn load_persisted_state() -> PersistedSchedulerState {
+    let Ok(data_dir) = modules::account::get_data_dir() else {
+        return PersistedSchedulerState::default();
+    };
+    let Ok(content) = fs::read_to_string(data_dir.join(SCHEDULER_STATE_FILE)) else {
+        return PersistedSchedulerState::default();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        modules::logger::log_warn(&format!("解析唤醒调度状态失败，将重新开始: {}", e));
+        PersistedSchedulerState::default()
+    })
+}
+
+/// 保存调度状态，失败只记录日志
+fn persist_state(state: &SchedulerState) {
+    let persisted = PersistedSchedulerState {
+        reset_states: state.reset_states.clone(),
+        last_fallback_run_at: state.last_fallback_run_at.clone(),
+        last_executed_at: state.last_executed_at.clone(),
+        schedule_cursor: state.schedule_cursor.clone(),
+    };
+    if let Err(e) = save_persisted_state(&persisted) {
+        modules::logger::log_error(&e);
+    }
+}
+
+/// 先写临时文件再替换，避免写入中断导致状态文件损坏
+fn save_persisted_state(persisted: &PersistedSchedulerState) -> Result<(), String> {
+    let data_dir = modules::account::get_data_dir()?;
+    let content = serde_json::to_string_pretty(persisted)
+        .map_err(|e| format!("序列化唤醒调度状态失败: {}", e))?;
+    let temp_path = data_dir.join(format!("{}.tmp", SCHEDULER_STATE_FILE));
+    fs::write(&temp_path, content).map_err(|e| format!("写入唤醒调度状态失败: {}", e))?;
+    fs::rename(temp_path, data_dir.join(SCHEDULER_STATE_FILE))
+        .map_err(|e| format!("替换唤醒调度状态文件失败: {}", e))
+}
+
+fn save_synced_tasks(synced: &SyncedTasks) -> Result<(), String> {
+    let data_dir = modules::account::get_data_dir()?;
+    let content =
+        serde_json::to_string_pretty(synced).map_err(|e| format!("序列化唤醒任务失败: {}", e))?;
+    let temp_path = data_dir.join(format!("{}.tmp", SYNCED_TASKS_FILE));
+    fs::write(&temp_path, content).map_err(|e| format!("写入唤醒任务失败: {}", e))?;
+    fs::rename(temp_path, data_dir.join(SYNCED_TASKS_FILE))
+        .map_err(|e| format!("替换唤醒任务文件失败: {}", e))
+}
+
+/// 从数据目录恢复前端最近同步的任务（无界面模式下没有前端推送任务），返回恢复的任务数
+pub fn restore_synced_tasks() -> Result<usize, String> {
+    let path = modules::account::get_data_dir()?.join(SYNCED_TASKS_FILE);
+    if !path.exists() {
+        return Ok(0);
+    }
+    let content = fs::read_to_string(&path).map_err(|e| format!("读取唤醒任务失败: {}", e))?;
+    let synced: SyncedTasks =
+        serde_json::from_str(&content).map_err(|e| format!("解析唤醒任务失败: {}", e))?;
+    let count = synced.tasks.len();
+    apply_tasks(synced.enabled, synced.tasks);
+    Ok(count)
 }
 
 fn started_flag() -> &'static Mutex<bool> {
@@ -149,10 +274,19 @@ fn normalize_schedule(raw: ScheduleConfig) -> ScheduleConfigNormalized {
         time_window_start: raw.time_window_start,
         time_window_end: raw.time_window_end,
         fallback_times,
+        catch_up_policy: CatchUpPolicy::parse(raw.catch_up_policy.as_deref()),
     }
 }
 
 pub fn sync_state(enabled: bool, tasks: Vec<WakeupTaskInput>) {
+    let synced = SyncedTasks { enabled, tasks };
+    if let Err(e) = save_synced_tasks(&synced) {
+        modules::logger::log_error(&e);
+    }
+    apply_tasks(synced.enabled, synced.tasks);
+}
+
+fn apply_tasks(enabled: bool, tasks: Vec<WakeupTaskInput>) {
     let mut guard = state().lock().expect("wakeup state lock");
     guard.enabled = enabled;
     guard.tasks = tasks
@@ -166,9 +300,28 @@ pub fn sync_state(enabled: bool, tasks: Vec<WakeupTaskInput>) {
             schedule: normalize_schedule(task.schedule),
         })
         .collect();
+
+    for task in guard.tasks.iter() {
+        if let Some(expr) = &task.schedule.crontab {
+            if let Err(e) = modules::wakeup_cron::parse_crontab(expr) {
+                modules::logger::log_warn(&format!("唤醒任务 {} 的 Crontab 表达式无效，已跳过: {}", task.name, e));
+            }
+        }
+    }
+
+    // 清理已删除任务的状态
+    let task_ids: HashSet<String> = guard.tasks.iter().map(|task| task.id.clone()).collect();
+    let before = guard.reset_states.len() + guard.last_executed_at.len() + guard.schedule_cursor.len();
+    guard.reset_states.retain(|id, _| task_ids.contains(id));
+    guard.last_fallback_run_at.retain(|id, _| task_ids.contains(id));
+    guard.last_executed_at.retain(|id, _| task_ids.contains(id));
+    guard.schedule_cursor.retain(|id, _| task_ids.contains(id));
+    if guard.reset_states.len() + guard.last_executed_at.len() + guard.schedule_cursor.len() != before {
+        persist_state(&guard);
+    }
 }
 
-pub fn ensure_started(app: AppHandle) {
+pub fn ensure_started() {
     let mut started = started_flag().lock().expect("wakeup started lock");
     if *started {
         return;
@@ -177,7 +330,7 @@ pub fn ensure_started(app: AppHandle) {
 
     tauri::async_runtime::spawn(async move {
         loop {
-            run_scheduler_once(&app).await;
+            run_scheduler_once().await;
             sleep(Duration::from_secs(30)).await;
         }
     });
@@ -310,73 +463,6 @@ fn build_datetime_from_date(date: DateTime<Local>, time: &str) -> Option<DateTim
     Local.from_local_datetime(&naive).single()
 }
 
-fn next_crontab_time(expr: &str, after: DateTime<Local>) -> Option<DateTime<Local>> {
-    let parts: Vec<&str> = expr.trim().split_whitespace().collect();
-    if parts.len() < 5 {
-        return None;
-    }
-    let minutes = parse_cron_field(parts[0], 59)?;
-    let hours = parse_cron_field(parts[1], 23)?;
-
-    for day_offset in 0..7 {
-        for h in &hours {
-            for m in &minutes {
-                let candidate = build_datetime(after, day_offset, &format!("{:02}:{:02}", h, m));
-                if let Some(candidate) = candidate {
-                    if candidate > after {
-                        return Some(candidate);
-                    }
-                }
-            }
-        }
-    }
-    None
-}
-
-fn parse_cron_field(field: &str, max: i32) -> Option<Vec<i32>> {
-    if field == "*" {
-        return Some((0..=max).collect());
-    }
-    if field.contains(',') {
-        let mut result = Vec::new();
-        for part in field.split(',') {
-            result.push(part.parse().ok()?);
-        }
-        return Some(result);
-    }
-    if field.contains('-') {
-        let parts: Vec<&str> = field.split('-').collect();
-        if parts.len() != 2 {
-            return None;
-        }
-        let start: i32 = parts[0].parse().ok()?;
-        let end: i32 = parts[1].parse().ok()?;
-        if end < start {
-            return None;
-        }
-        return Some((start..=end).collect());
-    }
-    if field.contains('/') {
-        let parts: Vec<&str> = field.split('/').collect();
-        if parts.len() != 2 {
-            return None;
-        }
-        let step: i32 = parts[1].parse().ok()?;
-        if step <= 0 {
-            return None;
-        }
-        let mut result = Vec::new();
-        let mut value = 0;
-        while value <= max {
-            result.push(value);
-            value += step;
-        }
-        return Some(result);
-    }
-    let value: i32 = field.parse().ok()?;
-    Some(vec![value])
-}
-
 fn normalize_max_tokens(value: i32) -> u32 {
     if value > 0 {
         value as u32
@@ -444,7 +530,7 @@ fn mark_reset_triggered(state: &mut ResetState, model_key: &str, reset_at: &str)
         .insert(model_key.to_string(), chrono::Utc::now().timestamp_millis());
 }
 
-async fn run_scheduler_once(app: &AppHandle) {
+async fn run_scheduler_once() {
     let snapshot = {
         let guard = state().lock().expect("wakeup state lock");
         guard.clone()
@@ -465,33 +551,130 @@ async fn run_scheduler_once(app: &AppHandle) {
         }
 
         if task.schedule.wake_on_reset {
-            handle_quota_reset_task(app, task, now).await;
+            handle_quota_reset_task(task, now).await;
             continue;
         }
 
-        // 优先使用本地记录的执行时间，避免被前端同步覆盖导致重复执行
-        let local_last_run = snapshot.last_executed_at.get(&task.id).copied();
+        // 优先使用本地记录的调度进度与执行时间，避免被前端同步覆盖导致重复执行
+        let local_last_run = snapshot
+            .schedule_cursor
+            .get(&task.id)
+            .or_else(|| snapshot.last_executed_at.get(&task.id))
+            .copied();
         let after = local_last_run
             .or(task.last_run_at)
             .and_then(|ts| Local.timestamp_millis_opt(ts).single())
             .unwrap_or_else(|| now - chrono::Duration::minutes(1));
 
-        let next_run = if let Some(expr) = &task.schedule.crontab {
-            next_crontab_time(expr, after)
-        } else {
-            next_run_time(&task.schedule, after)
+        // 只有到达预定时间才触发（不再提前30秒）
+        let Some(next_run) = next_scheduled_time(&task.schedule, after) else {
+            continue;
         };
+        if next_run > now {
+            continue;
+        }
 
-        // 只有到达预定时间才触发（不再提前30秒）
-        if let Some(next_run) = next_run {
-            if next_run <= now {
-                run_task(app, task, "scheduled").await;
+        if (now - next_run).num_seconds() <= MISSED_RUN_GRACE_SECS {
+            run_task(task, "scheduled").await;
+            set_schedule_cursor(&task.id, next_run);
+            continue;
+        }
+        catch_up_missed_runs(task, next_run, now).await;
+    }
+}
+
+fn next_scheduled_time(
+    schedule: &ScheduleConfigNormalized,
+    after: DateTime<Local>,
+) -> Option<DateTime<Local>> {
+    if let Some(expr) = &schedule.crontab {
+        modules::wakeup_cron::parse_crontab(expr)
+            .ok()
+            .and_then(|cron| cron.next_after(after))
+    } else {
+        next_run_time(schedule, after)
+    }
+}
+
+fn set_schedule_cursor(task_id: &str, at: DateTime<Local>) {
+    let mut guard = state().lock().expect("wakeup state lock");
+    guard
+        .schedule_cursor
+        .insert(task_id.to_string(), at.timestamp_millis());
+    persist_state(&guard);
+}
+
+/// 从 first_missed 起到 now 之间错过的预定时间，只保留最近的 limit 个
+fn missed_runs(
+    schedule: &ScheduleConfigNormalized,
+    first_missed: DateTime<Local>,
+    now: DateTime<Local>,
+    limit: usize,
+) -> VecDeque<DateTime<Local>> {
+    let mut runs = VecDeque::with_capacity(limit);
+    let mut cursor = Some(first_missed);
+    for _ in 0..MAX_MISSED_SCAN {
+        let Some(run) = cursor.filter(|run| *run <= now) else {
+            break;
+        };
+        if runs.len() == limit {
+            runs.pop_front();
+        }
+        runs.push_back(run);
+        cursor = next_scheduled_time(schedule, run);
+    }
+    runs
+}
+
+/// 应用关闭或系统休眠期间错过了执行：按任务的补跑策略处理
+async fn catch_up_missed_runs(
+    task: &WakeupTask,
+    first_missed: DateTime<Local>,
+    now: DateTime<Local>,
+) {
+    match task.schedule.catch_up_policy {
+        CatchUpPolicy::Skip => {
+            modules::logger::log_info(&format!(
+                "唤醒任务 {} 错过了 {} 的执行，按策略跳过",
+                task.name,
+                first_missed.format("%Y-%m-%d %H:%M")
+            ));
+            set_schedule_cursor(&task.id, now);
+        }
+        CatchUpPolicy::RunOnce => {
+            modules::logger::log_info(&format!(
+                "唤醒任务 {} 错过了 {} 起的执行，补跑一次",
+                task.name,
+                first_missed.format("%Y-%m-%d %H:%M")
+            ));
+            run_task(task, "scheduled").await;
+            set_schedule_cursor(&task.id, now);
+        }
+        CatchUpPolicy::RunAll => {
+            // 每轮调度补跑一次，依次推进到下一个错过的预定时间
+            let missed = missed_runs(&task.schedule, first_missed, now, MAX_CATCH_UP_RUNS);
+            let Some(run) = missed.front().copied() else {
+                return;
+            };
+            if run != first_missed {
+                modules::logger::log_warn(&format!(
+                    "唤醒任务 {} 错过的执行过多，仅补跑最近 {} 次",
+                    task.name, MAX_CATCH_UP_RUNS
+                ));
             }
+            modules::logger::log_info(&format!(
+                "唤醒任务 {} 补跑 {} 的执行（剩余 {} 次）",
+                task.name,
+                run.format("%Y-%m-%d %H:%M"),
+                missed.len() - 1
+            ));
+            run_task(task, "scheduled").await;
+            set_schedule_cursor(&task.id, run);
         }
     }
 }
 
-async fn handle_quota_reset_task(app: &AppHandle, task: &WakeupTask, now: DateTime<Local>) {
+async fn handle_quota_reset_task(task: &WakeupTask, now: DateTime<Local>) {
     let mut should_run_fallback = false;
     if task.schedule.time_window_enabled
         && !is_in_time_window(
@@ -510,7 +693,7 @@ async fn handle_quota_reset_task(app: &AppHandle, task: &WakeupTask, now: DateTi
             }
         }
         if should_run_fallback {
-            run_task(app, task, "scheduled").await;
+            run_task(task, "scheduled").await;
         }
         return;
     }
@@ -563,12 +746,14 @@ async fn handle_quota_reset_task(app: &AppHandle, task: &WakeupTask, now: DateTi
                 }
             }
         }
+        if !models_to_trigger.is_empty() {
+            persist_state(&state_guard);
+        }
         models_to_trigger
     };
 
     if !models_to_trigger.is_empty() {
         run_task_with_models(
-            app,
             task,
             "quota_reset",
             models_to_trigger.into_iter().collect(),
@@ -577,9 +762,8 @@ async fn handle_quota_reset_task(app: &AppHandle, task: &WakeupTask, now: DateTi
     }
 }
 
-async fn run_task(app: &AppHandle, task: &WakeupTask, trigger_source: &str) {
+async fn run_task(task: &WakeupTask, trigger_source: &str) {
     run_task_with_models(
-        app,
         task,
         trigger_source,
         task.schedule.selected_models.clone(),
@@ -588,7 +772,6 @@ async fn run_task(app: &AppHandle, task: &WakeupTask, trigger_source: &str) {
 }
 
 async fn run_task_with_models(
-    app: &AppHandle,
     task: &WakeupTask,
     trigger_source: &str,
     models: Vec<String>,
@@ -684,6 +867,7 @@ async fn run_task_with_models(
             .insert(task.id.clone(), executed_at);
         // 记录本地执行时间，防止被前端同步覆盖导致重复执行
         guard.last_executed_at.insert(task.id.clone(), executed_at);
+        persist_state(&guard);
     }
 
     // 写入历史文件
@@ -696,7 +880,7 @@ async fn run_task_with_models(
         last_run_at: chrono::Utc::now().timestamp_millis(),
         records: history,
     };
-    let _ = app.emit("wakeup://task-result", payload);
+    crate::emit_event("wakeup://task-result", payload);
 }
 
 #[derive(Debug, Clone, Serialize)]
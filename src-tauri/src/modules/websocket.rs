@@ -1,5 +1,6 @@
 //! WebSocket 服务模块
 //! 提供本地 WebSocket 服务供 VS Code 扩展实时通信
+//! 连接后须先配对（request.pair）或认证（request.auth），请求按客户端被授予的能力校验，见 websocket_auth
 
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
@@ -7,10 +8,15 @@ use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio_tungstenite::tungstenite::Message;
 
 use super::config::{get_preferred_port, init_server_status, PORT_RANGE};
+use super::websocket_auth::{self, Capability};
+use super::websocket_platforms::{self, Platform, PlatformAccountInfo};
+
+/// 连接后未完成认证的最长等待时间
+const AUTH_TIMEOUT_SECS: u64 = 10;
 
 /// 消息类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,7 +25,7 @@ pub enum WsMessage {
     // ============ 事件通知（Tools -> 扩展） ============
     /// 服务就绪
     #[serde(rename = "event.ready")]
-    Ready { version: String },
+    Ready { version: String, auth_required: bool },
 
     /// 数据已变更，请刷新
     #[serde(rename = "event.data_changed")]
@@ -41,7 +47,41 @@ pub enum WsMessage {
     #[serde(rename = "event.wakeup_override")]
     WakeupOverride { enabled: bool },
 
+    /// 任一平台账号切换完成（Antigravity 同时发送 event.account_switched）
+    #[serde(rename = "event.platform_account_switched")]
+    PlatformAccountSwitched {
+        platform: Platform,
+        account_id: String,
+        email: String,
+    },
+
+    /// 平台批量刷新后配额已更新
+    #[serde(rename = "event.quota_updated")]
+    QuotaUpdated {
+        platform: Platform,
+        accounts: Vec<PlatformAccountInfo>,
+        current_account_id: Option<String>,
+    },
+
     // ============ 请求（扩展 -> Tools） ============
+    /// 使用共享密钥配对，申请指定能力
+    #[serde(rename = "request.pair")]
+    Pair {
+        request_id: String,
+        client_id: String,
+        client_name: Option<String>,
+        capabilities: Vec<Capability>,
+        secret: String,
+    },
+
+    /// 使用配对获得的 Token 认证
+    #[serde(rename = "request.auth")]
+    Auth {
+        request_id: String,
+        client_id: String,
+        token: String,
+    },
+
     /// 请求获取账号列表
     #[serde(rename = "request.get_accounts")]
     GetAccounts { request_id: String },
@@ -80,6 +120,18 @@ pub enum WsMessage {
     #[serde(rename = "request.delete_account")]
     DeleteAccountByEmail { request_id: String, email: String },
 
+    /// 请求获取指定平台的账号列表（不含 Token）
+    #[serde(rename = "request.platform_get_accounts")]
+    PlatformGetAccounts { request_id: String, platform: Platform },
+
+    /// 请求切换指定平台的账号，完成后返回 response.success
+    #[serde(rename = "request.platform_switch_account")]
+    PlatformSwitchAccount {
+        request_id: String,
+        platform: Platform,
+        account_id: String,
+    },
+
     /// 通知数据已变更
     #[serde(rename = "request.data_changed")]
     NotifyDataChanged { source: String },
@@ -93,6 +145,21 @@ pub enum WsMessage {
     Pong,
 
     // ============ 响应（Tools -> 扩展） ============
+    /// 配对成功，Token 仅返回这一次，客户端需自行保存
+    #[serde(rename = "response.paired")]
+    PairedResponse {
+        request_id: String,
+        token: String,
+        capabilities: Vec<Capability>,
+    },
+
+    /// 认证成功
+    #[serde(rename = "response.authenticated")]
+    AuthenticatedResponse {
+        request_id: String,
+        capabilities: Vec<Capability>,
+    },
+
     /// 账号列表响应
     #[serde(rename = "response.accounts")]
     AccountsResponse {
@@ -116,6 +183,15 @@ pub enum WsMessage {
         account: Option<AccountInfo>,
     },
 
+    /// 平台账号列表响应
+    #[serde(rename = "response.platform_accounts")]
+    PlatformAccountsResponse {
+        request_id: String,
+        platform: Platform,
+        accounts: Vec<PlatformAccountInfo>,
+        current_account_id: Option<String>,
+    },
+
     /// 操作成功响应
     #[serde(rename = "response.success")]
     SuccessResponse { request_id: String, message: String },
@@ -206,10 +282,7 @@ pub fn broadcast_data_changed(source: &str) {
     crate::modules::logger::log_info(&format!("[WS] 广播数据变更: {}", source));
 
     // 同时发送 Tauri 事件通知前端刷新
-    if let Some(app_handle) = crate::get_app_handle() {
-        use tauri::Emitter;
-        let _ = app_handle.emit("accounts:refresh", source);
-    }
+    crate::emit_event("accounts:refresh", source);
 }
 
 /// 广播语言变更
@@ -224,10 +297,7 @@ pub fn broadcast_language_changed(language: &str, source: &str) {
         language, source
     ));
 
-    if let Some(app_handle) = crate::get_app_handle() {
-        use tauri::Emitter;
-        let _ = app_handle.emit("settings:language_changed", language);
-    }
+    crate::emit_event("settings:language_changed", language);
 }
 
 /// 广播账号切换完成
@@ -237,7 +307,41 @@ pub fn broadcast_account_switched(account_id: &str, email: &str) {
         account_id: account_id.to_string(),
         email: email.to_string(),
     });
-    crate::modules::logger::log_info("[WS] 广播账号切换");
+    broadcast_platform_account_switched(Platform::Antigravity, account_id, email);
+}
+
+/// 广播任一平台账号切换完成
+pub fn broadcast_platform_account_switched(platform: Platform, account_id: &str, email: &str) {
+    let server = get_server();
+    server.broadcast(WsMessage::PlatformAccountSwitched {
+        platform,
+        account_id: account_id.to_string(),
+        email: email.to_string(),
+    });
+    crate::modules::logger::log_info(&format!("[WS] 广播账号切换: {}", platform.label()));
+}
+
+/// 广播平台配额更新（批量刷新后调用），没有客户端连接时跳过
+pub fn broadcast_quota_updated(platform: Platform) {
+    let server = get_server();
+    if server.tx.receiver_count() == 0 {
+        return;
+    }
+    match websocket_platforms::list_accounts(platform) {
+        Ok((accounts, current_account_id)) => {
+            server.broadcast(WsMessage::QuotaUpdated {
+                platform,
+                accounts,
+                current_account_id,
+            });
+            crate::modules::logger::log_info(&format!("[WS] 广播配额更新: {}", platform.label()));
+        }
+        Err(e) => crate::modules::logger::log_warn(&format!(
+            "[WS] 读取 {} 账号失败，跳过配额更新广播: {}",
+            platform.label(),
+            e
+        )),
+    }
 }
 
 /// 广播唤醒互斥开关
@@ -290,6 +394,11 @@ pub async fn start_server() {
         None => return,
     };
 
+    // 配对共享密钥与服务状态写在同一目录（供 VS Code 扩展读取）
+    if let Err(e) = websocket_auth::ensure_secret() {
+        crate::modules::logger::log_error(&format!("[WS] 生成配对密钥失败: {}", e));
+    }
+
     // 保存服务状态到共享文件（供 VS Code 扩展读取）
     if let Err(e) = init_server_status(port) {
         crate::modules::logger::log_error(&format!("[WS] 保存服务状态失败: {}", e));
@@ -331,6 +440,7 @@ async fn handle_connection(server: Arc<WsServer>, stream: TcpStream, addr: Socke
     // 发送 Ready 消息
     let ready_msg = WsMessage::Ready {
         version: env!("CARGO_PKG_VERSION").to_string(),
+        auth_required: true,
     };
     if let Ok(json) = serde_json::to_string(&ready_msg) {
         let _ = ws_sender.send(Message::Text(json.into())).await;
@@ -338,6 +448,13 @@ async fn handle_connection(server: Arc<WsServer>, stream: TcpStream, addr: Socke
 
     // 订阅广播
     let mut broadcast_rx = server.tx.subscribe();
+    // 后台任务（如平台切号）完成后经此发回本连接的响应
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<String>();
+
+    // 已认证的客户端 ID
+    let mut client_id: Option<String> = None;
+    let auth_deadline = tokio::time::sleep(std::time::Duration::from_secs(AUTH_TIMEOUT_SECS));
+    tokio::pin!(auth_deadline);
 
     loop {
         tokio::select! {
@@ -345,8 +462,12 @@ async fn handle_connection(server: Arc<WsServer>, stream: TcpStream, addr: Socke
             msg = ws_receiver.next() => {
                 match msg {
                     Some(Ok(Message::Text(text))) => {
-                        if let Err(e) = handle_client_message(&server, &mut ws_sender, &text).await {
+                        if let Err(e) = handle_client_message(&server, &mut ws_sender, &reply_tx, &text, &mut client_id).await {
                             crate::modules::logger::log_error(&format!("[WS] 处理消息失败: {}", e));
+                            // 未认证的连接出错即断开
+                            if client_id.is_none() {
+                                break;
+                            }
                         }
                     }
                     Some(Ok(Message::Close(_))) => {
@@ -361,14 +482,30 @@ async fn handle_connection(server: Arc<WsServer>, stream: TcpStream, addr: Socke
                     _ => {}
                 }
             }
-            // 发送广播消息
+            // 发送广播消息（仅已认证且未被吊销的客户端）
             msg = broadcast_rx.recv() => {
+                let Some(id) = client_id.as_deref() else {
+                    continue;
+                };
+                if websocket_auth::current_capabilities(id).is_none() {
+                    crate::modules::logger::log_info(&format!("[WS] 客户端已被吊销，断开: {}", addr));
+                    break;
+                }
                 if let Ok(json) = msg {
                     if ws_sender.send(Message::Text(json.into())).await.is_err() {
                         break;
                     }
                 }
             }
+            Some(json) = reply_rx.recv() => {
+                if ws_sender.send(Message::Text(json.into())).await.is_err() {
+                    break;
+                }
+            }
+            _ = &mut auth_deadline, if client_id.is_none() => {
+                crate::modules::logger::log_warn(&format!("[WS] 客户端未在限定时间内认证，断开: {}", addr));
+                break;
+            }
         }
     }
 
@@ -381,17 +518,81 @@ async fn handle_connection(server: Arc<WsServer>, stream: TcpStream, addr: Socke
     crate::modules::logger::log_info(&format!("[WS] 连接关闭: {}", addr));
 }
 
+/// 请求所需的能力；None 表示无需认证
+fn required_capability(msg: &WsMessage) -> Option<Capability> {
+    match msg {
+        WsMessage::Ping | WsMessage::Pair { .. } | WsMessage::Auth { .. } => None,
+        WsMessage::GetAccountsWithTokens { .. } => Some(Capability::ReadTokens),
+        WsMessage::SwitchAccount { .. }
+        | WsMessage::PlatformSwitchAccount { .. }
+        | WsMessage::SetLanguage { .. }
+        | WsMessage::AddAccount { .. }
+        | WsMessage::DeleteAccountByEmail { .. }
+        | WsMessage::NotifyDataChanged { .. } => Some(Capability::Mutate),
+        _ => Some(Capability::Read),
+    }
+}
+
+fn request_id_of(msg: &WsMessage) -> String {
+    match msg {
+        WsMessage::GetAccounts { request_id }
+        | WsMessage::GetAccountsWithTokens { request_id }
+        | WsMessage::GetCurrentAccount { request_id }
+        | WsMessage::SetLanguage { request_id, .. }
+        | WsMessage::AddAccount { request_id, .. }
+        | WsMessage::DeleteAccountByEmail { request_id, .. }
+        | WsMessage::PlatformGetAccounts { request_id, .. }
+        | WsMessage::PlatformSwitchAccount { request_id, .. }
+        | WsMessage::Pair { request_id, .. }
+        | WsMessage::Auth { request_id, .. } => request_id.clone(),
+        _ => String::new(),
+    }
+}
+
+type WsSender = futures_util::stream::SplitSink<tokio_tungstenite::WebSocketStream<TcpStream>, Message>;
+
+async fn send_message(sender: &mut WsSender, message: &WsMessage) -> Result<(), String> {
+    let json = serde_json::to_string(message).map_err(|e| format!("序列化响应失败: {}", e))?;
+    sender
+        .send(Message::Text(json.into()))
+        .await
+        .map_err(|e| format!("发送响应失败: {}", e))
+}
+
 /// 处理客户端消息
 async fn handle_client_message(
     server: &WsServer,
-    sender: &mut futures_util::stream::SplitSink<
-        tokio_tungstenite::WebSocketStream<TcpStream>,
-        Message,
-    >,
+    sender: &mut WsSender,
+    replies: &mpsc::UnboundedSender<String>,
     text: &str,
+    client_id: &mut Option<String>,
 ) -> Result<(), String> {
     let msg: WsMessage = serde_json::from_str(text).map_err(|e| format!("解析消息失败: {}", e))?;
 
+    // 每条请求都按当前授予的能力校验，修改或吊销立即生效
+    if let Some(required) = required_capability(&msg) {
+        let granted = client_id.as_deref().and_then(websocket_auth::current_capabilities);
+        let error = match granted {
+            Some(capabilities) if websocket_auth::allows(&capabilities, required) => None,
+            Some(_) => Some("权限不足，请在 Cockpit Tools 设置中为该客户端授予相应能力"),
+            None => {
+                *client_id = None;
+                Some("未认证，请先配对或认证")
+            }
+        };
+        if let Some(error) = error {
+            send_message(
+                sender,
+                &WsMessage::ErrorResponse {
+                    request_id: request_id_of(&msg),
+                    error: error.to_string(),
+                },
+            )
+            .await?;
+            return Err(format!("拒绝请求: {}", error));
+        }
+    }
+
     match msg {
         WsMessage::Ping => {
             let pong = serde_json::to_string(&WsMessage::Pong).unwrap();
@@ -401,6 +602,57 @@ async fn handle_client_message(
                 .map_err(|e| format!("发送 Pong 失败: {}", e))?;
         }
 
+        WsMessage::Pair {
+            request_id,
+            client_id: id,
+            client_name,
+            capabilities,
+            secret,
+        } => {
+            let result = websocket_auth::pair(&secret, &id, client_name.as_deref().unwrap_or(""), capabilities);
+            match result {
+                Ok((token, capabilities)) => {
+                    crate::modules::logger::log_info(&format!("[WS] 客户端已配对: {} {:?}", id, capabilities));
+                    *client_id = Some(id.trim().to_string());
+                    send_message(
+                        sender,
+                        &WsMessage::PairedResponse {
+                            request_id,
+                            token,
+                            capabilities,
+                        },
+                    )
+                    .await?;
+                }
+                Err(e) => {
+                    send_message(sender, &WsMessage::ErrorResponse { request_id, error: e.clone() }).await?;
+                    return Err(format!("配对失败: {}", e));
+                }
+            }
+        }
+
+        WsMessage::Auth {
+            request_id,
+            client_id: id,
+            token,
+        } => match websocket_auth::authenticate(&id, &token) {
+            Ok(capabilities) => {
+                *client_id = Some(id);
+                send_message(
+                    sender,
+                    &WsMessage::AuthenticatedResponse {
+                        request_id,
+                        capabilities,
+                    },
+                )
+                .await?;
+            }
+            Err(e) => {
+                send_message(sender, &WsMessage::ErrorResponse { request_id, error: e.clone() }).await?;
+                return Err(format!("认证失败: {}", e));
+            }
+        },
+
         WsMessage::GetAccounts { request_id } => {
             crate::modules::logger::log_info("[WS] 收到获取账号列表请求");
 
@@ -476,15 +728,7 @@ async fn handle_client_message(
             let server_clone = server.tx.clone();
             tokio::spawn(async move {
                 match crate::modules::account::switch_account_internal(&account_id).await {
-                    Ok(account) => {
-                        let msg = WsMessage::AccountSwitched {
-                            account_id: account.id,
-                            email: account.email,
-                        };
-                        if let Ok(json) = serde_json::to_string(&msg) {
-                            let _ = server_clone.send(json);
-                        }
-                    }
+                    Ok(account) => broadcast_account_switched(&account.id, &account.email),
                     Err(e) => {
                         let msg = WsMessage::SwitchError { message: e };
                         if let Ok(json) = serde_json::to_string(&msg) {
@@ -584,6 +828,50 @@ async fn handle_client_message(
             }
         }
 
+        WsMessage::PlatformGetAccounts { request_id, platform } => {
+            crate::modules::logger::log_info(&format!("[WS] 收到获取 {} 账号列表请求", platform.label()));
+
+            let response = match websocket_platforms::list_accounts(platform) {
+                Ok((accounts, current_account_id)) => WsMessage::PlatformAccountsResponse {
+                    request_id,
+                    platform,
+                    accounts,
+                    current_account_id,
+                },
+                Err(e) => WsMessage::ErrorResponse {
+                    request_id,
+                    error: e,
+                },
+            };
+            send_message(sender, &response).await?;
+        }
+
+        WsMessage::PlatformSwitchAccount {
+            request_id,
+            platform,
+            account_id,
+        } => {
+            crate::modules::logger::log_info(&format!("[WS] 收到切换 {} 账号请求", platform.label()));
+
+            // 异步执行切换，完成后经 replies 回复本连接，不阻塞该连接的其它消息
+            let replies = replies.clone();
+            tokio::spawn(async move {
+                let response = match websocket_platforms::switch_account(platform, &account_id).await {
+                    Ok(email) => WsMessage::SuccessResponse {
+                        request_id,
+                        message: format!("已切换到 {}", email),
+                    },
+                    Err(e) => WsMessage::ErrorResponse {
+                        request_id,
+                        error: e,
+                    },
+                };
+                if let Ok(json) = serde_json::to_string(&response) {
+                    let _ = replies.send(json);
+                }
+            });
+        }
+
         WsMessage::NotifyDataChanged { source } => {
             crate::modules::logger::log_info(&format!("[WS] 收到数据变更通知: {}", source));
             // 广播给其他客户端
@@ -0,0 +1,265 @@
+//! WebSocket 服务 — 客户端配对与授权
+//! 共享密钥写在数据目录（与 server.json 同级）的 ws_secret 文件中，仅本机用户可读；
+//! 客户端首次连接时用共享密钥配对，获得专属 Token 与按能力划分的权限，之后凭 Token 认证，可在设置中单独吊销
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, RwLock};
+
+use super::config;
+use super::credential_vault::write_private_file;
+
+/// 共享密钥文件（与 server.json 同级）
+pub const SECRET_FILE: &str = "ws_secret";
+const CLIENTS_FILE: &str = "ws_clients.json";
+/// 认证时更新 last_seen_at 的最小间隔（秒）
+const LAST_SEEN_RESOLUTION_SECS: i64 = 300;
+
+static CLIENTS_LOCK: std::sync::LazyLock<Mutex<()>> = std::sync::LazyLock::new(|| Mutex::new(()));
+/// 运行时缓存，避免每条消息读取文件
+static CLIENTS_CACHE: std::sync::LazyLock<RwLock<Option<Vec<PairedClient>>>> =
+    std::sync::LazyLock::new(|| RwLock::new(None));
+
+/// 客户端能力
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// 读取账号列表、当前账号与事件通知（不含 Token）
+    Read,
+    /// 读取账号 Token
+    ReadTokens,
+    /// 切换、添加、删除账号与修改设置
+    Mutate,
+}
+
+/// 已配对的客户端
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairedClient {
+    /// 客户端自报的标识（如扩展 ID + 机器）
+    pub client_id: String,
+    pub name: String,
+    pub capabilities: Vec<Capability>,
+    /// Token 的 SHA-256，明文只在配对响应中返回一次
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub token_hash: String,
+    #[serde(default)]
+    pub paired_at: i64,
+    #[serde(default)]
+    pub last_seen_at: i64,
+}
+
+/// 是否具备指定能力（Read 由任意能力隐含）
+pub fn allows(granted: &[Capability], capability: Capability) -> bool {
+    granted.contains(&capability) || (capability == Capability::Read && !granted.is_empty())
+}
+
+fn secret_path() -> Result<PathBuf, String> {
+    Ok(config::get_data_dir()?.join(SECRET_FILE))
+}
+
+fn clients_path() -> Result<PathBuf, String> {
+    Ok(config::get_data_dir()?.join(CLIENTS_FILE))
+}
+
+fn random_hex(bytes: usize) -> String {
+    let mut buf = vec![0u8; bytes];
+    rand::thread_rng().fill_bytes(&mut buf);
+    buf.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn sha256_hex(value: &str) -> String {
+    Sha256::digest(value.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 长度相同时逐字节比较全部内容，避免按前缀提前返回
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// 读取共享密钥，不存在时生成
+pub fn ensure_secret() -> Result<String, String> {
+    let path = secret_path()?;
+    if let Ok(existing) = fs::read_to_string(&path) {
+        let existing = existing.trim();
+        if !existing.is_empty() {
+            return Ok(existing.to_string());
+        }
+    }
+    let secret = random_hex(32);
+    write_private_file(&path, secret.as_bytes())?;
+    Ok(secret)
+}
+
+/// 重新生成共享密钥（已配对的客户端不受影响，新客户端需使用新密钥配对）
+pub fn reset_secret() -> Result<(), String> {
+    write_private_file(&secret_path()?, random_hex(32).as_bytes())
+}
+
+fn load_clients_from_disk() -> Result<Vec<PairedClient>, String> {
+    let path = clients_path()?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(&path).map_err(|e| format!("读取已配对客户端失败: {}", e))?;
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&content).map_err(|e| format!("解析已配对客户端失败: {}", e))
+}
+
+fn save_clients(clients: &[PairedClient]) -> Result<(), String> {
+    let content =
+        serde_json::to_string_pretty(clients).map_err(|e| format!("序列化已配对客户端失败: {}", e))?;
+    write_private_file(&clients_path()?, content.as_bytes())?;
+    if let Ok(mut cache) = CLIENTS_CACHE.write() {
+        *cache = Some(clients.to_vec());
+    }
+    Ok(())
+}
+
+fn load_clients() -> Result<Vec<PairedClient>, String> {
+    if let Some(cached) = CLIENTS_CACHE.read().ok().and_then(|c| c.clone()) {
+        return Ok(cached);
+    }
+    let clients = load_clients_from_disk()?;
+    if let Ok(mut cache) = CLIENTS_CACHE.write() {
+        *cache = Some(clients.clone());
+    }
+    Ok(clients)
+}
+
+/// 已配对客户端列表（不含 Token 哈希）
+pub fn list_clients() -> Result<Vec<PairedClient>, String> {
+    Ok(load_clients()?
+        .into_iter()
+        .map(|c| PairedClient {
+            token_hash: String::new(),
+            ..c
+        })
+        .collect())
+}
+
+/// 当前授予的能力；客户端已吊销时返回 None
+pub fn current_capabilities(client_id: &str) -> Option<Vec<Capability>> {
+    load_clients()
+        .ok()?
+        .into_iter()
+        .find(|c| c.client_id == client_id)
+        .map(|c| c.capabilities)
+}
+
+fn normalize_capabilities(mut capabilities: Vec<Capability>) -> Result<Vec<Capability>, String> {
+    capabilities.sort_by_key(|c| *c as u8);
+    capabilities.dedup();
+    if capabilities.is_empty() {
+        return Err("至少需要一项能力".to_string());
+    }
+    Ok(capabilities)
+}
+
+/// 使用共享密钥配对，返回客户端 Token（同一 client_id 重新配对时旧 Token 失效）
+pub fn pair(
+    secret: &str,
+    client_id: &str,
+    name: &str,
+    capabilities: Vec<Capability>,
+) -> Result<(String, Vec<Capability>), String> {
+    let expected = ensure_secret()?;
+    if !constant_time_eq(secret.trim(), &expected) {
+        return Err("配对密钥错误".to_string());
+    }
+    let client_id = client_id.trim();
+    if client_id.is_empty() {
+        return Err("client_id 不能为空".to_string());
+    }
+    let capabilities = normalize_capabilities(capabilities)?;
+
+    let _lock = CLIENTS_LOCK.lock().map_err(|_| "获取客户端列表锁失败")?;
+    let mut clients = load_clients()?;
+    let token = random_hex(32);
+    let now = chrono::Utc::now().timestamp();
+    clients.retain(|c| c.client_id != client_id);
+    clients.push(PairedClient {
+        client_id: client_id.to_string(),
+        name: if name.trim().is_empty() { client_id.to_string() } else { name.trim().to_string() },
+        capabilities: capabilities.clone(),
+        token_hash: sha256_hex(&token),
+        paired_at: now,
+        last_seen_at: now,
+    });
+    save_clients(&clients)?;
+    Ok((token, capabilities))
+}
+
+/// 使用配对时获得的 Token 认证，返回授予的能力
+pub fn authenticate(client_id: &str, token: &str) -> Result<Vec<Capability>, String> {
+    let _lock = CLIENTS_LOCK.lock().map_err(|_| "获取客户端列表锁失败")?;
+    let mut clients = load_clients()?;
+    let client = clients
+        .iter_mut()
+        .find(|c| c.client_id == client_id && constant_time_eq(&c.token_hash, &sha256_hex(token)))
+        .ok_or("客户端未配对或 Token 已失效")?;
+    let capabilities = client.capabilities.clone();
+    // last_seen_at 只精确到 LAST_SEEN_RESOLUTION_SECS，避免每次认证都重写客户端列表
+    let now = chrono::Utc::now().timestamp();
+    if now - client.last_seen_at >= LAST_SEEN_RESOLUTION_SECS {
+        client.last_seen_at = now;
+        save_clients(&clients)?;
+    }
+    Ok(capabilities)
+}
+
+/// 修改客户端能力，对连接中的客户端立即生效
+pub fn update_client_capabilities(client_id: &str, capabilities: Vec<Capability>) -> Result<(), String> {
+    let capabilities = normalize_capabilities(capabilities)?;
+    let _lock = CLIENTS_LOCK.lock().map_err(|_| "获取客户端列表锁失败")?;
+    let mut clients = load_clients()?;
+    let client = clients
+        .iter_mut()
+        .find(|c| c.client_id == client_id)
+        .ok_or("客户端不存在")?;
+    client.capabilities = capabilities;
+    save_clients(&clients)
+}
+
+/// 吊销客户端，连接中的客户端在下一条消息时断开
+pub fn revoke_client(client_id: &str) -> Result<(), String> {
+    let _lock = CLIENTS_LOCK.lock().map_err(|_| "获取客户端列表锁失败")?;
+    let mut clients = load_clients()?;
+    let before = clients.len();
+    clients.retain(|c| c.client_id != client_id);
+    if clients.len() == before {
+        return Err("客户端不存在".to_string());
+    }
+    save_clients(&clients)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capability_checks_and_normalization() {
+        assert!(allows(&[Capability::Mutate], Capability::Read));
+        assert!(allows(&[Capability::ReadTokens], Capability::ReadTokens));
+        assert!(!allows(&[Capability::Read], Capability::ReadTokens));
+        assert!(!allows(&[Capability::ReadTokens], Capability::Mutate));
+        assert!(!allows(&[], Capability::Read));
+
+        let caps = normalize_capabilities(vec![Capability::Mutate, Capability::Read, Capability::Mutate]).unwrap();
+        assert_eq!(caps, vec![Capability::Read, Capability::Mutate]);
+        assert!(normalize_capabilities(Vec::new()).is_err());
+
+        assert!(constant_time_eq("abc", "abc"));
+        assert!(!constant_time_eq("abc", "abd"));
+        assert!(!constant_time_eq("abc", "abcd"));
+        assert_eq!(
+            serde_json::to_string(&Capability::ReadTokens).unwrap(),
+            "\"read_tokens\""
+        );
+    }
+}
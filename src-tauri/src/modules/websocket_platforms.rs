@@ -0,0 +1,266 @@
+//! WebSocket 服务 — 多平台账号
+//! 按平台统一账号列表与切换，供扩展通过 request.platform_* 访问 Codex / Kiro / Windsurf / GitHub Copilot / Warp 账号；
+//! 列表不含 Token，切换复用应用内的切号流程
+
+use serde::{Deserialize, Serialize};
+
+use crate::modules;
+
+/// 账号所属平台
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Platform {
+    Antigravity,
+    Codex,
+    Kiro,
+    Windsurf,
+    GithubCopilot,
+    Warp,
+}
+
+impl Platform {
+    pub fn label(self) -> &'static str {
+        match self {
+            Platform::Antigravity => "Antigravity",
+            Platform::Codex => "Codex",
+            Platform::Kiro => "Kiro",
+            Platform::Windsurf => "Windsurf",
+            Platform::GithubCopilot => "GitHub Copilot",
+            Platform::Warp => "Warp",
+        }
+    }
+}
+
+/// 单项配额的剩余百分比
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaMetric {
+    pub name: String,
+    pub remaining_percent: i32,
+}
+
+/// 平台账号信息（不含 Token）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlatformAccountInfo {
+    pub id: String,
+    pub email: String,
+    pub name: Option<String>,
+    pub is_current: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plan: Option<String>,
+    pub tags: Vec<String>,
+    pub quotas: Vec<QuotaMetric>,
+    pub last_used: i64,
+}
+
+fn metrics(items: Vec<(String, i32)>) -> Vec<QuotaMetric> {
+    items
+        .into_iter()
+        .map(|(name, remaining_percent)| QuotaMetric { name, remaining_percent })
+        .collect()
+}
+
+fn mark_current(
+    mut accounts: Vec<PlatformAccountInfo>,
+    current_id: Option<String>,
+) -> (Vec<PlatformAccountInfo>, Option<String>) {
+    for account in accounts.iter_mut() {
+        account.is_current = current_id.as_deref() == Some(account.id.as_str());
+    }
+    (accounts, current_id)
+}
+
+/// 平台账号列表与当前账号 ID
+pub fn list_accounts(platform: Platform) -> Result<(Vec<PlatformAccountInfo>, Option<String>), String> {
+    let result = match platform {
+        Platform::Antigravity => {
+            let accounts = modules::account::list_accounts()?;
+            let current_id = modules::account::get_current_account_id()?;
+            let infos = accounts
+                .into_iter()
+                .map(|acc| PlatformAccountInfo {
+                    plan: acc.quota.as_ref().and_then(|q| q.subscription_tier.clone()),
+                    quotas: acc
+                        .quota
+                        .as_ref()
+                        .map(|q| {
+                            q.models
+                                .iter()
+                                .map(|m| QuotaMetric {
+                                    name: m.name.clone(),
+                                    remaining_percent: if q.is_forbidden { 0 } else { m.percentage },
+                                })
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    id: acc.id,
+                    email: acc.email,
+                    name: acc.name,
+                    is_current: false,
+                    tags: acc.tags,
+                    last_used: acc.last_used,
+                })
+                .collect();
+            mark_current(infos, current_id)
+        }
+        Platform::Codex => {
+            let accounts = modules::codex_account::list_accounts()?;
+            let current_id = modules::codex_account::resolve_current_account_id(&accounts);
+            let infos = accounts
+                .into_iter()
+                .map(|acc| PlatformAccountInfo {
+                    quotas: metrics(modules::codex_account::extract_quota_metrics(&acc)),
+                    id: acc.id,
+                    email: acc.email,
+                    name: None,
+                    is_current: false,
+                    plan: acc.plan_type,
+                    tags: acc.tags.unwrap_or_default(),
+                    last_used: acc.last_used,
+                })
+                .collect();
+            mark_current(infos, current_id)
+        }
+        Platform::Kiro => {
+            let accounts = modules::kiro_account::list_accounts()?;
+            let current_id = modules::kiro_account::resolve_current_account_id(&accounts);
+            let infos = accounts
+                .into_iter()
+                .map(|acc| PlatformAccountInfo {
+                    quotas: metrics(modules::kiro_account::extract_quota_metrics(&acc)),
+                    id: acc.id,
+                    email: acc.email,
+                    name: None,
+                    is_current: false,
+                    plan: acc.plan_name,
+                    tags: acc.tags.unwrap_or_default(),
+                    last_used: acc.last_used,
+                })
+                .collect();
+            mark_current(infos, current_id)
+        }
+        Platform::Windsurf => {
+            let accounts = modules::windsurf_account::list_accounts()?;
+            let current_id = modules::windsurf_account::resolve_current_account_id(&accounts);
+            let infos = accounts
+                .into_iter()
+                .map(|acc| PlatformAccountInfo {
+                    quotas: metrics(modules::windsurf_account::extract_quota_metrics(&acc)),
+                    id: acc.id,
+                    email: acc.github_email.unwrap_or_else(|| acc.github_login.clone()),
+                    name: acc.github_name,
+                    is_current: false,
+                    plan: acc.copilot_plan,
+                    tags: acc.tags.unwrap_or_default(),
+                    last_used: acc.last_used,
+                })
+                .collect();
+            mark_current(infos, current_id)
+        }
+        Platform::GithubCopilot => {
+            let accounts = modules::github_copilot_account::list_accounts()?;
+            let current_id = modules::github_copilot_account::resolve_current_account_id(&accounts);
+            let infos = accounts
+                .into_iter()
+                .map(|acc| PlatformAccountInfo {
+                    quotas: metrics(modules::github_copilot_account::extract_quota_metrics(&acc)),
+                    id: acc.id,
+                    email: acc.github_email.unwrap_or_else(|| acc.github_login.clone()),
+                    name: acc.github_name,
+                    is_current: false,
+                    plan: acc.copilot_plan,
+                    tags: acc.tags.unwrap_or_default(),
+                    last_used: acc.last_used,
+                })
+                .collect();
+            mark_current(infos, current_id)
+        }
+        // Warp 暂无“当前账号”与配额解析
+        Platform::Warp => {
+            let infos = modules::warp_account::list_accounts()?
+                .into_iter()
+                .map(|acc| PlatformAccountInfo {
+                    id: acc.id,
+                    email: acc.email,
+                    name: None,
+                    is_current: false,
+                    plan: acc.plan_type,
+                    tags: acc.tags.unwrap_or_default(),
+                    quotas: Vec::new(),
+                    last_used: acc.last_used,
+                })
+                .collect();
+            (infos, None)
+        }
+    };
+    Ok(result)
+}
+
+/// 切换到指定账号，返回账号邮箱；与应用内切号走同一流程（写入凭据并重启默认实例），完成后广播切换事件
+pub async fn switch_account(platform: Platform, account_id: &str) -> Result<String, String> {
+    let app = crate::get_app_handle().cloned();
+    let account_id = account_id.to_string();
+    match platform {
+        Platform::Antigravity => {
+            let account = modules::account::switch_account_internal(&account_id).await?;
+            modules::websocket::broadcast_account_switched(&account.id, &account.email);
+            Ok(account.email)
+        }
+        Platform::Codex => match app {
+            Some(app) => Ok(crate::commands::codex::switch_codex_account(app, account_id).await?.email),
+            // 无界面模式：只写入 auth.json
+            None => {
+                modules::codex_account::prepare_account_for_injection(&account_id).await?;
+                let account = modules::codex_account::switch_account(&account_id)?;
+                modules::websocket::broadcast_platform_account_switched(platform, &account.id, &account.email);
+                Ok(account.email)
+            }
+        },
+        Platform::Kiro | Platform::Windsurf | Platform::GithubCopilot => {
+            let app = app.ok_or_else(|| format!("无界面模式下不支持切换 {} 账号", platform.label()))?;
+            match platform {
+                Platform::Kiro => crate::commands::kiro::inject_kiro_to_vscode(app, account_id.clone()).await?,
+                Platform::Windsurf => {
+                    crate::commands::windsurf::inject_windsurf_to_vscode(app, account_id.clone()).await?
+                }
+                _ => {
+                    crate::commands::github_copilot::inject_github_copilot_to_vscode(app, account_id.clone())
+                        .await?
+                }
+            };
+            let (accounts, _) = list_accounts(platform)?;
+            Ok(accounts
+                .into_iter()
+                .find(|a| a.id == account_id)
+                .map(|a| a.email)
+                .unwrap_or(account_id))
+        }
+        Platform::Warp => Err("暂不支持切换 Warp 账号".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_tags_and_current_marking() {
+        assert_eq!(serde_json::to_string(&Platform::GithubCopilot).unwrap(), "\"github_copilot\"");
+        assert_eq!(serde_json::from_str::<Platform>("\"kiro\"").unwrap(), Platform::Kiro);
+
+        let account = |id: &str| PlatformAccountInfo {
+            id: id.to_string(),
+            email: format!("{}@example.com", id),
+            name: None,
+            is_current: true,
+            plan: None,
+            tags: Vec::new(),
+            quotas: metrics(vec![("5h".to_string(), 40)]),
+            last_used: 0,
+        };
+        let (accounts, current) = mark_current(vec![account("a"), account("b")], Some("b".to_string()));
+        assert_eq!(current.as_deref(), Some("b"));
+        assert!(!accounts[0].is_current);
+        assert!(accounts[1].is_current);
+        assert_eq!(accounts[1].quotas[0].remaining_percent, 40);
+    }
+}
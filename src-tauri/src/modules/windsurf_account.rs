@@ -1,117 +1,93 @@
 use rusqlite::{Connection, OptionalExtension};
 use serde_json::Value;
 use std::collections::{HashMap, HashSet};
-use std::fs;
 use std::path::PathBuf;
 use std::sync::Mutex;
 use std::time::Instant;
 
 use crate::models::windsurf::{
-    WindsurfAccount, WindsurfAccountIndex, WindsurfOAuthCompletePayload,
+    WindsurfAccount, WindsurfAccountIndex, WindsurfAccountSummary, WindsurfOAuthCompletePayload,
 };
-use crate::modules::{account, logger, windsurf_oauth};
+use crate::modules::platform_store::{
+    calc_remaining_percent, clamp_percent, PlatformAccountStore, PlatformQuotaAlert,
+};
+use crate::modules::{config, logger, windsurf_oauth};
 
-const ACCOUNTS_INDEX_FILE: &str = "windsurf_accounts.json";
-const ACCOUNTS_DIR: &str = "windsurf_accounts";
-static WINDSURF_ACCOUNT_INDEX_LOCK: std::sync::LazyLock<Mutex<()>> =
-    std::sync::LazyLock::new(|| Mutex::new(()));
-static WINDSURF_QUOTA_ALERT_LAST_SENT: std::sync::LazyLock<Mutex<HashMap<String, i64>>> =
-    std::sync::LazyLock::new(|| Mutex::new(HashMap::new()));
-const WINDSURF_QUOTA_ALERT_COOLDOWN_SECONDS: i64 = 300;
+static WINDSURF_ACCOUNT_INDEX_LOCK: Mutex<()> = Mutex::new(());
 
-fn now_ts() -> i64 {
-    chrono::Utc::now().timestamp()
-}
+pub struct WindsurfStore;
 
-fn get_data_dir() -> Result<PathBuf, String> {
-    account::get_data_dir()
-}
+impl PlatformAccountStore for WindsurfStore {
+    type Account = WindsurfAccount;
+    type Summary = WindsurfAccountSummary;
+    type Index = WindsurfAccountIndex;
 
-fn get_accounts_dir() -> Result<PathBuf, String> {
-    let base = get_data_dir()?;
-    let dir = base.join(ACCOUNTS_DIR);
-    if !dir.exists() {
-        fs::create_dir_all(&dir).map_err(|e| format!("创建 Windsurf 账号目录失败: {}", e))?;
-    }
-    Ok(dir)
-}
+    const LABEL: &'static str = "Windsurf";
+    const INDEX_FILE: &'static str = "windsurf_accounts.json";
+    const ACCOUNTS_DIR: &'static str = "windsurf_accounts";
 
-fn get_accounts_index_path() -> Result<PathBuf, String> {
-    Ok(get_data_dir()?.join(ACCOUNTS_INDEX_FILE))
-}
-
-pub fn accounts_index_path_string() -> Result<String, String> {
-    Ok(get_accounts_index_path()?.to_string_lossy().to_string())
-}
-
-pub fn load_account(account_id: &str) -> Option<WindsurfAccount> {
-    let account_path = get_accounts_dir()
-        .ok()
-        .map(|dir| dir.join(format!("{}.json", account_id)))?;
-    if !account_path.exists() {
-        return None;
+    fn index_lock() -> &'static Mutex<()> {
+        &WINDSURF_ACCOUNT_INDEX_LOCK
     }
-    let content = fs::read_to_string(account_path).ok()?;
-    serde_json::from_str(&content).ok()
-}
-
-fn save_account_file(account: &WindsurfAccount) -> Result<(), String> {
-    let path = get_accounts_dir()?.join(format!("{}.json", account.id));
-    let content =
-        serde_json::to_string_pretty(account).map_err(|e| format!("序列化账号失败: {}", e))?;
-    fs::write(path, content).map_err(|e| format!("保存账号失败: {}", e))
-}
-
-fn delete_account_file(account_id: &str) -> Result<(), String> {
-    let path = get_accounts_dir()?.join(format!("{}.json", account_id));
-    if path.exists() {
-        fs::remove_file(path).map_err(|e| format!("删除账号文件失败: {}", e))?;
+    fn index_entries(index: &mut WindsurfAccountIndex) -> &mut Vec<WindsurfAccountSummary> {
+        &mut index.accounts
+    }
+    fn summary_id(summary: &WindsurfAccountSummary) -> &str {
+        &summary.id
+    }
+    fn summarize(account: &WindsurfAccount) -> WindsurfAccountSummary {
+        account.summary()
+    }
+    fn account_id(account: &WindsurfAccount) -> &str {
+        &account.id
+    }
+    fn set_tags(account: &mut WindsurfAccount, tags: Vec<String>) {
+        account.tags = Some(tags);
+        account.last_used = now_ts();
+    }
+    fn list_accounts() -> Result<Vec<WindsurfAccount>, String> {
+        list_accounts()
     }
-    Ok(())
 }
 
-fn load_account_index() -> WindsurfAccountIndex {
-    let path = match get_accounts_index_path() {
-        Ok(p) => p,
-        Err(_) => return WindsurfAccountIndex::new(),
-    };
+impl PlatformQuotaAlert for WindsurfStore {
+    const PLATFORM: &'static str = "windsurf";
 
-    if !path.exists() {
-        return WindsurfAccountIndex::new();
+    fn quota_alert_settings(cfg: &config::UserConfig) -> (bool, i32) {
+        (cfg.windsurf_quota_alert_enabled, cfg.windsurf_quota_alert_threshold)
     }
-
-    match fs::read_to_string(path) {
-        Ok(content) => {
-            serde_json::from_str(&content).unwrap_or_else(|_| WindsurfAccountIndex::new())
-        }
-        Err(_) => WindsurfAccountIndex::new(),
+    fn quota_metrics(account: &WindsurfAccount) -> Vec<(String, i32)> {
+        extract_quota_metrics(account)
+    }
+    fn resolve_current_account_id(accounts: &[WindsurfAccount]) -> Option<String> {
+        resolve_current_account_id(accounts)
+    }
+    fn display_email(account: &WindsurfAccount) -> String {
+        account
+            .github_email
+            .clone()
+            .filter(|text| !text.trim().is_empty())
+            .unwrap_or_else(|| account.github_login.clone())
+    }
+    fn last_used(account: &WindsurfAccount) -> i64 {
+        account.last_used
     }
 }
 
-fn save_account_index(index: &WindsurfAccountIndex) -> Result<(), String> {
-    let path = get_accounts_index_path()?;
-    let content =
-        serde_json::to_string_pretty(index).map_err(|e| format!("序列化账号索引失败: {}", e))?;
-    fs::write(path, content).map_err(|e| format!("写入账号索引失败: {}", e))
+fn now_ts() -> i64 {
+    chrono::Utc::now().timestamp()
 }
 
-fn refresh_summary(index: &mut WindsurfAccountIndex, account: &WindsurfAccount) {
-    if let Some(summary) = index.accounts.iter_mut().find(|item| item.id == account.id) {
-        *summary = account.summary();
-        return;
-    }
-    index.accounts.push(account.summary());
+pub fn get_accounts_dir() -> Result<PathBuf, String> {
+    WindsurfStore::accounts_dir()
 }
 
-fn upsert_account_record(account: WindsurfAccount) -> Result<WindsurfAccount, String> {
-    let _lock = WINDSURF_ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|_| "获取 Windsurf 账号锁失败".to_string())?;
-    let mut index = load_account_index();
-    save_account_file(&account)?;
-    refresh_summary(&mut index, &account);
-    save_account_index(&index)?;
-    Ok(account)
+pub fn accounts_index_path_string() -> Result<String, String> {
+    Ok(WindsurfStore::index_path()?.to_string_lossy().to_string())
+}
+
+pub fn load_account(account_id: &str) -> Result<Option<WindsurfAccount>, String> {
+    WindsurfStore::load_account(account_id)
 }
 
 fn normalize_login(payload: &WindsurfOAuthCompletePayload) -> String {
@@ -379,16 +355,12 @@ fn merge_account_group(group: Vec<WindsurfAccount>) -> WindsurfAccount {
 }
 
 fn deduplicate_accounts_by_identity() -> Result<(), String> {
-    let index = load_account_index();
+    let index = WindsurfStore::load_index();
     if index.accounts.len() <= 1 {
         return Ok(());
     }
 
-    let mut accounts: Vec<WindsurfAccount> = index
-        .accounts
-        .iter()
-        .filter_map(|summary| load_account(&summary.id))
-        .collect();
+    let mut accounts: Vec<WindsurfAccount> = WindsurfStore::load_indexed_accounts()?;
     if accounts.len() <= 1 {
         return Ok(());
     }
@@ -453,7 +425,7 @@ fn deduplicate_accounts_by_identity() -> Result<(), String> {
     merged_accounts.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
 
     for account in &merged_accounts {
-        save_account_file(account)?;
+        WindsurfStore::save_account_file(account)?;
     }
 
     let keep_ids: HashSet<String> = merged_accounts
@@ -462,7 +434,7 @@ fn deduplicate_accounts_by_identity() -> Result<(), String> {
         .collect();
     for old_id in old_ids {
         if !keep_ids.contains(&old_id) {
-            delete_account_file(&old_id)?;
+            WindsurfStore::delete_account_file(&old_id)?;
         }
     }
 
@@ -471,7 +443,7 @@ fn deduplicate_accounts_by_identity() -> Result<(), String> {
         .iter()
         .map(WindsurfAccount::summary)
         .collect();
-    save_account_index(&next_index)?;
+    WindsurfStore::save_index(&next_index)?;
 
     logger::log_info(&format!(
         "Windsurf 账号去重完成：合并 {} 条重复记录",
@@ -660,33 +632,28 @@ fn merge_refresh_payload_with_existing(
     preserved_quota
 }
 
-pub fn list_accounts() -> Vec<WindsurfAccount> {
+pub fn list_accounts() -> Result<Vec<WindsurfAccount>, String> {
     if let Err(err) = deduplicate_accounts_by_identity() {
         logger::log_warn(&format!("Windsurf 账号去重失败（已忽略）：{}", err));
     }
 
-    let index = load_account_index();
-    index
-        .accounts
-        .iter()
-        .filter_map(|summary| load_account(&summary.id))
+    Ok(WindsurfStore::load_indexed_accounts()?
+        .into_iter()
         .map(|mut account| {
             merge_local_auth_status_into_account(&mut account);
             account
         })
-        .collect()
+        .collect())
 }
 
 pub fn upsert_account(payload: WindsurfOAuthCompletePayload) -> Result<WindsurfAccount, String> {
-    let _lock = WINDSURF_ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|_| "获取 Windsurf 账号锁失败".to_string())?;
+    let _lock = WindsurfStore::lock_index()?;
     if let Err(err) = deduplicate_accounts_by_identity() {
         logger::log_warn(&format!("Windsurf upsert 前去重失败（已忽略）：{}", err));
     }
 
     let now = now_ts();
-    let mut index = load_account_index();
+    let mut index = WindsurfStore::load_index();
     let normalized_login = normalize_login(&payload);
     let payload_identity_keys = build_payload_identity_keys(&payload, &normalized_login);
     let payload_api_key = resolve_payload_api_key(&payload);
@@ -696,10 +663,8 @@ pub fn upsert_account(payload: WindsurfOAuthCompletePayload) -> Result<WindsurfA
     );
     let payload_identity_key_set: HashSet<String> = payload_identity_keys.into_iter().collect();
 
-    let account_id = index
-        .accounts
-        .iter()
-        .filter_map(|item| load_account(&item.id))
+    let account_id = WindsurfStore::load_indexed_accounts()?
+        .into_iter()
         .find(|account| {
             if let (Some(incoming_api), Some(existing_api)) = (
                 payload_api_key.as_ref(),
@@ -724,7 +689,7 @@ pub fn upsert_account(payload: WindsurfOAuthCompletePayload) -> Result<WindsurfA
         .map(|account| account.id)
         .unwrap_or(generated_id);
 
-    let existing = load_account(&account_id);
+    let existing = load_account(&account_id)?;
     let tags = existing.as_ref().and_then(|acc| acc.tags.clone());
     let created_at = existing.as_ref().map(|acc| acc.created_at).unwrap_or(now);
 
@@ -762,9 +727,9 @@ pub fn upsert_account(payload: WindsurfOAuthCompletePayload) -> Result<WindsurfA
     account.created_at = created_at;
     account.last_used = now;
 
-    save_account_file(&account)?;
-    refresh_summary(&mut index, &account);
-    save_account_index(&index)?;
+    WindsurfStore::save_account_file(&account)?;
+    WindsurfStore::refresh_summary(&mut index, &account);
+    WindsurfStore::save_index(&index)?;
 
     logger::log_info(&format!(
         "Windsurf 账号已保存: id={}, login={}",
@@ -775,7 +740,7 @@ pub fn upsert_account(payload: WindsurfOAuthCompletePayload) -> Result<WindsurfA
 
 pub async fn refresh_account_token(account_id: &str) -> Result<WindsurfAccount, String> {
     let started_at = Instant::now();
-    let mut account = load_account(account_id).ok_or_else(|| "账号不存在".to_string())?;
+    let mut account = load_account(account_id)?.ok_or_else(|| "账号不存在".to_string())?;
     logger::log_info(&format!(
         "[Windsurf Refresh] 开始刷新账号: id={}, login={}",
         account.id, account.github_login
@@ -806,7 +771,7 @@ pub async fn refresh_account_token(account_id: &str) -> Result<WindsurfAccount,
     account.last_used = now_ts();
 
     let updated = account.clone();
-    upsert_account_record(account)?;
+    WindsurfStore::upsert_account_record(account)?;
     logger::log_info(&format!(
         "[Windsurf Refresh] 刷新完成: id={}, login={}, preserved_quota={}, elapsed={}ms",
         updated.id,
@@ -824,7 +789,7 @@ pub async fn refresh_all_tokens() -> Result<Vec<(String, Result<WindsurfAccount,
     use tokio::sync::Semaphore;
 
     const MAX_CONCURRENT: usize = 5;
-    let accounts = list_accounts();
+    let accounts = list_accounts()?;
     logger::log_info(&format!(
         "[Windsurf Refresh] 开始批量刷新: total={}",
         accounts.len()
@@ -871,60 +836,32 @@ pub async fn refresh_all_tokens() -> Result<Vec<(String, Result<WindsurfAccount,
         "[Windsurf Refresh] 批量刷新结束: success={}, failed={}",
         success_count, failed_count
     ));
+    if success_count > 0 {
+        crate::modules::websocket::broadcast_quota_updated(
+            crate::modules::websocket_platforms::Platform::Windsurf,
+        );
+    }
     Ok(results)
 }
 
 pub fn remove_account(account_id: &str) -> Result<(), String> {
-    let _lock = WINDSURF_ACCOUNT_INDEX_LOCK
-        .lock()
-        .map_err(|_| "获取 Windsurf 账号锁失败".to_string())?;
-    let mut index = load_account_index();
-    index.accounts.retain(|item| item.id != account_id);
-    save_account_index(&index)?;
-    delete_account_file(account_id)?;
-    Ok(())
+    WindsurfStore::remove_account(account_id)
 }
 
 pub fn remove_accounts(account_ids: &[String]) -> Result<(), String> {
-    for id in account_ids {
-        remove_account(id)?;
-    }
-    Ok(())
+    WindsurfStore::remove_accounts(account_ids)
 }
 
 pub fn update_account_tags(account_id: &str, tags: Vec<String>) -> Result<WindsurfAccount, String> {
-    let mut account = load_account(account_id).ok_or_else(|| "账号不存在".to_string())?;
-    account.tags = Some(tags);
-    account.last_used = now_ts();
-    let updated = account.clone();
-    upsert_account_record(account)?;
-    Ok(updated)
+    WindsurfStore::update_tags(account_id, tags)
 }
 
 pub fn import_from_json(json_content: &str) -> Result<Vec<WindsurfAccount>, String> {
-    if let Ok(account) = serde_json::from_str::<WindsurfAccount>(json_content) {
-        let saved = upsert_account_record(account)?;
-        return Ok(vec![saved]);
-    }
-
-    if let Ok(accounts) = serde_json::from_str::<Vec<WindsurfAccount>>(json_content) {
-        let mut result = Vec::new();
-        for account in accounts {
-            let saved = upsert_account_record(account)?;
-            result.push(saved);
-        }
-        return Ok(result);
-    }
-
-    Err("无法解析 JSON 内容".to_string())
+    WindsurfStore::import_from_json(json_content)
 }
 
 pub fn export_accounts(account_ids: &[String]) -> Result<String, String> {
-    let accounts: Vec<WindsurfAccount> = account_ids
-        .iter()
-        .filter_map(|id| load_account(id))
-        .collect();
-    serde_json::to_string_pretty(&accounts).map_err(|e| format!("序列化失败: {}", e))
+    WindsurfStore::export_accounts(account_ids)
 }
 
 pub fn get_default_state_db_path() -> Result<PathBuf, String> {
@@ -996,14 +933,6 @@ pub fn read_local_login_hint() -> Option<String> {
         .map(|value| value.to_string())
 }
 
-fn normalize_quota_alert_threshold(raw: i32) -> i32 {
-    raw.clamp(0, 100)
-}
-
-fn clamp_percent(value: f64) -> i32 {
-    value.round().clamp(0.0, 100.0) as i32
-}
-
 fn parse_token_map(token: &str) -> HashMap<String, String> {
     let mut map = HashMap::new();
     let prefix = token.split(':').next().unwrap_or(token);
@@ -1034,13 +963,6 @@ fn get_json_number(value: &serde_json::Value) -> Option<f64> {
     .filter(|value| value.is_finite())
 }
 
-fn calc_remaining_percent(remaining: f64, total: f64) -> Option<i32> {
-    if total <= 0.0 {
-        return None;
-    }
-    Some(clamp_percent((remaining.max(0.0) / total) * 100.0))
-}
-
 fn extract_limited_metrics(account: &WindsurfAccount) -> Vec<(String, i32)> {
     let Some(limited) = account
         .copilot_limited_user_quotas
@@ -1094,7 +1016,7 @@ fn extract_premium_metric(account: &WindsurfAccount) -> Option<(String, i32)> {
     Some(("Premium Interactions".to_string(), percent_remaining))
 }
 
-fn extract_quota_metrics(account: &WindsurfAccount) -> Vec<(String, i32)> {
+pub fn extract_quota_metrics(account: &WindsurfAccount) -> Vec<(String, i32)> {
     let mut metrics = extract_limited_metrics(account);
     if let Some(premium) = extract_premium_metric(account) {
         metrics.push(premium);
@@ -1102,15 +1024,7 @@ fn extract_quota_metrics(account: &WindsurfAccount) -> Vec<(String, i32)> {
     metrics
 }
 
-fn average_quota_percentage(metrics: &[(String, i32)]) -> f64 {
-    if metrics.is_empty() {
-        return 0.0;
-    }
-    let sum: i32 = metrics.iter().map(|(_, pct)| *pct).sum();
-    sum as f64 / metrics.len() as f64
-}
-
-fn resolve_current_account_id(accounts: &[WindsurfAccount]) -> Option<String> {
+pub fn resolve_current_account_id(accounts: &[WindsurfAccount]) -> Option<String> {
     if let Ok(settings) = crate::modules::windsurf_instance::load_default_settings() {
         if let Some(bind_id) = settings.bind_account_id {
             let trimmed = bind_id.trim();
@@ -1126,116 +1040,7 @@ fn resolve_current_account_id(accounts: &[WindsurfAccount]) -> Option<String> {
         .map(|account| account.id.clone())
 }
 
-fn display_email(account: &WindsurfAccount) -> String {
-    account
-        .github_email
-        .clone()
-        .filter(|text| !text.trim().is_empty())
-        .unwrap_or_else(|| account.github_login.clone())
-}
-
-fn build_quota_alert_cooldown_key(account_id: &str, threshold: i32) -> String {
-    format!("windsurf:{}:{}", account_id, threshold)
-}
-
-fn should_emit_quota_alert(cooldown_key: &str, now: i64) -> bool {
-    let Ok(mut state) = WINDSURF_QUOTA_ALERT_LAST_SENT.lock() else {
-        return true;
-    };
-
-    if let Some(last_sent) = state.get(cooldown_key) {
-        if now - *last_sent < WINDSURF_QUOTA_ALERT_COOLDOWN_SECONDS {
-            return false;
-        }
-    }
-
-    state.insert(cooldown_key.to_string(), now);
-    true
-}
-
-fn clear_quota_alert_cooldown(account_id: &str, threshold: i32) {
-    if let Ok(mut state) = WINDSURF_QUOTA_ALERT_LAST_SENT.lock() {
-        state.remove(&build_quota_alert_cooldown_key(account_id, threshold));
-    }
-}
-
-fn pick_quota_alert_recommendation(
-    accounts: &[WindsurfAccount],
-    current_id: &str,
-) -> Option<WindsurfAccount> {
-    let mut candidates: Vec<WindsurfAccount> = accounts
-        .iter()
-        .filter(|account| account.id != current_id)
-        .filter(|account| !extract_quota_metrics(account).is_empty())
-        .cloned()
-        .collect();
-
-    if candidates.is_empty() {
-        return None;
-    }
-
-    candidates.sort_by(|a, b| {
-        let avg_a = average_quota_percentage(&extract_quota_metrics(a));
-        let avg_b = average_quota_percentage(&extract_quota_metrics(b));
-        avg_b
-            .partial_cmp(&avg_a)
-            .unwrap_or(std::cmp::Ordering::Equal)
-            .then_with(|| a.last_used.cmp(&b.last_used))
-    });
-
-    candidates.into_iter().next()
-}
-
 pub fn run_quota_alert_if_needed(
 ) -> Result<Option<crate::modules::account::QuotaAlertPayload>, String> {
-    let cfg = crate::modules::config::get_user_config();
-    if !cfg.windsurf_quota_alert_enabled {
-        return Ok(None);
-    }
-
-    let threshold = normalize_quota_alert_threshold(cfg.windsurf_quota_alert_threshold);
-    let accounts = list_accounts();
-    let current_id = match resolve_current_account_id(&accounts) {
-        Some(id) => id,
-        None => return Ok(None),
-    };
-
-    let current = match accounts.iter().find(|account| account.id == current_id) {
-        Some(account) => account,
-        None => return Ok(None),
-    };
-
-    let metrics = extract_quota_metrics(current);
-    let low_models: Vec<(String, i32)> = metrics
-        .into_iter()
-        .filter(|(_, pct)| *pct <= threshold)
-        .collect();
-
-    if low_models.is_empty() {
-        clear_quota_alert_cooldown(&current_id, threshold);
-        return Ok(None);
-    }
-
-    let now = chrono::Utc::now().timestamp();
-    let cooldown_key = build_quota_alert_cooldown_key(&current_id, threshold);
-    if !should_emit_quota_alert(&cooldown_key, now) {
-        return Ok(None);
-    }
-
-    let recommendation = pick_quota_alert_recommendation(&accounts, &current_id);
-    let lowest_percentage = low_models.iter().map(|(_, pct)| *pct).min().unwrap_or(0);
-    let payload = crate::modules::account::QuotaAlertPayload {
-        platform: "windsurf".to_string(),
-        current_account_id: current_id,
-        current_email: display_email(current),
-        threshold,
-        lowest_percentage,
-        low_models: low_models.into_iter().map(|(name, _)| name).collect(),
-        recommended_account_id: recommendation.as_ref().map(|account| account.id.clone()),
-        recommended_email: recommendation.as_ref().map(display_email),
-        triggered_at: now,
-    };
-
-    crate::modules::account::dispatch_quota_alert(&payload);
-    Ok(Some(payload))
+    WindsurfStore::run_quota_alert_if_needed()
 }
@@ -20,6 +20,7 @@ import { useGitHubCopilotAccountStore } from './stores/useGitHubCopilotAccountSt
 import { useWindsurfAccountStore } from './stores/useWindsurfAccountStore';
 import { useKiroAccountStore } from './stores/useKiroAccountStore';
 import type { UpdateCheckResult } from './components/UpdateNotification';
+import type { InstancePlatform } from './services/platform/createPlatformInstanceService';
 
 const DashboardPage = lazy(() =>
   import('./pages/DashboardPage').then((module) => ({ default: module.DashboardPage })),
@@ -97,6 +98,14 @@ type AppPathMissingDetail = {
   retry?: { kind: 'default' | 'instance'; instanceId?: string };
 };
 
+const INSTANCE_PLATFORM_BY_APP: Record<AppPathMissingDetail['app'], InstancePlatform> = {
+  antigravity: 'antigravity',
+  codex: 'codex',
+  vscode: 'github_copilot',
+  windsurf: 'windsurf',
+  kiro: 'kiro',
+};
+
 const WAKEUP_ENABLED_KEY = 'agtools.wakeup.enabled';
 const TASKS_STORAGE_KEY = 'agtools.wakeup.tasks';
 const WAKEUP_FORCE_DISABLE_MIGRATION_KEY = 'agtools.wakeup.migration.force_disable_0_8_14';
@@ -778,31 +787,10 @@ function App() {
       await invoke('set_app_path', { app, path });
       setAppPathMissing(null);
       setAppPathSetting(false);
-      if (retry?.kind === 'instance' && retry.instanceId) {
-        if (app === 'codex') {
-          await invoke('codex_start_instance', { instanceId: retry.instanceId });
-        } else if (app === 'vscode') {
-          await invoke('github_copilot_start_instance', { instanceId: retry.instanceId });
-        } else if (app === 'windsurf') {
-          await invoke('windsurf_start_instance', { instanceId: retry.instanceId });
-        } else if (app === 'kiro') {
-          await invoke('kiro_start_instance', { instanceId: retry.instanceId });
-        } else {
-          await invoke('start_instance', { instanceId: retry.instanceId });
-        }
-      } else {
-        if (app === 'codex') {
-          await invoke('codex_start_instance', { instanceId: '__default__' });
-        } else if (app === 'vscode') {
-          await invoke('github_copilot_start_instance', { instanceId: '__default__' });
-        } else if (app === 'windsurf') {
-          await invoke('windsurf_start_instance', { instanceId: '__default__' });
-        } else if (app === 'kiro') {
-          await invoke('kiro_start_instance', { instanceId: '__default__' });
-        } else {
-          await invoke('start_instance', { instanceId: '__default__' });
-        }
-      }
+      const platform = INSTANCE_PLATFORM_BY_APP[app];
+      const instanceId =
+        retry?.kind === 'instance' && retry.instanceId ? retry.instanceId : '__default__';
+      await invoke('start_instance', { platform, instanceId });
     } catch (error) {
       console.error('设置应用路径失败:', error);
       setAppPathSetting(false);
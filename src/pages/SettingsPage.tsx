@@ -31,6 +31,12 @@ interface ProviderProxyConfig {
   enabled: boolean;
   strategy: string;
   account_ids: string[];
+  /** 单账号最大并发请求数（0 为不限制） */
+  max_concurrent_per_account?: number;
+  /** 并发已满时的最长排队时间（秒） */
+  queue_timeout_secs?: number;
+  /** 会话粘滞时长（秒，0 为关闭） */
+  session_ttl_secs?: number;
 }
 
 /** API 代理配置 */
@@ -50,8 +56,69 @@ interface ApiProxyStatus {
   port: number;
   actual_port: number | null;
   enabled_providers: string[];
+  /** 冷却中的账号（429/402/403 后暂时移出账号池） */
+  cooldowns: ApiProxyCooldown[];
+  /** 是否以 HTTPS 提供服务 */
+  tls: boolean;
+  /** HTTPS 证书 SHA-256 指纹 */
+  tls_fingerprint: string | null;
+  /** 有请求进行中的账号并发占用 */
+  queues?: ApiProxyAccountSlots[];
+  /** 各 Provider 排队中的请求数 */
+  queued?: Record<string, number>;
+  /** 各 Provider 粘滞中的会话数 */
+  sessions?: Record<string, number>;
 }
 
+/** 代理账号并发占用 */
+interface ApiProxyAccountSlots {
+  provider: string;
+  account_id: string;
+  in_flight: number;
+  limit: number;
+}
+
+/** 冷却中的代理账号 */
+interface ApiProxyCooldown {
+  provider: string;
+  account_id: string;
+  account_email: string;
+  until: number;
+  remaining_seconds: number;
+  status: number;
+  reason: string;
+  strikes: number;
+}
+
+/** WebSocket 客户端能力 */
+type WsCapability = 'read' | 'read_tokens' | 'mutate';
+
+const WS_CAPABILITIES: WsCapability[] = ['read', 'read_tokens', 'mutate'];
+
+/** 已配对的 WebSocket 客户端 */
+interface WsPairedClient {
+  clientId: string;
+  name: string;
+  capabilities: WsCapability[];
+  pairedAt: number;
+  lastSeenAt: number;
+}
+
+/** 凭据加密存储状态 */
+interface VaultStatus {
+  enabled: boolean;
+  mode: 'passphrase' | 'keyring' | null;
+  locked: boolean;
+  /** 密钥实际存放位置 */
+  key_source: 'passphrase' | 'keyring' | 'file' | null;
+}
+
+const VAULT_KEY_SOURCE_LABELS: Record<string, string> = {
+  passphrase: '主密码',
+  keyring: '系统钥匙串',
+  file: '数据目录下的密钥文件',
+};
+
 /** 通用配置类型 */
 interface GeneralConfig {
   language: string;
@@ -267,6 +334,14 @@ export function SettingsPage() {
   const [proxySaving, setProxySaving] = useState(false);
   const [proxyStarting, setProxyStarting] = useState(false);
 
+  // Credential Vault States
+  const [wsClients, setWsClients] = useState<WsPairedClient[]>([]);
+  const [pairingSecret, setPairingSecret] = useState<string | null>(null);
+  const [vaultStatus, setVaultStatus] = useState<VaultStatus | null>(null);
+  const [vaultMode, setVaultMode] = useState<'keyring' | 'passphrase'>('keyring');
+  const [vaultPassphrase, setVaultPassphrase] = useState('');
+  const [vaultBusy, setVaultBusy] = useState(false);
+
   // 检测配额重置任务状态
   const [hasActiveResetTasks, setHasActiveResetTasks] = useState(false);
 
@@ -584,6 +659,12 @@ export function SettingsPage() {
     } catch (err) {
       console.error('加载网络配置失败:', err);
     }
+    // 加载已配对的 WebSocket 客户端
+    try {
+      setWsClients(await invoke<WsPairedClient[]>('ws_list_clients'));
+    } catch (err) {
+      console.error('加载 WebSocket 客户端失败:', err);
+    }
     // 加载代理配置
     try {
       const proxyConfig = await invoke<ApiProxyConfig>('get_api_proxy_config');
@@ -606,6 +687,12 @@ export function SettingsPage() {
     } catch (err) {
       console.error('获取代理状态失败:', err);
     }
+    // 加载凭据库状态
+    try {
+      setVaultStatus(await invoke<VaultStatus>('vault_get_status'));
+    } catch (err) {
+      console.error('获取凭据库状态失败:', err);
+    }
   };
 
   // 保存网络配置
@@ -687,6 +774,107 @@ export function SettingsPage() {
     }
   };
 
+  // WebSocket 客户端能力开关，至少保留一项
+  const handleToggleWsCapability = async (client: WsPairedClient, capability: WsCapability) => {
+    const capabilities = client.capabilities.includes(capability)
+      ? client.capabilities.filter((c) => c !== capability)
+      : [...client.capabilities, capability];
+    if (capabilities.length === 0) {
+      return;
+    }
+    try {
+      await invoke('ws_update_client_capabilities', { clientId: client.clientId, capabilities });
+      setWsClients(await invoke<WsPairedClient[]>('ws_list_clients'));
+    } catch (err) {
+      alert(t('settings.network.saveFailed').replace('{error}', String(err)));
+    }
+  };
+
+  const handleRevokeWsClient = async (client: WsPairedClient) => {
+    if (!confirm(t('settings.network.revokeConfirm'))) {
+      return;
+    }
+    try {
+      await invoke('ws_revoke_client', { clientId: client.clientId });
+      setWsClients(await invoke<WsPairedClient[]>('ws_list_clients'));
+    } catch (err) {
+      alert(t('settings.network.saveFailed').replace('{error}', String(err)));
+    }
+  };
+
+  const handleShowPairingSecret = async () => {
+    try {
+      setPairingSecret(await invoke<string>('ws_get_pairing_secret'));
+    } catch (err) {
+      console.error('获取配对密钥失败:', err);
+    }
+  };
+
+  const handleResetPairingSecret = async () => {
+    if (!confirm(t('settings.network.resetSecretConfirm'))) {
+      return;
+    }
+    try {
+      setPairingSecret(await invoke<string>('ws_reset_pairing_secret'));
+    } catch (err) {
+      alert(t('settings.network.saveFailed').replace('{error}', String(err)));
+    }
+  };
+
+  // 凭据库操作：执行后刷新状态
+  const runVaultAction = async (action: () => Promise<string | null>) => {
+    setVaultBusy(true);
+    try {
+      const message = await action();
+      setVaultPassphrase('');
+      setVaultStatus(await invoke<VaultStatus>('vault_get_status'));
+      if (message) {
+        alert(message);
+      }
+    } catch (err) {
+      alert(`操作失败: ${err}`);
+    } finally {
+      setVaultBusy(false);
+    }
+  };
+
+  const handleEnableVault = () =>
+    runVaultAction(async () => {
+      const count = await invoke<number>('vault_enable', {
+        mode: vaultMode,
+        passphrase: vaultMode === 'passphrase' ? vaultPassphrase : null,
+      });
+      return `凭据加密已开启，已加密 ${count} 个账号文件`;
+    });
+
+  const handleUnlockVault = () =>
+    runVaultAction(async () => {
+      await invoke('vault_unlock', { passphrase: vaultPassphrase || null });
+      return null;
+    });
+
+  const handleLockVault = () =>
+    runVaultAction(async () => {
+      await invoke('vault_lock');
+      return null;
+    });
+
+  const handleMigrateVault = () =>
+    runVaultAction(async () => {
+      const count = await invoke<number>('vault_migrate');
+      return `已加密 ${count} 个明文账号文件`;
+    });
+
+  const handleDisableVault = () => {
+    if (!confirm('关闭后账号凭据将以明文保存，确定继续？')) {
+      return;
+    }
+    return runVaultAction(async () => {
+      const count = await invoke<number>('vault_disable');
+      return `凭据加密已关闭，已还原 ${count} 个账号文件`;
+    });
+  };
+
   // 更新 Provider 配置
   const updateProviderConfig = (provider: string, updates: Partial<ProviderProxyConfig>) => {
     setProxyProviders(prev => ({
@@ -2112,6 +2300,64 @@ export function SettingsPage() {
                         </div>
                       </div>
                     )}
+
+                    <div className="settings-row" style={{ animation: 'fadeUp 0.3s ease both' }}>
+                      <div className="row-label">
+                        <div className="row-title">{t('settings.network.pairingSecret')}</div>
+                        <div className="row-desc">
+                          {pairingSecret ? (
+                            <span style={{ fontFamily: 'var(--font-mono)', wordBreak: 'break-all' }}>{pairingSecret}</span>
+                          ) : t('settings.network.pairingSecretDesc')}
+                        </div>
+                      </div>
+                      <div className="row-control" style={{ gap: '8px' }}>
+                        {!pairingSecret && (
+                          <button className="btn btn-secondary" onClick={handleShowPairingSecret}>
+                            {t('settings.network.showSecret')}
+                          </button>
+                        )}
+                        <button className="btn btn-secondary" onClick={handleResetPairingSecret}>
+                          {t('settings.network.resetSecret')}
+                        </button>
+                      </div>
+                    </div>
+
+                    <div className="settings-row" style={{ animation: 'fadeUp 0.3s ease both' }}>
+                      <div className="row-label">
+                        <div className="row-title">{t('settings.network.pairedClients')}</div>
+                        <div className="row-desc">
+                          {wsClients.length === 0
+                            ? t('settings.network.noPairedClients')
+                            : t('settings.network.pairedClientsDesc')}
+                        </div>
+                      </div>
+                    </div>
+                    {wsClients.map((client) => (
+                      <div className="settings-row" key={client.clientId}>
+                        <div className="row-label">
+                          <div className="row-title">{client.name}</div>
+                          <div className="row-desc">
+                            {client.clientId} · {t('settings.network.lastSeen')}{' '}
+                            {new Date(client.lastSeenAt * 1000).toLocaleString()}
+                          </div>
+                        </div>
+                        <div className="row-control" style={{ gap: '12px' }}>
+                          {WS_CAPABILITIES.map((capability) => (
+                            <label key={capability} style={{ display: 'flex', alignItems: 'center', gap: '4px' }}>
+                              <input
+                                type="checkbox"
+                                checked={client.capabilities.includes(capability)}
+                                onChange={() => handleToggleWsCapability(client, capability)}
+                              />
+                              {t(`settings.network.cap_${capability}`)}
+                            </label>
+                          ))}
+                          <button className="btn btn-secondary" onClick={() => handleRevokeWsClient(client)}>
+                            {t('settings.network.revoke')}
+                          </button>
+                        </div>
+                      </div>
+                    ))}
                   </>
                 )}
               </div>
@@ -2272,10 +2518,58 @@ export function SettingsPage() {
                             <option value="round_robin">轮询 (Round-Robin)</option>
                             <option value="random">随机 (Random)</option>
                             <option value="single">单账号 (Fixed)</option>
+                            <option value="least_used">剩余配额优先 (Least Used)</option>
+                            <option value="quota_weighted">按配额加权 (Quota Weighted)</option>
                           </select>
                         </div>
                       </div>
                     )}
+                    {cfg.enabled && (
+                      <div className="settings-row" style={{ animation: 'fadeUp 0.3s ease both', paddingLeft: '16px' }}>
+                        <div className="row-label">
+                          <div className="row-title">单账号并发上限</div>
+                          <div className="row-desc">
+                            0 为不限制；账号占满后排队，超过 {cfg.queue_timeout_secs ?? 30} 秒返回 429
+                          </div>
+                        </div>
+                        <div className="row-control">
+                          <input
+                            type="number"
+                            min={0}
+                            max={64}
+                            className="settings-select settings-select--input-mode"
+                            value={cfg.max_concurrent_per_account ?? 0}
+                            onChange={(e) =>
+                              updateProviderConfig(key, {
+                                max_concurrent_per_account: Math.max(0, Math.min(64, Number(e.target.value) || 0)),
+                              })
+                            }
+                          />
+                        </div>
+                      </div>
+                    )}
+                    {cfg.enabled && (
+                      <div className="settings-row" style={{ animation: 'fadeUp 0.3s ease both', paddingLeft: '16px' }}>
+                        <div className="row-label">
+                          <div className="row-title">会话粘滞（分钟）</div>
+                          <div className="row-desc">同一会话固定使用同一账号，仅在额度不足或鉴权失败时换号；0 为关闭</div>
+                        </div>
+                        <div className="row-control">
+                          <input
+                            type="number"
+                            min={0}
+                            max={1440}
+                            className="settings-select settings-select--input-mode"
+                            value={Math.round((cfg.session_ttl_secs ?? 1800) / 60)}
+                            onChange={(e) =>
+                              updateProviderConfig(key, {
+                                session_ttl_secs: Math.max(0, Math.min(1440, Number(e.target.value) || 0)) * 60,
+                              })
+                            }
+                          />
+                        </div>
+                      </div>
+                    )}
                   </div>
                 ))}
               </div>
@@ -2289,6 +2583,96 @@ export function SettingsPage() {
                   <Save size={16} /> {proxySaving ? '保存中...' : '保存代理配置'}
                 </button>
               </div>
+
+              {/* 凭据加密存储 */}
+              <div className="group-title" style={{ marginTop: '24px' }}>凭据加密</div>
+              <div className="settings-group">
+                <div className="settings-row">
+                  <div className="row-label">
+                    <div className="row-title">加密存储账号凭据</div>
+                    <div className="row-desc">
+                      {!vaultStatus?.enabled
+                        ? '未开启：各平台账号的 Token 与 API Key 以明文保存在数据目录'
+                        : `已开启，密钥保存在${VAULT_KEY_SOURCE_LABELS[vaultStatus.key_source ?? ''] ?? '未知位置'}；${
+                          vaultStatus.locked ? '当前已锁定，账号与反向代理不可用' : '当前已解锁'
+                        }`}
+                    </div>
+                  </div>
+                  <div className="row-control">
+                    <span style={{
+                      display: 'inline-block',
+                      width: '8px', height: '8px',
+                      borderRadius: '50%',
+                      background: !vaultStatus?.enabled ? '#94a3b8' : vaultStatus.locked ? '#f59e0b' : '#22c55e',
+                    }}></span>
+                  </div>
+                </div>
+
+                {!vaultStatus?.enabled && (
+                  <div className="settings-row">
+                    <div className="row-label">
+                      <div className="row-title">密钥来源</div>
+                      <div className="row-desc">
+                        系统钥匙串启动时自动解锁（不支持的平台改用密钥文件）；主密码每次启动需手动解锁，遗失后无法恢复
+                      </div>
+                    </div>
+                    <div className="row-control">
+                      <select
+                        className="settings-select"
+                        value={vaultMode}
+                        onChange={(e) => setVaultMode(e.target.value as 'keyring' | 'passphrase')}
+                      >
+                        <option value="keyring">系统钥匙串</option>
+                        <option value="passphrase">主密码</option>
+                      </select>
+                    </div>
+                  </div>
+                )}
+
+                {((!vaultStatus?.enabled && vaultMode === 'passphrase')
+                  || (vaultStatus?.locked && vaultStatus.mode === 'passphrase')) && (
+                  <div className="settings-row">
+                    <div className="row-label">
+                      <div className="row-title">主密码</div>
+                      <div className="row-desc">{vaultStatus?.enabled ? '输入主密码以解锁' : '至少 8 个字符'}</div>
+                    </div>
+                    <div className="row-control row-control--grow">
+                      <input
+                        type="password"
+                        className="settings-input settings-input--path"
+                        value={vaultPassphrase}
+                        onChange={(e) => setVaultPassphrase(e.target.value)}
+                      />
+                    </div>
+                  </div>
+                )}
+              </div>
+
+              <div style={{ display: 'flex', justifyContent: 'flex-end', gap: '8px', marginTop: '12px' }}>
+                {!vaultStatus?.enabled && (
+                  <button className="btn btn-primary" onClick={handleEnableVault} disabled={vaultBusy}>
+                    {vaultBusy ? '处理中...' : '开启并加密现有账号'}
+                  </button>
+                )}
+                {vaultStatus?.enabled && vaultStatus.locked && (
+                  <button className="btn btn-primary" onClick={handleUnlockVault} disabled={vaultBusy}>
+                    {vaultBusy ? '处理中...' : '解锁'}
+                  </button>
+                )}
+                {vaultStatus?.enabled && !vaultStatus.locked && (
+                  <>
+                    <button className="btn btn-secondary" onClick={handleMigrateVault} disabled={vaultBusy}>
+                      加密明文账号
+                    </button>
+                    <button className="btn btn-secondary" onClick={handleLockVault} disabled={vaultBusy}>
+                      锁定
+                    </button>
+                    <button className="btn btn-secondary" onClick={handleDisableVault} disabled={vaultBusy}>
+                      关闭加密
+                    </button>
+                  </>
+                )}
+              </div>
             </>
           )}
 
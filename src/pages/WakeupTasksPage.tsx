@@ -46,6 +46,8 @@ type Translator = (key: string, options?: Record<string, unknown>) => string;
 const getReadableModelLabel = (id: string) => getAntigravityModelDisplayName(id);
 
 type TriggerMode = 'scheduled' | 'crontab' | 'quota_reset';
+/** 应用关闭或系统休眠期间错过执行后的补跑策略 */
+type CatchUpPolicy = 'skip' | 'once' | 'all';
 type RepeatMode = 'daily' | 'weekly' | 'interval';
 
 type TriggerSource = 'scheduled' | 'crontab' | 'quota_reset';
@@ -78,6 +80,7 @@ interface ScheduleConfig {
   timeWindowStart?: string;
   timeWindowEnd?: string;
   fallbackTimes?: string[];
+  catchUpPolicy?: CatchUpPolicy;
 }
 
 interface WakeupTask {
@@ -346,53 +349,6 @@ const calculateNextRuns = (config: ScheduleConfig, count: number) => {
   return results.slice(0, count);
 };
 
-const calculateCrontabNextRuns = (crontab: string, count: number) => {
-  try {
-    const parts = crontab.trim().split(/\s+/);
-    if (parts.length < 5) return [];
-    const [minute, hour] = parts;
-    const results: Date[] = [];
-    const now = new Date();
-
-    const parseField = (field: string, max: number) => {
-      if (field === '*') return Array.from({ length: max + 1 }, (_, i) => i);
-      if (field.includes(',')) return field.split(',').map(Number);
-      if (field.includes('-')) {
-        const [start, end] = field.split('-').map(Number);
-        return Array.from({ length: end - start + 1 }, (_, i) => start + i);
-      }
-      if (field.includes('/')) {
-        const [, step] = field.split('/');
-        const stepValue = Number(step) || 1;
-        return Array.from({ length: Math.ceil((max + 1) / stepValue) }, (_, i) => i * stepValue);
-      }
-      return [Number(field)];
-    };
-
-    const minutes = parseField(minute, 59);
-    const hours = parseField(hour, 23);
-
-    for (let dayOffset = 0; dayOffset < 7 && results.length < count; dayOffset += 1) {
-      for (const h of hours) {
-        for (const m of minutes) {
-          const date = new Date(now);
-          date.setDate(date.getDate() + dayOffset);
-          date.setHours(h, m, 0, 0);
-          if (date > now) {
-            results.push(date);
-            if (results.length >= count) break;
-          }
-        }
-        if (results.length >= count) break;
-      }
-    }
-
-    return results;
-  } catch {
-    return [];
-  }
-};
-
 const formatDateTime = (timestamp: number | undefined, locale: string, t: Translator) => {
   if (!timestamp) return t('wakeup.format.none');
   const date = new Date(timestamp);
@@ -485,11 +441,14 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
   const [formMaxOutputTokens, setFormMaxOutputTokens] = useState(0);
   const [formCrontab, setFormCrontab] = useState('');
   const [formCrontabError, setFormCrontabError] = useState('');
+  const [previewCrontab, setPreviewCrontab] = useState<Date[]>([]);
+  const [crontabNextRuns, setCrontabNextRuns] = useState<Record<string, number | null>>({});
   const [formError, setFormError] = useState('');
   const [formTimeWindowEnabled, setFormTimeWindowEnabled] = useState(false);
   const [formTimeWindowStart, setFormTimeWindowStart] = useState('09:00');
   const [formTimeWindowEnd, setFormTimeWindowEnd] = useState('18:00');
   const [formFallbackTimes, setFormFallbackTimes] = useState<string[]>(['07:00']);
+  const [formCatchUpPolicy, setFormCatchUpPolicy] = useState<CatchUpPolicy>('once');
   const [customDailyTime, setCustomDailyTime] = useState('');
   const [customWeeklyTime, setCustomWeeklyTime] = useState('');
   const [customFallbackTime, setCustomFallbackTime] = useState('');
@@ -573,6 +532,35 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
     });
   }, [tasks, wakeupEnabled]);
 
+  // Crontab 任务的下次执行时间由后端计算，与调度器保持一致（null 表示表达式无效）
+  useEffect(() => {
+    let cancelled = false;
+    const refresh = async () => {
+      const entries = await Promise.all(
+        tasks
+          .filter((task) => task.schedule.crontab)
+          .map(async (task) => {
+            try {
+              const runs = await invoke<number[]>('wakeup_preview_crontab', {
+                expr: task.schedule.crontab,
+                count: 1,
+              });
+              return [task.id, runs[0] ?? null] as const;
+            } catch {
+              return [task.id, null] as const;
+            }
+          }),
+      );
+      if (!cancelled) setCrontabNextRuns(Object.fromEntries(entries));
+    };
+    refresh();
+    const timer = window.setInterval(refresh, 60_000);
+    return () => {
+      cancelled = true;
+      window.clearInterval(timer);
+    };
+  }, [tasks]);
+
   useEffect(() => {
     const handleTaskResult = (event: Event) => {
       const custom = event as CustomEvent<WakeupTaskResultPayload>;
@@ -984,10 +972,11 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
     const mode = getTriggerMode(task);
     if (mode === 'quota_reset') return t('wakeup.format.none');
     if (mode === 'crontab') {
-      const nextRuns = calculateCrontabNextRuns(task.schedule.crontab || '', 1);
       if (!task.schedule.crontab) return t('wakeup.format.none');
-      if (nextRuns.length === 0) return t('wakeup.format.invalidCrontab');
-      return formatRunTime(nextRuns[0], locale, t);
+      const nextRun = crontabNextRuns[task.id];
+      if (nextRun === undefined) return t('wakeup.format.none');
+      if (nextRun === null) return t('wakeup.format.invalidCrontab');
+      return formatRunTime(new Date(nextRun), locale, t);
     }
     const nextRuns = calculateNextRuns(task.schedule, 1);
     if (!nextRuns.length) return t('wakeup.format.none');
@@ -1016,6 +1005,7 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
     setFormTimeWindowStart('09:00');
     setFormTimeWindowEnd('18:00');
     setFormFallbackTimes(['07:00']);
+    setFormCatchUpPolicy('once');
     setCustomDailyTime('');
     setCustomWeeklyTime('');
     setCustomFallbackTime('');
@@ -1052,6 +1042,7 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
     setFormTimeWindowStart(schedule.timeWindowStart || '09:00');
     setFormTimeWindowEnd(schedule.timeWindowEnd || '18:00');
     setFormFallbackTimes(schedule.fallbackTimes?.length ? [...schedule.fallbackTimes] : ['07:00']);
+    setFormCatchUpPolicy(schedule.catchUpPolicy || 'once');
     setCustomDailyTime('');
     setCustomWeeklyTime('');
     setCustomFallbackTime('');
@@ -1223,6 +1214,14 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
       setFormCrontabError(t('wakeup.notice.crontabRequired'));
       return;
     }
+    if (formTriggerMode === 'crontab') {
+      try {
+        await invoke('wakeup_preview_crontab', { expr: formCrontab.trim(), count: 1 });
+      } catch (error) {
+        setFormCrontabError(String(error));
+        return;
+      }
+    }
 
     const resolvedDailyTimes = [...formDailyTimes];
     const pendingDailyTime = getPendingCustomTime('daily');
@@ -1288,6 +1287,7 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
         formTriggerMode === 'quota_reset' && formTimeWindowEnabled
           ? resolvedFallbackTimes
           : undefined,
+      catchUpPolicy: formTriggerMode === 'quota_reset' ? undefined : formCatchUpPolicy,
     });
 
     const now = Date.now();
@@ -1382,10 +1382,22 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
     formIntervalEnd,
   ]);
 
-  const previewCrontab = useMemo(() => {
-    if (formTriggerMode !== 'crontab') return [];
-    if (!formCrontab.trim()) return [];
-    return calculateCrontabNextRuns(formCrontab, 5);
+  useEffect(() => {
+    if (formTriggerMode !== 'crontab' || !formCrontab.trim()) {
+      setPreviewCrontab([]);
+      return;
+    }
+    let cancelled = false;
+    invoke<number[]>('wakeup_preview_crontab', { expr: formCrontab, count: 5 })
+      .then((runs) => {
+        if (!cancelled) setPreviewCrontab(runs.map((ts) => new Date(ts)));
+      })
+      .catch(() => {
+        if (!cancelled) setPreviewCrontab([]);
+      });
+    return () => {
+      cancelled = true;
+    };
   }, [formTriggerMode, formCrontab]);
 
   const triggerSourceLabel = (source: HistoryTriggerSource) => {
@@ -2067,11 +2079,16 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
                       />
                       <button
                         className="btn btn-secondary"
-                        onClick={() => {
+                        onClick={async () => {
                           if (!formCrontab.trim()) {
                             setFormCrontabError(t('wakeup.notice.crontabRequired'));
-                          } else {
-                            setFormCrontabError(t('wakeup.form.crontabValidateHint'));
+                            return;
+                          }
+                          try {
+                            await invoke('wakeup_preview_crontab', { expr: formCrontab, count: 1 });
+                            setFormCrontabError('');
+                          } catch (error) {
+                            setFormCrontabError(String(error));
                           }
                         }}
                       >
@@ -2094,6 +2111,22 @@ export function WakeupTasksPage({ onNavigate }: WakeupPageProps) {
                 </div>
               )}
 
+              {formTriggerMode !== 'quota_reset' && (
+                <div className="wakeup-form-group">
+                  <label>{t('wakeup.form.catchUp')}</label>
+                  <select
+                    className="wakeup-input wakeup-select"
+                    value={formCatchUpPolicy}
+                    onChange={(event) => setFormCatchUpPolicy(event.target.value as CatchUpPolicy)}
+                  >
+                    <option value="once">{t('wakeup.form.catchUpOnce')}</option>
+                    <option value="skip">{t('wakeup.form.catchUpSkip')}</option>
+                    <option value="all">{t('wakeup.form.catchUpAll')}</option>
+                  </select>
+                  <p className="wakeup-hint">{t('wakeup.form.catchUpHint')}</p>
+                </div>
+              )}
+
               {formTriggerMode === 'quota_reset' && (
                 <div className="wakeup-mode-panel">
                   <div className="wakeup-form-group">
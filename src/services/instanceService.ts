@@ -1,6 +1,6 @@
 import { createPlatformInstanceService } from './platform/createPlatformInstanceService';
 
-const service = createPlatformInstanceService('');
+const service = createPlatformInstanceService('antigravity');
 
 export const getInstanceDefaults = service.getInstanceDefaults;
 export const listInstances = service.listInstances;
@@ -1,7 +1,7 @@
 import { invoke } from '@tauri-apps/api/core';
 import { InstanceDefaults, InstanceInitMode, InstanceProfile } from '../../types/instance';
 
-type PlatformInstanceCommandPrefix = '' | 'codex' | 'github_copilot' | 'windsurf' | 'kiro';
+export type InstancePlatform = 'antigravity' | 'codex' | 'github_copilot' | 'windsurf' | 'kiro';
 
 type InstancePayload = {
   name: string;
@@ -32,23 +32,21 @@ export type PlatformInstanceService = {
   openInstanceWindow: (instanceId: string) => Promise<void>;
 };
 
-const commandFor = (prefix: PlatformInstanceCommandPrefix, command: string) =>
-  prefix ? `${prefix}_${command}` : command;
-
 export function createPlatformInstanceService(
-  prefix: PlatformInstanceCommandPrefix,
+  platform: InstancePlatform,
 ): PlatformInstanceService {
   return {
     getInstanceDefaults: async () => {
-      return await invoke(commandFor(prefix, 'get_instance_defaults'));
+      return await invoke('get_instance_defaults', { platform });
     },
 
     listInstances: async () => {
-      return await invoke(commandFor(prefix, 'list_instances'));
+      return await invoke('list_instances', { platform });
     },
 
     createInstance: async (payload) => {
-      return await invoke(commandFor(prefix, 'create_instance'), {
+      return await invoke('create_instance', {
+        platform,
         name: payload.name,
         userDataDir: payload.userDataDir,
         extraArgs: payload.extraArgs ?? '',
@@ -60,6 +58,7 @@ export function createPlatformInstanceService(
 
     updateInstance: async (payload) => {
       const body: Record<string, unknown> = {
+        platform,
         instanceId: payload.instanceId,
       };
       if (payload.name !== undefined) {
@@ -74,27 +73,27 @@ export function createPlatformInstanceService(
       if (payload.followLocalAccount !== undefined) {
         body.followLocalAccount = payload.followLocalAccount;
       }
-      return await invoke(commandFor(prefix, 'update_instance'), body);
+      return await invoke('update_instance', body);
     },
 
     deleteInstance: async (instanceId) => {
-      return await invoke(commandFor(prefix, 'delete_instance'), { instanceId });
+      return await invoke('delete_instance', { platform, instanceId });
     },
 
     startInstance: async (instanceId) => {
-      return await invoke(commandFor(prefix, 'start_instance'), { instanceId });
+      return await invoke('start_instance', { platform, instanceId });
     },
 
     stopInstance: async (instanceId) => {
-      return await invoke(commandFor(prefix, 'stop_instance'), { instanceId });
+      return await invoke('stop_instance', { platform, instanceId });
     },
 
     closeAllInstances: async () => {
-      return await invoke(commandFor(prefix, 'close_all_instances'));
+      return await invoke('close_all_instances', { platform });
     },
 
     openInstanceWindow: async (instanceId) => {
-      return await invoke(commandFor(prefix, 'open_instance_window'), { instanceId });
+      return await invoke('open_instance_window', { platform, instanceId });
     },
   };
 }